@@ -0,0 +1,26 @@
+const path = require('path');
+const chalk = require('chalk');
+
+function addCommand(program, utils) {
+    program.command('build')
+    .description('Build the project')
+    .option('--wpo', 'Whole-program optimization (slower build, faster code)', false)
+    .option('--release', 'Use [profile.release] from bytes.yml', false)
+    .option('--size-report', 'Print the machine-code size of each function as JIT-compiled for this machine', false)
+    .option('--emit <kind>', 'Print compiler analysis results (effects)')
+    .action((cmd) => {
+        const spinner = utils.startSpinner('Building your project...');
+        const config = utils.loadBytesYml();
+        utils.resolveDependencies(config);
+        const sourceDir = path.join(process.cwd(), config['<>'] || 'cmd');
+        const args = [path.join(utils.constants().VIRA_BIN, 'vira-compiler'), 'build', sourceDir];
+        if (cmd.wpo) args.push('--wpo');
+        if (cmd.release) args.push('--release');
+        if (cmd.sizeReport) args.push('--size-report');
+        if (cmd.emit) args.push('--emit', cmd.emit);
+        utils.runSubprocess(args);
+        spinner.succeed(chalk.green.bold('Build complete!'));
+    });
+}
+
+module.exports = { addCommand };
@@ -5,9 +5,18 @@ function addCommand(program, utils) {
     program.command('run')
     .description('Run Vira code in VM')
     .argument('<file>', 'File or directory to run')
-    .action((file) => {
+    .option('--engine <engine>', 'Execution engine: interp or jit')
+    .option('--release', 'Use [profile.release] from bytes.yml and the JIT by default', false)
+    .option('--dry-run', 'Log file writes, deletions and commands instead of performing them', false)
+    .option('--perf-counters', 'Report calls, instructions, branches and allocations per function (jit engine)', false)
+    .action((file, cmd) => {
         const spinner = utils.startSpinner('Running your code...');
-        utils.runSubprocess([path.join(utils.constants().VIRA_BIN, 'vira-compiler'), 'run', file], false, 300000);
+        const args = [path.join(utils.constants().VIRA_BIN, 'vira-compiler'), 'run', file];
+        if (cmd.engine) args.push('--engine', cmd.engine);
+        if (cmd.release) args.push('--release');
+        if (cmd.dryRun) args.push('--dry-run');
+        if (cmd.perfCounters) args.push('--perf-counters');
+        utils.runSubprocess(args, false, 300000);
         spinner.succeed(chalk.green.bold('Run complete!'));
     });
 }
@@ -0,0 +1,13 @@
+const path = require('path');
+
+function addCommand(program, utils) {
+    program.command('stats')
+    .description('Summarize the build history recorded on this machine')
+    .action(() => {
+        const config = utils.loadBytesYml();
+        const sourceDir = path.join(process.cwd(), config['<>'] || 'cmd');
+        utils.runSubprocess([path.join(utils.constants().VIRA_BIN, 'vira-compiler'), 'stats', sourceDir]);
+    });
+}
+
+module.exports = { addCommand };
@@ -5,7 +5,8 @@ const chalk = require('chalk');
 function addCommand(program, utils) {
     program.command('test')
     .description('Run tests')
-    .action(() => {
+    .option('--engine <engine>', 'Execution engine: interp or jit')
+    .action((cmd) => {
         const config = utils.loadBytesYml();
         const testDir = path.join(process.cwd(), config.test_dir || 'tests');
         if (!fs.existsSync(testDir)) {
@@ -13,7 +14,9 @@ function addCommand(program, utils) {
             process.exit(1);
         }
         const spinner = utils.startSpinner('Running tests...');
-        utils.runSubprocess([path.join(utils.constants().VIRA_BIN, 'vira-compiler'), 'test', testDir]);
+        const args = [path.join(utils.constants().VIRA_BIN, 'vira-compiler'), 'test', testDir];
+        if (cmd.engine) args.push('--engine', cmd.engine);
+        utils.runSubprocess(args);
         spinner.succeed(chalk.green.bold('Tests complete!'));
     });
 }
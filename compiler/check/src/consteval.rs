@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::thread;
+
+use vira_rt::decimal::{self, Decimal};
+use vira_rt::interpreter::{Interpreter, Limits, Value};
+use vira_syntax::arena::{Arena, ExprId, NodeId};
+use vira_syntax::ast::{literal_type, BinOp, Expr, Item, UnaryOp};
+use vira_syntax::lower;
+
+use crate::effects;
+
+// Compile-time evaluation of constant expressions: `const` initializers,
+// the sizes of `[value; count]` arrays and enum discriminants. A constant
+// expression is built from literals, arrays, earlier constants, operators
+// and calls with constant arguments; it folds to a single literal node. The
+// parser folds them all with `eval`, so the type checker and the engines
+// only ever see the literal.
+//
+// Calls run in an interpreter that only knows the constants, functions and
+// types declared before the constant. The called code has to be pure: output,
+// builtins with outside effects and top-level variables are errors, and
+// evaluation stops after a fixed number of steps.
+
+const LIMITS: Limits = Limits { steps: 1_000_000, depth: 1000 };
+// The interpreter recurses for every nested call, so it gets a stack deep
+// enough for `LIMITS.depth` calls.
+const STACK_SIZE: usize = 256 << 20;
+
+pub fn eval(arena: &mut Arena, id: ExprId, consts: &HashMap<String, ExprId>, declarations: &[NodeId]) -> Result<ExprId, String> {
+    let eval = |arena: &mut Arena, id: ExprId| eval(arena, id, consts, declarations);
+    match arena[id].clone() {
+        Expr::Literal(_) | Expr::FloatLiteral(_) | Expr::BoolLiteral(_) | Expr::StringLiteral(_) => Ok(id),
+        // A copy, so that every use of the constant is a tree of its own.
+        Expr::VarRef(name) => consts.get(&name).map(|&value| arena.deep_copy_expr(value)).ok_or(format!("'{}' is not a constant.", name)),
+        // `config.key`, which the config block declared as a constant.
+        Expr::FieldAccess(base, field) => match &arena[base] {
+            Expr::VarRef(name) => {
+                let key = format!("{}.{}", name, field);
+                consts.get(&key).map(|&value| arena.deep_copy_expr(value)).ok_or(format!("'{}' is not a constant.", key))
+            }
+            _ => Err("Not a constant expression.".to_string()),
+        },
+        Expr::ArrayLiteral(items) => {
+            let items = items.into_iter().map(|item| eval(arena, item)).collect::<Result<_, _>>()?;
+            Ok(arena.alloc_expr(Expr::ArrayLiteral(items)))
+        }
+        Expr::Index(base, index) => {
+            let (base, index) = (eval(arena, base)?, eval(arena, index)?);
+            match (&arena[base], &arena[index]) {
+                (Expr::ArrayLiteral(items), Expr::Literal(i)) => {
+                    usize::try_from(*i).ok().and_then(|i| items.get(i).copied()).ok_or("Index out of bounds in constant.".to_string())
+                }
+                _ => Err("Invalid index in constant.".to_string()),
+            }
+        }
+        Expr::Call(name, args) => {
+            let args = args.into_iter().map(|arg| eval(arena, arg)).collect::<Result<Vec<_>, _>>()?;
+            call(arena, &name, args, consts, declarations).map_err(|e| format!("Cannot evaluate '{}' at compile time: {}", name, e))
+        }
+        Expr::Unary(op, operand) => {
+            let operand = eval(arena, operand)?;
+            let folded = match (op, arena[operand].clone()) {
+                (UnaryOp::Neg, Expr::Literal(v)) => v.checked_neg().map(Expr::Literal).ok_or("Integer overflow in constant.".to_string()),
+                (UnaryOp::Neg, Expr::FloatLiteral(v)) => Ok(Expr::FloatLiteral(-v)),
+                (UnaryOp::Not, Expr::BoolLiteral(v)) => Ok(Expr::BoolLiteral(!v)),
+                (UnaryOp::Neg, _) => match decimal_value(arena, operand) {
+                    Some(d) => d.neg().map(|d| decimal_literal(arena, d)),
+                    None => Err("Invalid unary op in constant.".to_string()),
+                },
+                _ => Err("Invalid unary op in constant.".to_string()),
+            }?;
+            Ok(arena.alloc_expr(folded))
+        }
+        Expr::Binary(left, op, right) => {
+            let (left, right) = (eval(arena, left)?, eval(arena, right)?);
+            let folded = binary(arena, left, op, right)?;
+            Ok(arena.alloc_expr(folded))
+        }
+        _ => Err("Not a constant expression.".to_string()),
+    }
+}
+
+fn call(arena: &mut Arena, name: &str, args: Vec<ExprId>, consts: &HashMap<String, ExprId>, declarations: &[NodeId]) -> Result<ExprId, String> {
+    // The evaluator runs on a copy of the arena with the call added.
+    let mut nodes = arena.clone();
+    let mut program: Vec<NodeId> = Vec::new();
+    let mut config = Vec::new();
+    for (name, &value) in consts {
+        if let Some(key) = name.strip_prefix("config.") {
+            config.push((key.to_string(), value));
+        } else if let Some(typ) = literal_type(&nodes, value) {
+            program.push(nodes.alloc(Item::ConstDecl(name.clone(), typ, value)));
+        }
+    }
+    if !config.is_empty() {
+        config.sort_by(|a, b| a.0.cmp(&b.0));
+        program.push(nodes.alloc(Item::ConfigDecl(config)));
+    }
+    program.extend_from_slice(declarations);
+    program.push(nodes.alloc(Expr::Call(name.to_string(), args)));
+    // The parser is still reading the program, which is lowered after.
+    lower::lower(&mut nodes, &program);
+    // Calls through function values are left to the interpreter, which
+    // refuses I/O at run time.
+    let effects = effects::analyze(&nodes, declarations).into_iter().find(|(function, _)| function == name).map(|(_, effects)| effects);
+    if let Some(reason) = effects.filter(|e| e.io || e.state).and_then(|e| e.impurity()) {
+        return Err(format!("'{}' is not pure; it {}.", name, reason));
+    }
+    // Values are not `Send`, so the result comes back as literal nodes.
+    let run = || match Interpreter::compile_time(LIMITS, nodes).interpret(&program)? {
+        Some(value) => {
+            let mut result = Arena::new();
+            let id = literal(&mut result, &value).ok_or("The result is not a number, bool, string or array of those.".to_string())?;
+            Ok((result, id))
+        }
+        None => Err("The call produced no value.".to_string()),
+    };
+    let (result, id) = thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, run)
+            .map_err(|e| format!("Cannot start the evaluator: {}", e))?
+            .join()
+            .unwrap_or_else(|_| Err("The evaluator crashed.".to_string()))
+    })?;
+    Ok(arena.copy_expr_from(&result, id))
+}
+
+fn literal(arena: &mut Arena, value: &Value) -> Option<ExprId> {
+    let node = match value {
+        Value::Int(v) => Expr::Literal(*v),
+        Value::Float(v) => Expr::FloatLiteral(*v),
+        Value::Bool(v) => Expr::BoolLiteral(*v),
+        Value::String(s) => Expr::StringLiteral(s.clone()),
+        Value::Decimal(d) => decimal_literal(arena, *d),
+        Value::Array(items) => Expr::ArrayLiteral(items.iter().map(|item| literal(arena, item)).collect::<Option<_>>()?),
+        _ => return None,
+    };
+    Some(arena.alloc_expr(node))
+}
+
+fn binary(arena: &mut Arena, left: ExprId, op: BinOp, right: ExprId) -> Result<Expr, String> {
+    let overflow = || "Integer overflow in constant.".to_string();
+    match (arena[left].clone(), arena[right].clone()) {
+        (Expr::Literal(a), Expr::Literal(b)) => match op {
+            BinOp::Add => a.checked_add(b).map(Expr::Literal).ok_or_else(overflow),
+            BinOp::Sub => a.checked_sub(b).map(Expr::Literal).ok_or_else(overflow),
+            BinOp::Mul => a.checked_mul(b).map(Expr::Literal).ok_or_else(overflow),
+            BinOp::Div | BinOp::Mod if b == 0 => Err("Division by zero in constant.".to_string()),
+            BinOp::Div => a.checked_div(b).map(Expr::Literal).ok_or_else(overflow),
+            BinOp::Mod => a.checked_rem(b).map(Expr::Literal).ok_or_else(overflow),
+            BinOp::And | BinOp::Or => Err("Type mismatch in constant.".to_string()),
+            _ => Ok(Expr::BoolLiteral(compare(a.cmp(&b), op))),
+        },
+        (Expr::FloatLiteral(a), Expr::FloatLiteral(b)) => float_binary(a, op, b),
+        // An int meets a float as a float.
+        (Expr::Literal(a), Expr::FloatLiteral(b)) => float_binary(a as f64, op, b),
+        (Expr::FloatLiteral(a), Expr::Literal(b)) => float_binary(a, op, b as f64),
+        (Expr::BoolLiteral(a), Expr::BoolLiteral(b)) => match op {
+            BinOp::And => Ok(Expr::BoolLiteral(a && b)),
+            BinOp::Or => Ok(Expr::BoolLiteral(a || b)),
+            BinOp::Eq => Ok(Expr::BoolLiteral(a == b)),
+            BinOp::Neq => Ok(Expr::BoolLiteral(a != b)),
+            _ => Err("Type mismatch in constant.".to_string()),
+        },
+        (Expr::StringLiteral(a), Expr::StringLiteral(b)) => match op {
+            BinOp::Add => Ok(Expr::StringLiteral(a + &b)),
+            BinOp::Eq => Ok(Expr::BoolLiteral(a == b)),
+            BinOp::Neq => Ok(Expr::BoolLiteral(a != b)),
+            _ => Err("Type mismatch in constant.".to_string()),
+        },
+        _ if decimal_value(arena, left).is_some() || decimal_value(arena, right).is_some() => {
+            let (Some(a), Some(b)) = (decimal_value(arena, left), decimal_value(arena, right)) else {
+                return Err("Type mismatch in constant.".to_string());
+            };
+            match decimal::arith(a, op, b) {
+                Some(result) => result.map(|d| decimal_literal(arena, d)),
+                None if matches!(op, BinOp::And | BinOp::Or) => Err("Type mismatch in constant.".to_string()),
+                None => Ok(Expr::BoolLiteral(compare(a.cmp(&b), op))),
+            }
+        }
+        _ => Err("Type mismatch in constant.".to_string()),
+    }
+}
+
+fn float_binary(a: f64, op: BinOp, b: f64) -> Result<Expr, String> {
+    match op {
+        BinOp::Add => Ok(Expr::FloatLiteral(a + b)),
+        BinOp::Sub => Ok(Expr::FloatLiteral(a - b)),
+        BinOp::Mul => Ok(Expr::FloatLiteral(a * b)),
+        BinOp::Div => Ok(Expr::FloatLiteral(a / b)),
+        BinOp::Mod => Ok(Expr::FloatLiteral(a % b)),
+        BinOp::And | BinOp::Or => Err("Type mismatch in constant.".to_string()),
+        _ => match a.partial_cmp(&b) {
+            Some(ordering) => Ok(Expr::BoolLiteral(compare(ordering, op))),
+            None => Ok(Expr::BoolLiteral(op == BinOp::Neq)),
+        },
+    }
+}
+
+// Decimals have no literal syntax; a folded one is the call that makes it.
+fn decimal_literal(arena: &mut Arena, value: Decimal) -> Expr {
+    Expr::Call("decimal".to_string(), vec![arena.alloc_expr(Expr::StringLiteral(value.to_string()))])
+}
+
+// The decimal a folded operand stands for. Ints mix with decimals exactly.
+fn decimal_value(arena: &Arena, id: ExprId) -> Option<Decimal> {
+    match &arena[id] {
+        Expr::Call(name, args) if name == "decimal" => match args.as_slice() {
+            [arg] => match &arena[*arg] {
+                Expr::StringLiteral(text) => Decimal::parse(text).ok(),
+                _ => None,
+            },
+            _ => None,
+        },
+        Expr::Literal(v) => Some(Decimal::from_int(*v)),
+        _ => None,
+    }
+}
+
+fn compare(ordering: std::cmp::Ordering, op: BinOp) -> bool {
+    match op {
+        BinOp::Eq => ordering.is_eq(),
+        BinOp::Neq => ordering.is_ne(),
+        BinOp::Lt => ordering.is_lt(),
+        BinOp::Gt => ordering.is_gt(),
+        BinOp::Le => ordering.is_le(),
+        _ => ordering.is_ge(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vira_syntax::ast::AstNode;
+    use vira_syntax::diagnostic;
+    use vira_syntax::parser::Parser;
+    use vira_syntax::printer;
+    use vira_syntax::tokenizer::tokenize;
+
+    // The value a `const` declared from `init` folds to.
+    fn float_const(init: &str) -> Result<f64, String> {
+        let mut arena = Arena::new();
+        let mut parser = Parser::new(tokenize(&format!("const X: float = {}", init)), &mut arena);
+        parser.fold_with(&eval);
+        let items = parser.parse().map_err(|errors| diagnostic::render(&errors))?;
+        match items.first().map(|&id| &arena[id]) {
+            Some(AstNode::Item(Item::ConstDecl(_, _, value))) => match arena[*value] {
+                Expr::FloatLiteral(value) => Ok(value),
+                ref other => Err(format!("Constant folded to {:?}.", other)),
+            },
+            other => Err(format!("Expected a constant, got {:?}.", other)),
+        }
+    }
+
+    #[test]
+    fn printed_floats_parse_back_to_the_same_value() -> Result<(), String> {
+        let values = [0.0, -0.0, 1.5, -2.25, 0.1, 1e-300, f64::MIN_POSITIVE, f64::MAX, f64::MIN, f64::INFINITY, f64::NEG_INFINITY, f64::NAN];
+        for value in values {
+            let mut arena = Arena::new();
+            let literal = arena.alloc(Expr::FloatLiteral(value));
+            let printed = printer::print(&arena, literal);
+            let parsed = float_const(&printed)?;
+            assert!(parsed.to_bits() == value.to_bits() || parsed.is_nan() && value.is_nan(), "{} printed as {} parsed back as {}", value, printed, parsed);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn compile_time_calls_run_lowered_code() -> Result<(), String> {
+        let source = "func sum(n: int) -> int {\n    let s = 0\n    for i in 0..n { s += i }\n    s\n}\nconst X: int = sum(5)";
+        let mut arena = Arena::new();
+        let mut parser = Parser::new(tokenize(source), &mut arena);
+        parser.fold_with(&eval);
+        let items = parser.parse().map_err(|errors| diagnostic::render(&errors))?;
+        match items.last().map(|&id| &arena[id]) {
+            Some(AstNode::Item(Item::ConstDecl(_, _, value))) => assert!(matches!(arena[*value], Expr::Literal(10))),
+            other => return Err(format!("Expected a constant, got {:?}.", other)),
+        }
+        Ok(())
+    }
+}
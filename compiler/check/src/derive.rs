@@ -0,0 +1,186 @@
+use vira_syntax::arena::{Arena, ExprId, ItemId, NodeId};
+use vira_syntax::ast::{AstNode, BinOp, Expr, Item, ViraType};
+
+// `@derive(...)` on a struct declaration asks for methods generated from its
+// fields. The attribute stays in the tree as written, so the program prints
+// back as its source; the methods are added after parsing as an impl block
+// that follows the struct:
+//
+//   hash:      `hash(self.a, self.b, ...)`, the builtin mixing the fields.
+//   eq:        `self.a == other.a && self.b == other.b && ...`
+//   to_string: `S { a: 1, b: "x" }`, the fields rendered as `format` does.
+//   clone:     `S { a: self.a, b: self.b, ... }`
+//   json:      `to_json`, giving `{"a": 1, "b": "x"}` with each field
+//              encoded by the `json` builtin. Every field needs a type JSON
+//              can hold.
+
+// The method `@derive(name)` generates.
+fn method_name(derived: &str) -> &str {
+    match derived {
+        "json" => "to_json",
+        _ => derived,
+    }
+}
+
+pub fn expand(arena: &mut Arena, items: &mut Vec<NodeId>) -> Result<(), String> {
+    let mut i = 0;
+    while i < items.len() {
+        let (derived, item) = derived(arena, items[i]);
+        i += 1;
+        let AstNode::Item(Item::StructDecl(type_name, fields)) = &arena[item] else {
+            continue;
+        };
+        if derived.is_empty() {
+            continue;
+        }
+        let (type_name, fields) = (type_name.clone(), fields.clone());
+        for (j, name) in derived.iter().enumerate() {
+            if derived[..j].contains(name) {
+                return Err(format!("Struct '{}' derives '{}' twice.", type_name, name));
+            }
+            if implemented(arena, items, &type_name, method_name(name)) {
+                return Err(format!("Struct '{}' derives '{}' and also implements '{}'.", type_name, name, method_name(name)));
+            }
+            if let ("json", Some((field, typ))) = (name.as_str(), fields.iter().find(|(_, typ)| !encodable(typ))) {
+                return Err(format!("Struct '{}' cannot derive 'json': field '{}' has type {}, which JSON cannot hold.", type_name, field, typ));
+            }
+        }
+        let methods = derived.iter().map(|name| derive(arena, &type_name, &fields, name)).collect();
+        let impl_block = arena.alloc(Item::ImplDecl(None, type_name, methods));
+        items.insert(i, impl_block);
+        i += 1;
+    }
+    Ok(())
+}
+
+// The methods the `@derive` attributes around `id` ask for, and the
+// declaration inside them.
+fn derived(arena: &Arena, mut id: NodeId) -> (Vec<String>, NodeId) {
+    let mut methods = Vec::new();
+    while let AstNode::Item(Item::Attribute(name, args, item)) = &arena[id] {
+        if name == "derive" {
+            methods.extend(args.iter().filter_map(|&arg| match &arena[arg] {
+                Expr::VarRef(method) => Some(method.clone()),
+                _ => None,
+            }));
+        }
+        id = *item;
+    }
+    (methods, id)
+}
+
+fn implemented(arena: &Arena, items: &[NodeId], type_name: &str, method: &str) -> bool {
+    items.iter().any(|&item| match &arena[arena.item(item)] {
+        AstNode::Item(Item::ImplDecl(_, name, methods)) if name == type_name => {
+            methods.iter().any(|&m| matches!(&arena[m], Item::FuncDecl(name, ..) if name == method))
+        }
+        _ => false,
+    })
+}
+
+fn encodable(typ: &ViraType) -> bool {
+    match typ {
+        ViraType::Int | ViraType::Float | ViraType::Decimal | ViraType::Bool | ViraType::String | ViraType::Struct(_) | ViraType::Enum(_) => true,
+        ViraType::Array(element) | ViraType::Optional(element) => encodable(element),
+        ViraType::Tuple(elements) => elements.iter().all(encodable),
+        ViraType::Map(key, value) => **key == ViraType::String && encodable(value),
+        _ => false,
+    }
+}
+
+fn derive(arena: &mut Arena, type_name: &str, fields: &[(String, ViraType)], derived: &str) -> ItemId {
+    let this = ViraType::Struct(type_name.to_string());
+    let mut params = vec![("self".to_string(), this.clone())];
+    let (return_type, result) = match derived {
+        "hash" => {
+            let words = fields.iter().map(|(field, _)| field_of(arena, "self", field)).collect();
+            (ViraType::Int, arena.alloc_expr(Expr::Call("hash".to_string(), words)))
+        }
+        "eq" => {
+            params.push(("other".to_string(), this));
+            let mut result = None;
+            for (field, _) in fields {
+                let (left, right) = (field_of(arena, "self", field), field_of(arena, "other", field));
+                let equal = arena.alloc_expr(Expr::Binary(left, BinOp::Eq, right));
+                result = Some(match result {
+                    Some(previous) => arena.alloc_expr(Expr::Binary(previous, BinOp::And, equal)),
+                    None => equal,
+                });
+            }
+            (ViraType::Bool, result.unwrap_or_else(|| arena.alloc_expr(Expr::BoolLiteral(true))))
+        }
+        "to_string" => {
+            let mut text = Concat::default();
+            text.push(&format!("{} {{ ", type_name));
+            for (i, (field, typ)) in fields.iter().enumerate() {
+                text.push(&format!("{}{}: ", if i == 0 { "" } else { ", " }, field));
+                let value = field_of(arena, "self", field);
+                // Strings are quoted inside a struct, as `write` shows them.
+                if *typ == ViraType::String {
+                    text.push("\"");
+                    text.splice(arena, value);
+                    text.push("\"");
+                } else {
+                    let rendered = arena.alloc_expr(Expr::Call("format".to_string(), vec![value]));
+                    text.splice(arena, rendered);
+                }
+            }
+            text.push(" }");
+            (ViraType::String, text.finish(arena))
+        }
+        "clone" => {
+            let values = fields.iter().map(|(field, _)| (field.clone(), field_of(arena, "self", field))).collect();
+            (this, arena.alloc_expr(Expr::StructLiteral(type_name.to_string(), values)))
+        }
+        _ => {
+            let mut text = Concat::default();
+            text.push("{");
+            for (i, (field, _)) in fields.iter().enumerate() {
+                text.push(&format!("{}\"{}\": ", if i == 0 { "" } else { ", " }, field));
+                let value = field_of(arena, "self", field);
+                let encoded = arena.alloc_expr(Expr::Call("json".to_string(), vec![value]));
+                text.splice(arena, encoded);
+            }
+            text.push("}");
+            (ViraType::String, text.finish(arena))
+        }
+    };
+    let body = arena.alloc(Expr::Block(vec![result.node()]));
+    arena.alloc_item(Item::FuncDecl(method_name(derived).to_string(), Vec::new(), params, return_type, body))
+}
+
+fn field_of(arena: &mut Arena, variable: &str, field: &str) -> ExprId {
+    let base = arena.alloc_expr(Expr::VarRef(variable.to_string()));
+    arena.alloc_expr(Expr::FieldAccess(base, field.to_string()))
+}
+
+// Builds `[...].join("")` from literal text and string expressions.
+#[derive(Default)]
+struct Concat {
+    parts: Vec<ExprId>,
+    text: String,
+}
+
+impl Concat {
+    fn push(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    fn splice(&mut self, arena: &mut Arena, value: ExprId) {
+        self.flush(arena);
+        self.parts.push(value);
+    }
+
+    fn flush(&mut self, arena: &mut Arena) {
+        if !self.text.is_empty() {
+            self.parts.push(arena.alloc_expr(Expr::StringLiteral(std::mem::take(&mut self.text))));
+        }
+    }
+
+    fn finish(mut self, arena: &mut Arena) -> ExprId {
+        self.flush(arena);
+        let parts = arena.alloc_expr(Expr::ArrayLiteral(self.parts));
+        let separator = arena.alloc_expr(Expr::StringLiteral(String::new()));
+        arena.alloc_expr(Expr::MethodCall(parts, "join".to_string(), vec![separator]))
+    }
+}
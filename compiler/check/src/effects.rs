@@ -0,0 +1,405 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use vira_rt::builtins::{self, Receiver};
+use vira_rt::numerics;
+use vira_rt::sorting;
+use vira_syntax::arena::{Arena, NodeId};
+use vira_syntax::ast::{AstNode, BinOp, Expr, Item, Pattern, Stmt, ViraType};
+use vira_syntax::visit::{walk, Visitor};
+
+// Effect analysis: what calling each function can do besides computing its
+// result. A function is pure when it does no I/O, touches no top-level
+// variable and calls nothing the analysis cannot see; allocating is allowed.
+// Pure calls can be evaluated at compile time, and in any order.
+//
+// Calls are resolved by name, so a method call counts every method of that
+// name. Calls through function values and into other modules are unknown.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Effects {
+    // Writes output, touches files or runs processes.
+    pub io: bool,
+    // Reads or assigns a top-level variable.
+    pub state: bool,
+    pub allocates: bool,
+    pub unknown: bool,
+}
+
+impl Effects {
+    pub fn is_pure(self) -> bool {
+        !self.io && !self.state && !self.unknown
+    }
+
+    // Why the function is not pure, for error messages.
+    pub fn impurity(self) -> Option<&'static str> {
+        if self.io {
+            Some("does I/O")
+        } else if self.state {
+            Some("uses a top-level variable")
+        } else if self.unknown {
+            Some("calls a function value or another module")
+        } else {
+            None
+        }
+    }
+
+    fn union(self, other: Effects) -> Effects {
+        Effects {
+            io: self.io || other.io,
+            state: self.state || other.state,
+            allocates: self.allocates || other.allocates,
+            unknown: self.unknown || other.unknown,
+        }
+    }
+}
+
+impl fmt::Display for Effects {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let labels: Vec<&str> = [(self.io, "io"), (self.state, "state"), (self.allocates, "allocates"), (self.unknown, "unknown calls")]
+            .into_iter()
+            .filter_map(|(set, label)| set.then_some(label))
+            .collect();
+        if labels.is_empty() {
+            write!(f, "pure")
+        } else if self.is_pure() {
+            write!(f, "pure, {}", labels.join(", "))
+        } else {
+            write!(f, "{}", labels.join(", "))
+        }
+    }
+}
+
+// Effects of every function in the module, in declaration order. Methods are
+// listed as `Type::method`.
+pub fn analyze(arena: &Arena, program: &[NodeId]) -> Vec<(String, Effects)> {
+    let mut functions = Vec::new();
+    let mut methods: HashMap<&str, Vec<String>> = HashMap::new();
+    let mut enums = HashSet::new();
+    let mut globals = HashMap::new();
+    for &id in program {
+        let id = arena.item(id);
+        match &arena[id] {
+            AstNode::Stmt(Stmt::VarDecl(name, typ, _)) => {
+                globals.insert(name.as_str(), typ.clone());
+            }
+            AstNode::Stmt(Stmt::TupleDecl(names, typ, _)) => {
+                for (i, name) in names.iter().enumerate() {
+                    globals.insert(name.as_str(), typ.as_ref().and_then(|typ| element(typ, i)));
+                }
+            }
+            AstNode::Item(Item::EnumDecl(name, _)) => {
+                enums.insert(name.as_str());
+            }
+            AstNode::Item(Item::ImplDecl(_, typ, items)) => {
+                for &method in items {
+                    if let Item::FuncDecl(name, ..) = &arena[method] {
+                        let qualified = format!("{}::{}", typ, name);
+                        methods.entry(name).or_default().push(qualified.clone());
+                        functions.push((qualified, &arena[method]));
+                    }
+                }
+            }
+            _ => {}
+        }
+        Functions(&mut functions).visit(arena, id);
+    }
+    let names: HashMap<&str, &ViraType> = functions
+        .iter()
+        .filter_map(|(name, func)| match func {
+            Item::FuncDecl(_, _, _, ret, _) => Some((name.as_str(), ret)),
+            _ => None,
+        })
+        .collect();
+
+    // Each function's own effects and the functions it calls.
+    let mut direct = Vec::new();
+    for (_, func) in &functions {
+        let Item::FuncDecl(_, _, params, _, body) = func else { continue };
+        let mut walker = Walker { arena, globals: &globals, functions: &names, methods: &methods, enums: &enums, effects: Effects::default(), calls: HashSet::new() };
+        let mut locals: Scope = params.iter().map(|(name, typ)| (name.clone(), Some(typ.clone()))).collect();
+        walker.walk(*body, &mut locals);
+        direct.push((walker.effects, walker.calls));
+    }
+
+    // Propagate through calls until nothing changes.
+    let mut effects: HashMap<&str, Effects> = functions.iter().zip(&direct).map(|((name, _), (own, _))| (name.as_str(), *own)).collect();
+    loop {
+        let mut changed = false;
+        for ((name, _), (own, calls)) in functions.iter().zip(&direct) {
+            let total = calls.iter().fold(*own, |acc, callee| acc.union(effects[callee.as_str()]));
+            if total != effects[name.as_str()] {
+                effects.insert(name, total);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    functions.iter().map(|(name, _)| (name.clone(), effects[name.as_str()])).collect()
+}
+
+// Checks that every function marked `@pure` is.
+pub fn check(arena: &Arena, program: &[NodeId]) -> Result<(), String> {
+    let mut marked = Vec::new();
+    for &node in program {
+        PureMarks(&mut marked).visit(arena, node);
+    }
+    if marked.is_empty() {
+        return Ok(());
+    }
+    let effects: HashMap<String, Effects> = analyze(arena, program).into_iter().collect();
+    for name in marked {
+        if let Some(reason) = effects.get(&name).and_then(|e| e.impurity()) {
+            return Err(format!("Function '{}' is marked @pure but {}.", name, reason));
+        }
+    }
+    Ok(())
+}
+
+// Functions marked `@pure`, outside anonymous functions.
+struct PureMarks<'m>(&'m mut Vec<String>);
+
+impl<'a> Visitor<'a> for PureMarks<'_> {
+    fn visit(&mut self, arena: &'a Arena, id: NodeId) {
+        match &arena[id] {
+            AstNode::Item(Item::Attribute(name, _, item)) if name == "pure" => {
+                if let AstNode::Item(Item::FuncDecl(func, ..)) = &arena[arena.item(*item)] {
+                    self.0.push(func.clone());
+                }
+            }
+            AstNode::Expr(Expr::Lambda(..)) => return,
+            _ => {}
+        }
+        walk(self, arena, id);
+    }
+}
+
+// Named functions, including ones declared inside other functions.
+struct Functions<'a, 'f>(&'f mut Vec<(String, &'a Item)>);
+
+impl<'a> Visitor<'a> for Functions<'a, '_> {
+    fn visit(&mut self, arena: &'a Arena, id: NodeId) {
+        match &arena[id] {
+            AstNode::Item(func @ Item::FuncDecl(name, ..)) => self.0.push((name.clone(), func)),
+            AstNode::Item(Item::ImplDecl(..)) | AstNode::Expr(Expr::Lambda(..)) => return,
+            _ => {}
+        }
+        walk(self, arena, id);
+    }
+}
+
+// Local variables and their types, where known.
+type Scope = HashMap<String, Option<ViraType>>;
+
+struct Walker<'a> {
+    arena: &'a Arena,
+    // Top-level variables and their types.
+    globals: &'a HashMap<&'a str, Option<ViraType>>,
+    // Return types by function name.
+    functions: &'a HashMap<&'a str, &'a ViraType>,
+    methods: &'a HashMap<&'a str, Vec<String>>,
+    enums: &'a HashSet<&'a str>,
+    effects: Effects,
+    calls: HashSet<String>,
+}
+
+impl Walker<'_> {
+    fn walk(&mut self, id: NodeId, locals: &mut Scope) {
+        let arena = self.arena;
+        let node = &arena[id];
+        match node {
+            AstNode::Stmt(Stmt::Write(_)) => {
+                self.effects.io = true;
+                self.renders();
+            }
+            AstNode::Expr(Expr::VarRef(name)) | AstNode::Stmt(Stmt::Assign(name, _)) if !locals.contains_key(name) && self.globals.contains_key(name.as_str()) => {
+                self.effects.state = true;
+            }
+            AstNode::Expr(Expr::Call(name, args)) => {
+                if locals.contains_key(name) {
+                    self.effects.unknown = true;
+                } else if self.functions.contains_key(name.as_str()) {
+                    self.calls.insert(name.clone());
+                } else if name == "format" {
+                    self.renders();
+                } else if let Some(method) = user_method(name) {
+                    self.calls_all(method);
+                } else if sorting::calls_back(name, args.len()) || name == "array_generate" {
+                    // The comparator or generator runs inside the builtin.
+                    self.effects.unknown = true;
+                } else if builtins::has_effects(name) {
+                    self.effects.io = true;
+                } else if builtins::allocates(name) {
+                    self.effects.allocates = true;
+                }
+            }
+            AstNode::Expr(Expr::Apply(..)) => self.effects.unknown = true,
+            AstNode::Expr(Expr::MethodCall(receiver, method, _)) => {
+                let receiver = self.type_of(receiver.node(), locals);
+                match receiver.as_ref().and_then(Receiver::of_type).and_then(|r| builtins::method(r, method)) {
+                    Some(builtin) => self.effects.allocates |= builtins::allocates(builtin.builtin),
+                    None => self.method(receiver, method, true),
+                }
+            }
+            AstNode::Expr(Expr::Binary(left, op, _)) => {
+                if let Some(method) = op.method() {
+                    let left = self.type_of(left.node(), locals);
+                    self.method(left, method, false);
+                }
+            }
+            AstNode::Expr(Expr::Index(base, _)) => {
+                let base = self.type_of(base.node(), locals);
+                self.method(base, "index", false);
+            }
+            // `module.func(...)` parses like an enum constructor.
+            AstNode::Expr(Expr::EnumConstructor(name, member, _)) if name == numerics::MODULE => self.effects.allocates |= numerics::allocates(member),
+            AstNode::Expr(Expr::EnumConstructor(name, ..)) if !self.enums.contains(name.as_str()) => self.effects.unknown = true,
+            AstNode::Expr(Expr::ArrayLiteral(_) | Expr::MapLiteral(_)) => self.effects.allocates = true,
+            // Creating a closure allocates; what it does counts where it is called.
+            AstNode::Expr(Expr::Lambda(..)) => {
+                self.effects.allocates = true;
+                return;
+            }
+            AstNode::Item(Item::FuncDecl(..) | Item::ImplDecl(..)) => return,
+            _ => {}
+        }
+        match node {
+            AstNode::Stmt(Stmt::VarDecl(name, typ, init)) => {
+                self.walk(init.node(), locals);
+                let typ = typ.clone().or_else(|| self.type_of(init.node(), locals));
+                locals.insert(name.clone(), typ);
+            }
+            AstNode::Item(Item::ConstDecl(name, typ, init)) => {
+                self.walk(init.node(), locals);
+                locals.insert(name.clone(), Some(typ.clone()));
+            }
+            AstNode::Stmt(Stmt::TupleDecl(names, typ, init)) => {
+                self.walk(init.node(), locals);
+                let typ = typ.clone().or_else(|| self.type_of(init.node(), locals));
+                for (i, name) in names.iter().enumerate() {
+                    locals.insert(name.clone(), typ.as_ref().and_then(|typ| element(typ, i)));
+                }
+            }
+            AstNode::Expr(Expr::Match(scrutinee, arms)) => {
+                self.walk(scrutinee.node(), locals);
+                for (pattern, body) in arms {
+                    let mut scope = locals.clone();
+                    bind(pattern, &mut scope);
+                    self.walk(body.node(), &mut scope);
+                }
+            }
+            AstNode::Expr(Expr::Block(_)) => {
+                let mut scope = locals.clone();
+                node.for_each_child(&mut |child| self.walk(child, &mut scope));
+            }
+            _ => node.for_each_child(&mut |child| self.walk(child, locals)),
+        }
+    }
+
+    // `write` and `format` call `to_string` on whatever values they find
+    // inside the one they are given.
+    fn renders(&mut self) {
+        self.calls_all("to_string");
+    }
+
+    fn calls_all(&mut self, method: &str) {
+        if let Some(methods) = self.methods.get(method) {
+            self.calls.extend(methods.iter().cloned());
+        }
+    }
+
+    // A call of method `name` on a value of type `receiver`. Operators only
+    // call methods on user types; when the type is not evident, every
+    // method of that name may be the one called.
+    fn method(&mut self, receiver: Option<ViraType>, name: &str, required: bool) {
+        match (receiver, self.methods.get(name)) {
+            (Some(ViraType::Struct(typ) | ViraType::Enum(typ)), Some(methods)) => {
+                let qualified = format!("{}::{}", typ, name);
+                if methods.contains(&qualified) {
+                    self.calls.insert(qualified);
+                } else if required {
+                    // A trait object: any implementation may run.
+                    self.calls.extend(methods.iter().cloned());
+                }
+            }
+            (None | Some(ViraType::TypeVar(_)), Some(methods)) => self.calls.extend(methods.iter().cloned()),
+            (None | Some(ViraType::TypeVar(_) | ViraType::Struct(_) | ViraType::Enum(_)), None) if required => self.effects.unknown = true,
+            _ => {}
+        }
+    }
+
+    // The type of an expression where it is evident from declarations.
+    fn type_of(&self, id: NodeId, locals: &Scope) -> Option<ViraType> {
+        match &self.arena[id] {
+            AstNode::Expr(Expr::Literal(_)) => Some(ViraType::Int),
+            AstNode::Expr(Expr::FloatLiteral(_)) => Some(ViraType::Float),
+            AstNode::Expr(Expr::BoolLiteral(_)) => Some(ViraType::Bool),
+            AstNode::Expr(Expr::StringLiteral(_)) => Some(ViraType::String),
+            AstNode::Expr(Expr::StructLiteral(name, _)) => Some(ViraType::Struct(name.clone())),
+            AstNode::Expr(Expr::Range(..)) => Some(ViraType::Range),
+            AstNode::Expr(Expr::VarRef(name)) => match locals.get(name) {
+                Some(typ) => typ.clone(),
+                None => self.globals.get(name.as_str()).cloned().flatten(),
+            },
+            AstNode::Expr(Expr::Call(name, _)) if !locals.contains_key(name) => self.functions.get(name.as_str()).map(|ret| (*ret).clone()),
+            AstNode::Expr(Expr::MethodCall(receiver, method, args)) => {
+                let receiver = self.type_of(receiver.node(), locals)?;
+                let builtin = builtins::method(Receiver::of_type(&receiver)?, method)?;
+                let args: Vec<_> = std::iter::once(Some(receiver)).chain(args.iter().map(|arg| self.type_of(arg.node(), locals))).collect();
+                builtins::return_type(builtin.builtin, &args)
+            }
+            AstNode::Expr(Expr::Index(base, _)) => match self.type_of(base.node(), locals)? {
+                ViraType::Array(element) => Some(*element),
+                ViraType::Range => Some(ViraType::Int),
+                _ => None,
+            },
+            AstNode::Expr(Expr::Unary(_, operand)) => self.type_of(operand.node(), locals),
+            AstNode::Expr(Expr::IntToFloat(_)) => Some(ViraType::Float),
+            AstNode::Expr(Expr::Binary(_, BinOp::Eq | BinOp::Neq | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge | BinOp::And | BinOp::Or, _)) => Some(ViraType::Bool),
+            AstNode::Expr(Expr::Binary(left, ..)) => match self.type_of(left.node(), locals)? {
+                typ @ (ViraType::Int | ViraType::Float | ViraType::String) => Some(typ),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+// The method a builtin calls on the structs and enums it is given.
+fn user_method(builtin: &str) -> Option<&'static str> {
+    match builtin {
+        "hash" => Some("hash"),
+        "json" => Some("to_json"),
+        "clone" => Some("clone"),
+        _ => None,
+    }
+}
+
+// The type of element `i` of a tuple type.
+fn element(typ: &ViraType, i: usize) -> Option<ViraType> {
+    match typ {
+        ViraType::Tuple(types) => types.get(i).cloned(),
+        _ => None,
+    }
+}
+
+fn bind(pattern: &Pattern, scope: &mut Scope) {
+    match pattern {
+        Pattern::Binding(name) => {
+            scope.insert(name.clone(), None);
+        }
+        Pattern::EnumVariant(_, _, fields) => fields.iter().for_each(|field| bind(field, scope)),
+        _ => {}
+    }
+}
+
+pub fn report(functions: &[(String, Effects)]) -> String {
+    let width = functions.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max("Function".len());
+    let mut out = format!("\n{:<width$}  Effects\n", "Function");
+    for (name, effects) in functions {
+        out.push_str(&format!("{:<width$}  {}\n", name, effects));
+    }
+    out
+}
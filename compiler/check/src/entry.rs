@@ -0,0 +1,38 @@
+use vira_syntax::arena::{Arena, NodeId};
+use vira_syntax::ast::{AstNode, Expr, Item, ViraType};
+
+// The entry point of a program: its top-level statements, run in order, or a
+// `func main() -> int`, which is called after the declarations and whose
+// result is the exit code. A program has one or the other; declarations sit
+// beside either. Only the entry module is checked, since an imported
+// module's top level runs as it is loaded.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    TopLevel,
+    Main,
+}
+
+pub fn check(arena: &Arena, items: &[NodeId]) -> Result<Entry, String> {
+    let position = |id: NodeId| arena.span(id).map_or(String::new(), |((line, column), _)| format!("{}:{}: ", line, column));
+    let main = items.iter().copied().find(|&id| matches!(&arena[arena.item(id)], AstNode::Item(Item::FuncDecl(name, ..)) if name == "main"));
+    let Some(main) = main else {
+        return Ok(Entry::TopLevel);
+    };
+    if !matches!(&arena[arena.item(main)], AstNode::Item(Item::FuncDecl(_, generics, params, ViraType::Int, _)) if generics.is_empty() && params.is_empty()) {
+        return Err(format!("{}'main' must be declared as 'func main() -> int'.", position(main)));
+    }
+    if let Some(&statement) = items.iter().find(|&&id| !matches!(arena[id], AstNode::Item(_))) {
+        return Err(format!("{}A program with 'func main' cannot have top-level statements; move this one into main.", position(statement)));
+    }
+    Ok(Entry::Main)
+}
+
+// Checks the entry module and, when it has a main, ends it with the call.
+pub fn prepare(arena: &mut Arena, items: &mut Vec<NodeId>) -> Result<Entry, String> {
+    let entry = check(arena, items)?;
+    if entry == Entry::Main {
+        items.push(arena.alloc(Expr::Call("main".to_string(), Vec::new())));
+    }
+    Ok(entry)
+}
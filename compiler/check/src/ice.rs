@@ -0,0 +1,137 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::panic;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use vira_syntax::parser;
+
+// Internal compiler error (ICE) reporting. The driver records which phase is
+// running, and a crash while parsing is placed at the token the parser was
+// at, so a panic anywhere in the compiler can be turned into a report plus a
+// reproduction file.
+
+#[derive(Default, Clone)]
+struct IceContext {
+    phase: &'static str,
+    file: String,
+    source: String,
+    line: usize,
+    col: usize,
+    message: String,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<IceContext> = RefCell::new(IceContext::default());
+}
+
+// Minimization re-runs the phases below on reduced inputs. It never executes
+// the program, since a reduced program may loop forever or have side effects.
+const MINIMIZABLE_PHASES: [&str; 3] = ["tokenize", "parse", "codegen"];
+const MAX_MINIMIZE_ATTEMPTS: usize = 500;
+
+pub fn install_hook() {
+    panic::set_hook(Box::new(|info| {
+        let message = if let Some(s) = info.payload().downcast_ref::<&str>() {
+            s.to_string()
+        } else if let Some(s) = info.payload().downcast_ref::<String>() {
+            s.clone()
+        } else {
+            "unknown panic".to_string()
+        };
+        let location = info.location().map(|l| format!(" at {}:{}", l.file(), l.line())).unwrap_or_default();
+        CONTEXT.with(|ctx| {
+            let mut ctx = ctx.borrow_mut();
+            ctx.message = format!("{}{}", message, location);
+            if ctx.phase == "parse" {
+                (ctx.line, ctx.col) = parser::position();
+            }
+        });
+    }));
+}
+
+pub fn set_source(file: &str, source: &str) {
+    CONTEXT.with(|ctx| {
+        let mut ctx = ctx.borrow_mut();
+        ctx.file = file.to_string();
+        ctx.source = source.to_string();
+        ctx.line = 0;
+        ctx.col = 0;
+    });
+}
+
+pub fn enter_phase(phase: &'static str) {
+    CONTEXT.with(|ctx| {
+        let mut ctx = ctx.borrow_mut();
+        ctx.phase = phase;
+        ctx.line = 0;
+        ctx.col = 0;
+    });
+}
+
+pub fn report(minimize: bool, still_crashes: impl Fn(&str) -> bool) {
+    let ctx = CONTEXT.with(|ctx| ctx.borrow().clone());
+    eprintln!("error: internal compiler error: the Vira compiler crashed");
+    eprintln!("  version: vira-compiler {}", env!("CARGO_PKG_VERSION"));
+    eprintln!("  phase:   {}", if ctx.phase.is_empty() { "startup" } else { ctx.phase });
+    if ctx.line > 0 {
+        eprintln!("  span:    {}:{}:{}", ctx.file, ctx.line, ctx.col);
+    }
+    eprintln!("  panic:   {}", ctx.message);
+    if ctx.source.is_empty() {
+        return;
+    }
+
+    let mut reproduction = ctx.source.clone();
+    if minimize {
+        if MINIMIZABLE_PHASES.contains(&ctx.phase) {
+            reproduction = minimize_source(&ctx.source, &still_crashes);
+        } else {
+            eprintln!("note: minimization is not available for crashes during '{}'", ctx.phase);
+        }
+    }
+    match write_reproduction(&reproduction) {
+        Ok(path) => eprintln!("note: wrote a reproduction to {}", path.display()),
+        Err(e) => eprintln!("note: could not write a reproduction file: {}", e),
+    }
+    eprintln!("note: please file a bug report with the reproduction at https://github.com/Vira-Lang/vira/issues");
+}
+
+fn write_reproduction(source: &str) -> Result<PathBuf, String> {
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let path = env::temp_dir().join(format!("vira-ice-{}.vira", stamp));
+    fs::write(&path, source).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+// Line-based delta debugging: drop chunks of lines, halving the chunk size
+// whenever no chunk can be removed, as long as the reduced input still crashes.
+fn minimize_source(source: &str, still_crashes: &impl Fn(&str) -> bool) -> String {
+    let mut lines: Vec<&str> = source.lines().collect();
+    let mut chunk = lines.len().div_ceil(2).max(1);
+    let mut attempts = 0;
+    while attempts < MAX_MINIMIZE_ATTEMPTS {
+        let mut removed = false;
+        let mut start = 0;
+        while start < lines.len() && attempts < MAX_MINIMIZE_ATTEMPTS {
+            let end = (start + chunk).min(lines.len());
+            let candidate: Vec<&str> = lines[..start].iter().chain(&lines[end..]).copied().collect();
+            attempts += 1;
+            if !candidate.is_empty() && still_crashes(&candidate.join("\n")) {
+                lines = candidate;
+                removed = true;
+            } else {
+                start = end;
+            }
+        }
+        if !removed {
+            if chunk == 1 {
+                break;
+            }
+            chunk = chunk.div_ceil(2);
+        }
+    }
+    eprintln!("note: minimized reproduction from {} to {} line(s)", source.lines().count(), lines.len());
+    lines.join("\n") + "\n"
+}
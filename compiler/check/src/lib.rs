@@ -0,0 +1,14 @@
+#![deny(clippy::unwrap_used)]
+
+// From a parsed tree to a checked one: compile-time evaluation of constants,
+// which the parser is given to fold them with, `@derive` expansion, name
+// resolution, the effect analysis, the check of a program's entry point,
+// and the bookkeeping that turns a crash in any later stage into an internal
+// compiler error report.
+
+pub mod consteval;
+pub mod derive;
+pub mod effects;
+pub mod entry;
+pub mod ice;
+pub mod resolve;
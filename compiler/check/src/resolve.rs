@@ -0,0 +1,495 @@
+use std::collections::{HashMap, HashSet};
+
+use vira_rt::builtins;
+use vira_rt::sorting;
+use vira_syntax::arena::{Arena, NodeId, Position};
+use vira_syntax::ast::{AstNode, Expr, Item, Pattern, Stmt, ViraType};
+
+// Name resolution: every variable a module reads or assigns and every
+// function it calls has to be declared where it is used, which the
+// interpreter would otherwise only find out when it got there. Scopes are
+// those of compiled code: a block, branch, loop body, match arm or handler
+// ends what it declares. A name declared later in an enclosing scope is used
+// before its declaration, unless the use is inside a function declared in
+// between: a named function runs when it is called, so its body sees every
+// declaration of the scopes around it. A lambda captures what is declared
+// when it is created, so it does not. Calls into other modules, written
+// `module::name`, are resolved when they run.
+//
+// Named functions are defined when their scope starts, so they can be called
+// ahead of their declaration, as long as nothing they use, themselves or
+// through the functions they call, is a variable of that scope declared
+// after the call.
+//
+// A variable declared with `let` or bound by `for`, or a named function,
+// that nothing uses is worth a warning. Assigning a variable is not using
+// it, and neither is a function calling itself. Names starting with `_` are
+// meant to be unused, and `pub` and `@test` functions are used from outside.
+
+// Checks `program` after the names in `known`, such as earlier REPL lines, or
+// as a module on its own when there are none. Returns the warnings; the
+// top-level declarations of code run after `known` are left for what comes
+// next, so only those of a module on its own can be unused.
+pub fn check(arena: &Arena, program: &[NodeId], known: Option<&HashSet<String>>, shadowing: bool) -> Result<Vec<String>, String> {
+    let empty = HashSet::new();
+    let mut resolver = Resolver {
+        arena,
+        known: known.unwrap_or(&empty),
+        scopes: Vec::new(),
+        functions: 0,
+        at: (1, 1),
+        errors: Vec::new(),
+        used: HashSet::new(),
+        enclosing: Vec::new(),
+        uses: HashMap::new(),
+        exempt: HashSet::new(),
+        warnings: Vec::new(),
+        shadowing,
+        constants: program
+            .iter()
+            .filter_map(|&stmt| match &arena[arena.item(stmt)] {
+                AstNode::Item(Item::ConstDecl(name, ..) | Item::DataDecl(name, ..)) => Some(name.as_str()),
+                AstNode::Item(Item::ConfigDecl(_)) => Some("config"),
+                _ => None,
+            })
+            .collect(),
+    };
+    resolver.enter(program);
+    for &stmt in program {
+        resolver.visit(stmt);
+    }
+    let top = resolver.scopes.pop();
+    if let Some(top) = &top {
+        resolver.early_calls(top);
+    }
+    if let (None, Some(top)) = (known, top) {
+        resolver.unused(top);
+    }
+    if resolver.errors.is_empty() {
+        resolver.warnings.sort_by_key(|(at, _)| *at);
+        Ok(resolver.warnings.into_iter().map(|(_, warning)| warning).collect())
+    } else {
+        Err(resolver.errors.join("\n"))
+    }
+}
+
+// The names the statements declare directly, with the declaring node, the
+// first one of a name kept.
+pub fn declarations<'a>(arena: &'a Arena, stmts: &[NodeId]) -> HashMap<&'a str, NodeId> {
+    let mut declared = HashMap::new();
+    for &stmt in stmts {
+        let names: Vec<&str> = match &arena[arena.item(stmt)] {
+            AstNode::Stmt(Stmt::VarDecl(name, ..)) | AstNode::Item(Item::FuncDecl(name, ..) | Item::ConstDecl(name, ..) | Item::DataDecl(name, ..)) => vec![name],
+            AstNode::Stmt(Stmt::TupleDecl(names, ..)) => names.iter().map(|name| name.as_str()).collect(),
+            AstNode::Item(Item::ConfigDecl(_)) => vec!["config"],
+            _ => Vec::new(),
+        };
+        for name in names {
+            declared.entry(name).or_insert(arena.item(stmt));
+        }
+    }
+    declared
+}
+
+struct Scope<'a> {
+    // The declaration of each name declared so far, for those that can go
+    // unused.
+    declared: HashMap<&'a str, Option<NodeId>>,
+    // What the scope declares further on.
+    ahead: HashMap<&'a str, NodeId>,
+    // How many function bodies enclose the scope.
+    functions: usize,
+    // What the scope declared that can go unused, in order.
+    unused: Vec<(&'a str, NodeId)>,
+    // The calls of its functions made ahead of their declarations: where,
+    // and the function's name and declaration.
+    early: Vec<(Position, &'a str, NodeId)>,
+}
+
+struct Resolver<'a> {
+    arena: &'a Arena,
+    // Names declared before the module, such as earlier REPL lines.
+    known: &'a HashSet<String>,
+    scopes: Vec<Scope<'a>>,
+    functions: usize,
+    at: Position,
+    errors: Vec<String>,
+    // Declarations that something uses, by node and name, as a `let` of a
+    // tuple declares several names.
+    used: HashSet<(NodeId, &'a str)>,
+    // The named functions whose bodies are being visited.
+    enclosing: Vec<NodeId>,
+    // The declarations each named function's body refers to, with their
+    // names, for the calls made ahead of it.
+    uses: HashMap<NodeId, Vec<(&'a str, NodeId)>>,
+    exempt: HashSet<NodeId>,
+    warnings: Vec<(Position, String)>,
+    // Whether to warn about variables that shadow an outer name.
+    shadowing: bool,
+    // The names of the module's constants.
+    constants: HashSet<&'a str>,
+}
+
+enum Lookup {
+    // Declared already, by the node, when it is one that can go unused.
+    Found(Option<NodeId>),
+    // Declared further on, by the node.
+    Ahead(NodeId),
+    Missing,
+}
+
+impl<'a> Resolver<'a> {
+    fn error(&mut self, message: String) {
+        self.errors.push(format!("{}:{}: {}", self.at.0, self.at.1, message));
+    }
+
+    fn lookup(&self, name: &str) -> Lookup {
+        for scope in self.scopes.iter().rev() {
+            if let Some(&decl) = scope.declared.get(name) {
+                return Lookup::Found(decl);
+            }
+            if let Some(&decl) = scope.ahead.get(name) {
+                if self.functions > scope.functions {
+                    return Lookup::Found(Some(decl));
+                }
+                return Lookup::Ahead(decl);
+            }
+        }
+        if self.known.contains(name) {
+            Lookup::Found(None)
+        } else {
+            Lookup::Missing
+        }
+    }
+
+    fn is_function(&self, decl: NodeId) -> bool {
+        matches!(self.arena[decl], AstNode::Item(Item::FuncDecl(..)))
+    }
+
+    // A function used ahead of its declaration.
+    fn early(&mut self, name: &'a str, decl: NodeId) {
+        self.use_of(name, Some(decl));
+        let at = self.at;
+        if let Some(scope) = self.scopes.iter_mut().rev().find(|scope| scope.ahead.get(name) == Some(&decl)) {
+            scope.early.push((at, name, decl));
+        }
+    }
+
+    fn position(&self, decl: NodeId) -> Option<Position> {
+        self.arena.span(decl).map(|(start, _)| start)
+    }
+
+    fn refers_to(&mut self, name: &'a str, decl: Option<NodeId>) {
+        if let Some(decl) = decl {
+            for &function in &self.enclosing {
+                self.uses.entry(function).or_default().push((name, decl));
+            }
+        }
+    }
+
+    fn use_of(&mut self, name: &'a str, decl: Option<NodeId>) {
+        self.refers_to(name, decl);
+        if let Some(decl) = decl.filter(|decl| !self.enclosing.contains(decl)) {
+            self.used.insert((decl, name));
+        }
+    }
+
+    fn declare(&mut self, name: &'a str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.declared.insert(name, None);
+        }
+    }
+
+    // Declares `name` at `decl`, a declaration that can go unused. A function
+    // or variable declared twice in one scope would replace the first, so
+    // that is an error; a scope inside can declare the name again, which
+    // hides the outer one until the scope ends. With `shadowing`, such a
+    // variable is worth a warning too. Constants, `data` and `config` are the
+    // module's in every scope, so no `let` can take their names.
+    fn declare_at(&mut self, name: &'a str, decl: NodeId) {
+        if self.constants.contains(name) && matches!(self.arena[decl], AstNode::Stmt(Stmt::VarDecl(..) | Stmt::TupleDecl(..))) {
+            self.error(format!("Cannot redeclare constant '{}'.", name));
+        }
+        let first = self.scopes.last().and_then(|scope| scope.declared.get(name).copied().flatten());
+        if let Some((first, (line, column))) = first.and_then(|first| Some((first, self.arena.span(first)?.0))) {
+            match &self.arena[first] {
+                AstNode::Item(Item::FuncDecl(..)) => self.error(format!("Function '{}' is already defined at {}:{}.", name, line, column)),
+                _ => self.error(format!("Variable '{}' is already declared in this scope at {}:{}.", name, line, column)),
+            }
+        }
+        // A top-level `func main` is called as the entry point.
+        let entry = name == "main" && self.scopes.len() == 1 && matches!(self.arena[decl], AstNode::Item(Item::FuncDecl(..)));
+        let exempt = name.starts_with('_') || entry || self.exempt.contains(&decl);
+        if self.shadowing && !name.starts_with('_') && !matches!(self.arena[decl], AstNode::Item(Item::FuncDecl(..))) {
+            self.shadows(name, decl);
+        }
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.declared.insert(name, Some(decl));
+            if !exempt {
+                scope.unused.push((name, decl));
+            }
+        }
+    }
+
+    // Warns when the variable `name` declared at `decl` hides one of an
+    // enclosing scope.
+    fn shadows(&mut self, name: &str, decl: NodeId) {
+        let Some(&outer) = self.scopes.iter().rev().skip(1).find_map(|scope| scope.declared.get(name)) else {
+            return;
+        };
+        let (line, column) = self.arena.span(decl).map_or(self.at, |(start, _)| start);
+        let warning = match outer.and_then(|outer| self.arena.span(outer)) {
+            Some(((outer_line, outer_column), _)) => {
+                format!("{}:{}: warning: variable '{}' shadows the one declared at {}:{}", line, column, name, outer_line, outer_column)
+            }
+            None => format!("{}:{}: warning: variable '{}' shadows an outer one", line, column, name),
+        };
+        self.warnings.push(((line, column), warning));
+    }
+
+    fn enter(&mut self, stmts: &[NodeId]) {
+        let ahead = declarations(self.arena, stmts);
+        self.scopes.push(Scope { declared: HashMap::new(), ahead, functions: self.functions, unused: Vec::new(), early: Vec::new() });
+    }
+
+    fn leave(&mut self) {
+        if let Some(scope) = self.scopes.pop() {
+            self.early_calls(&scope);
+            self.unused(scope);
+        }
+    }
+
+    // Checks the calls made ahead of the scope's functions against the
+    // scope's variables that are only declared after them.
+    fn early_calls(&mut self, scope: &Scope<'a>) {
+        for &(at, function, decl) in &scope.early {
+            let mut seen = HashSet::from([decl]);
+            let mut pending = vec![decl];
+            let mut late: Option<(&str, Position)> = None;
+            while let Some(next) = pending.pop() {
+                for &(name, used) in self.uses.get(&next).into_iter().flatten() {
+                    match &self.arena[used] {
+                        AstNode::Item(Item::FuncDecl(..)) if seen.insert(used) => pending.push(used),
+                        AstNode::Stmt(Stmt::VarDecl(..) | Stmt::TupleDecl(..)) if scope.ahead.get(name) == Some(&used) => {
+                            if let Some(declared) = self.position(used).filter(|&declared| declared > at) {
+                                if late.is_none_or(|(_, first)| declared < first) {
+                                    late = Some((name, declared));
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            if let Some((name, declared)) = late {
+                let message = format!("Function '{}' is called before the declaration of '{}'{}, which it uses.", function, name, on_line(Some(declared)));
+                self.errors.push(format!("{}:{}: {}", at.0, at.1, message));
+            }
+        }
+    }
+
+    fn unused(&mut self, scope: Scope<'a>) {
+        for (name, decl) in scope.unused {
+            if self.used.contains(&(decl, name)) {
+                continue;
+            }
+            let what = match &self.arena[decl] {
+                AstNode::Item(Item::FuncDecl(..)) => "function",
+                _ => "variable",
+            };
+            let (line, column) = self.arena.span(decl).map_or(self.at, |(start, _)| start);
+            self.warnings.push(((line, column), format!("{}:{}: warning: {} '{}' is never used", line, column, what, name)));
+        }
+    }
+
+    fn block(&mut self, stmts: &[NodeId]) {
+        self.enter(stmts);
+        for &stmt in stmts {
+            self.visit(stmt);
+        }
+        self.leave();
+    }
+
+    // `id` in a scope of its own that starts with `names` declared.
+    fn scoped(&mut self, names: &[&'a str], id: NodeId) {
+        self.enter(&[]);
+        for name in names {
+            self.declare(name);
+        }
+        self.visit(id);
+        self.leave();
+    }
+
+    fn function(&mut self, params: &'a [(String, ViraType)], body: NodeId) {
+        self.functions += 1;
+        let params: Vec<&str> = params.iter().map(|(name, _)| name.as_str()).collect();
+        self.scoped(&params, body);
+        self.functions -= 1;
+    }
+
+    // A variable that is read, or only assigned.
+    fn variable(&mut self, name: &'a str, read: bool) {
+        match self.lookup(name) {
+            Lookup::Found(decl) if read => self.use_of(name, decl),
+            // Not a use, but the variable has to exist.
+            Lookup::Found(decl) => self.refers_to(name, decl),
+            Lookup::Ahead(decl) if read && self.is_function(decl) => self.early(name, decl),
+            Lookup::Ahead(decl) => self.error(format!("Variable '{}' is used before its declaration{}.", name, on_line(self.position(decl)))),
+            Lookup::Missing => self.error(format!("Undefined variable '{}'.", name)),
+        }
+    }
+
+    fn call(&mut self, name: &'a str) {
+        match self.lookup(name) {
+            Lookup::Found(decl) => self.use_of(name, decl),
+            Lookup::Missing if builtins::is_function(name) || sorting::is_function(name) => {}
+            Lookup::Ahead(decl) if self.is_function(decl) => self.early(name, decl),
+            Lookup::Ahead(decl) => self.error(format!("Function '{}' is called before its declaration{}.", name, on_line(self.position(decl)))),
+            Lookup::Missing => self.error(format!("Undefined function '{}'.", name)),
+        }
+    }
+
+    fn visit(&mut self, id: NodeId) {
+        let outer = self.at;
+        if let Some((start, _)) = self.arena.span(id) {
+            self.at = start;
+        }
+        self.node(id);
+        self.at = outer;
+    }
+
+    fn node(&mut self, id: NodeId) {
+        let arena = self.arena;
+        match &arena[id] {
+            AstNode::Expr(Expr::VarRef(name)) => self.variable(name, true),
+            AstNode::Stmt(Stmt::Assign(name, value)) => {
+                self.visit(value.node());
+                self.variable(name, false);
+            }
+            AstNode::Expr(Expr::Call(name, args)) => {
+                self.call(name);
+                for &arg in args {
+                    self.visit(arg.node());
+                }
+            }
+            AstNode::Stmt(Stmt::VarDecl(name, _, init)) => {
+                self.visit(init.node());
+                self.declare_at(name, id);
+            }
+            // The initializer is folded to a literal, but what it used is
+            // still used.
+            AstNode::Item(Item::ConstDecl(name, _, init)) => {
+                self.visit(arena.folded_from(id).unwrap_or(init.node()));
+                self.declare(name);
+            }
+            AstNode::Stmt(Stmt::TupleDecl(names, _, init)) => {
+                self.visit(init.node());
+                for name in names {
+                    self.declare_at(name, id);
+                }
+            }
+            AstNode::Item(Item::DataDecl(name, ..)) => self.declare(name),
+            AstNode::Item(Item::ConfigDecl(entries)) => {
+                for (_, value) in entries {
+                    self.visit(value.node());
+                }
+                self.declare("config");
+            }
+            // Declared before its body, so it can call itself.
+            AstNode::Item(Item::FuncDecl(name, _, params, _, body)) => {
+                self.declare_at(name, id);
+                self.enclosing.push(id);
+                self.function(params, *body);
+                self.enclosing.pop();
+            }
+            AstNode::Item(Item::ImplDecl(_, _, methods)) => {
+                for &method in methods {
+                    if let Item::FuncDecl(_, _, params, _, body) = &arena[method] {
+                        self.function(params, *body);
+                    }
+                }
+            }
+            AstNode::Expr(Expr::Lambda(params, _, body)) => {
+                let params: Vec<&str> = params.iter().map(|(name, _)| name.as_str()).collect();
+                self.scoped(&params, body.node());
+            }
+            AstNode::Expr(Expr::Block(stmts)) => self.block(stmts),
+            AstNode::Expr(Expr::If(condition, then, otherwise)) => {
+                self.visit(condition.node());
+                self.scoped(&[], *then);
+                if let Some(otherwise) = otherwise {
+                    self.scoped(&[], *otherwise);
+                }
+            }
+            AstNode::Stmt(Stmt::While(condition, body)) => {
+                self.visit(condition.node());
+                self.scoped(&[], *body);
+            }
+            AstNode::Stmt(Stmt::Try(body, name, handler)) => {
+                self.scoped(&[], body.node());
+                self.scoped(&[name], handler.node());
+            }
+            AstNode::Expr(Expr::Match(scrutinee, arms)) => {
+                self.visit(scrutinee.node());
+                for (pattern, body) in arms {
+                    let mut names = Vec::new();
+                    bindings(pattern, &mut names);
+                    self.scoped(&names, body.node());
+                }
+            }
+            // The arguments of an attribute, such as a contract, are checked
+            // by what reads them.
+            AstNode::Item(Item::Attribute(name, _, item)) => {
+                if name == "test" {
+                    self.exempt.insert(arena.item(*item));
+                }
+                self.visit(*item)
+            }
+            AstNode::Item(Item::Pub(item)) => {
+                self.exempt.insert(arena.item(*item));
+                self.visit(*item)
+            }
+            node => node.for_each_child(&mut |child| self.visit(child)),
+        }
+    }
+}
+
+fn bindings<'a>(pattern: &'a Pattern, names: &mut Vec<&'a str>) {
+    match pattern {
+        Pattern::Binding(name) => names.push(name),
+        Pattern::EnumVariant(_, _, fields) => fields.iter().for_each(|field| bindings(field, names)),
+        _ => {}
+    }
+}
+
+fn on_line(at: Option<Position>) -> String {
+    at.map_or(String::new(), |(line, _)| format!(" on line {}", line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vira_syntax::diagnostic;
+    use vira_syntax::parser::Parser;
+    use vira_syntax::tokenizer::tokenize;
+
+    fn resolve(source: &str) -> Result<Vec<String>, String> {
+        let mut arena = Arena::new();
+        let items = Parser::new(tokenize(source), &mut arena).parse().map_err(|errors| diagnostic::render(&errors))?;
+        check(&arena, &items, None, false)
+    }
+
+    #[test]
+    fn a_function_declared_twice_names_both() {
+        let source = "func foo() -> int { return 1 }\nfunc foo() -> int { return 2 }\nwrite foo()";
+        assert_eq!(resolve(source).err(), Some("2:1: Function 'foo' is already defined at 1:1.".to_string()));
+    }
+
+    #[test]
+    fn a_let_repeated_in_one_scope_names_both() {
+        let source = "{\n    let x = 1\n    let x = 2\n    write x\n}";
+        assert_eq!(resolve(source).err(), Some("3:5: Variable 'x' is already declared in this scope at 2:5.".to_string()));
+        // A scope inside may declare it again.
+        assert!(resolve("let x = 1\n{\n    let x = 2\n    write x\n}\nwrite x").is_ok());
+    }
+}
@@ -0,0 +1,208 @@
+use crate::arena::{Arena, NodeId, Position};
+use crate::ast::{AstNode, Expr, Item, Pattern, Stmt, UnaryOp, ViraType};
+use crate::json::{object, Json};
+use crate::printer::bin_op;
+
+// The parsed program as JSON, for `--emit ast`. Every node is an object
+// whose "node" member names its kind, followed by its "id", which is the
+// same every time the source is loaded, its "span" in the source, null for
+// nodes that loading made up, and its fields and children under fixed
+// names. Types and patterns are objects too, except the types without
+// parameters, which are their names. Tools depend on this shape: extend it
+// with new members rather than renaming old ones.
+
+pub fn program(arena: &Arena, modules: &[(String, Vec<NodeId>)]) -> Json {
+    let modules = modules
+        .iter()
+        .map(|(name, items)| object(vec![("name", Json::String(name.clone())), ("items", nodes(arena, items))]))
+        .collect();
+    object(vec![("modules", Json::Array(modules))])
+}
+
+fn nodes(arena: &Arena, ids: &[impl Into<NodeId> + Copy]) -> Json {
+    Json::Array(ids.iter().map(|&id| node(arena, id.into())).collect())
+}
+
+fn optional(arena: &Arena, id: Option<impl Into<NodeId>>) -> Json {
+    id.map_or(Json::Null, |id| node(arena, id.into()))
+}
+
+fn text(s: &str) -> Json {
+    Json::String(s.to_string())
+}
+
+fn string_list(names: &[String]) -> Json {
+    Json::Array(names.iter().map(|name| text(name)).collect())
+}
+
+fn param_list(params: &[(String, ViraType)]) -> Json {
+    Json::Array(params.iter().map(|(name, typ)| object(vec![("name", text(name)), ("type", vira_type(typ))])).collect())
+}
+
+fn node(arena: &Arena, id: NodeId) -> Json {
+    let n = |id: NodeId| node(arena, id);
+    let (kind, fields) = match &arena[id] {
+        AstNode::Expr(Expr::Literal(v)) => ("Literal", vec![("value", Json::Int(*v))]),
+        AstNode::Expr(Expr::FloatLiteral(v)) => ("FloatLiteral", vec![("value", Json::Float(*v))]),
+        AstNode::Expr(Expr::BoolLiteral(v)) => ("BoolLiteral", vec![("value", Json::Bool(*v))]),
+        AstNode::Expr(Expr::StringLiteral(s)) => ("StringLiteral", vec![("value", text(s))]),
+        AstNode::Expr(Expr::Nil) => ("Nil", vec![]),
+        AstNode::Expr(Expr::Binary(left, op, right)) => ("Binary", vec![("left", n(left.node())), ("op", text(bin_op(*op))), ("right", n(right.node()))]),
+        AstNode::Expr(Expr::Unary(op, operand)) => {
+            let op = match op {
+                UnaryOp::Neg => "-",
+                UnaryOp::Not => "!",
+            };
+            ("Unary", vec![("op", text(op)), ("operand", n(operand.node()))])
+        }
+        AstNode::Expr(Expr::IntToFloat(operand)) => ("IntToFloat", vec![("operand", n(operand.node()))]),
+        AstNode::Stmt(Stmt::VarDecl(name, typ, value)) => ("VarDecl", vec![("name", text(name)), ("type", typ.as_ref().map_or(Json::Null, vira_type)), ("value", n(value.node()))]),
+        AstNode::Stmt(Stmt::TupleDecl(names, typ, value)) => ("TupleDecl", vec![("names", string_list(names)), ("type", typ.as_ref().map_or(Json::Null, vira_type)), ("value", n(value.node()))]),
+        AstNode::Item(Item::ConstDecl(name, typ, value)) => ("ConstDecl", vec![("name", text(name)), ("type", vira_type(typ)), ("value", n(value.node()))]),
+        AstNode::Item(Item::ConfigDecl(entries)) => {
+            let entries = entries.iter().map(|(key, value)| object(vec![("key", text(key)), ("value", n(value.node()))])).collect();
+            ("ConfigDecl", vec![("entries", Json::Array(entries))])
+        }
+        AstNode::Item(Item::DataDecl(name, typ, delimiter, contents)) => (
+            "DataDecl",
+            vec![("name", text(name)), ("type", vira_type(typ)), ("delimiter", text(delimiter)), ("contents", text(contents))],
+        ),
+        AstNode::Stmt(Stmt::Assign(name, value)) => ("Assign", vec![("name", text(name)), ("value", n(value.node()))]),
+        AstNode::Stmt(Stmt::CompoundAssign(name, op, value)) => {
+            ("CompoundAssign", vec![("name", text(name)), ("op", text(bin_op(*op))), ("value", n(value.node()))])
+        }
+        AstNode::Expr(Expr::VarRef(name)) => ("VarRef", vec![("name", text(name))]),
+        AstNode::Item(Item::FuncDecl(name, type_params, params, ret, body)) => (
+            "FuncDecl",
+            vec![
+                ("name", text(name)),
+                ("type_params", string_list(type_params)),
+                ("params", param_list(params)),
+                ("return_type", vira_type(ret)),
+                ("body", n(*body)),
+            ],
+        ),
+        AstNode::Expr(Expr::Call(name, args)) => ("Call", vec![("name", text(name)), ("args", nodes(arena, args))]),
+        AstNode::Expr(Expr::If(condition, then, otherwise)) => {
+            ("If", vec![("condition", n(condition.node())), ("then", n(*then)), ("else", optional(arena, *otherwise))])
+        }
+        AstNode::Stmt(Stmt::While(condition, body)) => ("While", vec![("condition", n(condition.node())), ("body", n(*body))]),
+        AstNode::Stmt(Stmt::For(init, condition, increment, body)) => (
+            "For",
+            vec![("init", n(*init)), ("condition", n(condition.node())), ("increment", n(*increment)), ("body", n(*body))],
+        ),
+        AstNode::Stmt(Stmt::Return(value)) => ("Return", vec![("value", optional(arena, *value))]),
+        AstNode::Expr(Expr::Block(items)) => ("Block", vec![("items", nodes(arena, items))]),
+        AstNode::Stmt(Stmt::Write(value)) => ("Write", vec![("value", n(value.node()))]),
+        AstNode::Expr(Expr::ArrayLiteral(items)) => ("ArrayLiteral", vec![("items", nodes(arena, items))]),
+        AstNode::Expr(Expr::Interpolation(parts)) => ("Interpolation", vec![("parts", nodes(arena, parts))]),
+        AstNode::Expr(Expr::Index(base, index)) => ("Index", vec![("base", n(base.node())), ("index", n(index.node()))]),
+        AstNode::Item(Item::StructDecl(name, fields)) => ("StructDecl", vec![("name", text(name)), ("fields", param_list(fields))]),
+        AstNode::Expr(Expr::StructLiteral(name, fields)) => {
+            let fields = fields.iter().map(|(field, value)| object(vec![("name", text(field)), ("value", n(value.node()))])).collect();
+            ("StructLiteral", vec![("name", text(name)), ("fields", Json::Array(fields))])
+        }
+        AstNode::Expr(Expr::FieldAccess(base, field)) => ("FieldAccess", vec![("base", n(base.node())), ("field", text(field))]),
+        AstNode::Expr(Expr::SafeAccess(base, field)) => ("SafeAccess", vec![("base", n(base.node())), ("field", text(field))]),
+        AstNode::Expr(Expr::Coalesce(value, fallback)) => ("Coalesce", vec![("value", n(value.node())), ("fallback", n(fallback.node()))]),
+        AstNode::Expr(Expr::Tuple(items)) => ("Tuple", vec![("items", nodes(arena, items))]),
+        AstNode::Expr(Expr::TupleIndex(base, index)) => ("TupleIndex", vec![("base", n(base.node())), ("index", Json::Int(*index as i64))]),
+        AstNode::Expr(Expr::MapLiteral(entries)) => {
+            let entries = entries.iter().map(|(key, value)| object(vec![("key", n(key.node())), ("value", n(value.node()))])).collect();
+            ("MapLiteral", vec![("entries", Json::Array(entries))])
+        }
+        AstNode::Item(Item::EnumDecl(name, variants)) => {
+            let variants = variants
+                .iter()
+                .map(|(variant, payload, discriminant)| {
+                    object(vec![("name", text(variant)), ("payload", Json::Array(payload.iter().map(vira_type).collect())), ("discriminant", Json::Int(*discriminant))])
+                })
+                .collect();
+            ("EnumDecl", vec![("name", text(name)), ("variants", Json::Array(variants))])
+        }
+        AstNode::Expr(Expr::EnumConstructor(name, variant, args)) => {
+            ("EnumConstructor", vec![("enum", text(name)), ("variant", text(variant)), ("args", nodes(arena, args))])
+        }
+        AstNode::Item(Item::TraitDecl(name, methods)) => {
+            let methods = methods
+                .iter()
+                .map(|(method, params, ret)| object(vec![("name", text(method)), ("params", param_list(params)), ("return_type", vira_type(ret))]))
+                .collect();
+            ("TraitDecl", vec![("name", text(name)), ("methods", Json::Array(methods))])
+        }
+        AstNode::Item(Item::ImplDecl(trait_name, type_name, methods)) => (
+            "ImplDecl",
+            vec![
+                ("trait", trait_name.as_deref().map_or(Json::Null, text)),
+                ("type_name", text(type_name)),
+                ("methods", nodes(arena, methods)),
+            ],
+        ),
+        AstNode::Expr(Expr::MethodCall(receiver, method, args)) => {
+            ("MethodCall", vec![("receiver", n(receiver.node())), ("method", text(method)), ("args", nodes(arena, args))])
+        }
+        AstNode::Expr(Expr::Match(scrutinee, arms)) => {
+            let arms = arms.iter().map(|(p, body)| object(vec![("pattern", pattern(p)), ("body", n(body.node()))])).collect();
+            ("Match", vec![("scrutinee", n(scrutinee.node())), ("arms", Json::Array(arms))])
+        }
+        AstNode::Expr(Expr::Range(start, end)) => ("Range", vec![("start", n(start.node())), ("end", n(end.node()))]),
+        AstNode::Stmt(Stmt::ForIn(name, iterable, body)) => ("ForIn", vec![("name", text(name)), ("iterable", n(iterable.node())), ("body", n(*body))]),
+        AstNode::Expr(Expr::Lambda(params, ret, body)) => ("Lambda", vec![("params", param_list(params)), ("return_type", vira_type(ret)), ("body", n(body.node()))]),
+        AstNode::Item(Item::Pub(item)) => ("Pub", vec![("item", n(*item))]),
+        AstNode::Stmt(Stmt::Defer(statement)) => ("Defer", vec![("statement", n(*statement))]),
+        AstNode::Item(Item::TypeAlias(name, typ)) => ("TypeAlias", vec![("name", text(name)), ("type", vira_type(typ))]),
+        AstNode::Stmt(Stmt::Throw(value)) => ("Throw", vec![("value", n(value.node()))]),
+        AstNode::Stmt(Stmt::Try(body, name, handler)) => ("Try", vec![("body", n(body.node())), ("name", text(name)), ("handler", n(handler.node()))]),
+        AstNode::Item(Item::Attribute(name, args, item)) => ("Attribute", vec![("name", text(name)), ("args", nodes(arena, args)), ("item", n(*item))]),
+        AstNode::Expr(Expr::Apply(callee, args)) => ("Apply", vec![("callee", n(callee.node())), ("args", nodes(arena, args))]),
+        AstNode::Item(Item::Import(module)) => ("Import", vec![("module", text(module))]),
+    };
+    let span = arena.span(id).map_or(Json::Null, |(start, end)| object(vec![("start", position(start)), ("end", position(end))]));
+    let mut members = vec![("node", text(kind)), ("id", Json::Int(id.index() as i64)), ("span", span)];
+    members.extend(fields);
+    object(members)
+}
+
+fn position((line, col): Position) -> Json {
+    object(vec![("line", Json::Int(line as i64)), ("col", Json::Int(col as i64))])
+}
+
+fn vira_type(typ: &ViraType) -> Json {
+    match typ {
+        ViraType::Int => text("int"),
+        ViraType::Float => text("float"),
+        ViraType::Decimal => text("decimal"),
+        ViraType::Complex => text("complex"),
+        ViraType::Bool => text("bool"),
+        ViraType::String => text("string"),
+        ViraType::Range => text("range"),
+        ViraType::Array(element) => object(vec![("array", vira_type(element))]),
+        ViraType::Struct(name) => object(vec![("struct", text(name))]),
+        ViraType::Enum(name) => object(vec![("enum", text(name))]),
+        ViraType::TypeVar(name) => object(vec![("type_var", text(name))]),
+        ViraType::Function(params, ret) => {
+            object(vec![("function", object(vec![("params", Json::Array(params.iter().map(vira_type).collect())), ("return", vira_type(ret))]))])
+        }
+        ViraType::Tuple(elements) => object(vec![("tuple", Json::Array(elements.iter().map(vira_type).collect()))]),
+        ViraType::Map(key, value) => object(vec![("map", object(vec![("key", vira_type(key)), ("value", vira_type(value))]))]),
+        ViraType::Optional(inner) => object(vec![("optional", vira_type(inner))]),
+    }
+}
+
+fn pattern(p: &Pattern) -> Json {
+    let (kind, fields) = match p {
+        Pattern::Int(v) => ("Int", vec![("value", Json::Int(*v))]),
+        Pattern::Float(v) => ("Float", vec![("value", Json::Float(*v))]),
+        Pattern::Bool(v) => ("Bool", vec![("value", Json::Bool(*v))]),
+        Pattern::String(s) => ("String", vec![("value", text(s))]),
+        Pattern::Binding(name) => ("Binding", vec![("name", text(name))]),
+        Pattern::EnumVariant(name, variant, fields) => (
+            "EnumVariant",
+            vec![("enum", text(name)), ("variant", text(variant)), ("fields", Json::Array(fields.iter().map(pattern).collect()))],
+        ),
+        Pattern::Wildcard => ("Wildcard", vec![]),
+    };
+    let mut members = vec![("pattern", text(kind))];
+    members.extend(fields);
+    object(members)
+}
@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+
+use crate::arena::{Arena, NodeId};
+use crate::ast::{AstNode, Expr, Item, Program, Stmt, ViraType};
+use crate::engine::Engine;
+use crate::numerics;
+
+// Which constructs each engine can execute, named as in `AstNode::kind`.
+// Programs are checked against this after parsing, so an unsupported
+// construct is reported once, before anything runs, instead of failing
+// partway through execution. Keep the lists in step with the engines.
+
+// Anonymous functions that capture variables of the enclosing scope.
+const CLOSURES: &str = "closures";
+// `import numerics` without a module of that name in the program.
+const NUMERICS: &str = "the numerics module";
+
+const JIT: &[&str] = &[
+    "int literals",
+    "float literals",
+    "int to float conversions",
+    "bool literals",
+    "strings",
+    "unary operators",
+    "let declarations",
+    "tuple destructuring",
+    "constants",
+    "config blocks",
+    "data sections",
+    "assignments",
+    "compound assignments",
+    "variables",
+    "function declarations",
+    "function calls",
+    "blocks",
+    "if expressions",
+    "struct declarations",
+    "struct literals",
+    "field access",
+    "nil",
+    "optional field access",
+    "'??' operators",
+    "tuples",
+    "tuple indexing",
+    "enum declarations",
+    "enum constructors",
+    "trait declarations",
+    "impl blocks",
+    "method calls",
+    "match expressions",
+    "ranges",
+    "while loops",
+    "for loops",
+    "for-in loops",
+    "return statements",
+    "write statements",
+    "anonymous functions",
+    "calls of function values",
+    "maps",
+    "arrays",
+    "visibility modifiers",
+    "defer statements",
+    "type aliases",
+    "imports",
+    "throw statements",
+    "try statements",
+    "attributes",
+];
+
+// Codegen compiles operators on ints, floats, bools and strings, and those
+// that call an operator method of a struct or enum, and indexes arrays,
+// ranges, structs and enums. Telling takes static types, so codegen reports
+// the other uses, such as decimal arithmetic, itself.
+const OPERATORS: [&str; 2] = ["binary operators", "indexing"];
+
+fn supports(engine: Engine, feature: &str) -> bool {
+    match engine {
+        Engine::Interp => true,
+        Engine::Jit => JIT.contains(&feature) || OPERATORS.contains(&feature),
+    }
+}
+
+pub fn check(engine: Engine, program: &Program) -> Result<(), String> {
+    for (_, ast) in &program.modules {
+        let mut scope = HashSet::new();
+        for &node in ast {
+            let unsupported = match &program.arena[node] {
+                AstNode::Item(Item::Import(module)) if is_numerics(program, module) && !supports(engine, NUMERICS) => Some(NUMERICS),
+                _ => first_unsupported(engine, &program.arena, node, &mut scope),
+            };
+            if let Some(feature) = unsupported {
+                return Err(format!("The {} engine does not yet support {}; use --engine interp.", engine, feature));
+            }
+        }
+    }
+    Ok(())
+}
+
+// A module of the program shadows the builtin one.
+fn is_numerics(program: &Program, module: &str) -> bool {
+    module == numerics::MODULE && !program.modules.iter().any(|(name, _)| name == module)
+}
+
+// `scope` holds the variables declared so far, which is what an anonymous
+// function has to capture.
+fn first_unsupported(engine: Engine, arena: &Arena, id: NodeId, scope: &mut HashSet<String>) -> Option<&'static str> {
+    let node = &arena[id];
+    if !supports(engine, node.kind()) {
+        return Some(node.kind());
+    }
+    match node {
+        AstNode::Stmt(Stmt::VarDecl(name, _, _)) => {
+            scope.insert(name.clone());
+        }
+        AstNode::Stmt(Stmt::TupleDecl(names, _, _)) => scope.extend(names.iter().cloned()),
+        // Named functions, like anonymous ones, are compiled on their own.
+        AstNode::Expr(Expr::Lambda(params, _, body)) => return function(engine, arena, params, body.node(), scope),
+        AstNode::Item(Item::FuncDecl(_, _, params, _, body)) => return function(engine, arena, params, *body, scope),
+        // Methods are compiled on their own and only see their parameters.
+        AstNode::Item(Item::ImplDecl(_, _, methods)) => {
+            return methods.iter().find_map(|&method| match &arena[method] {
+                Item::FuncDecl(_, _, params, _, body) => {
+                    first_unsupported(engine, arena, *body, &mut params.iter().map(|(p, _)| p.clone()).collect())
+                }
+                _ => None,
+            });
+        }
+        _ => {}
+    }
+    let mut found = None;
+    node.for_each_child(&mut |child| {
+        if found.is_none() {
+            found = first_unsupported(engine, arena, child, scope);
+        }
+    });
+    found
+}
+
+fn function(engine: Engine, arena: &Arena, params: &[(String, ViraType)], body: NodeId, scope: &HashSet<String>) -> Option<&'static str> {
+    if !supports(engine, CLOSURES) && captures(arena, body, scope, &params.iter().map(|(p, _)| p.as_str()).collect()) {
+        return Some(CLOSURES);
+    }
+    let mut inner = scope.clone();
+    inner.extend(params.iter().map(|(p, _)| p.clone()));
+    first_unsupported(engine, arena, body, &mut inner)
+}
+
+fn captures(arena: &Arena, id: NodeId, scope: &HashSet<String>, params: &HashSet<&str>) -> bool {
+    let node = &arena[id];
+    if let AstNode::Expr(Expr::VarRef(name) | Expr::Call(name, _)) = node {
+        if scope.contains(name) && !params.contains(name.as_str()) {
+            return true;
+        }
+    }
+    let mut found = false;
+    node.for_each_child(&mut |child| found = found || captures(arena, child, scope, params));
+    found
+}
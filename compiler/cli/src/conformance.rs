@@ -0,0 +1,174 @@
+use crate::arena::Arena;
+use crate::ast::{BinOp, Program, UnaryOp, ViraType};
+use crate::diagnostic;
+use crate::engine::{Engine, RunOptions};
+use crate::interpreter::Value;
+use crate::lower;
+use crate::parser;
+use crate::printer::bin_op;
+use crate::profile::Profile;
+use crate::tokenizer::tokenize;
+use crate::typecheck::{binary_type, unary_type};
+
+// `conformance`: the operator semantics of `typecheck::binary_type` and
+// `unary_type` as tests. Every operator is applied to every pair of the
+// types below, and an engine has to agree with the table: give a value of
+// the type it names, or fail where it reports an error. The programs skip
+// the type checker, which applies the same table, so the engine itself is
+// what is tested. A case an engine reports it cannot run yet is skipped.
+//
+// Numbers also have to survive being printed: each of those below, printed
+// as the interpreter prints it, has to read back as a literal to the very
+// same value, the sign of a zero included. Every engine takes its literals
+// from the same parser, so the values the engines print and read agree. The
+// check needs the value back, so only the interpreter makes it.
+
+// A value of each type the table covers, as source.
+fn samples() -> Vec<(ViraType, &'static str)> {
+    vec![
+        (ViraType::Int, "7"),
+        (ViraType::Float, "2.5"),
+        (ViraType::Decimal, "decimal(\"1.5\")"),
+        (ViraType::Complex, "complex(1.0, 2.0)"),
+        (ViraType::Bool, "true"),
+        (ViraType::String, "\"ab\""),
+        (ViraType::Array(Box::new(ViraType::Int)), "[1, 2]"),
+        (ViraType::Tuple(vec![ViraType::Int, ViraType::Int]), "(1, 2)"),
+    ]
+}
+
+fn numbers() -> Vec<Value> {
+    let ints = [0, 1, -1, 10, i64::MAX, i64::MIN].map(Value::Int);
+    let floats = [0.0, -0.0, 1.0, -2.5, 0.1, 0.1 + 0.2, 1.0 / 3.0, 1e-7, 1e16, 1e300, 123456789.125, f64::EPSILON, f64::MIN_POSITIVE, 5e-324, f64::MAX, f64::MIN]
+        .map(Value::Float);
+    ints.into_iter().chain(floats).collect()
+}
+
+const BINARY: [BinOp; 13] = [
+    BinOp::Add,
+    BinOp::Sub,
+    BinOp::Mul,
+    BinOp::Div,
+    BinOp::Mod,
+    BinOp::Eq,
+    BinOp::Neq,
+    BinOp::Lt,
+    BinOp::Gt,
+    BinOp::Le,
+    BinOp::Ge,
+    BinOp::And,
+    BinOp::Or,
+];
+
+struct Case {
+    name: String,
+    source: String,
+    expected: Expected,
+}
+
+enum Expected {
+    // The type of the result, or the error the table reports.
+    Typed(Result<ViraType, String>),
+    // The number the source prints.
+    Number(Value),
+}
+
+fn cases() -> Vec<Case> {
+    let samples = samples();
+    let mut cases = Vec::new();
+    for &op in &BINARY {
+        for (left, a) in &samples {
+            for (right, b) in &samples {
+                let Some(expected) = binary_type(op, left, right).transpose() else {
+                    continue;
+                };
+                // `&&` and `||` are lowered to `if`s, which only the checker
+                // holds to bools.
+                if expected.is_ok() || !matches!(op, BinOp::And | BinOp::Or) {
+                    cases.push(Case {
+                        name: format!("{} {} {}", left, bin_op(op), right),
+                        source: format!("let a = {}\nlet b = {}\nlet c = a {} b\nc\n", a, b, bin_op(op)),
+                        expected: Expected::Typed(expected),
+                    });
+                }
+            }
+        }
+    }
+    for (op, symbol) in [(UnaryOp::Neg, "-"), (UnaryOp::Not, "!")] {
+        for (operand, a) in &samples {
+            if let Some(expected) = unary_type(op, operand).transpose() {
+                cases.push(Case {
+                    name: format!("{}{}", symbol, operand),
+                    source: format!("let a = {}\nlet b = {}a\nb\n", a, symbol),
+                    expected: Expected::Typed(expected),
+                });
+            }
+        }
+    }
+    for number in numbers() {
+        cases.push(Case { name: format!("reading back {}", number), source: format!("{}\n", number), expected: Expected::Number(number) });
+    }
+    cases
+}
+
+// Runs one case, None when the engine cannot run it.
+fn check(engine: Engine, case: &Case) -> Option<Result<(), String>> {
+    let mut arena = Arena::new();
+    let ast = match parser(tokenize(&case.source), &mut arena).parse() {
+        Ok(ast) => ast,
+        Err(errors) => return Some(Err(format!("the generated program does not parse: {}", diagnostic::render(&errors)))),
+    };
+    lower::lower(&mut arena, &ast);
+    let result = engine.run(Program { arena, modules: vec![(String::new(), ast)] }, Profile::debug(), RunOptions::default());
+    if result.as_ref().is_err_and(|e| e.contains("--engine interp")) {
+        return None;
+    }
+    let expected = match &case.expected {
+        Expected::Typed(expected) => expected,
+        Expected::Number(number) => {
+            return match result {
+                Ok(Some(value)) if same(&value, number) => Some(Ok(())),
+                Ok(Some(value)) => Some(Err(format!("read back as {} : {}", value, value.type_name()))),
+                Ok(None) => None,
+                Err(e) => Some(Err(format!("does not read back: {}", e))),
+            };
+        }
+    };
+    Some(match (result, expected) {
+        // Only the interpreter gives back the value to check the type of.
+        (Ok(Some(value)), Ok(typ)) if value.type_name() != typ.to_string() => Err(format!("expected a {} but got {} : {}", typ, value, value.type_name())),
+        (Ok(_), Ok(_)) => Ok(()),
+        (Err(_), Err(_)) => Ok(()),
+        (Err(e), Ok(typ)) => Err(format!("expected a {} but it failed: {}", typ, e)),
+        (Ok(value), Err(expected)) => {
+            let value = value.map_or(String::new(), |value| format!(" with {} : {}", value, value.type_name()));
+            Err(format!("expected '{}' but it ran{}", expected, value))
+        }
+    })
+}
+
+// Bit for bit, so that 0.0 and -0.0 differ.
+fn same(value: &Value, number: &Value) -> bool {
+    match (value, number) {
+        (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+        (Value::Int(a), Value::Int(b)) => a == b,
+        _ => false,
+    }
+}
+
+// Returns how many cases failed.
+pub fn run(engine: Engine) -> usize {
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for case in cases() {
+        match check(engine, &case) {
+            Some(Ok(())) => passed += 1,
+            Some(Err(e)) => {
+                println!("FAIL {}: {}", case.name, e);
+                failed += 1;
+            }
+            None => skipped += 1,
+        }
+    }
+    println!("{} passed, {} failed, {} skipped on the {} engine.", passed, failed, skipped, engine);
+    failed
+}
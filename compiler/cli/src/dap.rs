@@ -0,0 +1,292 @@
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use crate::debugger::{Session, Stopped};
+use crate::json::{self, object, Json};
+
+// `dap`: the debugger over the Debug Adapter Protocol on stdin and stdout,
+// for editors with a debug UI. Each message is a JSON object after a
+// `Content-Length` header. `launch` records the program as `debug` does, and
+// the requests then move through the recording: `stepIn` goes a statement
+// forward, `next` over the calls it makes and `stepOut` out of the current
+// call, `stepBack` one back, and `continue` and `reverseContinue` to the
+// nearest breakpoint. Data breakpoints are the debugger's watchpoints, on a
+// variable by name. There is one thread, and one frame, the function of the
+// current statement, whose variables are its one scope. What the program
+// wrote is sent as output events as the recording reaches it. Standard input
+// carries the protocol, so the program's `readline` fails.
+
+const THREAD: i64 = 1;
+const LOCALS: i64 = 1;
+
+pub fn serve() -> Result<(), String> {
+    let mut server = Server { session: None, seq: 0, stop_on_entry: false };
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    while let Some(request) = read_message(&mut input)? {
+        let command = request.get("command").and_then(Json::as_str).unwrap_or("").to_string();
+        let seq = request.get("seq").and_then(Json::as_int).unwrap_or(0);
+        let arguments = request.get("arguments").cloned().unwrap_or(Json::Null);
+        let result = server.handle(&command, &arguments);
+        let (success, body, message) = match &result {
+            Ok(body) => (true, body.clone(), None),
+            Err(e) => (false, Json::Null, Some(e.clone())),
+        };
+        let mut response = vec![
+            ("type", Json::String("response".to_string())),
+            ("request_seq", Json::Int(seq)),
+            ("success", Json::Bool(success)),
+            ("command", Json::String(command.clone())),
+            ("body", body),
+        ];
+        if let Some(message) = message {
+            response.push(("message", Json::String(message)));
+        }
+        server.send(response)?;
+        if result.is_ok() {
+            server.after(&command)?;
+        }
+        if command == "disconnect" {
+            break;
+        }
+    }
+    Ok(())
+}
+
+struct Server {
+    session: Option<Session>,
+    seq: i64,
+    stop_on_entry: bool,
+}
+
+impl Server {
+    fn session(&mut self) -> Result<&mut Session, String> {
+        self.session.as_mut().ok_or_else(|| "No program is launched.".to_string())
+    }
+
+    // The response body of a request.
+    fn handle(&mut self, command: &str, arguments: &Json) -> Result<Json, String> {
+        match command {
+            "initialize" => Ok(object(vec![
+                ("supportsConfigurationDoneRequest", Json::Bool(true)),
+                ("supportsConditionalBreakpoints", Json::Bool(true)),
+                ("supportsStepBack", Json::Bool(true)),
+                ("supportsDataBreakpoints", Json::Bool(true)),
+                ("supportsEvaluateForHovers", Json::Bool(true)),
+            ])),
+            "launch" => {
+                let program = arguments.get("program").and_then(Json::as_str).ok_or("launch needs the program to debug.")?;
+                self.stop_on_entry = arguments.get("stopOnEntry").and_then(Json::as_bool).unwrap_or(false);
+                self.session = Some(Session::record(Path::new(program), false)?);
+                Ok(Json::Null)
+            }
+            "setBreakpoints" => {
+                let session = self.session()?;
+                let path = arguments.get("source").and_then(|source| source.get("path")).and_then(Json::as_str).unwrap_or("");
+                let ours = Path::new(path) == session.file() || session.file().ends_with(path);
+                session.clear_breakpoints();
+                let mut breakpoints = Vec::new();
+                for breakpoint in arguments.get("breakpoints").map_or(&[][..], Json::as_array) {
+                    let line = breakpoint.get("line").and_then(Json::as_int).unwrap_or(0);
+                    let condition = breakpoint.get("condition").and_then(Json::as_str).filter(|condition| !condition.trim().is_empty());
+                    // Only the program's own file is recorded.
+                    let added = if ours { session.add_breakpoint_at(line as usize, condition) } else { Err("Only the launched program can be stopped in.".to_string()) };
+                    let mut members = vec![("verified", Json::Bool(added.is_ok())), ("line", Json::Int(line))];
+                    match added {
+                        Ok(id) => members.push(("id", Json::Int(id as i64))),
+                        Err(e) => members.push(("message", Json::String(e))),
+                    }
+                    breakpoints.push(object(members));
+                }
+                Ok(object(vec![("breakpoints", Json::Array(breakpoints))]))
+            }
+            "dataBreakpointInfo" => {
+                let name = arguments.get("name").and_then(Json::as_str).ok_or("dataBreakpointInfo needs a variable name.")?;
+                if !self.session()?.sees(name) {
+                    return Ok(object(vec![("dataId", Json::Null), ("description", Json::String(format!("No step sees a variable '{}'.", name)))]));
+                }
+                Ok(object(vec![
+                    ("dataId", Json::String(name.to_string())),
+                    ("description", Json::String(format!("When {} changes", name))),
+                    ("accessTypes", Json::Array(vec![Json::String("write".to_string())])),
+                ]))
+            }
+            "setDataBreakpoints" => {
+                let session = self.session()?;
+                session.clear_watchpoints();
+                let mut breakpoints = Vec::new();
+                for breakpoint in arguments.get("breakpoints").map_or(&[][..], Json::as_array) {
+                    let name = breakpoint.get("dataId").and_then(Json::as_str).unwrap_or("");
+                    let members = match session.add_watchpoint(name) {
+                        Ok(id) => vec![("verified", Json::Bool(true)), ("id", Json::Int(id as i64))],
+                        Err(e) => vec![("verified", Json::Bool(false)), ("message", Json::String(e))],
+                    };
+                    breakpoints.push(object(members));
+                }
+                Ok(object(vec![("breakpoints", Json::Array(breakpoints))]))
+            }
+            "threads" => Ok(object(vec![("threads", Json::Array(vec![object(vec![("id", Json::Int(THREAD)), ("name", Json::String("main".to_string()))])]))])),
+            "stackTrace" => {
+                let session = self.session()?;
+                let frames = match session.frame() {
+                    Some(frame) => {
+                        let (line, column) = frame.position.unwrap_or((0, 0));
+                        let source = object(vec![("path", Json::String(session.file().display().to_string()))]);
+                        vec![object(vec![
+                            ("id", Json::Int(0)),
+                            ("name", Json::String(frame.function.clone())),
+                            ("line", Json::Int(line as i64)),
+                            ("column", Json::Int(column as i64)),
+                            ("source", source),
+                        ])]
+                    }
+                    None => Vec::new(),
+                };
+                Ok(object(vec![("totalFrames", Json::Int(frames.len() as i64)), ("stackFrames", Json::Array(frames))]))
+            }
+            "scopes" => Ok(object(vec![(
+                "scopes",
+                Json::Array(vec![object(vec![("name", Json::String("Locals".to_string())), ("variablesReference", Json::Int(LOCALS)), ("expensive", Json::Bool(false))])]),
+            )])),
+            "variables" => {
+                let session = self.session()?;
+                let variables = session
+                    .locals()
+                    .iter()
+                    .map(|(name, value)| {
+                        object(vec![
+                            ("name", Json::String(name.clone())),
+                            ("value", Json::String(value.to_string())),
+                            ("type", Json::String(value.type_name())),
+                            ("variablesReference", Json::Int(0)),
+                        ])
+                    })
+                    .collect();
+                Ok(object(vec![("variables", Json::Array(variables))]))
+            }
+            "evaluate" => {
+                let expression = arguments.get("expression").and_then(Json::as_str).ok_or("evaluate needs an expression.")?;
+                let value = self.session()?.evaluate(expression)?;
+                let (result, typ) = value.map_or((String::new(), String::new()), |value| (value.to_string(), value.type_name()));
+                Ok(object(vec![("result", Json::String(result)), ("type", Json::String(typ)), ("variablesReference", Json::Int(0))]))
+            }
+            "continue" => Ok(object(vec![("allThreadsContinued", Json::Bool(true))])),
+            "configurationDone" | "next" | "stepIn" | "stepOut" | "stepBack" | "reverseContinue" | "pause" | "disconnect" => Ok(Json::Null),
+            other => Err(format!("Unsupported request '{}'.", other)),
+        }
+    }
+
+    // What follows a request's response: the events of moving through the
+    // recording.
+    fn after(&mut self, command: &str) -> Result<(), String> {
+        match command {
+            "initialize" => self.event("initialized", Json::Null),
+            "configurationDone" if self.stop_on_entry => self.stopped("entry", None),
+            "configurationDone" | "continue" | "reverseContinue" => {
+                // A breakpoint on the first statement stops the run before it
+                // has gone anywhere.
+                if command == "configurationDone" {
+                    if let Some(stopped) = self.session()?.stop_reason() {
+                        return self.stop(Some(stopped));
+                    }
+                }
+                let (written, stopped) = self.session()?.resume(command != "reverseContinue");
+                let written = written.to_vec();
+                self.output(&written)?;
+                self.stop(stopped)
+            }
+            "stepIn" => {
+                let written = self.session()?.step().map(<[String]>::to_vec).unwrap_or_default();
+                self.output(&written)?;
+                self.moved()
+            }
+            "next" | "stepOut" => {
+                let session = self.session()?;
+                let moved = if command == "next" { session.step_over() } else { session.step_out() };
+                let (written, stopped) = moved.map(|(written, stopped)| (written.to_vec(), stopped)).unwrap_or_default();
+                self.output(&written)?;
+                self.stop(stopped)
+            }
+            "stepBack" => {
+                // At the first step there is nowhere further back to go.
+                let _ = self.session()?.rstep();
+                self.stopped("step", None)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Stopped by a breakpoint or watchpoint, or a condition that failed, or
+    // at the end of the run.
+    fn stop(&mut self, stopped: Stopped) -> Result<(), String> {
+        match stopped {
+            Some(Ok(reason)) if reason.starts_with("Watchpoint") => self.stopped("data breakpoint", Some(reason)),
+            Some(Ok(reason)) => self.stopped("breakpoint", Some(reason)),
+            Some(Err(e)) => self.stopped("exception", Some(e)),
+            None => self.moved(),
+        }
+    }
+
+    // Stopped at a step, or the end of the run.
+    fn moved(&mut self) -> Result<(), String> {
+        match self.session()?.ended().cloned() {
+            None => self.stopped("step", None),
+            Some(result) => {
+                if let Err(e) = &result {
+                    self.output(&[format!("The program failed: {}", e)])?;
+                }
+                self.event("exited", object(vec![("exitCode", Json::Int(if result.is_ok() { 0 } else { 1 }))]))?;
+                self.event("terminated", Json::Null)
+            }
+        }
+    }
+
+    fn stopped(&mut self, reason: &str, description: Option<String>) -> Result<(), String> {
+        let mut body = vec![("reason", Json::String(reason.to_string())), ("threadId", Json::Int(THREAD))];
+        if let Some(description) = description {
+            body.push(("description", Json::String(description)));
+        }
+        self.event("stopped", object(body))
+    }
+
+    fn output(&mut self, lines: &[String]) -> Result<(), String> {
+        for line in lines {
+            self.event("output", object(vec![("category", Json::String("stdout".to_string())), ("output", Json::String(format!("{}\n", line)))]))?;
+        }
+        Ok(())
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> Result<(), String> {
+        self.send(vec![("type", Json::String("event".to_string())), ("event", Json::String(event.to_string())), ("body", body)])
+    }
+
+    fn send(&mut self, mut members: Vec<(&str, Json)>) -> Result<(), String> {
+        self.seq += 1;
+        members.insert(0, ("seq", Json::Int(self.seq)));
+        let text = object(members).compact();
+        let mut stdout = io::stdout().lock();
+        write!(stdout, "Content-Length: {}\r\n\r\n{}", text.len(), text).and_then(|()| stdout.flush()).map_err(|e| e.to_string())
+    }
+}
+
+// The next message, or None at the end of the input.
+fn read_message(input: &mut impl BufRead) -> Result<Option<Json>, String> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).map_err(|e| e.to_string())? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = Some(value.trim().parse::<usize>().map_err(|_| format!("Malformed header '{}'.", header))?);
+        }
+    }
+    let length = length.ok_or("A message without a Content-Length header.")?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body).map_err(|e| e.to_string())?;
+    json::parse(&String::from_utf8_lossy(&body)).map(Some)
+}
@@ -0,0 +1,377 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::arena::NodeId;
+use crate::ast::Program;
+use crate::ice;
+use crate::interpreter::{Frame, Interpreter, Value, TRACE_LIMIT};
+use crate::interrupt;
+use crate::profile::Profile;
+
+// `debug <file>`: the program runs once on the interpreter with its
+// statements recorded, then the recording is stepped through. Going back a
+// statement with `rstep` is as cheap as going forward, and neither runs the
+// program again, so input it read and time it saw stay as they were. A run
+// that fails is recorded up to the statement that failed. `next` steps over
+// the calls a statement makes, and `finish` out of the current call, by the
+// depth of the calls each step was recorded in.
+//
+// `continue` and `rcontinue` go forward or back to the nearest step where a
+// breakpoint or watchpoint stops. A breakpoint stops at the steps on its
+// line, those where its condition holds if it has one; the condition is an
+// expression over the variables of the step. A watchpoint stops where its
+// variable gets a new value, compared with the step before in the same call,
+// so a variable of the same name in a caller or callee is another one. The
+// first step of a call is compared with the caller's step that made it.
+
+pub struct Session {
+    file: PathBuf,
+    lines: Vec<String>,
+    interp: Interpreter,
+    trace: Vec<(Frame, usize)>,
+    output: Vec<String>,
+    result: Result<(), String>,
+    // The current step; `trace.len()` once the run has ended.
+    at: usize,
+    // By number, less one. Deleted ones are None, so numbers stay.
+    stops: Vec<Option<Stop>>,
+}
+
+enum Stop {
+    // Line, and the condition with its source.
+    Break(usize, Option<(Vec<NodeId>, String)>),
+    Watch(String),
+}
+
+// Why a move stopped: a breakpoint or watchpoint, or the condition of one
+// that failed. None when it did not.
+pub type Stopped = Option<Result<String, String>>;
+
+pub const COMMANDS: &str = "step, next, finish, rstep, continue, rcontinue, break [file:]line [if expr], watch name, delete N, locals, quit";
+
+impl Session {
+    // Without `stdin`, `readline` fails, as the program's standard input is
+    // the debugger's.
+    pub fn record(file: &Path, stdin: bool) -> Result<Session, String> {
+        let source = fs::read_to_string(file).map_err(|e| format!("Cannot read {}: {}", file.display(), e))?;
+        let profile = Profile::load(file.parent().unwrap_or(Path::new(".")), false)?;
+        let Program { arena, mut modules } = crate::load_program(file)?;
+        let (_, entry) = modules.pop().ok_or("Nothing to run.")?;
+        ice::enter_phase("interpret");
+        interrupt::install();
+        let mut interp = Interpreter::with_profile(profile, arena);
+        interp.record_trace();
+        if !stdin {
+            interp.close_stdin();
+        }
+        let result = modules
+            .iter()
+            .try_for_each(|(name, module)| interp.interpret_module(name, module))
+            .and_then(|()| interp.interpret(&entry))
+            .map(|_| ());
+        let output = interp.take_output();
+        let trace = interp.take_trace();
+        if trace.is_empty() {
+            return Err("The program ran no statements to step through.".to_string());
+        }
+        let lines = source.lines().map(str::to_string).collect();
+        Ok(Session { file: file.to_path_buf(), lines, interp, trace, output, result, at: 0, stops: Vec::new() })
+    }
+
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    pub fn steps(&self) -> usize {
+        self.trace.len()
+    }
+
+    // The step the session is at, or None once the run has ended.
+    pub fn frame(&self) -> Option<&Frame> {
+        self.trace.get(self.at).map(|(frame, _)| frame)
+    }
+
+    // How the run ended, once the session has got there.
+    pub fn ended(&self) -> Option<&Result<(), String>> {
+        (self.at == self.trace.len()).then_some(&self.result)
+    }
+
+    // The lines written between steps `from` and `to`, `from` first.
+    fn written(&self, from: usize, to: usize) -> &[String] {
+        let at = |step: usize| self.trace.get(step).map_or(self.output.len(), |(_, written)| *written);
+        &self.output[at(from)..at(to)]
+    }
+
+    // Moves one step forward, returning what the statement left wrote.
+    pub fn step(&mut self) -> Result<&[String], String> {
+        if self.at == self.trace.len() {
+            return Err("The program has ended; rstep goes back.".to_string());
+        }
+        self.at += 1;
+        Ok(self.written(self.at - 1, self.at))
+    }
+
+    // Moves forward over the calls the statement makes, to the next step no
+    // deeper than this one, unless a breakpoint or watchpoint stops in them.
+    pub fn step_over(&mut self) -> Result<(&[String], Stopped), String> {
+        let depth = self.frame().map_or(0, |frame| frame.depth);
+        self.step_past(move |deeper| deeper > depth)
+    }
+
+    // Moves forward to the step after the current call returns, or to the end
+    // at the top level, unless a breakpoint or watchpoint stops first.
+    pub fn step_out(&mut self) -> Result<(&[String], Stopped), String> {
+        let depth = self.frame().map_or(0, |frame| frame.depth);
+        self.step_past(move |deeper| deeper >= depth)
+    }
+
+    // Steps forward, then on over the steps at the depths `skip` holds for.
+    fn step_past(&mut self, skip: impl Fn(usize) -> bool) -> Result<(&[String], Stopped), String> {
+        let from = self.at;
+        self.step()?;
+        let mut stopped = None;
+        while self.frame().is_some_and(|frame| skip(frame.depth)) {
+            if let Some(reason) = self.stops_at(self.at).transpose() {
+                stopped = Some(reason);
+                break;
+            }
+            self.at += 1;
+        }
+        Ok((self.written(from, self.at), stopped))
+    }
+
+    pub fn rstep(&mut self) -> Result<(), String> {
+        if self.at == 0 {
+            return Err("At the first step.".to_string());
+        }
+        self.at -= 1;
+        Ok(())
+    }
+
+    // Moves forward, or back, to the nearest step where a breakpoint or
+    // watchpoint stops, or to the end or the first step when none does. A
+    // condition that fails stops where it did. Returns what was written on
+    // the way forward and why it stopped, or how the condition failed.
+    pub fn resume(&mut self, forward: bool) -> (&[String], Stopped) {
+        let from = self.at;
+        let mut stopped = None;
+        loop {
+            match (forward, self.at) {
+                (true, at) if at == self.trace.len() => break,
+                (false, 0) => break,
+                (true, _) => self.at += 1,
+                (false, _) => self.at -= 1,
+            }
+            if let Some(reason) = self.stops_at(self.at).transpose() {
+                stopped = Some(reason);
+                break;
+            }
+        }
+        let written = if forward { self.written(from, self.at) } else { &[] };
+        (written, stopped)
+    }
+
+    // Why the session would stop at the current step, as it does at the first
+    // one when that has a breakpoint.
+    pub fn stop_reason(&mut self) -> Stopped {
+        self.stops_at(self.at).transpose()
+    }
+
+    // Why the session stops at `step`, if it does.
+    fn stops_at(&mut self, step: usize) -> Result<Option<String>, String> {
+        let Some((frame, _)) = self.trace.get(step) else { return Ok(None) };
+        let line = frame.position.map(|(line, _)| line);
+        let value = |step: usize, name: &str| self.trace[step].0.locals.iter().find(|(local, _)| local == name).map(|(_, value)| value);
+        let before = self.previous(step).unwrap_or(step.saturating_sub(1));
+        for (n, stop) in self.stops.iter().enumerate().filter_map(|(i, stop)| Some((i + 1, stop.as_ref()?))) {
+            match stop {
+                Stop::Break(at, None) if line == Some(*at) => return Ok(Some(format!("Breakpoint {}.", n))),
+                Stop::Break(at, Some((condition, text))) if line == Some(*at) => match self.interp.interpret_with(&frame.locals, condition) {
+                    Ok(Some(Value::Bool(true))) => return Ok(Some(format!("Breakpoint {}, where {}.", n, text))),
+                    Ok(Some(Value::Bool(false))) => {}
+                    Ok(_) => return Err(format!("The condition of breakpoint {}, {}, is not a bool.", n, text)),
+                    Err(e) => return Err(format!("The condition of breakpoint {}, {}, failed: {}", n, text, e)),
+                },
+                Stop::Watch(name) if step > 0 => match (value(before, name), value(step, name)) {
+                    (Some(before), Some(after)) if before != after => return Ok(Some(format!("Watchpoint {}: {} changed from {} to {}.", n, name, before, after))),
+                    (None, Some(after)) => return Ok(Some(format!("Watchpoint {}: {} is now {}.", n, name, after))),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    // The step before `step` in the same call: the last one as deep before
+    // the run went shallower, as when the call began.
+    fn previous(&self, step: usize) -> Option<usize> {
+        let depth = self.trace.get(step)?.0.depth;
+        let before = self.trace[..step].iter().rposition(|(frame, _)| frame.depth <= depth)?;
+        (self.trace[before].0.depth == depth).then_some(before)
+    }
+
+    // `line`, `file:line` or either followed by `if expr`. Returns the
+    // breakpoint's number.
+    pub fn add_breakpoint(&mut self, spec: &str) -> Result<usize, String> {
+        let (place, condition) = match spec.split_once(" if ") {
+            Some((place, condition)) => (place.trim(), Some(condition.trim())),
+            None => (spec.trim(), None),
+        };
+        let line = match place.rsplit_once(':') {
+            Some((file, _)) if !self.file.ends_with(file) => {
+                return Err(format!("Only {} is recorded, so there is no stopping in {}.", self.file.display(), file));
+            }
+            Some((_, line)) => line,
+            None => place,
+        };
+        let line = line.parse::<usize>().map_err(|_| format!("Usage: break [file:]line [if expr], not 'break {}'.", spec.trim()))?;
+        self.add_breakpoint_at(line, condition)
+    }
+
+    pub fn add_breakpoint_at(&mut self, line: usize, condition: Option<&str>) -> Result<usize, String> {
+        let condition = match condition {
+            Some(condition) => Some((self.parse("<condition>", condition)?, condition.to_string())),
+            None => None,
+        };
+        self.stops.push(Some(Stop::Break(line, condition)));
+        Ok(self.stops.len())
+    }
+
+    // Deletes every breakpoint, leaving the watchpoints.
+    pub fn clear_breakpoints(&mut self) {
+        for stop in &mut self.stops {
+            if let Some(Stop::Break(..)) = stop {
+                *stop = None;
+            }
+        }
+    }
+
+    // Deletes every watchpoint, leaving the breakpoints.
+    pub fn clear_watchpoints(&mut self) {
+        for stop in &mut self.stops {
+            if let Some(Stop::Watch(_)) = stop {
+                *stop = None;
+            }
+        }
+    }
+
+    // Parses an expression to run at steps of the recording.
+    fn parse(&mut self, name: &str, expression: &str) -> Result<Vec<NodeId>, String> {
+        // A variable of any step may be what it is about.
+        let mut known: HashSet<String> = self.interp.defined();
+        known.extend(self.trace.iter().flat_map(|(frame, _)| frame.locals.iter().map(|(name, _)| name.clone())));
+        crate::parse_after(self.interp.arena_mut(), name, expression, &known)
+    }
+
+    // The value of an expression over the variables of the current step.
+    pub fn evaluate(&mut self, expression: &str) -> Result<Option<Value>, String> {
+        let parsed = self.parse("<evaluate>", expression)?;
+        let locals = self.locals().to_vec();
+        self.interp.interpret_with(&locals, &parsed)
+    }
+
+    // Whether some step has a variable `name`, so it can be watched.
+    pub fn sees(&self, name: &str) -> bool {
+        self.trace.iter().any(|(frame, _)| frame.locals.iter().any(|(local, _)| local == name))
+    }
+
+    pub fn add_watchpoint(&mut self, name: &str) -> Result<usize, String> {
+        if !self.sees(name) {
+            return Err(format!("No step sees a variable '{}'.", name));
+        }
+        self.stops.push(Some(Stop::Watch(name.to_string())));
+        Ok(self.stops.len())
+    }
+
+    pub fn delete(&mut self, n: usize) -> Result<(), String> {
+        match self.stops.get_mut(n.wrapping_sub(1)) {
+            Some(stop @ Some(_)) => {
+                *stop = None;
+                Ok(())
+            }
+            _ => Err(format!("No breakpoint or watchpoint {}.", n)),
+        }
+    }
+
+    // The step as `line:col in function: source`, or how the run ended.
+    pub fn describe(&self) -> String {
+        match (self.frame(), &self.result) {
+            (Some(frame), _) => {
+                let Some((line, column)) = frame.position else { return frame.function.clone() };
+                let text = self.lines.get(line - 1).map_or("", |text| text.trim());
+                format!("{}:{} in {}: {}", line, column, frame.function, text)
+            }
+            (None, Ok(())) => "The program finished.".to_string(),
+            (None, Err(e)) => format!("The program failed: {}", e),
+        }
+    }
+
+    // The variables of the step, or of the last one once the run has ended.
+    pub fn locals(&self) -> &[(String, Value)] {
+        self.frame().or(self.trace.last().map(|(frame, _)| frame)).map_or(&[], |frame| &frame.locals)
+    }
+}
+
+pub fn debug(file: &Path) -> Result<(), String> {
+    let mut session = Session::record(file, true)?;
+    let limit = if session.steps() == TRACE_LIMIT { ", the most kept" } else { "" };
+    println!("Recorded {} step(s){}. Commands: {}.", session.steps(), limit, COMMANDS);
+    println!("{}", session.describe());
+    let stdin = io::stdin();
+    loop {
+        print!("(debug) ");
+        io::stdout().flush().map_err(|e| e.to_string())?;
+        let mut input = String::new();
+        if stdin.lock().read_line(&mut input).map_err(|e| e.to_string())? == 0 {
+            break;
+        }
+        let (command, rest) = input.trim().split_once(' ').unwrap_or((input.trim(), ""));
+        let rest = rest.trim();
+        // Whether the command moved, so the new step is shown.
+        let moved = match command {
+            "step" | "s" => session.step().map(|written| written.iter().for_each(|line| println!("{}", line))).map(|()| true),
+            "rstep" | "rs" => session.rstep().map(|()| true),
+            "next" | "n" | "finish" | "f" => {
+                let moved = if command.starts_with('n') { session.step_over() } else { session.step_out() };
+                moved.map(|(written, stopped)| show(written, stopped)).map(|()| true)
+            }
+            "continue" | "c" | "rcontinue" | "rc" => {
+                let (written, stopped) = session.resume(command.starts_with('c'));
+                show(written, stopped);
+                Ok(true)
+            }
+            "break" | "b" => session.add_breakpoint(rest).map(|n| println!("Breakpoint {} at {}.", n, rest)).map(|()| false),
+            "watch" | "w" => session.add_watchpoint(rest).map(|n| println!("Watchpoint {} on {}.", n, rest)).map(|()| false),
+            "delete" | "d" => match rest.parse() {
+                Ok(n) => session.delete(n).map(|()| false),
+                Err(_) => Err("Usage: delete N, the number of a breakpoint or watchpoint.".to_string()),
+            },
+            "locals" | "l" => {
+                for (name, value) in session.locals() {
+                    println!("{} = {} : {}", name, value, value.type_name());
+                }
+                Ok(false)
+            }
+            "quit" | "q" => break,
+            "" => Ok(false),
+            other => Err(format!("Unknown command '{}'; expected one of {}.", other, COMMANDS)),
+        };
+        match moved {
+            Ok(true) => println!("{}", session.describe()),
+            Ok(false) => {}
+            Err(e) => println!("{}", e),
+        }
+    }
+    Ok(())
+}
+
+// What was written on the way to a step, and why it stopped there.
+fn show(written: &[String], stopped: Stopped) {
+    written.iter().for_each(|line| println!("{}", line));
+    if let Some(Ok(reason) | Err(reason)) = stopped {
+        println!("{}", reason);
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::Path;
+
+use crate::ast::Program;
+use crate::ice;
+use crate::interpreter::Interpreter;
+use crate::parser::Comment;
+use crate::profile::Profile;
+use crate::resolve;
+
+// Examples in doc comments, run by `test` so the documentation keeps up with
+// the language. An example is a fenced block in a `///` comment, untagged or
+// tagged `vira`:
+//
+//   /// ```
+//   /// write double(2)
+//   /// //> Int(4)
+//   /// ```
+//
+// Its `//>` lines are the output it has to print, one per line written. An
+// example without any only has to run without an error. It runs after the
+// declarations of its file, so it can call what it documents, and always on
+// the interpreter, as the only engine that can write.
+
+pub struct Doctest {
+    // The line of the opening fence.
+    pub line: usize,
+    pub code: String,
+    pub expected: Vec<String>,
+}
+
+// Reads the examples from the comments the parser kept for a file.
+pub fn extract(comments: &[Comment]) -> Vec<Doctest> {
+    let mut doctests = Vec::new();
+    // The example being read, and whether it is one to run.
+    let mut open: Option<(Doctest, bool)> = None;
+    let mut last_line = 0;
+    for comment in comments {
+        let ((line, _), _) = comment.span;
+        let doc = comment.text.strip_prefix("///");
+        // A fence left open ends with its comment.
+        if doc.is_none() || line != last_line + 1 {
+            open = None;
+        }
+        last_line = line;
+        let Some(doc) = doc else { continue };
+        let doc = doc.strip_prefix(' ').unwrap_or(doc);
+        match (&mut open, doc.trim().strip_prefix("```")) {
+            (None, Some(tag)) => {
+                let runs = matches!(tag.trim(), "" | "vira");
+                open = Some((Doctest { line, code: String::new(), expected: Vec::new() }, runs));
+            }
+            (Some(_), Some(_)) => {
+                if let Some((doctest, true)) = open.take() {
+                    doctests.push(doctest);
+                }
+            }
+            (Some((doctest, _)), None) => match doc.trim_start().strip_prefix("//>") {
+                Some(expected) => doctest.expected.push(expected.strip_prefix(' ').unwrap_or(expected).to_string()),
+                None => {
+                    doctest.code.push_str(doc);
+                    doctest.code.push('\n');
+                }
+            },
+            (None, None) => {}
+        }
+    }
+    doctests
+}
+
+// Runs the example in `program`, the file it was found in with its imports.
+pub fn run(file: &Path, mut program: Program, doctest: &Doctest) -> Result<(), String> {
+    let profile = Profile::load(file.parent().unwrap_or(Path::new(".")), false)?;
+    crate::keep_declarations(&mut program);
+    let Program { mut arena, mut modules } = program;
+    let (_, mut entry) = modules.pop().ok_or("Nothing to run.")?;
+    let example = format!("{} (doctest at line {})", file.display(), doctest.line);
+    let known = resolve::declarations(&arena, &entry).into_keys().map(str::to_string).collect();
+    entry.extend(crate::parse_after(&mut arena, &example, &doctest.code, &known)?);
+    ice::enter_phase("interpret");
+    let mut interp = Interpreter::with_profile(profile, arena);
+    interp.capture_output();
+    for (name, module) in &modules {
+        interp.interpret_module(name, module)?;
+    }
+    interp.interpret(&entry)?;
+    let output = interp.take_output().join("\n");
+    let output: Vec<&str> = output.lines().collect();
+    if doctest.expected.is_empty() || output == doctest.expected {
+        Ok(())
+    } else {
+        Err(format!("expected output\n{}\nbut got\n{}", doctest.expected.join("\n"), output.join("\n")))
+    }
+}
@@ -0,0 +1,106 @@
+use std::fmt;
+
+use crate::ast::Program;
+use crate::capabilities;
+use crate::codegen::CodeGen;
+use crate::fold;
+use crate::hir;
+use crate::ice;
+use crate::interpreter::{Interpreter, Value};
+use crate::interrupt;
+use crate::link;
+use crate::mono;
+use crate::perf;
+use crate::profile::Profile;
+
+// How `run`, `eval` and `test` execute a program. Scripts default to the
+// interpreter and `--release` runs default to the JIT.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Engine {
+    Interp,
+    Jit,
+}
+
+// Flags of `run` that change how a program executes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunOptions {
+    // Builtins with outside effects log instead of acting. Compiled code
+    // cannot call them yet.
+    pub dry_run: bool,
+    // Count calls, instructions, branches and allocations per compiled
+    // function and report them after the run.
+    pub perf_counters: bool,
+    // Fold constant expressions before running (`-O`).
+    pub fold: bool,
+}
+
+impl Engine {
+    pub fn select(flag: Option<&str>, release: bool) -> Result<Engine, String> {
+        match flag {
+            None if release => Ok(Engine::Jit),
+            None | Some("interp") => Ok(Engine::Interp),
+            Some("jit") => Ok(Engine::Jit),
+            Some(other) => Err(format!("Unknown engine '{}'; expected interp or jit.", other)),
+        }
+    }
+
+    // Runs a loaded program: imported modules in order, then the entry module,
+    // which comes last. Returns the value of a trailing top-level expression
+    // when the engine produces one.
+    pub fn run(self, mut program: Program, profile: Profile, options: RunOptions) -> Result<Option<Value>, String> {
+        if options.perf_counters && self != Engine::Jit {
+            return Err("--perf-counters needs the jit engine; add --engine jit.".to_string());
+        }
+        if options.fold {
+            fold::fold_program(&mut program);
+        }
+        capabilities::check(self, &program)?;
+        let Program { mut arena, mut modules } = program;
+        interrupt::install();
+        match self {
+            Engine::Interp => {
+                let (_, entry) = modules.pop().ok_or("Nothing to run.")?;
+                ice::enter_phase("interpret");
+                let mut interp = Interpreter::with_profile(profile, arena);
+                interp.set_dry_run(options.dry_run);
+                for (name, module) in &modules {
+                    interp.interpret_module(name, module)?;
+                }
+                interp.interpret(&entry)
+            }
+            // Compiled as one program: the imported modules' top-level code
+            // runs first, in order, as the interpreter runs it.
+            Engine::Jit => {
+                ice::enter_phase("codegen");
+                let program = link::link(&mut arena, modules)?;
+                let ast = mono::monomorphize(&mut arena, program)?;
+                let hir = hir::build(&arena, &ast);
+                let mut codegen = CodeGen::new(&profile)?;
+                if options.perf_counters {
+                    codegen.enable_perf_counters();
+                }
+                let code = codegen
+                    .compile(&arena, &ast, &hir)
+                    .map_err(|e| format!("{} Use --engine interp to run this program.", e))?;
+                ice::enter_phase("execute");
+                // `compile` returns the finalized entry function, which takes
+                // no arguments and returns an I64 in the host calling convention.
+                let main = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i64>(code) };
+                let value = main();
+                if let Some(rows) = codegen.perf_counters() {
+                    eprint!("{}", perf::report(rows));
+                }
+                Ok(codegen.returns_value().then_some(Value::Int(value)))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Engine::Interp => write!(f, "interp"),
+            Engine::Jit => write!(f, "jit"),
+        }
+    }
+}
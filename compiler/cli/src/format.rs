@@ -0,0 +1,121 @@
+use crate::arena::{Arena, NodeId};
+use crate::ast::{AstNode, Expr, Item};
+use crate::diagnostic;
+use crate::parser;
+use crate::parser::Comment;
+use crate::printer::{print_source, Comments};
+use crate::tokenizer::tokenize;
+
+// `fmt`: a file reprinted by the printer in canonical form, as written:
+// constants keep their initializers and type aliases their names. Comments
+// are not in the tree; the parser keeps them with their spans and they are
+// carried over by position: those between top-level items stay before the
+// item that follows them, and one after the last token of an item, on the
+// same line, stays at the end of it. Inside an item, a comment goes with the
+// statement, method or match arm whose line it ends, or else the one it is in
+// or before, or at the end of the block around it. Runs of blank lines
+// between items become one.
+
+pub fn format(source: &str) -> Result<String, String> {
+    let mut arena = Arena::new();
+    let mut parser = parser(tokenize(source), &mut arena);
+    parser.keep_aliases();
+    let items = parser.parse().map_err(|errors| diagnostic::render(&errors))?;
+    let comments = parser.comments().to_vec();
+
+    let mut out = Output { text: String::new(), last_line: None };
+    let mut comments = comments.iter().peekable();
+    for &item in &items {
+        let Some((first, last)) = arena.span(item) else { continue };
+        while let Some(comment) = comments.next_if(|c| c.span.0 < first) {
+            out.comment(comment);
+        }
+        let mut inside = Comments::default();
+        while let Some(comment) = comments.next_if(|c| c.span.0 < last) {
+            attach(&arena, item, comment, &mut inside);
+        }
+        out.blank_line_before(first.0);
+        out.text.push_str(&print_source(&arena, item, &inside));
+        out.text.push('\n');
+        out.last_line = Some(last.0);
+        if let Some(comment) = comments.next_if(|c| c.span.0 .0 == last.0) {
+            out.text.pop();
+            out.text.push(' ');
+            out.comment(comment);
+        }
+    }
+    for comment in comments {
+        out.comment(comment);
+    }
+    Ok(out.text)
+}
+
+// A block, impl or match of `item` and the nodes that start its lines.
+fn lines(arena: &Arena, id: NodeId, found: &mut Vec<(NodeId, Vec<NodeId>)>) {
+    match &arena[id] {
+        AstNode::Expr(Expr::Block(stmts)) => found.push((id, stmts.clone())),
+        AstNode::Item(Item::ImplDecl(_, _, methods)) => found.push((id, methods.iter().map(|method| method.node()).collect())),
+        AstNode::Expr(Expr::Match(_, arms)) => found.push((id, arms.iter().map(|(_, body)| body.node()).collect())),
+        _ => {}
+    }
+    arena[id].for_each_child(&mut |child| lines(arena, child, found));
+}
+
+// Attaches a comment inside `item` to the node it goes with. One that is in
+// no block, impl or match, such as one between struct fields, goes before
+// the item.
+fn attach(arena: &Arena, item: NodeId, comment: &Comment, comments: &mut Comments) {
+    let at = comment.span.0;
+    let text = comment.text.trim_end().to_string();
+    let mut found = Vec::new();
+    lines(arena, item, &mut found);
+    let span = |id: NodeId| arena.span(id).unwrap_or((at, at));
+    // The outermost line that ends before the comment on its line.
+    let ended = found
+        .iter()
+        .flat_map(|(_, lines)| lines.iter().copied())
+        .filter(|&id| span(id).1 .0 == at.0 && span(id).1 < at)
+        .max_by_key(|&id| (span(id).1, std::cmp::Reverse(span(id).0)));
+    if let Some(id) = ended {
+        let after = comments.after.entry(id).or_default();
+        if !after.is_empty() {
+            after.push(' ');
+        }
+        after.push_str(&text);
+        return;
+    }
+    // Spans only grow inwards, so the last block around the comment is the
+    // innermost one.
+    let Some((container, lines)) = found.iter().rfind(|(id, _)| span(*id).0 < at && at < span(*id).1) else {
+        comments.before.entry(item).or_default().push(text);
+        return;
+    };
+    match lines.iter().find(|&&id| at < span(id).1) {
+        Some(&line) => comments.before.entry(line).or_default().push(text),
+        None => comments.closing.entry(*container).or_default().push(text),
+    }
+}
+
+struct Output {
+    text: String,
+    // The source line the last printed item or comment ended on.
+    last_line: Option<usize>,
+}
+
+impl Output {
+    fn blank_line_before(&mut self, line: usize) {
+        if self.last_line.is_some_and(|last| line > last + 1) {
+            self.text.push('\n');
+        }
+    }
+
+    fn comment(&mut self, comment: &Comment) {
+        let ((line, _), (end, _)) = comment.span;
+        if !self.text.ends_with(' ') {
+            self.blank_line_before(line);
+        }
+        self.text.push_str(comment.text.trim_end());
+        self.text.push('\n');
+        self.last_line = Some(end);
+    }
+}
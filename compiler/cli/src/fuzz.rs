@@ -0,0 +1,186 @@
+use std::env;
+use std::fs;
+use std::panic;
+
+use crate::arena::{Arena, NodeId};
+use crate::ast::Program;
+use crate::codegen::CodeGen;
+use crate::derive;
+use crate::diff::same_tree;
+use crate::fold;
+use crate::hir;
+use crate::lower;
+use crate::mono;
+use crate::parser;
+use crate::printer::print_program;
+use crate::profile::Profile;
+use crate::resolve;
+use crate::strip;
+use crate::tokenizer::tokenize;
+use crate::typecheck;
+
+// Mutation fuzzer for the user-facing front end. Every input must produce
+// either a result or a diagnostic; any panic is a bug. Inputs that parse must
+// also survive a round trip through the pretty-printer unchanged. The program
+// is never executed, so mutated loops cannot hang the fuzzer.
+
+const SEEDS: [&str; 26] = [
+    "let x: int = 1 + 2 * 3\nwrite x\n",
+    "func add(a: int, b: int) -> int { return a + b }\nwrite add(1, 2)\n",
+    "struct Point { x: int, y: float }\nlet p = Point { x: 1, y: 2.5 }\nwrite p.x\n",
+    "enum Color { Red, Rgb(int, int, int) }\nlet c = Color::Rgb(1, 2, 3)\n",
+    "let xs = [1, 2, 3]\nwrite xs[1]\nif xs[0] < 2 { write \"small\" } else { write \"big\" }\n",
+    "while false { write 1 }\nlet s: string = \"text\"\nfor let i: int = 0; i < 3; i = i + 1 { write i }\nfor i = 0; f(i); g(i) write i\n",
+    "let f = |x: int| -> int x + 1\nlet g = func(a: int) -> int { a * 2 }\nwrite g(f(1))\n",
+    "for i in 0..3 { write -i }\nlet m = match Color::Red { Color::Rgb(r, _, _) => r, _ => 0 }\n",
+    "let t: (int, (string,)) = (1, (\"a\",))\nlet (a, b) = t\nwrite t.1.0\n",
+    "let m: map<string, int> = { \"a\": 1, \"b\": 2 }\nm = set(m, \"c\", 3)\nwrite m[\"a\"]\n",
+    "let v = if 1 < 2 { \"a\" } else if true { \"b\" } else { \"c\" }\nwrite (if v == \"a\" { 1 } else { 2 }) * 3\n",
+    "trait Shape { func area(self) -> float func grow(self, by: int) -> Shape }\nimpl Shape for Circle { func area(self) -> float { self.r } func grow(self, by: int) -> Shape { self } }\nwrite c.area() + (c.f)(1)\n",
+    "pub func area(r: float) -> float { return r * r }\npub const PI: float = 3.14\npub let unit: int = 1\nwrite geometry::area(2.0)\n",
+    "func f() -> int { defer write 1\n defer { write 2 }\n return 3 }\n{ defer write f()\n write 4 }\n",
+    "impl Point { func add(self, o: Point) -> Point { o } func index(self, i: int) -> int { i } }\nwrite (p + p)[0] != p\n",
+    "let g: int = 0\n@pure func sq(x: int) -> int { return x * x }\n@pure\nfunc h(f: func(int) -> int) -> int { return f(g) }\nconst C: int = sq(3)\n",
+    "@requires(n >= 0) @ensures(result > n)\nfunc next(n: int) -> int { return n + 1 }\nwrite next(1) == 2\nwrite next(-1)\n",
+    "@derive(hash, eq, to_string, clone, json)\nstruct P { x: int, y: int }\nlet m: map<P, int> = { P { x: 1, y: 2 }: 3 }\nwrite get(m, P { x: 1, y: 2 }.clone())\nwrite json(m.len())\n",
+    "let a: array<float> = array_filled(1000000, 0.5)\nlet b: array<int> = array_generate(a.len(), |i: int| -> int i * i)\nwrite b[3] + [1, 2][0]\n",
+    "func f(n: int) -> int { if n < 0 { throw n }\n return n }\ntry { write f(-1) } catch (e) { try { throw e } catch (e) { write e } }\n",
+    "type Ids = array<int>\ntype Pair = (Ids, func(int) -> int)\nfunc first(ids: Ids) -> int { return ids[0] }\nlet type: Pair = ([1], |x: int| -> int x)\n",
+    "struct Node { v: int, next: Node? }\nlet n: Node? = Node { v: 1, next: nil }\nlet m: int? = n?.next?.v\nwrite m ?? n?.v ?? 0\nwrite m == nil\n",
+    "config { threads = 2 * 2, verbose = true, name = \"x\" }\nconst T: int = config.threads + 1\nwrite config.threads * T\nwrite config\n",
+    "data text = <<<END\n  a <<<b\nEND\ndata bytes: array<int> = <<<X\nhi\nX\nwrite text\nwrite bytes.len()\n",
+    "func half(n: int) -> int { return n / 2 }\nfunc used() -> int { return 1 }\n@test\nfunc halves() -> int { if half(4) != 2 { throw 1 } return 0 }\nwrite used()\n",
+    "let n = 1\nn += 2\nn %= 3\nfor let i = 1; i < 9; i *= 2 { write \"i is ${i + n} of ${[n][0]} {}\" }\nlet s = \"${n}\"\n",
+];
+
+const FRAGMENTS: [&str; 45] = [
+    "func", "let", "const", "if", "else", "while", "for", "return", "defer", "throw", "try", "catch", "write", "struct", "enum", "trait", "impl", "pub", "@", "(", ")", "{", "}", "[", "]",
+    ":", "::", "->", ",", ";", ".", "=", "==", "\"", "|", "?", "??", "?.", "+=", "${", "nil", "1.5", "9223372036854775807", "x", "\n",
+];
+
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        if bound == 0 {
+            0
+        } else {
+            (self.next() % bound as u64) as usize
+        }
+    }
+}
+
+fn mutate(rng: &mut Rng, input: &str) -> String {
+    let mut chars: Vec<char> = input.chars().collect();
+    for _ in 0..=rng.below(4) {
+        let at = rng.below(chars.len() + 1);
+        match rng.below(4) {
+            0 => {
+                let fragment = FRAGMENTS.get(rng.below(FRAGMENTS.len())).copied().unwrap_or("");
+                chars.splice(at..at, fragment.chars());
+            }
+            1 => {
+                let end = (at + rng.below(8)).min(chars.len());
+                chars.drain(at..end);
+            }
+            2 => {
+                let end = (at + rng.below(16)).min(chars.len());
+                let copy: Vec<char> = chars.get(at..end).map(|c| c.to_vec()).unwrap_or_default();
+                chars.splice(at..at, copy);
+            }
+            _ => {
+                let c = char::from_u32(rng.below(0x3000) as u32).unwrap_or('?');
+                chars.insert(at, c);
+            }
+        }
+    }
+    chars.into_iter().collect()
+}
+
+// Whether the printed program parses back to the tree it was printed from.
+fn round_trip(arena: &Arena, ast: &[NodeId]) -> Result<(), String> {
+    let printed = print_program(arena, ast);
+    let mut reparsed_arena = Arena::new();
+    match parser(tokenize(&printed), &mut reparsed_arena).parse() {
+        Ok(reparsed) if reparsed.len() == ast.len() && ast.iter().zip(&reparsed).all(|(&x, &y)| same_tree(arena, x, &reparsed_arena, y)) => Ok(()),
+        _ => Err(format!("pretty-printed program does not parse back to the same tree:\n{}", printed)),
+    }
+}
+
+fn front_end(source: &str) -> Result<(), String> {
+    let mut arena = Arena::new();
+    if let Ok(mut ast) = parser(tokenize(source), &mut arena).parse() {
+        round_trip(&arena, &ast)?;
+        if derive::expand(&mut arena, &mut ast).is_err() {
+            return Ok(());
+        }
+        lower::lower(&mut arena, &ast);
+        let _ = resolve::check(&arena, &ast, None, false);
+        let hir = typecheck::check(&mut arena, &ast).unwrap_or_else(|_| hir::build(&arena, &ast));
+        fold::fold(&mut arena, &ast, &hir);
+        let mut program = Program { arena, modules: vec![(String::new(), ast)] };
+        strip::strip_tests(&mut program);
+        let Program { mut arena, mut modules } = program;
+        let ast = modules.pop().map(|(_, ast)| ast).unwrap_or_default();
+        if let (Ok(ast), Ok(mut codegen)) = (mono::monomorphize(&mut arena, ast), CodeGen::new(&Profile::debug())) {
+            let hir = hir::build(&arena, &ast);
+            let _ = codegen.compile(&arena, &ast, &hir);
+        }
+    }
+    Ok(())
+}
+
+pub fn run(iterations: usize, seed: u64) -> Result<(), String> {
+    let mut rng = Rng(seed.max(1));
+    for i in 0..iterations {
+        let base = SEEDS.get(rng.below(SEEDS.len())).copied().unwrap_or("");
+        let input = mutate(&mut rng, base);
+        let failure = match panic::catch_unwind(|| front_end(&input)) {
+            Ok(Ok(())) => continue,
+            Ok(Err(e)) => e,
+            Err(_) => "panic".to_string(),
+        };
+        let path = env::temp_dir().join(format!("vira-fuzz-{}-{}.vira", seed, i));
+        fs::write(&path, &input).map_err(|e| e.to_string())?;
+        return Err(format!("{} on iteration {}; input saved to {}", failure, i, path.display()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The seeds and a fixed run of their mutations: every one that parses
+    // prints to a program that parses back to the same tree.
+    #[test]
+    fn printed_programs_parse_back_to_the_same_tree() -> Result<(), String> {
+        let mut rng = Rng(2534);
+        let mutated: Vec<String> = (0..2000)
+            .map(|_| {
+                let base = SEEDS.get(rng.below(SEEDS.len())).copied().unwrap_or("");
+                mutate(&mut rng, base)
+            })
+            .collect();
+        let mut parsed = 0;
+        for (i, source) in SEEDS.iter().copied().chain(mutated.iter().map(String::as_str)).enumerate() {
+            let mut arena = Arena::new();
+            match parser(tokenize(source), &mut arena).parse() {
+                Ok(ast) => round_trip(&arena, &ast).map_err(|e| format!("input {}:\n{}\n{}", i, source, e))?,
+                Err(_) if i < SEEDS.len() => return Err(format!("seed {} does not parse:\n{}", i, source)),
+                Err(_) => continue,
+            }
+            parsed += 1;
+        }
+        // Enough mutations still parse for the run to say something.
+        assert!(parsed > SEEDS.len() + 200, "only {} inputs parsed", parsed);
+        Ok(())
+    }
+}
@@ -0,0 +1,766 @@
+#![deny(clippy::unwrap_used)]
+
+use std::collections::{BTreeSet, HashSet};
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+mod ast_json;
+mod capabilities;
+mod conformance;
+mod dap;
+mod debugger;
+mod doctest;
+mod engine;
+mod format;
+mod fuzz;
+mod stats;
+
+// The other stages live in crates of their own; the CLI reaches them by the
+// module paths they always had.
+use vira_check::{consteval, derive, effects, entry, ice, resolve};
+use vira_codegen_cranelift::codegen;
+use vira_ir::{deadcode, escape, fold, hir, link, mono, strip, typecheck, wpo};
+use vira_rt::{interpreter, interrupt, json, numerics, perf, profile};
+use vira_syntax::{arena, ast, confusables, diagnostic, diff, lower, parser, printer, tokenizer};
+
+use arena::{Arena, NodeId};
+use ast::Program;
+use codegen::CodeGen;
+use engine::{Engine, RunOptions};
+use entry::Entry;
+use interpreter::{Interpreter, Value};
+use parser::Parser;
+use profile::Profile;
+use tokenizer::{tokenize, Token};
+
+// Set by `--deny-warnings`, which makes the front end's warnings errors.
+static DENY_WARNINGS: AtomicBool = AtomicBool::new(false);
+// Set by `--warn-shadowing`, which warns about variables that hide an outer
+// one of the same name.
+static WARN_SHADOWING: AtomicBool = AtomicBool::new(false);
+
+fn parse_source(arena: &mut Arena, file: &str, source: &str) -> Result<Vec<NodeId>, String> {
+    parse_module(arena, file, source, None)
+}
+
+// Parses source that runs after the names in `known` have been declared, as
+// a REPL line does after the earlier ones.
+fn parse_after(arena: &mut Arena, file: &str, source: &str, known: &HashSet<String>) -> Result<Vec<NodeId>, String> {
+    parse_module(arena, file, source, Some(known))
+}
+
+// A parser that folds constants with the compile-time evaluator.
+fn parser(tokens: Vec<Token>, arena: &mut Arena) -> Parser<'_> {
+    let mut parser = Parser::new(tokens, arena);
+    parser.fold_with(&consteval::eval);
+    parser
+}
+
+fn parse_module(arena: &mut Arena, file: &str, source: &str, known: Option<&HashSet<String>>) -> Result<Vec<NodeId>, String> {
+    ice::set_source(file, source);
+    ice::enter_phase("tokenize");
+    let tokens = tokenize(source);
+    warn(confusables::confusable_warnings(&tokens))?;
+    ice::enter_phase("parse");
+    let mut ast = parser(tokens, arena).parse().map_err(|errors| diagnostic::render(&errors))?;
+    derive::expand(arena, &mut ast)?;
+    lower::lower(arena, &ast);
+    ice::enter_phase("resolve");
+    warn(resolve::check(arena, &ast, known, WARN_SHADOWING.load(Ordering::Relaxed))?)?;
+    effects::check(arena, &ast)?;
+    ice::enter_phase("typecheck");
+    typecheck::check(arena, &ast)?;
+    warn(deadcode::check(arena, &ast))?;
+    Ok(ast)
+}
+
+fn warn(warnings: Vec<String>) -> Result<(), String> {
+    if DENY_WARNINGS.load(Ordering::Relaxed) && !warnings.is_empty() {
+        return Err(format!("{}\n{} warning(s) denied by --deny-warnings.", warnings.join("\n"), warnings.len()));
+    }
+    for warning in warnings {
+        eprintln!("{}", warning);
+    }
+    Ok(())
+}
+
+fn compile_to_object(_source_dir: &Path, _platform: &str, _output_dir: &Path) -> Result<(), String> {
+    let main_file = _source_dir.join("main.vira");
+    let mut program = load_program(&main_file)?;
+    strip::strip_tests(&mut program);
+    let Program { mut arena, mut modules } = program;
+    if let Some((_, items)) = modules.last_mut() {
+        entry::prepare(&mut arena, items)?;
+    }
+
+    let profile = Profile::load(_source_dir, false)?;
+    ice::enter_phase("codegen");
+    for (name, ast) in modules {
+        let ast = mono::monomorphize(&mut arena, ast).map_err(|e| format!("{}: {}", name, e))?;
+        let mut codegen = CodeGen::new(&profile)?;
+        let hir = hir::build(&arena, &ast);
+        let _code = codegen.compile(&arena, &ast, &hir).map_err(|e| format!("{}: {}", name, e))?;
+    }
+
+    // Code is compiled in memory for this process, and refers to runtime
+    // state by its address here, so there is no object file to write yet.
+    Ok(())
+}
+
+fn vira_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "vira"))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+fn load_modules(source_dir: &Path) -> Result<Program, String> {
+    let mut program = Program { arena: Arena::new(), modules: Vec::new() };
+    for path in vira_files(source_dir)? {
+        let name = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        let source = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        let ast = parse_source(&mut program.arena, &path.display().to_string(), &source).map_err(|e| format!("{}: {}", path.display(), e))?;
+        program.modules.push((name, ast));
+    }
+    Ok(program)
+}
+
+fn module_name(path: &Path) -> String {
+    path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+// Loads `entry` and everything it imports, dependencies first, so the entry
+// module is always last.
+fn load_program(entry: &Path) -> Result<Program, String> {
+    let mut program = Program { arena: Arena::new(), modules: Vec::new() };
+    load_module(entry, &mut Vec::new(), &mut HashSet::new(), &mut program)?;
+    Ok(program)
+}
+
+fn load_module(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    loaded: &mut HashSet<PathBuf>,
+    program: &mut Program,
+) -> Result<(), String> {
+    let path = fs::canonicalize(path).map_err(|e| format!("Cannot load module {}: {}", path.display(), e))?;
+    if let Some(start) = stack.iter().position(|p| *p == path) {
+        let cycle: Vec<String> = stack.iter().skip(start).chain([&path]).map(|p| module_name(p)).collect();
+        return Err(format!("Import cycle: {}", cycle.join(" -> ")));
+    }
+    if !loaded.insert(path.clone()) {
+        return Ok(());
+    }
+    let source = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let ast = parse_source(&mut program.arena, &path.display().to_string(), &source).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    stack.push(path.clone());
+    let dir = path.parent().unwrap_or(Path::new("."));
+    for &node in &ast {
+        if let ast::AstNode::Item(ast::Item::Import(import)) = &program.arena[node] {
+            let file = dir.join(format!("{}.vira", import));
+            // A module of the program shadows a builtin one.
+            if import == numerics::MODULE && !file.exists() {
+                continue;
+            }
+            load_module(&file, stack, loaded, program)?;
+        }
+    }
+    stack.pop();
+
+    let name = module_name(&path);
+    if program.modules.iter().any(|(existing, _)| *existing == name) {
+        return Err(format!("Two imported modules are named '{}'.", name));
+    }
+    program.modules.push((name, ast));
+    Ok(())
+}
+
+struct Built {
+    modules: usize,
+    // Compile time and code size of each compiled unit; a whole-program
+    // build is a single unit.
+    units: Vec<stats::Unit>,
+    // Every compiled function, qualified by its module outside `--wpo`.
+    functions: Vec<(String, usize)>,
+    runtime: BTreeSet<&'static str>,
+}
+
+fn build(source_dir: &Path, wpo_enabled: bool, optimize: bool, profile: &Profile) -> Result<Built, String> {
+    let mut program = load_modules(source_dir)?;
+    strip::strip_tests(&mut program);
+    if optimize {
+        fold::fold_program(&mut program);
+    }
+    let Program { mut arena, mut modules } = program;
+    if let Some((_, items)) = modules.iter_mut().find(|(name, _)| name == "main") {
+        entry::prepare(&mut arena, items).map_err(|e| format!("main: {}", e))?;
+    }
+    let mut built = Built { modules: modules.len(), units: Vec::new(), functions: Vec::new(), runtime: BTreeSet::new() };
+    ice::enter_phase("codegen");
+    if wpo_enabled {
+        let start = Instant::now();
+        let program = wpo::optimize(&mut arena, modules);
+        let program = mono::monomorphize(&mut arena, program)?;
+        let hir = hir::build(&arena, &program);
+        let mut codegen = CodeGen::new(profile)?;
+        codegen.compile(&arena, &program, &hir)?;
+        built.units.push(stats::Unit { name: "(whole program)".to_string(), millis: start.elapsed().as_millis(), bytes: codegen.code_size() });
+        built.functions.extend(codegen.emitted().functions.iter().cloned());
+        built.runtime.extend(&codegen.emitted().runtime);
+    } else {
+        for (name, ast) in modules {
+            let start = Instant::now();
+            let ast = mono::monomorphize(&mut arena, ast).map_err(|e| format!("{}: {}", name, e))?;
+            let hir = hir::build(&arena, &ast);
+            let mut codegen = CodeGen::new(profile)?;
+            codegen.compile(&arena, &ast, &hir).map_err(|e| format!("{}: {}", name, e))?;
+            let emitted = codegen.emitted();
+            built.functions.extend(emitted.functions.iter().map(|(function, size)| (format!("{}::{}", name, function), *size)));
+            built.runtime.extend(&emitted.runtime);
+            built.units.push(stats::Unit { name, millis: start.elapsed().as_millis(), bytes: codegen.code_size() });
+        }
+    }
+    Ok(built)
+}
+
+// What each function may do when called, qualified by its module. Needs
+// only the front end, so it is available for code codegen cannot compile yet.
+fn effects_report(source_dir: &Path) -> Result<String, String> {
+    let mut functions = Vec::new();
+    let program = load_modules(source_dir)?;
+    for (name, ast) in &program.modules {
+        functions.extend(effects::analyze(&program.arena, ast).into_iter().map(|(function, effects)| (format!("{}::{}", name, function), effects)));
+    }
+    Ok(effects::report(&functions))
+}
+
+// Where each function's arrays and strings are allocated: in the region
+// freed when it returns, or on the heap.
+fn escapes_report(source_dir: &Path) -> Result<String, String> {
+    let program = load_modules(source_dir)?;
+    let analyses: Vec<(&String, escape::Escapes)> =
+        program.modules.iter().map(|(name, ast)| (name, escape::analyze(&program.arena, ast, &hir::build(&program.arena, ast)))).collect();
+    let sites: Vec<(String, &escape::Site)> =
+        analyses.iter().flat_map(|(name, escapes)| escapes.sites().iter().map(move |site| (format!("{}::{}", name, site.function), site))).collect();
+    Ok(escape::report(&sites))
+}
+
+fn has_flag(args: &[String], flag: &str) -> bool {
+    args.iter().any(|a| a == flag)
+}
+
+// `--flag value` or `--flag=value`.
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter().enumerate().find_map(|(i, arg)| match arg.strip_prefix(flag) {
+        Some("") => args.get(i + 1).map(|s| s.as_str()),
+        Some(value) => value.strip_prefix('='),
+        None => None,
+    })
+}
+
+// `run` and `check` with `--emit ast`: the parsed program as JSON instead.
+// `--emit source` prints it back as source after the passes that rewrite
+// the tree on loading, such as `@derive`, and with `-O` after folding too.
+fn emit(file: &Path, kind: &str, optimize: bool) -> Result<(), String> {
+    let mut program = load_program(file)?;
+    if optimize {
+        fold::fold_program(&mut program);
+    }
+    match kind {
+        "ast" => println!("{}", ast_json::program(&program.arena, &program.modules).pretty()),
+        "source" => {
+            for (name, items) in &program.modules {
+                println!("// module {}", name);
+                print!("{}", printer::print_program(&program.arena, items));
+            }
+        }
+        _ => return Err(format!("Unknown --emit kind '{}'; expected 'ast' or 'source'.", kind)),
+    }
+    Ok(())
+}
+
+// Prints the file in canonical form, or rewrites it with `--write`.
+fn format_file(file: &Path, write: bool) -> Result<(), String> {
+    let source = fs::read_to_string(file).map_err(|e| format!("Cannot read {}: {}", file.display(), e))?;
+    let formatted = format::format(&source)?;
+    if !write {
+        print!("{}", formatted);
+    } else if formatted != source {
+        fs::write(file, formatted).map_err(|e| format!("Cannot write {}: {}", file.display(), e))?;
+    }
+    Ok(())
+}
+
+// A program with `func main` exits with its result.
+fn run_file(file: &Path, release: bool, engine: Engine, options: RunOptions) -> Result<(), String> {
+    let mut program = load_program(file)?;
+    let Program { arena, modules } = &mut program;
+    let entry = match modules.last_mut() {
+        Some((_, items)) => entry::prepare(arena, items)?,
+        None => Entry::TopLevel,
+    };
+    let value = run_program(file, program, release, engine, options)?;
+    if let (Entry::Main, Some(Value::Int(code))) = (entry, value) {
+        io::stdout().flush().map_err(|e| e.to_string())?;
+        std::process::exit(code as i32);
+    }
+    Ok(())
+}
+
+fn run_program(file: &Path, program: Program, release: bool, engine: Engine, options: RunOptions) -> Result<Option<Value>, String> {
+    let profile = Profile::load(file.parent().unwrap_or(Path::new(".")), release)?;
+    engine.run(program, profile, options)
+}
+
+// Runs the tests of every .vira file in `dir`. A file with `@test`
+// functions runs each of them on its own: the file's declarations, then a
+// call to the test, without the rest of its top-level code. Any other file is
+// a single test run as a whole. The examples in a file's doc comments are
+// tests as well. A test passes when it runs to completion without an error.
+// A file whose config block sets `skip = true` is not run.
+fn run_tests(dir: &Path, release: bool, engine: Engine) -> Result<usize, String> {
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    let mut report = |name: String, result: Result<(), String>| match result {
+        Ok(()) => {
+            println!("PASS {}", name);
+            passed += 1;
+        }
+        Err(e) => {
+            println!("FAIL {}: {}", name, e);
+            failed += 1;
+        }
+    };
+    for file in &vira_files(dir)? {
+        let program = match load_program(file) {
+            Ok(program) => program,
+            Err(e) => {
+                report(file.display().to_string(), Err(e));
+                continue;
+            }
+        };
+        if skips(&program) {
+            println!("SKIP {}", file.display());
+            skipped += 1;
+            continue;
+        }
+        let tests = program.modules.last().map(|(_, items)| ast::tests(&program.arena, items)).unwrap_or_default();
+        if tests.is_empty() {
+            report(file.display().to_string(), run_program(file, program, release, engine, RunOptions::default()).map(|_| ()));
+        }
+        for test in tests {
+            let result = load_program(file).and_then(|mut program| {
+                keep_declarations(&mut program);
+                let (_, entry) = program.modules.last_mut().ok_or("Nothing to run.")?;
+                entry.push(program.arena.alloc(ast::Expr::Call(test.clone(), Vec::new())));
+                run_program(file, program, release, engine, RunOptions::default()).map(|_| ())
+            });
+            report(format!("{}::{}", file.display(), test), result);
+        }
+        let source = fs::read_to_string(file).map_err(|e| e.to_string())?;
+        let mut arena = Arena::new();
+        for example in doctest::extract(parser(tokenize(&source), &mut arena).comments()) {
+            let result = load_program(file).and_then(|program| doctest::run(file, program, &example));
+            report(format!("{}:{} (doctest)", file.display(), example.line), result);
+        }
+    }
+    println!("{} passed, {} failed, {} skipped on the {} engine.", passed, failed, skipped, engine);
+    Ok(failed)
+}
+
+// Drops the top-level code of the entry module, keeping its declarations,
+// top-level variables included, for a test to run after them.
+fn keep_declarations(program: &mut Program) {
+    let Program { arena, modules } = program;
+    if let Some((_, items)) = modules.last_mut() {
+        items.retain(|&item| matches!(arena[arena.item(item)], ast::AstNode::Item(_) | ast::AstNode::Stmt(ast::Stmt::VarDecl(..) | ast::Stmt::TupleDecl(..))));
+    }
+}
+
+// The file of the example named `name` in the manifest of the current
+// directory's project.
+fn example_file(name: &str) -> Result<PathBuf, String> {
+    let dir = env::current_dir().map_err(|e| e.to_string())?;
+    let examples = profile::examples(&dir)?;
+    match examples.into_iter().find(|example| example.name == name) {
+        Some(example) => Ok(example.path),
+        None => Err(format!("No example named '{}' in the manifest.", name)),
+    }
+}
+
+// `test --examples`: checks that every example in the manifest still loads,
+// which parses and type-checks it with its imports, without running it.
+// Returns how many do not.
+fn check_examples(dir: &Path) -> Result<usize, String> {
+    let examples = profile::examples(dir)?;
+    let mut failed = 0;
+    for example in &examples {
+        match load_program(&example.path) {
+            Ok(_) => println!("PASS example {}", example.name),
+            Err(e) => {
+                println!("FAIL example {}: {}", example.name, e);
+                failed += 1;
+            }
+        }
+    }
+    println!("{} of {} example(s) passed.", examples.len() - failed, examples.len());
+    Ok(failed)
+}
+
+// The REPL's `:backtrace`, which lists the frames the last evaluation
+// unwound when it failed, innermost first, and `:locals N`, which shows the
+// variables of frame N, the innermost one without N.
+fn post_mortem(interp: &Interpreter, command: &str) -> Result<(), String> {
+    let frames = interp.backtrace();
+    let mut words = command.split_whitespace();
+    let name = words.next().unwrap_or("");
+    if !matches!(name, "backtrace" | "locals") {
+        return Err(format!("Unknown command ':{}'; try :backtrace or :locals N.", name));
+    }
+    if frames.is_empty() {
+        return Err("The last evaluation did not fail; there is nothing to inspect.".to_string());
+    }
+    if name == "backtrace" {
+        for (i, frame) in frames.iter().enumerate() {
+            match frame.position {
+                Some((line, column)) => println!("#{} {} at {}:{}", i, frame.function, line, column),
+                None => println!("#{} {}", i, frame.function),
+            }
+        }
+        return Ok(());
+    }
+    let n = match words.next() {
+        None => 0,
+        Some(n) => n.parse::<usize>().map_err(|_| format!("Usage: :locals N, where N is a frame number from :backtrace, not '{}'.", n))?,
+    };
+    let frame = frames.get(n).ok_or_else(|| format!("No frame #{}; the backtrace has {}.", n, frames.len()))?;
+    for (name, value) in &frame.locals {
+        println!("{} = {} : {}", name, value, value.type_name());
+    }
+    Ok(())
+}
+
+// Whether the entry module's config block sets `skip = true`.
+fn skips(program: &Program) -> bool {
+    let Some((_, items)) = program.modules.last() else { return false };
+    ast::config(&program.arena, items)
+        .and_then(|entries| entries.iter().find(|(key, _)| key == "skip"))
+        .is_some_and(|&(_, value)| matches!(program.arena[value], ast::Expr::BoolLiteral(true)))
+}
+
+// Oracle for ICE minimization: does the front end or codegen still panic?
+fn front_end_crashes(source: &str) -> bool {
+    panic::catch_unwind(|| {
+        let mut arena = Arena::new();
+        if let Ok(ast) = parse_source(&mut arena, "<minimize>", source) {
+            ice::enter_phase("codegen");
+            if let (Ok(ast), Ok(mut codegen)) = (mono::monomorphize(&mut arena, ast), CodeGen::new(&Profile::debug())) {
+                let hir = hir::build(&arena, &ast);
+                let _ = codegen.compile(&arena, &ast, &hir);
+            }
+        }
+    })
+    .is_err()
+}
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    ice::install_hook();
+    match panic::catch_unwind(|| dispatch(&args)) {
+        Ok(result) => result,
+        Err(_) => {
+            ice::report(has_flag(&args, "--minimize"), front_end_crashes);
+            std::process::exit(101);
+        }
+    }
+}
+
+fn dispatch(args: &[String]) -> io::Result<()> {
+    let Some(command) = args.get(1) else {
+        println!("Usage: vira-compiler <command> [args]");
+        println!("Commands: compile <dir> --platform <plat> --output <out>, build <dir> [--wpo] [--release] [--size-report] [--emit effects|escapes], stats <dir>, run <file> | --example <name> [--release] [--dry-run] [--perf-counters] [--emit ast|source], repl, debug <file>, dap, test <dir> [--examples], conformance, eval <code>, check <file> [--emit ast|source], fmt <file> [--write], fuzz [iterations] [--seed <n>]");
+        println!("run, eval, test and conformance take --engine interp|jit (default: interp, or jit with --release).");
+        println!("Pass --minimize to shrink the reproduction written after an internal compiler error.");
+        println!("Pass --deny-warnings to make warnings, such as unused variables and functions or unreachable code, errors.");
+        println!("Pass --warn-shadowing to warn about variables that hide an outer one of the same name.");
+        return Ok(());
+    };
+    DENY_WARNINGS.store(has_flag(args, "--deny-warnings"), Ordering::Relaxed);
+    WARN_SHADOWING.store(has_flag(args, "--warn-shadowing"), Ordering::Relaxed);
+
+    match command.as_str() {
+        "compile" => {
+            let (Some(dir), Some(platform), Some(output)) = (args.get(2), args.get(4), args.get(6)) else {
+                println!("Usage: compile <dir> --platform <plat> --output <out>");
+                return Ok(());
+            };
+            let dir = Path::new(dir);
+            let output = Path::new(output);
+            if let Err(e) = compile_to_object(dir, platform, output) {
+                eprintln!("Compile error: {}", e);
+            } else {
+                println!("Compiled {}; object files are not written yet, so nothing was written to {}.", dir.display(), output.display());
+            }
+        }
+        "build" => {
+            let Some(dir) = args.get(2) else {
+                println!("Usage: build <dir> [--wpo] [--release] [-O] [--size-report] [--emit effects|escapes]");
+                return Ok(());
+            };
+            let dir = Path::new(dir);
+            let emit = flag_value(args, "--emit");
+            if let Some(kind) = emit.filter(|kind| *kind != "effects" && *kind != "escapes") {
+                eprintln!("Unknown --emit kind '{}'; expected 'effects' or 'escapes'.", kind);
+                return Ok(());
+            }
+            let report = match emit {
+                Some("effects") => Some(effects_report(dir)),
+                Some("escapes") => Some(escapes_report(dir)),
+                _ => None,
+            };
+            match report {
+                Some(Ok(report)) => print!("{}", report),
+                Some(Err(e)) => eprintln!("Build error: {}", e),
+                None => {}
+            }
+            let wpo_enabled = has_flag(args, "--wpo");
+            let profile = match Profile::load(dir, has_flag(args, "--release")) {
+                Ok(profile) => profile,
+                Err(e) => {
+                    eprintln!("Manifest error: {}", e);
+                    return Ok(());
+                }
+            };
+            let start = Instant::now();
+            let result = build(dir, wpo_enabled, has_flag(args, "-O"), &profile);
+            let millis = start.elapsed().as_millis();
+            let record = match result {
+                Ok(built) => {
+                    println!(
+                        "Built {} module(s) [{}]{} in {} ms",
+                        built.modules,
+                        profile.name,
+                        if wpo_enabled { " with whole-program optimization" } else { "" },
+                        millis
+                    );
+                    if has_flag(args, "--size-report") {
+                        print!("{}", stats::size_report(&built.functions, &built.runtime));
+                    }
+                    stats::Build::new(&profile.name, millis, true, built.units)
+                }
+                Err(e) => {
+                    eprintln!("Build error: {}", e);
+                    stats::Build::new(&profile.name, millis, false, Vec::new())
+                }
+            };
+            if let Err(e) = stats::record(dir, &record) {
+                eprintln!("Warning: build statistics not recorded: {}", e);
+            }
+        }
+        "stats" => {
+            let Some(dir) = args.get(2) else {
+                println!("Usage: stats <dir>");
+                return Ok(());
+            };
+            match stats::report(Path::new(dir)) {
+                Ok(report) => print!("{}", report),
+                Err(e) => eprintln!("Stats error: {}", e),
+            }
+        }
+        "run" => {
+            let file = match (flag_value(args, "--example"), args.get(2)) {
+                (Some(name), _) => match example_file(name) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        eprintln!("Run error: {}", e);
+                        return Ok(());
+                    }
+                },
+                (None, Some(file)) => PathBuf::from(file),
+                (None, None) => {
+                    println!("Usage: run <file> | --example <name> [--release] [-O] [--dry-run] [--perf-counters] [--emit ast|source]");
+                    return Ok(());
+                }
+            };
+            let file = file.as_path();
+            if let Some(kind) = flag_value(args, "--emit") {
+                if let Err(e) = emit(file, kind, has_flag(args, "-O")) {
+                    eprintln!("Run error: {}", e);
+                }
+                return Ok(());
+            }
+            let release = has_flag(args, "--release");
+            let options = RunOptions {
+                dry_run: has_flag(args, "--dry-run"),
+                perf_counters: has_flag(args, "--perf-counters"),
+                fold: has_flag(args, "-O"),
+            };
+            let result = Engine::select(flag_value(args, "--engine"), release).and_then(|engine| run_file(file, release, engine, options));
+            if let Err(e) = result {
+                eprintln!("Run error: {}", e);
+            }
+        }
+        // Parses and checks a program without running it.
+        "check" => {
+            let Some(file) = args.get(2) else {
+                println!("Usage: check <file> [-O] [--emit ast|source]");
+                return Ok(());
+            };
+            let file = Path::new(file);
+            let result = match flag_value(args, "--emit") {
+                Some(kind) => emit(file, kind, has_flag(args, "-O")),
+                None => load_program(file).and_then(|program| {
+                    if let Some((_, items)) = program.modules.last() {
+                        entry::check(&program.arena, items)?;
+                    }
+                    println!("Checked {} module(s).", program.modules.len());
+                    Ok(())
+                }),
+            };
+            if let Err(e) = result {
+                eprintln!("Check error: {}", e);
+            }
+        }
+        "fmt" => {
+            let Some(file) = args.get(2) else {
+                println!("Usage: fmt <file> [--write]");
+                return Ok(());
+            };
+            if let Err(e) = format_file(Path::new(file), has_flag(args, "--write")) {
+                eprintln!("Format error: {}", e);
+            }
+        }
+        "repl" => {
+            println!("Vira REPL");
+            interrupt::install();
+            let mut interp = Interpreter::new();
+            interp.keep_failed_frames();
+            let stdin = io::stdin();
+            loop {
+                print!("> ");
+                io::stdout().flush()?;
+                let mut input = String::new();
+                if stdin.lock().read_line(&mut input)? == 0 {
+                    break;
+                }
+                let input_trim = input.trim();
+                if input_trim == "exit" {
+                    break;
+                }
+                if let Some(command) = input_trim.strip_prefix(':') {
+                    if let Err(e) = post_mortem(&interp, command) {
+                        eprintln!("{}", e);
+                    }
+                    continue;
+                }
+                let known = interp.defined();
+                match parse_after(interp.arena_mut(), "<repl>", &input, &known) {
+                    Ok(ast) => {
+                        ice::enter_phase("interpret");
+                        interrupt::clear();
+                        match interp.interpret(&ast) {
+                            Ok(Some(value)) => println!("{} : {}", value, value.type_name()),
+                            Ok(None) => {}
+                            Err(e) => eprintln!("Error: {}", e),
+                        }
+                    }
+                    Err(e) => eprintln!("Parse error: {}", e),
+                }
+            }
+        }
+        "debug" => {
+            let Some(file) = args.get(2) else {
+                println!("Usage: debug <file>");
+                return Ok(());
+            };
+            if let Err(e) = debugger::debug(Path::new(file)) {
+                eprintln!("Debug error: {}", e);
+            }
+        }
+        "dap" => {
+            if let Err(e) = dap::serve() {
+                eprintln!("DAP error: {}", e);
+            }
+        }
+        "test" => {
+            let Some(dir) = args.get(2) else {
+                println!("Usage: test <dir> [--engine <engine>] [--release] [--examples]");
+                return Ok(());
+            };
+            let release = has_flag(args, "--release");
+            let result = Engine::select(flag_value(args, "--engine"), release).and_then(|engine| {
+                let failed = run_tests(Path::new(dir), release, engine)?;
+                if has_flag(args, "--examples") {
+                    return Ok(failed + check_examples(Path::new(dir))?);
+                }
+                Ok(failed)
+            });
+            match result {
+                Ok(0) => {}
+                Ok(_) => std::process::exit(1),
+                Err(e) => eprintln!("Test error: {}", e),
+            }
+        }
+        "conformance" => {
+            // Both engines unless one is named.
+            let engines = match flag_value(args, "--engine") {
+                None => Ok(vec![Engine::Interp, Engine::Jit]),
+                flag => Engine::select(flag, false).map(|engine| vec![engine]),
+            };
+            match engines {
+                Ok(engines) => {
+                    let failed: usize = engines.into_iter().map(conformance::run).sum();
+                    if failed > 0 {
+                        std::process::exit(1);
+                    }
+                }
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        }
+        "eval" => {
+            let Some(code) = args.get(2) else {
+                println!("Usage: eval <code>");
+                return Ok(());
+            };
+            let engine = match Engine::select(flag_value(args, "--engine"), false) {
+                Ok(engine) => engine,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return Ok(());
+                }
+            };
+            let mut arena = Arena::new();
+            match parse_source(&mut arena, "<eval>", code) {
+                Ok(ast) => match engine.run(Program { arena, modules: vec![(String::new(), ast)] }, Profile::debug(), RunOptions::default()) {
+                    Ok(Some(value)) => println!("{} : {}", value, value.type_name()),
+                    Ok(None) => {}
+                    Err(e) => eprintln!("Error: {}", e),
+                },
+                Err(e) => eprintln!("Parse error: {}", e),
+            }
+        }
+        "fuzz" => {
+            let iterations = args.get(2).and_then(|n| n.parse().ok()).unwrap_or(10_000);
+            let seed = flag_value(args, "--seed").and_then(|n| n.parse().ok()).unwrap_or(0x5eed);
+            match fuzz::run(iterations, seed) {
+                Ok(()) => println!("Fuzzed {} inputs without a failure.", iterations),
+                Err(e) => {
+                    eprintln!("Fuzz failure: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => println!("Unknown command"),
+    }
+
+    Ok(())
+}
+
@@ -0,0 +1,192 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Local build statistics. Every `build` appends a record to
+// `.vira/build-log` in the project directory and `stats` summarizes the log.
+// Nothing is sent anywhere; deleting the file resets the history.
+//
+// The log is plain text, one tab-separated line per build followed by one
+// line per compiled unit:
+//
+//     build <unix time> <profile> <ms> ok|failed
+//     unit <module> <ms> <bytes of machine code>
+
+const LOG: &str = ".vira/build-log";
+const RECENT_BUILDS: usize = 10;
+
+// A module, or the whole program for a `--wpo` build.
+pub struct Unit {
+    pub name: String,
+    pub millis: u128,
+    pub bytes: usize,
+}
+
+pub struct Build {
+    pub time: u64,
+    pub profile: String,
+    pub millis: u128,
+    pub ok: bool,
+    pub units: Vec<Unit>,
+}
+
+impl Build {
+    pub fn new(profile: &str, millis: u128, ok: bool, units: Vec<Unit>) -> Build {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        Build { time, profile: profile.to_string(), millis, ok, units }
+    }
+
+    fn bytes(&self) -> usize {
+        self.units.iter().map(|u| u.bytes).sum()
+    }
+}
+
+pub fn record(dir: &Path, build: &Build) -> Result<(), String> {
+    let path = dir.join(LOG);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Cannot create {}: {}", parent.display(), e))?;
+    }
+    let mut text = format!(
+        "build\t{}\t{}\t{}\t{}\n",
+        build.time,
+        build.profile,
+        build.millis,
+        if build.ok { "ok" } else { "failed" }
+    );
+    for unit in &build.units {
+        text.push_str(&format!("unit\t{}\t{}\t{}\n", unit.name, unit.millis, unit.bytes));
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .map_err(|e| format!("Cannot write {}: {}", path.display(), e))
+}
+
+fn parse(text: &str) -> Result<Vec<Build>, String> {
+    let mut builds: Vec<Build> = Vec::new();
+    for (line_no, line) in text.lines().enumerate() {
+        let malformed = || format!("{}:{}: malformed record.", LOG, line_no + 1);
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields.as_slice() {
+            ["build", time, profile, millis, status] => builds.push(Build {
+                time: time.parse().map_err(|_| malformed())?,
+                profile: profile.to_string(),
+                millis: millis.parse().map_err(|_| malformed())?,
+                ok: *status == "ok",
+                units: Vec::new(),
+            }),
+            ["unit", name, millis, bytes] => builds.last_mut().ok_or_else(malformed)?.units.push(Unit {
+                name: name.to_string(),
+                millis: millis.parse().map_err(|_| malformed())?,
+                bytes: bytes.parse().map_err(|_| malformed())?,
+            }),
+            [""] => {}
+            _ => return Err(malformed()),
+        }
+    }
+    Ok(builds)
+}
+
+pub fn report(dir: &Path) -> Result<String, String> {
+    let path = dir.join(LOG);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(_) => return Ok(format!("No builds recorded in {} yet.\n", dir.display())),
+    };
+    let builds = parse(&text)?;
+    let (Some(first), Some(last)) = (builds.first(), builds.last()) else {
+        return Ok(format!("No builds recorded in {} yet.\n", dir.display()));
+    };
+    let failed = builds.iter().filter(|b| !b.ok).count();
+    let mut out = format!(
+        "{} build(s) from {} to {}, {} failed.\n",
+        builds.len(),
+        date(first.time),
+        date(last.time),
+        failed
+    );
+
+    // Per unit, over the successful builds: (builds, total ms, first size, last ms, last size).
+    let mut units: BTreeMap<&str, (usize, u128, usize, u128, usize)> = BTreeMap::new();
+    for unit in builds.iter().filter(|b| b.ok).flat_map(|b| &b.units) {
+        let entry = units.entry(&unit.name).or_insert((0, 0, unit.bytes, 0, 0));
+        entry.0 += 1;
+        entry.1 += unit.millis;
+        entry.3 = unit.millis;
+        entry.4 = unit.bytes;
+    }
+    if !units.is_empty() {
+        let width = units.keys().map(|name| name.len()).max().unwrap_or(0).max("Module".len());
+        out.push_str(&format!(
+            "\n{:<width$}  {:>6}  {:>7}  {:>7}  {:>10}  {:>10}\n",
+            "Module", "Builds", "Last ms", "Avg ms", "Last size", "Change"
+        ));
+        for (name, (count, total, first_bytes, last_millis, last_bytes)) in &units {
+            let change = *last_bytes as i64 - *first_bytes as i64;
+            out.push_str(&format!(
+                "{:<width$}  {:>6}  {:>7}  {:>7}  {:>10}  {:>10}\n",
+                name,
+                count,
+                last_millis,
+                total / *count as u128,
+                format!("{} B", last_bytes),
+                format!("{:+} B", change)
+            ));
+        }
+    }
+
+    out.push_str("\nRecent builds\n");
+    for build in builds.iter().rev().take(RECENT_BUILDS) {
+        out.push_str(&format!(
+            "{}  {:<8}  {:<6}  {:>7} ms  {:>10}\n",
+            date(build.time),
+            build.profile,
+            if build.ok { "ok" } else { "failed" },
+            build.millis,
+            format!("{} B", build.bytes())
+        ));
+    }
+    Ok(out)
+}
+
+// Machine code per function, largest first, as the JIT compiles it for this
+// machine; a build writes no binary to measure. Runtime functions are part
+// of the compiler binary, so they are listed but have no size of their own.
+pub fn size_report(functions: &[(String, usize)], runtime: &BTreeSet<&str>) -> String {
+    let mut functions = functions.to_vec();
+    functions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let total: usize = functions.iter().map(|(_, size)| size).sum();
+    let width = functions.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max("Function".len());
+    let mut out = format!("\n{:<width$}  {:>10}  {:>6}\n", "Function", "Size", "Share");
+    for (name, size) in &functions {
+        let share = if total == 0 { 0.0 } else { *size as f64 * 100.0 / total as f64 };
+        out.push_str(&format!("{:<width$}  {:>10}  {:>5.1}%\n", name, format!("{} B", size), share));
+    }
+    out.push_str(&format!("{:<width$}  {:>10}\n", "Total", format!("{} B", total)));
+    if !runtime.is_empty() {
+        let runtime: Vec<&str> = runtime.iter().copied().collect();
+        out.push_str(&format!("Runtime (part of the compiler, not counted): {}\n", runtime.join(", ")));
+    }
+    out
+}
+
+// `YYYY-MM-DD HH:MM` in UTC.
+fn date(time: u64) -> String {
+    let days = (time / 86400) as i64;
+    let minutes = time % 86400 / 60;
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, minutes / 60, minutes % 60)
+}
@@ -0,0 +1,90 @@
+// What `build --wpo` costs at build time and saves at run time. A program of
+// generated library modules and a main module whose loop calls a small
+// function is built module by module, as `build` does, and as one unit, as
+// `build --wpo` does. Then the main module's loop runs, compiled each way.
+
+use std::time::{Duration, Instant};
+
+use vira_codegen_cranelift::codegen::CodeGen;
+use vira_ir::{hir, mono, wpo};
+use vira_rt::profile::Profile;
+use vira_syntax::arena::{Arena, NodeId};
+use vira_syntax::diagnostic;
+use vira_syntax::lower;
+use vira_syntax::parser::Parser;
+use vira_syntax::tokenizer::tokenize;
+
+const LIBRARIES: usize = 100;
+const ITERATIONS: usize = 10_000_000;
+const SAMPLES: u32 = 5;
+
+fn library(index: usize) -> String {
+    let mut source = String::new();
+    for function in 0..10 {
+        source += &format!("pub func f{}_{}(a: int, b: int) -> int {{\n    checked_add(a, b) ?? {}\n}}\n", index, function, function);
+    }
+    source
+}
+
+fn main_module() -> String {
+    format!(
+        "func add(a: int, b: int) -> int {{\n    checked_add(a, b) ?? 0\n}}\nlet total = 0\nfor i in 0..{} {{\n    total = add(total, i)\n}}\ntotal\n",
+        ITERATIONS
+    )
+}
+
+fn parse(arena: &mut Arena, source: &str) -> Result<Vec<NodeId>, String> {
+    let ast = Parser::new(tokenize(source), arena).parse().map_err(|errors| diagnostic::render(&errors))?;
+    lower::lower(arena, &ast);
+    Ok(ast)
+}
+
+// Builds the program as `build` does, or as `build --wpo` does, and returns
+// the time that took with the compiled main module.
+fn build(whole_program: bool) -> Result<(Duration, CodeGen, *const u8), String> {
+    let mut arena = Arena::new();
+    let mut modules = Vec::new();
+    for index in 0..LIBRARIES {
+        modules.push((format!("lib{}", index), parse(&mut arena, &library(index))?));
+    }
+    modules.push(("main".to_string(), parse(&mut arena, &main_module())?));
+    let profile = Profile::release();
+    let start = Instant::now();
+    if whole_program {
+        let program = wpo::optimize(&mut arena, modules);
+        let program = mono::monomorphize(&mut arena, program)?;
+        let hir = hir::build(&arena, &program);
+        let mut codegen = CodeGen::new(&profile)?;
+        let code = codegen.compile(&arena, &program, &hir)?;
+        return Ok((start.elapsed(), codegen, code));
+    }
+    let (_, main) = modules.pop().ok_or("No main module.")?;
+    for (_, ast) in modules {
+        let ast = mono::monomorphize(&mut arena, ast)?;
+        let hir = hir::build(&arena, &ast);
+        CodeGen::new(&profile)?.compile(&arena, &ast, &hir)?;
+    }
+    let main = mono::monomorphize(&mut arena, main)?;
+    let hir = hir::build(&arena, &main);
+    let mut codegen = CodeGen::new(&profile)?;
+    let code = codegen.compile(&arena, &main, &hir)?;
+    Ok((start.elapsed(), codegen, code))
+}
+
+fn main() -> Result<(), String> {
+    for (label, whole_program) in [("build", false), ("build --wpo", true)] {
+        let (mut building, mut running) = (Duration::MAX, Duration::MAX);
+        for _ in 0..SAMPLES {
+            let (took, _codegen, code) = build(whole_program)?;
+            // The entry function takes no arguments and returns an I64; the
+            // code lives as long as `_codegen`.
+            let main = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i64>(code) };
+            let start = Instant::now();
+            std::hint::black_box(main());
+            building = building.min(took);
+            running = running.min(start.elapsed());
+        }
+        println!("{:<12} {} modules built in {:>7.1?}, {} calls ran in {:>7.1?}", label, LIBRARIES + 1, building, ITERATIONS, running);
+    }
+    Ok(())
+}
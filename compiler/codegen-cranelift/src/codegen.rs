@@ -0,0 +1,2071 @@
+#![deny(clippy::indexing_slicing)]
+
+use std::collections::{BTreeSet, HashMap};
+
+use cranelift::codegen::cursor::{Cursor, FuncCursor};
+use cranelift::codegen::ir::{FuncRef, Function, Inst, InstructionData};
+use cranelift::prelude::*;
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{DataDescription, DataId, FuncId, Linkage, Module};
+use vira_ir::escape::{self, Escapes};
+use vira_ir::hir::{self, Hir};
+use vira_ir::typecheck;
+use vira_rt::builtins::{self, Receiver};
+use vira_rt::interrupt;
+use vira_rt::perf;
+use vira_rt::profile::Profile;
+use vira_rt::runtime;
+use vira_syntax::arena::{Arena, ExprId, ItemId, NodeId};
+use vira_syntax::ast::{AstNode, BinOp, Expr, Item, MethodSig, Pattern, Stmt, UnaryOp, Variant, ViraType};
+
+pub struct CodeGen {
+    builder_context: FunctionBuilderContext,
+    ctx: codegen::Context,
+    module: JITModule,
+    emitted: Emitted,
+    // Whether the entry function returns the value of a trailing top-level
+    // int expression, such as the call of `func main`, rather than 0.
+    returns_value: bool,
+    overflow_checks: bool,
+}
+
+// Every function compiled so far with its machine-code size, and the runtime
+// functions the code calls.
+#[derive(Default)]
+pub struct Emitted {
+    pub functions: Vec<(String, usize)>,
+    pub runtime: BTreeSet<&'static str>,
+    anonymous: usize,
+    // Counters of the instrumented functions, when perf counters are on.
+    perf: Option<Vec<perf::Row>>,
+}
+
+impl Emitted {
+    // Makes a finished function count its calls, and per executed block its
+    // IR instructions, conditional branches and allocations. Counts are taken
+    // before the counter updates are added, so they do not count themselves.
+    fn instrument(&mut self, name: &str, func: &mut Function, allocating: &[FuncRef], pointer_type: Type) {
+        let Some(rows) = &mut self.perf else {
+            return;
+        };
+        let row = perf::Row::new(name.to_string());
+        let entry = func.layout.entry_block();
+        let blocks: Vec<Block> = func.layout.blocks().collect();
+        for block in blocks {
+            let mut counts = [0i64; perf::COUNTERS];
+            if Some(block) == entry {
+                counts[perf::CALLS] = 1;
+            }
+            for inst in func.layout.block_insts(block) {
+                counts[perf::INSTRUCTIONS] += 1;
+                match func.dfg.insts[inst] {
+                    InstructionData::Brif { .. } | InstructionData::BranchTable { .. } => counts[perf::BRANCHES] += 1,
+                    InstructionData::Call { func_ref, .. } if allocating.contains(&func_ref) => counts[perf::ALLOCATIONS] += 1,
+                    _ => {}
+                }
+            }
+            let mut pos = FuncCursor::new(func).at_first_insertion_point(block);
+            for (counter, count) in counts.into_iter().enumerate() {
+                if count > 0 {
+                    let addr = pos.ins().iconst(pointer_type, row.address(counter));
+                    let old = pos.ins().load(types::I64, MemFlags::trusted(), addr, 0);
+                    let new = pos.ins().iadd_imm(old, count);
+                    pos.ins().store(MemFlags::trusted(), new, addr, 0);
+                }
+            }
+        }
+        rows.push(row);
+    }
+}
+
+// Every field occupies one 8-byte slot: ints, bools and pointers to nested
+// aggregates as I64, floats as F64.
+#[derive(Clone)]
+struct StructLayout {
+    fields: Vec<(String, ViraType)>,
+}
+
+impl StructLayout {
+    const FIELD_SIZE: u32 = 8;
+
+    fn size(&self) -> u32 {
+        self.fields.len() as u32 * Self::FIELD_SIZE
+    }
+
+    fn field(&self, name: &str) -> Option<(i32, &ViraType)> {
+        self.fields
+            .iter()
+            .enumerate()
+            .find(|(_, (f, _))| f == name)
+            .map(|(i, (_, typ))| ((i as u32 * Self::FIELD_SIZE) as i32, typ))
+    }
+}
+
+// Enum values are a discriminant slot followed by enough 8-byte payload slots
+// for the largest variant. The slot holds the variant's declared discriminant,
+// which `discriminant(value)` reads back.
+#[derive(Clone)]
+struct EnumLayout {
+    variants: Vec<Variant>,
+}
+
+impl EnumLayout {
+    fn size(&self) -> u32 {
+        let max_payload = self.variants.iter().map(|(_, p, _)| p.len()).max().unwrap_or(0);
+        (1 + max_payload as u32) * StructLayout::FIELD_SIZE
+    }
+
+    fn discriminant(&self, variant: &str) -> Option<(i64, &[ViraType])> {
+        self.variants.iter().find(|(v, ..)| v == variant).map(|(_, payload, discriminant)| (*discriminant, payload.as_slice()))
+    }
+}
+
+// A compiled named function or impl method. A method's `params` exclude
+// `self`.
+#[derive(Clone)]
+struct Method {
+    id: FuncId,
+    params: Vec<ViraType>,
+}
+
+struct FunctionTranslator<'a> {
+    ast: &'a Arena,
+    hir: &'a Hir,
+    // The arrays that cannot outlive their function, which are freed when
+    // it returns.
+    escapes: &'a Escapes,
+    builder: FunctionBuilder<'a>,
+    pointer_type: Type,
+    structs: HashMap<String, StructLayout>,
+    enums: HashMap<String, EnumLayout>,
+    // A value whose static type is a trait is a trait object: a pointer to a
+    // (value, vtable) pair. The vtable holds the addresses of the impl's
+    // methods in the order the trait declares them.
+    traits: HashMap<String, Vec<MethodSig>>,
+    // Keyed by implementing type and method name.
+    methods: HashMap<(String, String), Method>,
+    // Keyed by trait and implementing type.
+    vtables: HashMap<(String, String), DataId>,
+    // Named functions declared so far in this function or around it.
+    functions: HashMap<String, Method>,
+    // The functions of the blocks being compiled, declared when the block
+    // starts so that calls ahead of them and mutual recursion compile, by
+    // body, until they are defined.
+    hoisted: HashMap<NodeId, FuncId>,
+    variables: HashMap<hir::SymbolId, Variable>,
+    // Constants are folded literals and are emitted as immediates at each use.
+    consts: HashMap<String, NodeId>,
+    // Data sections, with their length in bytes, for arrays to be built
+    // from at each use.
+    data: HashMap<String, (DataId, ViraType, usize)>,
+    next_variable: usize,
+    module: &'a mut JITModule,
+    emitted: &'a mut Emitted,
+    // Calls to runtime functions that allocate, for the perf counters.
+    allocating: Vec<FuncRef>,
+    // The catch block of the innermost `try` in this function, which takes
+    // the thrown value as its parameter.
+    handler: Option<Block>,
+    // The return type of the function being compiled; None for main.
+    ret: Option<ViraType>,
+    // Whether the program has a `throw` anywhere. Calls only check for a
+    // thrown value when it does.
+    may_throw: bool,
+    // The deferred statements of the blocks being compiled, innermost last,
+    // which a `return` runs first.
+    deferred: Vec<Vec<NodeId>>,
+    // The mark of the function's region, which a `return` leaves.
+    region: Option<Value>,
+    // Whether int arithmetic fails on overflow instead of wrapping.
+    overflow_checks: bool,
+}
+
+impl CodeGen {
+    pub fn new(profile: &Profile) -> Result<Self, String> {
+        let mut flag_builder = settings::builder();
+        let backend_error = |e: settings::SetError| format!("Backend configuration error: {}", e);
+        flag_builder.set("use_colocated_libcalls", "false").map_err(backend_error)?;
+        flag_builder.set("is_pic", "false").map_err(backend_error)?;
+        flag_builder.set("opt_level", profile.cranelift_opt_level()).map_err(backend_error)?;
+        let isa_builder = cranelift_native::builder().map_err(|msg| format!("Host machine is not supported: {}", msg))?;
+        let flags = settings::Flags::new(flag_builder);
+        let isa = isa_builder.finish(flags).map_err(|e| format!("Backend configuration error: {}", e))?;
+        let mut builder = JITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
+        for (name, address) in runtime::symbols() {
+            builder.symbol(name, address);
+        }
+        let module = JITModule::new(builder);
+
+        Ok(CodeGen {
+            builder_context: FunctionBuilderContext::new(),
+            ctx: module.make_context(),
+            module,
+            emitted: Emitted::default(),
+            returns_value: false,
+            overflow_checks: profile.overflow_checks,
+        })
+    }
+
+    // Compiles a module, or the whole program, with the HIR of its tree as
+    // the last pass left it.
+    pub fn compile(&mut self, ast: &Arena, program: &[NodeId], hir: &Hir) -> Result<*const u8, String> {
+        let mut sig = self.module.make_signature();
+        sig.returns.push(AbiParam::new(types::I64));
+        self.ctx.func.signature = sig.clone();
+
+        let func_id = self
+            .module
+            .declare_function("main", Linkage::Export, &sig)
+            .map_err(|e| format!("Codegen error: {}", e))?;
+        let mut fn_builder = FunctionBuilder::new(&mut self.ctx.func, &mut self.builder_context);
+
+        let entry_block = fn_builder.create_block();
+        fn_builder.switch_to_block(entry_block);
+        fn_builder.seal_block(entry_block);
+
+        let escapes = escape::analyze(ast, program, hir);
+        let mut translator = FunctionTranslator {
+            ast,
+            hir,
+            escapes: &escapes,
+            builder: fn_builder,
+            pointer_type: self.module.target_config().pointer_type(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            traits: HashMap::new(),
+            methods: HashMap::new(),
+            vtables: HashMap::new(),
+            functions: HashMap::new(),
+            hoisted: HashMap::new(),
+            variables: HashMap::new(),
+            consts: HashMap::new(),
+            data: HashMap::new(),
+            next_variable: 0,
+            module: &mut self.module,
+            emitted: &mut self.emitted,
+            allocating: Vec::new(),
+            handler: None,
+            ret: None,
+            may_throw: program.iter().any(|&node| throws(ast, node)),
+            deferred: Vec::new(),
+            region: None,
+            overflow_checks: self.overflow_checks,
+        };
+        let mut last = None;
+        if let Err(e) = translator.hoist(program) {
+            self.module.clear_context(&mut self.ctx);
+            return Err(e);
+        }
+        for &node in program {
+            match translator.translate(node) {
+                Ok(value) => last = Some(value),
+                Err(e) => {
+                    self.module.clear_context(&mut self.ctx);
+                    return Err(e);
+                }
+            }
+        }
+
+        let value = match (program.last(), last) {
+            (Some(&node), Some(value)) if ast[node].is_expression() && hir.type_of(node) == Some(&ViraType::Int) => Some(value),
+            _ => None,
+        };
+        self.returns_value = value.is_some();
+        let value = value.unwrap_or_else(|| translator.builder.ins().iconst(types::I64, 0));
+        translator.builder.ins().return_(&[value]);
+
+        let allocating = std::mem::take(&mut translator.allocating);
+        translator.builder.finalize();
+        let pointer_type = self.module.target_config().pointer_type();
+        self.emitted.instrument("main", &mut self.ctx.func, &allocating, pointer_type);
+        let defined = self.module.define_function(func_id, &mut self.ctx);
+        let size = self.ctx.compiled_code().map_or(0, |code| code.code_buffer().len());
+        self.emitted.functions.push(("main".to_string(), size));
+        self.module.clear_context(&mut self.ctx);
+        defined.map_err(|e| format!("Codegen error: {}", e))?;
+        self.module.finalize_definitions().map_err(|e| format!("Codegen error: {}", e))?;
+
+        let code = self.module.get_finalized_function(func_id);
+        Ok(code)
+    }
+
+    pub fn returns_value(&self) -> bool {
+        self.returns_value
+    }
+
+    pub fn code_size(&self) -> usize {
+        self.emitted.functions.iter().map(|(_, size)| size).sum()
+    }
+
+    pub fn emitted(&self) -> &Emitted {
+        &self.emitted
+    }
+
+    // Must be called before `compile`.
+    pub fn enable_perf_counters(&mut self) {
+        self.emitted.perf = Some(Vec::new());
+    }
+
+    pub fn perf_counters(&self) -> Option<&[perf::Row]> {
+        self.emitted.perf.as_deref()
+    }
+}
+
+impl FunctionTranslator<'_> {
+    fn translate(&mut self, id: NodeId) -> Result<Value, String> {
+        match &self.ast[id] {
+            AstNode::Expr(expr) => self.translate_expr(id, expr),
+            AstNode::Stmt(stmt) => self.translate_stmt(id, stmt),
+            AstNode::Item(item) => self.translate_item(item),
+        }
+    }
+
+    fn translate_expr(&mut self, id: NodeId, expr: &Expr) -> Result<Value, String> {
+        let ast = self.ast;
+        match expr {
+            Expr::Literal(val) => Ok(self.builder.ins().iconst(types::I64, *val)),
+            Expr::FloatLiteral(val) => Ok(self.builder.ins().f64const(*val)),
+            Expr::IntToFloat(operand) => {
+                let value = self.translate(operand.node())?;
+                Ok(self.builder.ins().fcvt_from_sint(types::F64, value))
+            }
+            Expr::BoolLiteral(val) => Ok(self.builder.ins().iconst(types::I64, *val as i64)),
+            // An empty data section cannot be placed, so "" passes no bytes.
+            Expr::StringLiteral(text) if text.is_empty() => {
+                let (bytes, len) = (self.builder.ins().iconst(self.pointer_type, 0), self.builder.ins().iconst(types::I64, 0));
+                self.call_runtime(runtime::STRING_FROM_BYTES, &[bytes, len])
+            }
+            Expr::StringLiteral(text) => {
+                let codegen_error = |e: cranelift_module::ModuleError| format!("Codegen error: {}", e);
+                let mut description = DataDescription::new();
+                description.define(text.as_bytes().to_vec().into_boxed_slice());
+                let data_id = self.module.declare_anonymous_data(false, false).map_err(codegen_error)?;
+                self.module.define_data(data_id, &description).map_err(codegen_error)?;
+                let global = self.module.declare_data_in_func(data_id, self.builder.func);
+                let bytes = self.builder.ins().global_value(self.pointer_type, global);
+                let len = self.builder.ins().iconst(types::I64, text.len() as i64);
+                self.call_runtime(runtime::STRING_FROM_BYTES, &[bytes, len])
+            }
+            // A block's deferred statements are emitted after its last
+            // statement, and before each `return` inside it.
+            Expr::Block(stmts) => {
+                self.hoist(stmts)?;
+                let mut last = self.builder.ins().iconst(types::I64, 0);
+                self.deferred.push(Vec::new());
+                for &stmt in stmts {
+                    if let AstNode::Stmt(Stmt::Defer(stmt)) = &ast[stmt] {
+                        self.deferred.last_mut().ok_or("Missing block.")?.push(*stmt);
+                        last = self.builder.ins().iconst(types::I64, 0);
+                    } else {
+                        last = self.translate(stmt)?;
+                    }
+                }
+                let deferred = self.deferred.pop().unwrap_or_default();
+                // A throw would jump past them.
+                if self.may_throw && !deferred.is_empty() {
+                    return Err("Codegen does not support 'defer' in programs that throw.".to_string());
+                }
+                for stmt in deferred.into_iter().rev() {
+                    self.translate(stmt)?;
+                }
+                Ok(last)
+            }
+            Expr::VarRef(name) if self.variable(id).is_none() && self.consts.contains_key(name) => {
+                let value = *self.consts.get(name).ok_or(format!("Undefined constant '{}'.", name))?;
+                self.translate(value)
+            }
+            Expr::VarRef(name) if self.variable(id).is_none() && self.data.contains_key(name) => {
+                let (data_id, typ, len) = self.data.get(name).cloned().ok_or(format!("Undefined data section '{}'.", name))?;
+                let global = self.module.declare_data_in_func(data_id, self.builder.func);
+                let bytes = self.builder.ins().global_value(self.pointer_type, global);
+                let len = self.builder.ins().iconst(types::I64, len as i64);
+                let function = if typ == ViraType::String { runtime::STRING_FROM_BYTES } else { runtime::ARRAY_FROM_BYTES };
+                self.call_runtime(function, &[bytes, len])
+            }
+            // A named function used as a value is its address.
+            Expr::VarRef(name) if self.variable(id).is_none() && self.functions.contains_key(name) => {
+                let func = self.functions.get(name).ok_or(format!("Undefined function '{}'.", name))?;
+                let func_ref = self.module.declare_func_in_func(func.id, self.builder.func);
+                Ok(self.builder.ins().func_addr(self.pointer_type, func_ref))
+            }
+            Expr::VarRef(name) => {
+                let var = self.variable(id).ok_or(format!("Undefined variable '{}'.", name))?;
+                Ok(self.builder.use_var(var))
+            }
+            Expr::StructLiteral(name, inits) => {
+                let layout = self.structs.get(name).ok_or(format!("Undefined struct '{}'.", name))?;
+                let size = layout.size();
+                let mut stores = Vec::new();
+                for (field, _) in &layout.fields {
+                    let (offset, _) = layout.field(field).ok_or(format!("Struct '{}' has no field '{}'.", name, field))?;
+                    let init = inits
+                        .iter()
+                        .find(|(f, _)| f == field)
+                        .ok_or(format!("Missing field '{}' in '{}' literal.", field, name))?;
+                    stores.push((offset, init.1));
+                }
+                let addr = self.allocate(size)?;
+                for (offset, init) in stores {
+                    let value = self.translate(init.node())?;
+                    self.builder.ins().store(MemFlags::trusted(), value, addr, offset);
+                }
+                Ok(addr)
+            }
+            Expr::FieldAccess(base, field) if self.config_value(base.node(), field).is_some() => {
+                let value = self.config_value(base.node(), field).ok_or(format!("Undefined config key '{}'.", field))?;
+                self.translate(value)
+            }
+            Expr::FieldAccess(base, field) => {
+                let struct_name = match self.static_type(base.node()) {
+                    Some(ViraType::Struct(name)) => name,
+                    _ => return Err(format!("Cannot access field '{}' on non-struct value.", field)),
+                };
+                let layout = self.structs.get(&struct_name).ok_or(format!("Undefined struct '{}'.", struct_name))?;
+                let (offset, field_type) = layout
+                    .field(field)
+                    .ok_or(format!("Struct '{}' has no field '{}'.", struct_name, field))?;
+                let ty = self.cranelift_type(field_type);
+                let addr = self.translate(base.node())?;
+                Ok(self.builder.ins().load(ty, MemFlags::trusted(), addr, offset))
+            }
+            // Tuples are laid out like structs, one 8-byte slot per element.
+            Expr::Tuple(items) => {
+                let addr = self.allocate(items.len() as u32 * StructLayout::FIELD_SIZE)?;
+                for (i, item) in items.iter().enumerate() {
+                    let value = self.translate(item.node())?;
+                    self.builder.ins().store(MemFlags::trusted(), value, addr, (i as u32 * StructLayout::FIELD_SIZE) as i32);
+                }
+                Ok(addr)
+            }
+            Expr::TupleIndex(base, index) => {
+                let element = match self.static_type(base.node()) {
+                    Some(ViraType::Tuple(elements)) => elements
+                        .get(*index)
+                        .cloned()
+                        .ok_or(format!("Tuple index {} is out of range for a tuple of {} element(s).", index, elements.len()))?,
+                    _ => return Err(format!("Cannot take element {} of a non-tuple value.", index)),
+                };
+                let ty = self.cranelift_type(&element);
+                let addr = self.translate(base.node())?;
+                let offset = (*index as u32 * StructLayout::FIELD_SIZE) as i32;
+                Ok(self.builder.ins().load(ty, MemFlags::trusted(), addr, offset))
+            }
+            Expr::EnumConstructor(name, variant, args) => {
+                let layout = self.enums.get(name).ok_or(format!("Undefined enum '{}'.", name))?;
+                let (discriminant, payload) = layout
+                    .discriminant(variant)
+                    .ok_or(format!("Enum '{}' has no variant '{}'.", name, variant))?;
+                if payload.len() != args.len() {
+                    return Err(format!("Variant '{}::{}' expects {} value(s), got {}.", name, variant, payload.len(), args.len()));
+                }
+                let addr = self.allocate(layout.size())?;
+                let tag = self.builder.ins().iconst(types::I64, discriminant);
+                self.builder.ins().store(MemFlags::trusted(), tag, addr, 0);
+                for (i, arg) in args.iter().enumerate() {
+                    let value = self.translate(arg.node())?;
+                    let offset = ((i as u32 + 1) * StructLayout::FIELD_SIZE) as i32;
+                    self.builder.ins().store(MemFlags::trusted(), value, addr, offset);
+                }
+                Ok(addr)
+            }
+            // Operators on structs and enums call the type's method of that name.
+            Expr::Binary(left, op, right) if self.is_user_type(left.node()) => {
+                let method = op.method().ok_or(format!("Codegen does not support {} yet.", expr.kind()))?;
+                let result = self.translate_method_call(*left, method, std::slice::from_ref(right))?;
+                Ok(if *op == BinOp::Neq { self.builder.ins().bxor_imm(result, 1) } else { result })
+            }
+            Expr::Binary(left, op, right) => self.translate_binary(*left, *op, *right),
+            Expr::Unary(op, operand) => self.translate_unary(*op, operand.node()),
+            Expr::Index(base, index) if self.is_user_type(base.node()) => self.translate_method_call(*base, "index", std::slice::from_ref(index)),
+            Expr::Index(base, index) => self.translate_index(*base, *index),
+            Expr::MethodCall(receiver, method, args) => self.translate_method_call(*receiver, method, args),
+            Expr::Match(scrutinee, arms) => self.translate_match(*scrutinee, arms),
+            Expr::If(cond, then, else_) => self.translate_if(cond.node(), *then, *else_),
+            Expr::Nil => self.optional(None),
+            Expr::Coalesce(left, right) => self.translate_coalesce(*left, *right),
+            Expr::SafeAccess(base, field) => self.translate_safe_access(base.node(), field),
+            // Ranges are lowered to a (start, end) pair of I64 slots.
+            Expr::Range(start, end) => {
+                let start = self.translate(start.node())?;
+                let end = self.translate(end.node())?;
+                let addr = self.allocate(2 * StructLayout::FIELD_SIZE)?;
+                self.builder.ins().store(MemFlags::trusted(), start, addr, 0);
+                self.builder.ins().store(MemFlags::trusted(), end, addr, StructLayout::FIELD_SIZE as i32);
+                Ok(addr)
+            }
+            Expr::Lambda(params, ret, body) => self.translate_lambda(params, ret, body.node()),
+            Expr::Call(name, args) => match (self.variable(id), self.hir.resolve(id).and_then(|symbol| self.hir.symbol(symbol).typ.clone())) {
+                (Some(var), Some(ViraType::Function(params, ret))) => {
+                    let callee = self.builder.use_var(var);
+                    self.translate_indirect_call(callee, &params, &ret, args)
+                }
+                (None, _) if self.functions.contains_key(name) => self.translate_direct_call(name, args),
+                _ if name.starts_with("checked_") => self.translate_checked(name, args),
+                _ if name == "hash" => self.translate_hash(args),
+                _ if name == "discriminant" => self.translate_discriminant(args),
+                _ if name == "clone" => self.translate_clone(args),
+                _ if name == "array_generate" => self.translate_array_generate(id, args),
+                _ if name == "array_filled" && self.escapes.is_local(id) => self.translate_local_array_filled(args),
+                _ => self.translate_builtin(name, args),
+            },
+            // Allocated at its final length, then filled in place.
+            Expr::ArrayLiteral(items) => {
+                let len = self.builder.ins().iconst(types::I64, items.len() as i64);
+                let zero = self.builder.ins().iconst(types::I64, 0);
+                let array = self.call_runtime(self.allocator(id), &[len, zero])?;
+                for (i, item) in items.iter().enumerate() {
+                    let index = self.builder.ins().iconst(types::I64, i as i64);
+                    let value = self.word(item.node())?;
+                    self.call_runtime(runtime::ARRAY_SET, &[array, index, value])?;
+                }
+                Ok(array)
+            }
+            Expr::MapLiteral(entries) => {
+                let map = self.call_runtime(runtime::MAP_NEW, &[])?;
+                for (key, value) in entries {
+                    if !matches!(self.static_type(key.node()), Some(ViraType::Int | ViraType::Bool)) {
+                        return Err("Codegen only supports int and bool map keys.".to_string());
+                    }
+                    let key = self.word(key.node())?;
+                    let value = self.word(value.node())?;
+                    self.call_runtime(runtime::MAP_INSERT, &[map, key, value])?;
+                }
+                Ok(map)
+            }
+            Expr::Apply(callee, args) => match self.static_type(callee.node()) {
+                Some(ViraType::Function(params, ret)) => {
+                    let callee = self.translate(callee.node())?;
+                    self.translate_indirect_call(callee, &params, &ret, args)
+                }
+                _ => Err("Cannot call a non-function value.".to_string()),
+            },
+            _ => Err(format!("Codegen does not support {} yet.", expr.kind())),
+        }
+    }
+
+    fn translate_stmt(&mut self, id: NodeId, stmt: &Stmt) -> Result<Value, String> {
+        match stmt {
+            Stmt::VarDecl(name, typ, init) => {
+                let symbol = self.hir.declared(id, name).ok_or(format!("Undefined variable '{}'.", name))?;
+                let typ = self.hir.symbol(symbol).typ.clone().or_else(|| typ.clone()).ok_or(format!("Cannot infer the type of '{}'; give it one with `let {}: T`.", name, name))?;
+                let value = self.translate_as(init.node(), &typ)?;
+                self.define(symbol, value);
+                Ok(value)
+            }
+            Stmt::TupleDecl(names, typ, init) => {
+                let elements = match self.static_type(init.node()).or_else(|| typ.clone()) {
+                    Some(ViraType::Tuple(elements)) if elements.len() == names.len() => elements,
+                    _ => return Err(format!("Cannot destructure this value into {} name(s).", names.len())),
+                };
+                let addr = self.translate(init.node())?;
+                for (i, (name, element)) in names.iter().zip(elements).enumerate() {
+                    let ty = self.cranelift_type(&element);
+                    let offset = (i as u32 * StructLayout::FIELD_SIZE) as i32;
+                    let value = self.builder.ins().load(ty, MemFlags::trusted(), addr, offset);
+                    let symbol = self.hir.declared(id, name).ok_or(format!("Undefined variable '{}'.", name))?;
+                    self.define(symbol, value);
+                }
+                Ok(addr)
+            }
+            Stmt::Assign(name, value) => {
+                if self.consts.contains_key(name) {
+                    return Err(format!("Cannot assign to constant '{}'.", name));
+                }
+                let var = self.variable(id).ok_or(format!("Undefined variable '{}'.", name))?;
+                let value = self.translate(value.node())?;
+                self.builder
+                    .try_def_var(var, value)
+                    .map_err(|_| format!("Cannot assign a value of a different type to '{}'.", name))?;
+                Ok(value)
+            }
+            Stmt::While(cond, body) => self.translate_while(cond.node(), *body),
+            Stmt::Return(value) => self.translate_return(value.map(ExprId::node)),
+            Stmt::Write(value) => {
+                let function = match self.static_type(value.node()) {
+                    Some(ViraType::Int) => runtime::WRITE_INT,
+                    Some(ViraType::Float) => runtime::WRITE_FLOAT,
+                    Some(ViraType::Bool) => runtime::WRITE_BOOL,
+                    Some(ViraType::String) => runtime::WRITE_STRING,
+                    _ => return Err("Codegen only supports writing ints, floats, bools and strings.".to_string()),
+                };
+                let word = self.word(value.node())?;
+                self.call_runtime(function, &[word])
+            }
+            Stmt::Throw(value) => self.translate_throw(value.node()),
+            Stmt::Try(body, name, handler) => self.translate_try(*body, name, *handler),
+            _ => Err(format!("Codegen does not support {} yet.", stmt.kind())),
+        }
+    }
+
+    fn translate_item(&mut self, item: &Item) -> Result<Value, String> {
+        match item {
+            Item::ConstDecl(name, _, value) => {
+                self.consts.insert(name.clone(), value.node());
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            Item::ConfigDecl(entries) => {
+                for (key, value) in entries {
+                    self.consts.insert(format!("config.{}", key), value.node());
+                }
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            Item::DataDecl(name, typ, _, contents) => {
+                let codegen_error = |e: cranelift_module::ModuleError| format!("Codegen error: {}", e);
+                let mut description = DataDescription::new();
+                description.define(contents.as_bytes().to_vec().into_boxed_slice());
+                let data_id = self.module.declare_anonymous_data(false, false).map_err(codegen_error)?;
+                self.module.define_data(data_id, &description).map_err(codegen_error)?;
+                self.data.insert(name.clone(), (data_id, typ.clone(), contents.len()));
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            Item::StructDecl(name, fields) => {
+                self.structs.insert(name.clone(), StructLayout { fields: fields.clone() });
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            Item::EnumDecl(name, variants) => {
+                self.enums.insert(name.clone(), EnumLayout { variants: variants.clone() });
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            Item::TraitDecl(name, methods) => {
+                self.traits.insert(name.clone(), methods.clone());
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            }
+            Item::ImplDecl(trait_name, type_name, methods) => self.translate_impl(trait_name.as_deref(), type_name, methods),
+            Item::FuncDecl(name, _, params, ret, body) => self.translate_func(name, params, ret, *body),
+            // Visibility only matters when resolving names across modules.
+            Item::Pub(item) | Item::Attribute(_, _, item) => self.translate(*item),
+            // Each module is compiled on its own; imports only affect loading.
+            // Aliases were replaced while parsing.
+            Item::Import(_) | Item::TypeAlias(..) => Ok(self.builder.ins().iconst(types::I64, 0)),
+        }
+    }
+
+    // Anonymous functions become separate functions and evaluate to their
+    // address. Captured variables would need an environment, which codegen
+    // does not build yet.
+    fn translate_lambda(&mut self, params: &[(String, ViraType)], ret: &ViraType, body: NodeId) -> Result<Value, String> {
+        if let Some(name) = self.hir.captured(body) {
+            return Err(format!("Codegen does not support anonymous functions that capture variables ('{}').", name));
+        }
+        let sig = self.signature(params.iter().map(|(_, typ)| typ), ret);
+        let func_id = self
+            .module
+            .declare_anonymous_function(&sig)
+            .map_err(|e| format!("Codegen error: {}", e))?;
+        self.emitted.anonymous += 1;
+        let name = format!("<anonymous {}>", self.emitted.anonymous);
+        self.define_function(func_id, params, ret, body, name, "Anonymous function")?;
+        let func_ref = self.module.declare_func_in_func(func_id, self.builder.func);
+        Ok(self.builder.ins().func_addr(self.pointer_type, func_ref))
+    }
+
+    // Named functions, nested ones included, are compiled like anonymous ones
+    // but are declared first, so they can call themselves.
+    fn translate_func(&mut self, name: &str, params: &[(String, ViraType)], ret: &ViraType, body: NodeId) -> Result<Value, String> {
+        if let Some(variable) = self.hir.captured(body) {
+            return Err(format!("Codegen does not support functions that use variables of the enclosing scope ('{}').", variable));
+        }
+        let id = match self.hoisted.remove(&body) {
+            Some(id) => id,
+            None => self.declare_function(name, params, ret)?,
+        };
+        self.define_function(id, params, ret, body, name.to_string(), &format!("Function '{}'", name))?;
+        Ok(self.builder.ins().iconst(types::I64, 0))
+    }
+
+    fn declare_function(&mut self, name: &str, params: &[(String, ViraType)], ret: &ViraType) -> Result<FuncId, String> {
+        let sig = self.signature(params.iter().map(|(_, typ)| typ), ret);
+        let id = self.module.declare_anonymous_function(&sig).map_err(|e| format!("Codegen error: {}", e))?;
+        let param_types = params.iter().map(|(_, typ)| typ.clone()).collect();
+        self.functions.insert(name.to_string(), Method { id, params: param_types });
+        Ok(id)
+    }
+
+    fn hoist(&mut self, stmts: &[NodeId]) -> Result<(), String> {
+        for &stmt in stmts {
+            if let AstNode::Item(Item::FuncDecl(name, _, params, ret, body)) = &self.ast[self.ast.item(stmt)] {
+                let id = self.declare_function(name, params, ret)?;
+                self.hoisted.insert(*body, id);
+            }
+        }
+        Ok(())
+    }
+
+    fn translate_direct_call(&mut self, name: &str, args: &[ExprId]) -> Result<Value, String> {
+        let func = self.functions.get(name).cloned().ok_or(format!("Undefined function '{}'.", name))?;
+        if func.params.len() != args.len() {
+            return Err(format!("Function '{}' expects {} argument(s), got {}.", name, func.params.len(), args.len()));
+        }
+        let values = self.call_args(Vec::new(), &func.params, args)?;
+        let func_ref = self.module.declare_func_in_func(func.id, self.builder.func);
+        let call = self.builder.ins().call(func_ref, &values);
+        self.call_result(call)
+    }
+
+    // Compiles `body` as the definition of `func_id`. The body sees its
+    // parameters and the declarations made so far, but no local variables.
+    fn define_function(
+        &mut self,
+        func_id: FuncId,
+        params: &[(String, ViraType)],
+        ret: &ViraType,
+        body: NodeId,
+        name: String,
+        what: &str,
+    ) -> Result<(), String> {
+        let mut ctx = self.module.make_context();
+        ctx.func.signature = self.signature(params.iter().map(|(_, typ)| typ), ret);
+        let mut builder_context = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut ctx.func, &mut builder_context);
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        builder.seal_block(entry);
+        let args = builder.block_params(entry).to_vec();
+
+        let mut inner = FunctionTranslator {
+            ast: self.ast,
+            hir: self.hir,
+            escapes: self.escapes,
+            builder,
+            pointer_type: self.pointer_type,
+            structs: self.structs.clone(),
+            enums: self.enums.clone(),
+            traits: self.traits.clone(),
+            methods: self.methods.clone(),
+            vtables: self.vtables.clone(),
+            functions: self.functions.clone(),
+            hoisted: HashMap::new(),
+            variables: HashMap::new(),
+            consts: self.consts.clone(),
+            data: self.data.clone(),
+            next_variable: 0,
+            module: &mut *self.module,
+            emitted: &mut *self.emitted,
+            allocating: Vec::new(),
+            handler: None,
+            ret: Some(ret.clone()),
+            may_throw: self.may_throw,
+            deferred: Vec::new(),
+            region: None,
+            overflow_checks: self.overflow_checks,
+        };
+        for ((name, _), arg) in params.iter().zip(args) {
+            let symbol = inner.hir.declared(body, name).ok_or(format!("Undefined variable '{}'.", name))?;
+            inner.define(symbol, arg);
+        }
+        if inner.escapes.has_region(body) {
+            inner.region = Some(inner.call_runtime(runtime::REGION_ENTER, &[])?);
+        }
+        let value = inner.translate_as(body, ret)?;
+        if inner.builder.func.dfg.value_type(value) != inner.cranelift_type(ret) {
+            return Err(format!("{} body does not match its return type.", what));
+        }
+        if let Some(mark) = inner.region {
+            inner.call_runtime(runtime::REGION_LEAVE, &[mark])?;
+        }
+        inner.builder.ins().return_(&[value]);
+        let allocating = std::mem::take(&mut inner.allocating);
+        inner.builder.finalize();
+        self.emitted.instrument(&name, &mut ctx.func, &allocating, self.pointer_type);
+
+        self.module
+            .define_function(func_id, &mut ctx)
+            .map_err(|e| format!("Codegen error: {}", e))?;
+        let size = ctx.compiled_code().map_or(0, |code| code.code_buffer().len());
+        self.emitted.functions.push((name, size));
+        Ok(())
+    }
+
+    fn translate_indirect_call(&mut self, callee: Value, params: &[ViraType], ret: &ViraType, args: &[ExprId]) -> Result<Value, String> {
+        if params.len() != args.len() {
+            return Err(format!("Function expects {} argument(s), got {}.", params.len(), args.len()));
+        }
+        let values = self.call_args(Vec::new(), params, args)?;
+        let sig = self.signature(params.iter(), ret);
+        let sig_ref = self.builder.import_signature(sig);
+        let call = self.builder.ins().call_indirect(sig_ref, callee, &values);
+        self.call_result(call)
+    }
+
+    // The value a call returned, after leaving for the handler or the caller
+    // if the callee threw.
+    fn call_result(&mut self, call: Inst) -> Result<Value, String> {
+        let result = self.builder.inst_results(call).first().copied().ok_or_else(|| "Call produced no value.".to_string())?;
+        if self.may_throw {
+            let thrown = self.builder.ins().iconst(self.pointer_type, runtime::thrown_address());
+            let set = self.builder.ins().load(types::I64, MemFlags::trusted(), thrown, 0);
+            let unwind = self.builder.create_block();
+            let resume = self.builder.create_block();
+            self.builder.ins().brif(set, unwind, &[], resume, &[]);
+            self.builder.switch_to_block(unwind);
+            self.builder.seal_block(unwind);
+            self.unwind()?;
+            self.builder.switch_to_block(resume);
+            self.builder.seal_block(resume);
+        }
+        Ok(result)
+    }
+
+    // Compiled code has no unwinding tables. A `throw` inside a `try` of the
+    // same function jumps to its handler; any other puts the value in the
+    // runtime's thrown slot and returns, and each caller checks the slot.
+    fn translate_throw(&mut self, value: NodeId) -> Result<Value, String> {
+        if !matches!(self.static_type(value), Some(ViraType::Int)) {
+            return Err("Codegen only supports throwing ints.".to_string());
+        }
+        let value = self.translate(value)?;
+        match self.handler {
+            Some(handler) => {
+                self.builder.ins().jump(handler, &[value]);
+            }
+            None => {
+                let thrown = self.builder.ins().iconst(self.pointer_type, runtime::thrown_address());
+                let set = self.builder.ins().iconst(types::I64, 1);
+                self.builder.ins().store(MemFlags::trusted(), set, thrown, 0);
+                self.builder.ins().store(MemFlags::trusted(), value, thrown, StructLayout::FIELD_SIZE as i32);
+                self.unwind()?;
+            }
+        }
+        // Whatever follows the throw is unreachable, but still needs a block.
+        let rest = self.builder.create_block();
+        self.builder.switch_to_block(rest);
+        self.builder.seal_block(rest);
+        Ok(self.builder.ins().iconst(types::I64, 0))
+    }
+
+    // Leaves for the handler, taking the value out of the thrown slot, or
+    // returns with the slot still set. Main has no caller to return to.
+    fn unwind(&mut self) -> Result<(), String> {
+        let thrown = self.builder.ins().iconst(self.pointer_type, runtime::thrown_address());
+        if let Some(handler) = self.handler {
+            let value = self.builder.ins().load(types::I64, MemFlags::trusted(), thrown, StructLayout::FIELD_SIZE as i32);
+            let clear = self.builder.ins().iconst(types::I64, 0);
+            self.builder.ins().store(MemFlags::trusted(), clear, thrown, 0);
+            self.builder.ins().jump(handler, &[value]);
+        } else if let Some(ret) = self.ret.clone() {
+            // The caller ignores the value once it sees the flag.
+            let placeholder = self.placeholder(&ret);
+            self.builder.ins().return_(&[placeholder]);
+        } else {
+            self.call_runtime(runtime::UNCAUGHT, &[])?;
+            self.builder.ins().trap(TrapCode::UnreachableCodeReached);
+        }
+        Ok(())
+    }
+
+    fn translate_try(&mut self, body: ExprId, name: &str, handler_body: ExprId) -> Result<Value, String> {
+        let handler = self.builder.create_block();
+        self.builder.append_block_param(handler, types::I64);
+        let merge = self.builder.create_block();
+        let outer = self.handler.replace(handler);
+        let translated = self.translate(body.node());
+        self.handler = outer;
+        translated?;
+        self.builder.ins().jump(merge, &[]);
+
+        self.builder.switch_to_block(handler);
+        self.builder.seal_block(handler);
+        let thrown = self.builder.block_params(handler).first().copied().ok_or("Missing thrown value.")?;
+        let symbol = self.hir.declared(handler_body.node(), name).ok_or(format!("Undefined variable '{}'.", name))?;
+        self.define(symbol, thrown);
+        self.translate(handler_body.node())?;
+        self.builder.ins().jump(merge, &[]);
+
+        self.builder.switch_to_block(merge);
+        self.builder.seal_block(merge);
+        Ok(self.builder.ins().iconst(types::I64, 0))
+    }
+
+    // Appends the translated `args` to `values`, converting each to its
+    // parameter type.
+    fn call_args(&mut self, mut values: Vec<Value>, params: &[ViraType], args: &[ExprId]) -> Result<Vec<Value>, String> {
+        for (&arg, typ) in args.iter().zip(params) {
+            let value = self.translate_as(arg.node(), typ)?;
+            if self.builder.func.dfg.value_type(value) != self.cranelift_type(typ) {
+                return Err("Argument does not match the parameter type.".to_string());
+            }
+            values.push(value);
+        }
+        Ok(values)
+    }
+
+    // Translates `node` where a value of type `target` is expected. A concrete
+    // value used as a trait is wrapped in a trait object.
+    fn translate_as(&mut self, node: NodeId, target: &ViraType) -> Result<Value, String> {
+        // The value of code that returns or throws is never used.
+        if typecheck::diverges(self.ast, node) {
+            self.translate(node)?;
+            return Ok(self.placeholder(target));
+        }
+        if let ViraType::Optional(inner) = target {
+            if self.is_nil(node) || matches!(self.static_type(node), Some(ViraType::Optional(_))) {
+                return self.translate(node);
+            }
+            let value = self.translate_as(node, inner)?;
+            return self.optional(Some(value));
+        }
+        if let AstNode::Expr(Expr::Nil) = self.ast[node] {
+            return Err(format!("Cannot use nil as {}; declare it as {}?.", target, target));
+        }
+        let value = self.translate(node)?;
+        let ViraType::Struct(trait_name) = target else {
+            return Ok(value);
+        };
+        if !self.traits.contains_key(trait_name) {
+            return Ok(value);
+        }
+        let type_name = match self.static_type(node) {
+            Some(ViraType::Struct(name) | ViraType::Enum(name)) if name == *trait_name => return Ok(value),
+            Some(ViraType::Struct(name) | ViraType::Enum(name)) => name,
+            _ => return Err(format!("Cannot use this value as '{}'.", trait_name)),
+        };
+        let vtable = *self
+            .vtables
+            .get(&(trait_name.clone(), type_name.clone()))
+            .ok_or(format!("'{}' does not implement '{}'.", type_name, trait_name))?;
+        let vtable = self.module.declare_data_in_func(vtable, self.builder.func);
+        let vtable = self.builder.ins().global_value(self.pointer_type, vtable);
+        let addr = self.allocate(2 * StructLayout::FIELD_SIZE)?;
+        self.builder.ins().store(MemFlags::trusted(), value, addr, 0);
+        self.builder.ins().store(MemFlags::trusted(), vtable, addr, StructLayout::FIELD_SIZE as i32);
+        Ok(addr)
+    }
+
+    // Compiles the methods of an impl block. Methods are declared before any
+    // body is compiled, so they can call each other. An impl of a trait also
+    // gets a vtable for the trait objects made from the type, listing the
+    // methods in trait order.
+    fn translate_impl(&mut self, trait_name: Option<&str>, type_name: &str, methods: &[ItemId]) -> Result<Value, String> {
+        let ast = self.ast;
+        let required = match trait_name {
+            Some(trait_name) => {
+                if self.vtables.contains_key(&(trait_name.to_string(), type_name.to_string())) {
+                    return Err(format!("'{}' already implements '{}'.", type_name, trait_name));
+                }
+                Some(self.traits.get(trait_name).cloned().ok_or(format!("Undefined trait '{}'.", trait_name))?)
+            }
+            None => None,
+        };
+        let codegen_error = |e: cranelift_module::ModuleError| format!("Codegen error: {}", e);
+        let mut declared = Vec::new();
+        for &method in methods {
+            let Item::FuncDecl(name, _, params, ret, body) = &ast[method] else {
+                continue;
+            };
+            let own_params: Vec<ViraType> = params.iter().skip(1).map(|(_, typ)| typ.clone()).collect();
+            if let (Some(trait_name), Some(required)) = (trait_name, &required) {
+                let (_, trait_params, trait_ret) = required
+                    .iter()
+                    .find(|(m, _, _)| m == name)
+                    .ok_or(format!("Method '{}' is not part of trait '{}'.", name, trait_name))?;
+                if own_params != trait_params.iter().map(|(_, typ)| typ.clone()).collect::<Vec<_>>() || ret != trait_ret {
+                    return Err(format!("Method '{}' does not match its declaration in trait '{}'.", name, trait_name));
+                }
+            }
+            let key = (type_name.to_string(), name.clone());
+            if self.methods.contains_key(&key) {
+                return Err(format!("Type '{}' already has a method '{}'.", type_name, name));
+            }
+            let sig = self.signature(params.iter().map(|(_, typ)| typ), ret);
+            let id = self.module.declare_anonymous_function(&sig).map_err(codegen_error)?;
+            self.methods.insert(key, Method { id, params: own_params });
+            declared.push((name.clone(), id, params, ret, *body));
+        }
+        for (name, id, params, ret, body) in declared {
+            let what = format!("Method '{}'", name);
+            self.define_function(id, params, ret, body, format!("{}::{}", type_name, name), &what)?;
+        }
+        let (Some(trait_name), Some(required)) = (trait_name, required) else {
+            return Ok(self.builder.ins().iconst(types::I64, 0));
+        };
+
+        let mut vtable = DataDescription::new();
+        let word = self.pointer_type.bytes();
+        vtable.define_zeroinit(required.len() * word as usize);
+        vtable.set_align(word as u64);
+        for (i, (name, _, _)) in required.iter().enumerate() {
+            let method = self
+                .methods
+                .get(&(type_name.to_string(), name.clone()))
+                .ok_or(format!("Impl of '{}' for '{}' is missing method '{}'.", trait_name, type_name, name))?;
+            let func_ref = self.module.declare_func_in_data(method.id, &mut vtable);
+            vtable.write_function_addr(i as u32 * word, func_ref);
+        }
+        let id = self.module.declare_anonymous_data(false, false).map_err(codegen_error)?;
+        self.module.define_data(id, &vtable).map_err(codegen_error)?;
+        self.vtables.insert((trait_name.to_string(), type_name.to_string()), id);
+        Ok(self.builder.ins().iconst(types::I64, 0))
+    }
+
+    // Calls on a trait object go through its vtable; calls on a concrete type
+    // call the method directly.
+    fn translate_method_call(&mut self, receiver: ExprId, method: &str, args: &[ExprId]) -> Result<Value, String> {
+        let type_name = match self.static_type(receiver.node()) {
+            Some(ViraType::Struct(name) | ViraType::Enum(name)) => name,
+            Some(typ) => {
+                let Some(builtin) = Receiver::of_type(&typ).and_then(|r| builtins::method(r, method)) else {
+                    return Err(format!("Type '{}' has no method '{}'.", typ, method));
+                };
+                if builtin.params != args.len() {
+                    return Err(format!("Method '{}' expects {} argument(s), got {}.", method, builtin.params, args.len()));
+                }
+                if builtins::mutates(builtin.builtin) {
+                    return Err(format!("Codegen does not support '{}', which updates its receiver.", method));
+                }
+                let args: Vec<ExprId> = std::iter::once(receiver).chain(args.iter().copied()).collect();
+                return self.translate_builtin(builtin.builtin, &args);
+            }
+            None => return Err(format!("Cannot call method '{}' on this value.", method)),
+        };
+        let arity_error = |expected: usize| format!("Method '{}' expects {} argument(s), got {}.", method, expected, args.len());
+        if let Some(trait_methods) = self.traits.get(&type_name).cloned() {
+            let (index, (_, params, ret)) = trait_methods
+                .iter()
+                .enumerate()
+                .find(|(_, (m, _, _))| m == method)
+                .ok_or(format!("Trait '{}' has no method '{}'.", type_name, method))?;
+            if params.len() != args.len() {
+                return Err(arity_error(params.len()));
+            }
+            let object = self.translate(receiver.node())?;
+            let word = self.pointer_type.bytes() as i32;
+            let this = self.builder.ins().load(self.pointer_type, MemFlags::trusted(), object, 0);
+            let vtable = self.builder.ins().load(self.pointer_type, MemFlags::trusted(), object, StructLayout::FIELD_SIZE as i32);
+            let callee = self.builder.ins().load(self.pointer_type, MemFlags::trusted(), vtable, index as i32 * word);
+            let params: Vec<ViraType> = params.iter().map(|(_, typ)| typ.clone()).collect();
+            let values = self.call_args(vec![this], &params, args)?;
+            let receiver_type = ViraType::Struct(type_name);
+            let sig = self.signature(std::iter::once(&receiver_type).chain(&params), ret);
+            let sig_ref = self.builder.import_signature(sig);
+            let call = self.builder.ins().call_indirect(sig_ref, callee, &values);
+            return self.call_result(call);
+        }
+        if let Some(found) = self.methods.get(&(type_name.clone(), method.to_string())).cloned() {
+            if found.params.len() != args.len() {
+                return Err(arity_error(found.params.len()));
+            }
+            let this = self.translate(receiver.node())?;
+            let values = self.call_args(vec![this], &found.params, args)?;
+            let func_ref = self.module.declare_func_in_func(found.id, self.builder.func);
+            let call = self.builder.ins().call(func_ref, &values);
+            return self.call_result(call);
+        }
+        // A struct field holding a function value.
+        let field = self.structs.get(&type_name).and_then(|layout| layout.field(method)).map(|(offset, typ)| (offset, typ.clone()));
+        if let Some((offset, ViraType::Function(params, ret))) = field {
+            let addr = self.translate(receiver.node())?;
+            let callee = self.builder.ins().load(self.pointer_type, MemFlags::trusted(), addr, offset);
+            return self.translate_indirect_call(callee, &params, &ret, args);
+        }
+        Err(format!("Type '{}' has no method '{}'.", type_name, method))
+    }
+
+    // Placed at loop back-edges so Ctrl-C can stop compiled code.
+    fn poll_interrupt(&mut self) -> Result<(), String> {
+        let flag = self.builder.ins().iconst(self.pointer_type, interrupt::flag_address());
+        let set = self.builder.ins().load(types::I8, MemFlags::trusted(), flag, 0);
+        self.fail_if(set, runtime::INTERRUPTED)
+    }
+
+    // Ends the run with the runtime's `function` when `cond` holds.
+    fn fail_if(&mut self, cond: Value, function: &'static str) -> Result<(), String> {
+        let failed = self.builder.create_block();
+        let resume = self.builder.create_block();
+        self.builder.ins().brif(cond, failed, &[], resume, &[]);
+        self.builder.switch_to_block(failed);
+        self.builder.seal_block(failed);
+        self.call_runtime(function, &[])?;
+        self.builder.ins().trap(TrapCode::UnreachableCodeReached);
+        self.builder.switch_to_block(resume);
+        self.builder.seal_block(resume);
+        Ok(())
+    }
+
+    fn translate_builtin(&mut self, name: &str, args: &[ExprId]) -> Result<Value, String> {
+        match (name, args) {
+            // What a for-in loop indexes is the range or array itself.
+            ("range.items" | "array.items", &[receiver]) => return self.translate(receiver.node()),
+            ("range.len", &[receiver]) => {
+                let (start, end) = self.range_bounds(receiver.node())?;
+                let len = self.builder.ins().isub(end, start);
+                let empty = self.builder.ins().icmp(IntCC::SignedLessThan, end, start);
+                let zero = self.builder.ins().iconst(types::I64, 0);
+                return Ok(self.builder.ins().select(empty, zero, len));
+            }
+            _ => {}
+        }
+        let arg_types: Vec<_> = args.iter().map(|&arg| self.static_type(arg.node())).collect();
+        let (Some(ret), Some(function)) = (builtins::return_type(name, &arg_types), runtime_builtin(name)) else {
+            return Err(format!("Codegen only supports calls through function values, not '{}'.", name));
+        };
+        let mut values = Vec::new();
+        for &arg in args {
+            values.push(self.word(arg.node())?);
+        }
+        let result = self.call_runtime(function, &values)?;
+        Ok(self.value_from(result, &ret))
+    }
+
+    // Uses the overflow flag of the arithmetic instead of calling into the
+    // runtime. Division swaps in a divisor of 1 for the cases that would trap.
+    fn translate_checked(&mut self, name: &str, args: &[ExprId]) -> Result<Value, String> {
+        let &[a, b] = args else {
+            return Err(format!("Function '{}' expects 2 argument(s), got {}.", name, args.len()));
+        };
+        let a = self.translate(a.node())?;
+        let b = self.translate(b.node())?;
+        if self.builder.func.dfg.value_type(a) != types::I64 || self.builder.func.dfg.value_type(b) != types::I64 {
+            return Err(format!("'{}' expects two ints.", name));
+        }
+        let (result, failed) = match name {
+            "checked_add" => self.builder.ins().sadd_overflow(a, b),
+            "checked_sub" => self.builder.ins().ssub_overflow(a, b),
+            "checked_mul" => self.builder.ins().smul_overflow(a, b),
+            "checked_div" | "checked_rem" => {
+                let zero = self.builder.ins().icmp_imm(IntCC::Equal, b, 0);
+                let min = self.builder.ins().icmp_imm(IntCC::Equal, a, i64::MIN);
+                let minus_one = self.builder.ins().icmp_imm(IntCC::Equal, b, -1);
+                let overflow = self.builder.ins().band(min, minus_one);
+                let failed = self.builder.ins().bor(zero, overflow);
+                let one = self.builder.ins().iconst(types::I64, 1);
+                let divisor = self.builder.ins().select(failed, one, b);
+                let result = if name == "checked_div" {
+                    self.builder.ins().sdiv(a, divisor)
+                } else {
+                    self.builder.ins().srem(a, divisor)
+                };
+                (result, failed)
+            }
+            _ => return Err(format!("Codegen only supports calls through function values, not '{}'.", name)),
+        };
+        let failed = self.builder.ins().uextend(types::I64, failed);
+        let flag = self.builder.ins().bxor_imm(failed, 1);
+        self.optional_pair(flag, Some(result))
+    }
+
+    // Compiled values are never changed in place, so sharing one is as good
+    // as a copy. Only a user `clone` method has anything to do.
+    fn translate_clone(&mut self, args: &[ExprId]) -> Result<Value, String> {
+        let &[value] = args else {
+            return Err(format!("Function 'clone' expects 1 argument(s), got {}.", args.len()));
+        };
+        if let Some(ViraType::Struct(type_name) | ViraType::Enum(type_name)) = self.static_type(value.node()) {
+            if self.methods.contains_key(&(type_name, "clone".to_string())) {
+                return self.translate_method_call(value, "clone", &[]);
+            }
+        }
+        self.translate(value.node())
+    }
+
+    fn translate_discriminant(&mut self, args: &[ExprId]) -> Result<Value, String> {
+        let &[value] = args else {
+            return Err(format!("Function 'discriminant' expects 1 argument(s), got {}.", args.len()));
+        };
+        // Annotations name enums as structs.
+        if !matches!(self.static_type(value.node()), Some(ViraType::Struct(name) | ViraType::Enum(name)) if self.enums.contains_key(&name)) {
+            return Err("'discriminant' expects an enum value.".to_string());
+        }
+        let value = self.translate(value.node())?;
+        Ok(self.builder.ins().load(types::I64, MemFlags::trusted(), value, 0))
+    }
+
+    // The interpreter's FNV-1a mix, inline. A struct or enum argument
+    // contributes what its `hash` method returns.
+    fn translate_hash(&mut self, args: &[ExprId]) -> Result<Value, String> {
+        let mut hash = self.builder.ins().iconst(types::I64, builtins::HASH_BASIS as i64);
+        for &arg in args {
+            let word = match self.static_type(arg.node()) {
+                Some(ViraType::Int | ViraType::Bool) => self.word(arg.node())?,
+                Some(ViraType::Struct(_) | ViraType::Enum(_)) => self.translate_method_call(arg, "hash", &[])?,
+                _ => return Err("Codegen only supports hashing ints, bools, structs and enums.".to_string()),
+            };
+            let mixed = self.builder.ins().bxor(hash, word);
+            hash = self.builder.ins().imul_imm(mixed, builtins::HASH_PRIME as i64);
+        }
+        Ok(hash)
+    }
+
+    // Translates `node` to the I64 form runtime functions take.
+    fn word(&mut self, node: NodeId) -> Result<Value, String> {
+        let value = self.translate(node)?;
+        Ok(self.word_from(value))
+    }
+
+    fn word_from(&mut self, value: Value) -> Value {
+        if self.builder.func.dfg.value_type(value) == types::F64 {
+            self.builder.ins().bitcast(types::I64, MemFlags::new(), value)
+        } else {
+            value
+        }
+    }
+
+    // The inverse of `word_from` for a value of static type `typ`.
+    fn value_from(&mut self, word: Value, typ: &ViraType) -> Value {
+        if self.cranelift_type(typ) == types::F64 {
+            self.builder.ins().bitcast(types::F64, MemFlags::new(), word)
+        } else {
+            word
+        }
+    }
+
+    // The ints of a range are worked out, not stored.
+    fn translate_index(&mut self, base: ExprId, index: ExprId) -> Result<Value, String> {
+        if !matches!(self.static_type(index.node()), Some(ViraType::Int)) {
+            return Err("Codegen only supports int indices.".to_string());
+        }
+        let element = match self.static_type(base.node()) {
+            Some(ViraType::Array(element)) => element,
+            Some(ViraType::Range) => {
+                let (start, end) = self.range_bounds(base.node())?;
+                let index = self.translate(index.node())?;
+                let item = self.builder.ins().iadd(start, index);
+                let negative = self.builder.ins().icmp_imm(IntCC::SignedLessThan, index, 0);
+                let past_end = self.builder.ins().icmp(IntCC::SignedGreaterThanOrEqual, item, end);
+                let out_of_bounds = self.builder.ins().bor(negative, past_end);
+                self.fail_if(out_of_bounds, runtime::OUT_OF_BOUNDS)?;
+                return Ok(item);
+            }
+            _ => return Err("Codegen only supports indexing arrays, ranges, structs and enums.".to_string()),
+        };
+        let array = self.translate(base.node())?;
+        let index = self.translate(index.node())?;
+        let word = self.call_runtime(runtime::ARRAY_GET, &[array, index])?;
+        Ok(self.value_from(word, &element))
+    }
+
+    // `array_generate(len, f)` as a loop storing `f(i)` into an array that
+    // is allocated once, before the loop.
+    fn translate_array_generate(&mut self, id: NodeId, args: &[ExprId]) -> Result<Value, String> {
+        let &[len, function] = args else {
+            return Err(format!("Function 'array_generate' expects 2 argument(s), got {}.", args.len()));
+        };
+        let Some(ViraType::Function(params, ret)) = self.static_type(function.node()) else {
+            return Err("'array_generate' expects a length and a function.".to_string());
+        };
+        if params != [ViraType::Int] {
+            return Err("The function passed to 'array_generate' must take one int.".to_string());
+        }
+        let len = self.translate(len.node())?;
+        let callee = self.translate(function.node())?;
+        let zero = self.builder.ins().iconst(types::I64, 0);
+        let array = self.call_runtime(self.allocator(id), &[len, zero])?;
+
+        let header = self.builder.create_block();
+        let body = self.builder.create_block();
+        let exit = self.builder.create_block();
+        self.builder.append_block_param(header, types::I64);
+        self.builder.ins().jump(header, &[zero]);
+
+        self.builder.switch_to_block(header);
+        let index = self.builder.block_params(header).first().copied().ok_or("Missing loop index.")?;
+        let in_range = self.builder.ins().icmp(IntCC::SignedLessThan, index, len);
+        self.builder.ins().brif(in_range, body, &[], exit, &[]);
+
+        self.builder.switch_to_block(body);
+        self.builder.seal_block(body);
+        let sig = self.signature(params.iter(), &ret);
+        let sig_ref = self.builder.import_signature(sig);
+        let call = self.builder.ins().call_indirect(sig_ref, callee, &[index]);
+        let value = self.call_result(call)?;
+        let value = self.word_from(value);
+        self.call_runtime(runtime::ARRAY_SET, &[array, index, value])?;
+        self.poll_interrupt()?;
+        let next = self.builder.ins().iadd_imm(index, 1);
+        self.builder.ins().jump(header, &[next]);
+
+        self.builder.seal_block(header);
+        self.builder.switch_to_block(exit);
+        self.builder.seal_block(exit);
+        Ok(array)
+    }
+
+    // `array_filled` allocating in the region; the builtin reports what is
+    // wrong with the call.
+    fn translate_local_array_filled(&mut self, args: &[ExprId]) -> Result<Value, String> {
+        let arg_types: Vec<_> = args.iter().map(|&arg| self.static_type(arg.node())).collect();
+        if builtins::return_type("array_filled", &arg_types).is_none() {
+            return self.translate_builtin("array_filled", args);
+        }
+        let mut values = Vec::new();
+        for &arg in args {
+            values.push(self.word(arg.node())?);
+        }
+        self.call_runtime(runtime::ARRAY_FILLED_LOCAL, &values)
+    }
+
+    // The runtime function that allocates the array made at `site`.
+    fn allocator(&self, site: NodeId) -> &'static str {
+        if self.escapes.is_local(site) {
+            runtime::ARRAY_FILLED_LOCAL
+        } else {
+            runtime::ARRAY_FILLED
+        }
+    }
+
+    // Memory for an aggregate of `size` bytes. Aggregates live on the heap,
+    // so that a function can return one.
+    fn allocate(&mut self, size: u32) -> Result<Value, String> {
+        let size = self.builder.ins().iconst(types::I64, size as i64);
+        self.call_runtime(runtime::ALLOC, &[size])
+    }
+
+    // Calls a function from `runtime`; all of them take and return I64s.
+    fn call_runtime(&mut self, name: &'static str, args: &[Value]) -> Result<Value, String> {
+        self.emitted.runtime.insert(name);
+        let mut sig = self.module.make_signature();
+        sig.params.extend(args.iter().map(|_| AbiParam::new(types::I64)));
+        sig.returns.push(AbiParam::new(types::I64));
+        let func_id = self
+            .module
+            .declare_function(name, Linkage::Import, &sig)
+            .map_err(|e| format!("Codegen error: {}", e))?;
+        let func_ref = self.module.declare_func_in_func(func_id, self.builder.func);
+        if runtime::allocates(name) {
+            self.allocating.push(func_ref);
+        }
+        let call = self.builder.ins().call(func_ref, args);
+        self.builder.inst_results(call).first().copied().ok_or_else(|| "Call produced no value.".to_string())
+    }
+
+    fn signature<'t>(&self, params: impl Iterator<Item = &'t ViraType>, ret: &ViraType) -> Signature {
+        let mut sig = self.module.make_signature();
+        for typ in params {
+            sig.params.push(AbiParam::new(self.cranelift_type(typ)));
+        }
+        sig.returns.push(AbiParam::new(self.cranelift_type(ret)));
+        sig
+    }
+
+    fn variable(&self, id: NodeId) -> Option<Variable> {
+        self.hir.resolve(id).and_then(|symbol| self.variables.get(&symbol).copied())
+    }
+
+    // The value of `config.key` when `base` is the config block rather than
+    // a variable.
+    fn config_value(&self, base: NodeId, field: &str) -> Option<NodeId> {
+        match &self.ast[base] {
+            AstNode::Expr(Expr::VarRef(name)) if self.variable(base).is_none() => self.consts.get(&format!("{}.{}", name, field)).copied(),
+            _ => None,
+        }
+    }
+
+    // Declares the variable of `symbol` with the type of its first value, so
+    // a mismatched annotation never reaches the builder, which panics on
+    // inconsistent variables.
+    fn define(&mut self, symbol: hir::SymbolId, value: Value) {
+        let var = Variable::new(self.next_variable);
+        self.next_variable += 1;
+        let value_type = self.builder.func.dfg.value_type(value);
+        self.builder.declare_var(var, value_type);
+        self.builder.def_var(var, value);
+        self.variables.insert(symbol, var);
+    }
+
+    fn range_bounds(&mut self, range: NodeId) -> Result<(Value, Value), String> {
+        let pair = self.translate(range)?;
+        let start = self.builder.ins().load(types::I64, MemFlags::trusted(), pair, 0);
+        let end = self.builder.ins().load(types::I64, MemFlags::trusted(), pair, StructLayout::FIELD_SIZE as i32);
+        Ok((start, end))
+    }
+
+    // The condition is tested in a header block that the end of the body
+    // jumps back to, so it is sealed only once the body is compiled.
+    fn translate_while(&mut self, cond: NodeId, body: NodeId) -> Result<Value, String> {
+        let header = self.builder.create_block();
+        let body_block = self.builder.create_block();
+        let exit = self.builder.create_block();
+        self.builder.ins().jump(header, &[]);
+
+        self.builder.switch_to_block(header);
+        let cond = self.translate(cond)?;
+        self.builder.ins().brif(cond, body_block, &[], exit, &[]);
+
+        self.builder.switch_to_block(body_block);
+        self.builder.seal_block(body_block);
+        self.translate(body)?;
+        self.poll_interrupt()?;
+        self.builder.ins().jump(header, &[]);
+
+        self.builder.seal_block(header);
+        self.builder.switch_to_block(exit);
+        self.builder.seal_block(exit);
+        Ok(self.builder.ins().iconst(types::I64, 0))
+    }
+
+    // Runs the deferred statements of the enclosing blocks, innermost first,
+    // and leaves the region before returning.
+    fn translate_return(&mut self, value: Option<NodeId>) -> Result<Value, String> {
+        let Some(ret) = self.ret.clone() else {
+            return Err("Codegen does not support 'return' outside a function.".to_string());
+        };
+        let value = match value {
+            Some(value) => self.translate_as(value, &ret)?,
+            None => self.builder.ins().iconst(types::I64, 0),
+        };
+        if self.builder.func.dfg.value_type(value) != self.cranelift_type(&ret) {
+            return Err("Returned value does not match the return type.".to_string());
+        }
+        let deferred: Vec<NodeId> = self.deferred.iter().rev().flat_map(|block| block.iter().rev().copied()).collect();
+        for stmt in deferred {
+            self.translate(stmt)?;
+        }
+        if let Some(mark) = self.region {
+            self.call_runtime(runtime::REGION_LEAVE, &[mark])?;
+        }
+        self.builder.ins().return_(&[value]);
+        // Whatever follows the return is unreachable, but still needs a block.
+        let rest = self.builder.create_block();
+        self.builder.switch_to_block(rest);
+        self.builder.seal_block(rest);
+        Ok(self.placeholder(&ret))
+    }
+
+    // A value of type `typ` for code that never runs or whose value is ignored.
+    fn placeholder(&mut self, typ: &ViraType) -> Value {
+        let ty = self.cranelift_type(typ);
+        if ty == types::F64 {
+            self.builder.ins().f64const(0.0)
+        } else {
+            self.builder.ins().iconst(ty, 0)
+        }
+    }
+
+    // Operators on ints, floats, bools and strings, and comparisons with
+    // nil. Comparisons give a bool, so their I8 result is widened to I64.
+    fn translate_binary(&mut self, left: ExprId, op: BinOp, right: ExprId) -> Result<Value, String> {
+        let equality = matches!(op, BinOp::Eq | BinOp::Neq);
+        let (left, right) = if self.is_nil(left.node()) { (right, left) } else { (left, right) };
+        if equality && self.is_nil(right.node()) {
+            let is_nil = match self.static_type(left.node()) {
+                Some(ViraType::Optional(_)) => {
+                    let optional = self.translate(left.node())?;
+                    let flag = self.builder.ins().load(types::I64, MemFlags::trusted(), optional, 0);
+                    self.builder.ins().bxor_imm(flag, 1)
+                }
+                // Only an optional can be nil.
+                _ => {
+                    self.translate(left.node())?;
+                    let both_nil = self.is_nil(left.node());
+                    self.builder.ins().iconst(types::I64, both_nil as i64)
+                }
+            };
+            return Ok(if op == BinOp::Eq { is_nil } else { self.builder.ins().bxor_imm(is_nil, 1) });
+        }
+        let typ = self.static_type(left.node()).ok_or("Codegen cannot tell the type of this operand.")?;
+        let a = self.translate(left.node())?;
+        let b = self.translate(right.node())?;
+        let comparison = match (&typ, op) {
+            (ViraType::Int, BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod) => return self.translate_int_arith(a, op, b),
+            (ViraType::Float, BinOp::Add) => return Ok(self.builder.ins().fadd(a, b)),
+            (ViraType::Float, BinOp::Sub) => return Ok(self.builder.ins().fsub(a, b)),
+            (ViraType::Float, BinOp::Mul) => return Ok(self.builder.ins().fmul(a, b)),
+            (ViraType::Float, BinOp::Div) => return Ok(self.builder.ins().fdiv(a, b)),
+            (ViraType::Float, BinOp::Mod) => {
+                let (a, b) = (self.word_from(a), self.word_from(b));
+                let rem = self.call_runtime(runtime::FLOAT_REM, &[a, b])?;
+                return Ok(self.value_from(rem, &typ));
+            }
+            (ViraType::String, BinOp::Add) => return self.call_runtime(runtime::STRING_CONCAT, &[a, b]),
+            (ViraType::String, _) if equality => {
+                let equal = self.call_runtime(runtime::STRING_EQ, &[a, b])?;
+                return Ok(if op == BinOp::Eq { equal } else { self.builder.ins().bxor_imm(equal, 1) });
+            }
+            (ViraType::Float, _) => {
+                let cc = match op {
+                    BinOp::Eq => FloatCC::Equal,
+                    BinOp::Neq => FloatCC::NotEqual,
+                    BinOp::Lt => FloatCC::LessThan,
+                    BinOp::Gt => FloatCC::GreaterThan,
+                    BinOp::Le => FloatCC::LessThanOrEqual,
+                    _ => FloatCC::GreaterThanOrEqual,
+                };
+                self.builder.ins().fcmp(cc, a, b)
+            }
+            (ViraType::Int, _) | (ViraType::Bool, BinOp::Eq | BinOp::Neq) => {
+                let cc = match op {
+                    BinOp::Eq => IntCC::Equal,
+                    BinOp::Neq => IntCC::NotEqual,
+                    BinOp::Lt => IntCC::SignedLessThan,
+                    BinOp::Gt => IntCC::SignedGreaterThan,
+                    BinOp::Le => IntCC::SignedLessThanOrEqual,
+                    _ => IntCC::SignedGreaterThanOrEqual,
+                };
+                self.builder.ins().icmp(cc, a, b)
+            }
+            _ => return Err(format!("Codegen does not support this operator on {} values.", typ)),
+        };
+        Ok(self.builder.ins().uextend(types::I64, comparison))
+    }
+
+    // Fails on overflow when the profile checks it and wraps otherwise, as
+    // the interpreter does. Dividing by -1 is negating, because the
+    // instruction traps for `i64::MIN / -1`.
+    fn translate_int_arith(&mut self, a: Value, op: BinOp, b: Value) -> Result<Value, String> {
+        if matches!(op, BinOp::Div | BinOp::Mod) {
+            let zero = self.builder.ins().icmp_imm(IntCC::Equal, b, 0);
+            self.fail_if(zero, runtime::DIVISION_BY_ZERO)?;
+            let minus_one = self.builder.ins().icmp_imm(IntCC::Equal, b, -1);
+            if self.overflow_checks {
+                let min = self.builder.ins().icmp_imm(IntCC::Equal, a, i64::MIN);
+                let overflow = self.builder.ins().band(min, minus_one);
+                self.fail_if(overflow, runtime::OVERFLOW)?;
+            }
+            let one = self.builder.ins().iconst(types::I64, 1);
+            let divisor = self.builder.ins().select(minus_one, one, b);
+            let (result, by_minus_one) = if op == BinOp::Div {
+                (self.builder.ins().sdiv(a, divisor), self.builder.ins().ineg(a))
+            } else {
+                (self.builder.ins().srem(a, divisor), self.builder.ins().iconst(types::I64, 0))
+            };
+            return Ok(self.builder.ins().select(minus_one, by_minus_one, result));
+        }
+        if !self.overflow_checks {
+            return Ok(match op {
+                BinOp::Add => self.builder.ins().iadd(a, b),
+                BinOp::Sub => self.builder.ins().isub(a, b),
+                _ => self.builder.ins().imul(a, b),
+            });
+        }
+        let (result, overflow) = match op {
+            BinOp::Add => self.builder.ins().sadd_overflow(a, b),
+            BinOp::Sub => self.builder.ins().ssub_overflow(a, b),
+            _ => self.builder.ins().smul_overflow(a, b),
+        };
+        self.fail_if(overflow, runtime::OVERFLOW)?;
+        Ok(result)
+    }
+
+    fn translate_unary(&mut self, op: UnaryOp, operand: NodeId) -> Result<Value, String> {
+        let typ = self.static_type(operand);
+        let value = self.translate(operand)?;
+        match (op, typ) {
+            (UnaryOp::Neg, Some(ViraType::Int)) => {
+                if self.overflow_checks {
+                    let min = self.builder.ins().icmp_imm(IntCC::Equal, value, i64::MIN);
+                    self.fail_if(min, runtime::OVERFLOW)?;
+                }
+                Ok(self.builder.ins().ineg(value))
+            }
+            (UnaryOp::Neg, Some(ViraType::Float)) => Ok(self.builder.ins().fneg(value)),
+            (UnaryOp::Not, Some(ViraType::Bool)) => Ok(self.builder.ins().bxor_imm(value, 1)),
+            _ => Err("Codegen only supports negating ints and floats, and '!' on bools.".to_string()),
+        }
+    }
+
+    // Both branches jump to a merge block that carries the if's value as a
+    // block parameter. Without an else branch there is no value.
+    fn translate_if(&mut self, cond: NodeId, then: NodeId, else_: Option<NodeId>) -> Result<Value, String> {
+        let cond = self.translate(cond)?;
+        let then_block = self.builder.create_block();
+        let else_block = self.builder.create_block();
+        let merge = self.builder.create_block();
+        self.builder.ins().brif(cond, then_block, &[], else_block, &[]);
+
+        self.builder.switch_to_block(then_block);
+        self.builder.seal_block(then_block);
+        let then_value = self.translate(then)?;
+        let value_type = self.builder.func.dfg.value_type(then_value);
+        if else_.is_some() {
+            self.builder.append_block_param(merge, value_type);
+            self.builder.ins().jump(merge, &[then_value]);
+        } else {
+            self.builder.ins().jump(merge, &[]);
+        }
+
+        self.builder.switch_to_block(else_block);
+        self.builder.seal_block(else_block);
+        if let Some(else_) = else_ {
+            let else_value = self.translate(else_)?;
+            if self.builder.func.dfg.value_type(else_value) != value_type {
+                return Err("If branches produce values of different types.".to_string());
+            }
+            self.builder.ins().jump(merge, &[else_value]);
+        } else {
+            self.builder.ins().jump(merge, &[]);
+        }
+
+        self.builder.switch_to_block(merge);
+        self.builder.seal_block(merge);
+        match self.builder.block_params(merge).first() {
+            Some(value) => Ok(*value),
+            None => Ok(self.builder.ins().iconst(types::I64, 0)),
+        }
+    }
+
+    // Optionals are lowered to a (flag, payload) pair of slots; nil has flag
+    // 0 and no payload.
+    fn optional(&mut self, payload: Option<Value>) -> Result<Value, String> {
+        let flag = self.builder.ins().iconst(types::I64, payload.is_some() as i64);
+        self.optional_pair(flag, payload)
+    }
+
+    // Like `optional`, for a flag only known at run time.
+    fn optional_pair(&mut self, flag: Value, payload: Option<Value>) -> Result<Value, String> {
+        let addr = self.allocate(2 * StructLayout::FIELD_SIZE)?;
+        self.builder.ins().store(MemFlags::trusted(), flag, addr, 0);
+        if let Some(payload) = payload {
+            self.builder.ins().store(MemFlags::trusted(), payload, addr, StructLayout::FIELD_SIZE as i32);
+        }
+        Ok(addr)
+    }
+
+    // Branches on the flag, so the fallback only runs for nil. An optional
+    // fallback keeps the result optional.
+    fn translate_coalesce(&mut self, left: ExprId, right: ExprId) -> Result<Value, String> {
+        let Some(ViraType::Optional(inner)) = self.static_type(left.node()) else {
+            return Err("The left side of '??' must be an optional.".to_string());
+        };
+        let optional_fallback = matches!(self.ast[right], Expr::Nil) || matches!(self.static_type(right.node()), Some(ViraType::Optional(_)));
+        let result_type = if optional_fallback { self.pointer_type } else { self.cranelift_type(&inner) };
+        let optional = self.translate(left.node())?;
+        let flag = self.builder.ins().load(types::I64, MemFlags::trusted(), optional, 0);
+        let some_block = self.builder.create_block();
+        let nil_block = self.builder.create_block();
+        let merge = self.builder.create_block();
+        self.builder.append_block_param(merge, result_type);
+        self.builder.ins().brif(flag, some_block, &[], nil_block, &[]);
+
+        self.builder.switch_to_block(some_block);
+        self.builder.seal_block(some_block);
+        let value = if optional_fallback {
+            optional
+        } else {
+            self.builder.ins().load(result_type, MemFlags::trusted(), optional, StructLayout::FIELD_SIZE as i32)
+        };
+        self.builder.ins().jump(merge, &[value]);
+
+        self.builder.switch_to_block(nil_block);
+        self.builder.seal_block(nil_block);
+        let fallback = if optional_fallback { self.translate(right.node())? } else { self.translate_as(right.node(), &inner)? };
+        if self.builder.func.dfg.value_type(fallback) != result_type {
+            return Err(format!("The fallback of '??' must be a {}.", inner));
+        }
+        self.builder.ins().jump(merge, &[fallback]);
+
+        self.builder.switch_to_block(merge);
+        self.builder.seal_block(merge);
+        self.builder.block_params(merge).first().copied().ok_or_else(|| "The merge block has no value.".to_string())
+    }
+
+    // `base?.field` passes a nil base through and wraps the field otherwise,
+    // unless the field is optional itself.
+    fn translate_safe_access(&mut self, base: NodeId, field: &str) -> Result<Value, String> {
+        let struct_name = match self.static_type(base) {
+            Some(ViraType::Optional(inner)) => match *inner {
+                ViraType::Struct(name) => name,
+                _ => return Err(format!("Cannot access field '{}' on non-struct value.", field)),
+            },
+            _ => return Err(format!("The left side of '?.{}' must be an optional struct.", field)),
+        };
+        let layout = self.structs.get(&struct_name).ok_or(format!("Undefined struct '{}'.", struct_name))?;
+        let (offset, field_type) = layout
+            .field(field)
+            .ok_or(format!("Struct '{}' has no field '{}'.", struct_name, field))?;
+        let optional_field = matches!(field_type, ViraType::Optional(_));
+        let ty = self.cranelift_type(field_type);
+        let optional = self.translate(base)?;
+        let flag = self.builder.ins().load(types::I64, MemFlags::trusted(), optional, 0);
+        let some_block = self.builder.create_block();
+        let merge = self.builder.create_block();
+        self.builder.append_block_param(merge, self.pointer_type);
+        self.builder.ins().brif(flag, some_block, &[], merge, &[optional]);
+
+        self.builder.switch_to_block(some_block);
+        self.builder.seal_block(some_block);
+        let addr = self.builder.ins().load(self.pointer_type, MemFlags::trusted(), optional, StructLayout::FIELD_SIZE as i32);
+        let value = self.builder.ins().load(ty, MemFlags::trusted(), addr, offset);
+        let value = if optional_field { value } else { self.optional(Some(value))? };
+        self.builder.ins().jump(merge, &[value]);
+
+        self.builder.switch_to_block(merge);
+        self.builder.seal_block(merge);
+        self.builder.block_params(merge).first().copied().ok_or_else(|| "The merge block has no value.".to_string())
+    }
+
+    // Arms are lowered as a chain of compare-and-branch tests; each arm body
+    // jumps to a merge block that carries the arm's value as a block param.
+    fn translate_match(&mut self, scrutinee: ExprId, arms: &[(Pattern, ExprId)]) -> Result<Value, String> {
+        let value = self.translate(scrutinee.node())?;
+        let merge = self.builder.create_block();
+        let mut result_type = None;
+        for (pattern, body) in arms {
+            let body_block = self.builder.create_block();
+            let next_block = self.builder.create_block();
+            match self.pattern_test(pattern, value)? {
+                Some(cond) => {
+                    self.builder.ins().brif(cond, body_block, &[], next_block, &[]);
+                }
+                None => {
+                    self.builder.ins().jump(body_block, &[]);
+                }
+            }
+            self.builder.seal_block(body_block);
+            self.builder.switch_to_block(body_block);
+
+            self.bind_pattern(pattern, value, body.node())?;
+            let arm_value = self.translate(body.node())?;
+
+            let arm_type = self.builder.func.dfg.value_type(arm_value);
+            match result_type {
+                None => {
+                    self.builder.append_block_param(merge, arm_type);
+                    result_type = Some(arm_type);
+                }
+                Some(t) if t != arm_type => return Err("Match arms produce values of different types.".to_string()),
+                Some(_) => {}
+            }
+            self.builder.ins().jump(merge, &[arm_value]);
+            self.builder.switch_to_block(next_block);
+            self.builder.seal_block(next_block);
+        }
+        // No arm matched.
+        self.builder.ins().trap(TrapCode::UnreachableCodeReached);
+        self.builder.seal_block(merge);
+        self.builder.switch_to_block(merge);
+        self.builder
+            .block_params(merge)
+            .first()
+            .copied()
+            .ok_or_else(|| "Match needs at least one arm.".to_string())
+    }
+
+    // Returns the condition under which `pattern` matches, or None when the
+    // pattern is irrefutable.
+    fn pattern_test(&mut self, pattern: &Pattern, value: Value) -> Result<Option<Value>, String> {
+        match pattern {
+            Pattern::Wildcard | Pattern::Binding(_) => Ok(None),
+            Pattern::Int(v) => Ok(Some(self.builder.ins().icmp_imm(IntCC::Equal, value, *v))),
+            Pattern::Bool(v) => Ok(Some(self.builder.ins().icmp_imm(IntCC::Equal, value, *v as i64))),
+            Pattern::Float(v) => {
+                let expected = self.builder.ins().f64const(*v);
+                Ok(Some(self.builder.ins().fcmp(FloatCC::Equal, value, expected)))
+            }
+            Pattern::String(_) => Err("String patterns are not supported in codegen.".to_string()),
+            Pattern::EnumVariant(enum_name, variant, fields) => {
+                let (discriminant, payload) = self.variant_layout(enum_name, variant)?;
+                if payload.len() != fields.len() {
+                    return Err(format!("Pattern '{}::{}' expects {} field(s).", enum_name, variant, payload.len()));
+                }
+                let tag = self.builder.ins().load(types::I64, MemFlags::trusted(), value, 0);
+                let mut cond = self.builder.ins().icmp_imm(IntCC::Equal, tag, discriminant);
+                for (i, (field, typ)) in fields.iter().zip(&payload).enumerate() {
+                    let field_value = self.load_payload(value, i, typ);
+                    if let Some(field_cond) = self.pattern_test(field, field_value)? {
+                        cond = self.builder.ins().band(cond, field_cond);
+                    }
+                }
+                Ok(Some(cond))
+            }
+        }
+    }
+
+    // Binds the variables of `pattern` for the arm `body`.
+    fn bind_pattern(&mut self, pattern: &Pattern, value: Value, body: NodeId) -> Result<(), String> {
+        match pattern {
+            Pattern::Binding(name) => {
+                let symbol = self.hir.declared(body, name).ok_or(format!("Undefined variable '{}'.", name))?;
+                self.define(symbol, value);
+                Ok(())
+            }
+            Pattern::EnumVariant(enum_name, variant, fields) => {
+                let (_, payload) = self.variant_layout(enum_name, variant)?;
+                for (i, (field, typ)) in fields.iter().zip(&payload).enumerate() {
+                    let field_value = self.load_payload(value, i, typ);
+                    self.bind_pattern(field, field_value, body)?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn variant_layout(&self, enum_name: &str, variant: &str) -> Result<(i64, Vec<ViraType>), String> {
+        let layout = self.enums.get(enum_name).ok_or(format!("Undefined enum '{}'.", enum_name))?;
+        layout
+            .discriminant(variant)
+            .map(|(d, payload)| (d, payload.to_vec()))
+            .ok_or(format!("Enum '{}' has no variant '{}'.", enum_name, variant))
+    }
+
+    fn load_payload(&mut self, enum_value: Value, index: usize, typ: &ViraType) -> Value {
+        let offset = ((index as u32 + 1) * StructLayout::FIELD_SIZE) as i32;
+        let ty = self.cranelift_type(typ);
+        self.builder.ins().load(ty, MemFlags::trusted(), enum_value, offset)
+    }
+
+    // Whether `node` is `nil`, or a block that ends in it.
+    fn is_nil(&self, node: NodeId) -> bool {
+        match &self.ast[node] {
+            AstNode::Expr(Expr::Nil) => true,
+            AstNode::Expr(Expr::Block(stmts)) => stmts.last().is_some_and(|&last| self.is_nil(last)),
+            _ => false,
+        }
+    }
+
+    fn is_user_type(&self, node: NodeId) -> bool {
+        matches!(self.static_type(node), Some(ViraType::Struct(_) | ViraType::Enum(_)))
+    }
+
+    fn static_type(&self, id: NodeId) -> Option<ViraType> {
+        self.hir.type_of(id).cloned()
+    }
+
+    fn cranelift_type(&self, typ: &ViraType) -> Type {
+        match typ {
+            ViraType::Float => types::F64,
+            ViraType::Int | ViraType::Bool => types::I64,
+            ViraType::String
+            | ViraType::Decimal
+            | ViraType::Complex
+            | ViraType::Array(_)
+            | ViraType::Struct(_)
+            | ViraType::Enum(_)
+            | ViraType::Range
+            | ViraType::Function(..)
+            | ViraType::TypeVar(_)
+            | ViraType::Tuple(_)
+            | ViraType::Map(..)
+            | ViraType::Optional(_) => self.pointer_type,
+        }
+    }
+}
+
+// The runtime function behind each builtin that codegen supports.
+fn runtime_builtin(name: &str) -> Option<&'static str> {
+    match name {
+        "get" => Some(runtime::MAP_GET),
+        "contains" => Some(runtime::MAP_CONTAINS),
+        "set" => Some(runtime::MAP_WITH),
+        "map.len" => Some(runtime::MAP_LEN),
+        "array_filled" => Some(runtime::ARRAY_FILLED),
+        "array.len" => Some(runtime::ARRAY_LEN),
+        _ => None,
+    }
+}
+
+// Whether `node` has a `throw` anywhere inside it.
+fn throws(ast: &Arena, node: NodeId) -> bool {
+    let mut found = matches!(ast[node], AstNode::Stmt(Stmt::Throw(_)));
+    ast[node].for_each_child(&mut |child| found = found || throws(ast, child));
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vira_ir::{link, typecheck};
+    use vira_syntax::diagnostic;
+    use vira_syntax::lower;
+    use vira_syntax::parser::Parser;
+    use vira_syntax::tokenizer::tokenize;
+
+    // Compiles `source` and runs it, returning what main returns.
+    fn run(source: &str) -> Result<i64, String> {
+        run_with(&mut CodeGen::new(&Profile::debug())?, source)
+    }
+
+    fn run_with(codegen: &mut CodeGen, source: &str) -> Result<i64, String> {
+        let mut arena = Arena::new();
+        let program = Parser::new(tokenize(source), &mut arena).parse().map_err(|errors| diagnostic::render(&errors))?;
+        lower::lower(&mut arena, &program);
+        typecheck::check(&mut arena, &program)?;
+        let hir = hir::build(&arena, &program);
+        let code = codegen.compile(&arena, &program, &hir)?;
+        let main = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i64>(code) };
+        Ok(main())
+    }
+
+    // The callee's frame is reused by the next call, so a struct it left
+    // there would be overwritten before the fields are read.
+    #[test]
+    fn returned_structs_outlive_the_callee() -> Result<(), String> {
+        let source = "struct Pair { left: int, right: int }
+            func pair(left: int, right: int) -> Pair { Pair { left: left, right: right } }
+            let a = pair(3, 4)
+            let b = pair(5, 6)
+            pair(a.right, b.left).left";
+        assert_eq!(run(source)?, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn returned_tuples_keep_both_elements() -> Result<(), String> {
+        let source = "func split(n: int) -> (int, bool) { (n, true) }
+            let (n, ok) = split(7)
+            let other = split(9)
+            if ok { n } else { other.0 }";
+        assert_eq!(run(source)?, 7);
+        Ok(())
+    }
+
+    #[test]
+    fn returned_enum_values_keep_their_payload() -> Result<(), String> {
+        let source = "enum Shape { Circle(int), Square(int, int) }
+            func square(side: int) -> Shape { Shape::Square(side, 2) }
+            let shape = square(5)
+            square(6)
+            match shape { Shape::Square(side, _) => side, Shape::Circle(r) => r }";
+        assert_eq!(run(source)?, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn returned_optionals_keep_their_value() -> Result<(), String> {
+        let source = "func find(n: int) -> int? { n }
+            func missing() -> int? { nil }
+            let found = find(8)
+            let other = missing()
+            find(9)
+            match other ?? 0 { 0 => found ?? 0, _ => 0 }";
+        assert_eq!(run(source)?, 8);
+        Ok(())
+    }
+
+    #[test]
+    fn checked_results_survive_the_return() -> Result<(), String> {
+        let source = "func add(a: int, b: int) -> int? { checked_add(a, b) }
+            let sum = add(2, 3)
+            let overflowed = add(9223372036854775807, 1)
+            add(4, 4)
+            match overflowed ?? 0 { 0 => sum ?? 0, _ => 0 }";
+        assert_eq!(run(source)?, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn returned_ranges_keep_their_bounds() -> Result<(), String> {
+        let source = "func upto(n: int) -> range { 2..n }
+            let r = upto(5)
+            upto(9)
+            let last = 0
+            for i in r { last = i }
+            last";
+        assert_eq!(run(source)?, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn for_in_loops_run_as_while_loops() -> Result<(), String> {
+        let source = "func sum(items: array<int>) -> int {
+                let total = 0
+                for item in items { total += item }
+                total
+            }
+            func first_over(limit: int) -> int {
+                for i in 0..100 {
+                    if i * i > limit { return i }
+                }
+                -1
+            }
+            sum([1, 2, 3]) * 10 + first_over(20)";
+        assert_eq!(run(source)?, 65);
+        Ok(())
+    }
+
+    extern "C" {
+        fn raise(signum: i32) -> i32;
+    }
+
+    // Compiled code ends the run at the loop's back-edge, which exits the
+    // process, so the loop runs in a copy of the test binary.
+    #[test]
+    fn ctrl_c_stops_a_compiled_loop() -> Result<(), String> {
+        const CHILD: &str = "VIRA_INTERRUPT_TEST_CHILD";
+        if std::env::var_os(CHILD).is_some() {
+            interrupt::install();
+            unsafe { raise(2) };
+            run("let i = 0\nwhile i < 10 { i += 1 }\ni")?;
+            return Err("The loop ran to the end.".to_string());
+        }
+        let test = std::env::current_exe().map_err(|e| e.to_string())?;
+        let output = std::process::Command::new(test)
+            .args(["--exact", "codegen::tests::ctrl_c_stops_a_compiled_loop", "--nocapture"])
+            .env(CHILD, "1")
+            .output()
+            .map_err(|e| e.to_string())?;
+        assert_eq!(output.status.code(), Some(130));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Run error: Interrupted."));
+        Ok(())
+    }
+
+    #[test]
+    fn perf_counters_count_what_each_function_ran() -> Result<(), String> {
+        let source = "struct Pair { left: int, right: int }
+            func count(n: int) -> int {
+                let i = 0
+                while i < n { i += 1 }
+                i
+            }
+            func pair(n: int) -> Pair { Pair { left: n, right: n } }
+            count(3) + count(4) + pair(1).left";
+        let mut codegen = CodeGen::new(&Profile::debug())?;
+        codegen.enable_perf_counters();
+        assert_eq!(run_with(&mut codegen, source)?, 8);
+        let report = perf::report(codegen.perf_counters().unwrap_or_default());
+        // Calls, instructions, branches and allocations of `function`.
+        let row = |function: &str| -> Result<[u64; 4], String> {
+            let line = report.lines().find(|line| line.split_whitespace().next() == Some(function)).unwrap_or_default();
+            let counts: Vec<u64> = line.split_whitespace().skip(1).filter_map(|count| count.parse().ok()).collect();
+            counts.try_into().map_err(|_| format!("No counters for '{}' in:{}", function, report))
+        };
+        // The two calls test the loop condition 4 + 5 times, and each of
+        // their 7 iterations branches on overflow and on an interrupt.
+        let [calls, instructions, branches, allocations] = row("count")?;
+        assert_eq!((calls, branches, allocations), (2, 23, 0));
+        assert!(instructions > branches);
+        let [calls, _, branches, allocations] = row("pair")?;
+        assert_eq!((calls, branches, allocations), (1, 0, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn ints_are_promoted_when_mixed_with_floats() -> Result<(), String> {
+        let source = "func mix(a: int, b: float) -> float { a + b }
+            let half = 0.5
+            let x = mix(2, half) * 4
+            if x == 10.0 && 1 < half + 1 { 1 } else { 0 }";
+        assert_eq!(run(source)?, 1);
+        Ok(())
+    }
+
+    // Both modules declare `count` and `twice`; each keeps its own.
+    #[test]
+    fn imported_modules_link_into_one_program() -> Result<(), String> {
+        let util = "let count = 3
+            pub let total = count + 1
+            pub func twice(x: int) -> int { x * 2 }";
+        let main = "import util
+            let count = 40
+            func twice(x: int) -> int { x + x + 1 }
+            util::twice(count) + util::total + twice(1)";
+        let mut arena = Arena::new();
+        let mut modules = Vec::new();
+        for (name, source) in [("util", util), ("main", main)] {
+            let program = Parser::new(tokenize(source), &mut arena).parse().map_err(|errors| diagnostic::render(&errors))?;
+            lower::lower(&mut arena, &program);
+            typecheck::check(&mut arena, &program)?;
+            modules.push((name.to_string(), program));
+        }
+        let program = link::link(&mut arena, modules)?;
+        let hir = hir::build(&arena, &program);
+        let mut codegen = CodeGen::new(&Profile::debug())?;
+        let code = codegen.compile(&arena, &program, &hir)?;
+        let main = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i64>(code) };
+        assert_eq!(main(), 87);
+        Ok(())
+    }
+
+    #[test]
+    fn returned_trait_objects_keep_their_value_and_vtable() -> Result<(), String> {
+        let source = "trait Shape { func area(self) -> int }
+            struct Square { side: int }
+            impl Shape for Square { func area(self) -> int { self.side } }
+            func square(side: int) -> Shape { Square { side: side } }
+            let shape = square(3)
+            square(4)
+            shape.area()";
+        assert_eq!(run(source)?, 3);
+        Ok(())
+    }
+}
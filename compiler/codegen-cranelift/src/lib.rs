@@ -0,0 +1,6 @@
+#![deny(clippy::unwrap_used)]
+
+// The Cranelift backend: compiles a monomorphized tree, with its HIR, to
+// machine code for the JIT.
+
+pub mod codegen;
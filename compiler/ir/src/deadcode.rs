@@ -0,0 +1,55 @@
+use vira_syntax::arena::{Arena, NodeId, Position};
+use vira_syntax::ast::{AstNode, Expr, Stmt};
+use vira_syntax::visit::{walk, Visitor};
+
+use crate::typecheck::diverges;
+
+// Warnings about code that can never run: the statements after one that
+// always returns or throws, such as `return`, an `if` whose branches both
+// return or a `while true` loop, and the body of a `while false` loop. Only
+// the first statement of a run of unreachable ones is reported. Declarations
+// after a `return` are not code that runs, so they are left alone. Runs on
+// the checked tree, where a diverging statement means the same as it does
+// to the type checker.
+
+pub fn check(arena: &Arena, program: &[NodeId]) -> Vec<String> {
+    let mut deadcode = DeadCode { warnings: Vec::new() };
+    deadcode.statements(arena, program);
+    for &id in program {
+        deadcode.visit(arena, id);
+    }
+    deadcode.warnings.sort_by_key(|&(at, _)| at);
+    deadcode.warnings.into_iter().map(|(_, warning)| warning).collect()
+}
+
+struct DeadCode {
+    warnings: Vec<(Position, String)>,
+}
+
+impl DeadCode {
+    fn warn(&mut self, arena: &Arena, id: NodeId, message: &str) {
+        if let Some(((line, column), _)) = arena.span(id) {
+            self.warnings.push(((line, column), format!("{}:{}: warning: {}", line, column, message)));
+        }
+    }
+
+    fn statements(&mut self, arena: &Arena, stmts: &[NodeId]) {
+        let Some(end) = stmts.iter().position(|&stmt| diverges(arena, stmt)) else { return };
+        if let Some(&unreachable) = stmts[end + 1..].iter().find(|&&stmt| !matches!(arena[arena.item(stmt)], AstNode::Item(_))) {
+            self.warn(arena, unreachable, "unreachable statement");
+        }
+    }
+}
+
+impl<'a> Visitor<'a> for DeadCode {
+    fn visit(&mut self, arena: &'a Arena, id: NodeId) {
+        match &arena[id] {
+            AstNode::Expr(Expr::Block(stmts)) => self.statements(arena, stmts),
+            AstNode::Stmt(Stmt::While(condition, body)) if matches!(arena[*condition], Expr::BoolLiteral(false)) => {
+                self.warn(arena, *body, "the body of 'while false' never runs");
+            }
+            _ => {}
+        }
+        walk(self, arena, id);
+    }
+}
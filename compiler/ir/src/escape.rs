@@ -0,0 +1,375 @@
+use std::collections::{HashMap, HashSet};
+
+use vira_rt::builtins::{self, Receiver};
+use vira_syntax::arena::{Arena, ExprId, NodeId, Position};
+use vira_syntax::ast::{AstNode, BinOp, Expr, Item, Stmt, ViraType};
+use vira_syntax::visit::{walk, Visitor};
+
+use crate::hir::{Hir, SymbolId};
+
+// Escape analysis: for each array or string a function allocates, whether
+// the value can outlive the call. One that cannot is local, and codegen puts
+// it in the function's region, which is freed when the function returns,
+// instead of leaving it on the heap for good.
+//
+// A value escapes when it is returned, thrown, stored in an aggregate,
+// passed to a function, assigned to a variable of another function, or
+// flows into a variable that escapes. A builtin only keeps an argument when
+// its result could hold one, and mutating methods keep theirs. Anything the
+// analysis cannot follow, such as a match on the value or a function that
+// captures variables of the one being analyzed, counts as escaping. What the
+// top level allocates lives for the whole run anyway.
+//
+// String concatenations are reported, but always on the heap: codegen does
+// not concatenate strings yet, so one would give its function a region with
+// nothing in it.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Source {
+    Site(NodeId),
+    Variable(SymbolId),
+}
+
+// An allocation, for reports.
+pub struct Site {
+    pub function: String,
+    pub at: Option<Position>,
+    pub what: &'static str,
+    pub local: bool,
+}
+
+#[derive(Default)]
+pub struct Escapes {
+    sites: Vec<Site>,
+    local: HashSet<NodeId>,
+    // The bodies with at least one local site, which get a region.
+    regions: HashSet<NodeId>,
+}
+
+impl Escapes {
+    pub fn is_local(&self, site: NodeId) -> bool {
+        self.local.contains(&site)
+    }
+
+    pub fn has_region(&self, body: NodeId) -> bool {
+        self.regions.contains(&body)
+    }
+
+    // In source order.
+    pub fn sites(&self) -> &[Site] {
+        &self.sites
+    }
+}
+
+pub fn analyze(arena: &Arena, program: &[NodeId], hir: &Hir) -> Escapes {
+    let mut escapes = Escapes::default();
+    let mut sites = Vec::new();
+    // A function the program declares takes the place of a builtin of its
+    // name.
+    let mut declared = Declared::default();
+    for &node in program {
+        declared.visit(arena, node);
+    }
+    let declared = declared.0;
+    let mut top = Function::new(arena, hir, &declared);
+    for &node in program {
+        top.value(node);
+    }
+    sites.extend(top.sites.iter().map(|&site| ("(top level)".to_string(), site, false)));
+    let mut pending = top.nested;
+    while let Some((name, body)) = pending.pop() {
+        let mut function = Function::new(arena, hir, &declared);
+        let value = function.value(body);
+        function.escapes(value);
+        let escaping = function.escaping();
+        for &site in &function.sites {
+            let local = !function.captured && !escaping.contains(&site) && !matches!(arena[site], AstNode::Expr(Expr::Binary(..)));
+            if local {
+                escapes.local.insert(site);
+                escapes.regions.insert(body);
+            }
+            sites.push((name.clone(), site, local));
+        }
+        pending.extend(function.nested.into_iter().map(|(nested, body)| (if nested.is_empty() { format!("{}::<anonymous>", name) } else { nested }, body)));
+    }
+    sites.sort_by_key(|&(_, site, _)| arena.span(site).map(|(start, _)| start));
+    escapes.sites = sites
+        .into_iter()
+        .map(|(function, site, local)| Site { function, at: arena.span(site).map(|(start, _)| start), what: what(arena, site), local })
+        .collect();
+    escapes
+}
+
+fn what(arena: &Arena, site: NodeId) -> &'static str {
+    match &arena[site] {
+        AstNode::Expr(Expr::ArrayLiteral(_)) => "array literal",
+        AstNode::Expr(Expr::Call(name, _)) if name == "array_generate" => "array_generate",
+        AstNode::Expr(Expr::Call(..)) => "array_filled",
+        _ => "string concatenation",
+    }
+}
+
+// The names of the functions declared anywhere.
+#[derive(Default)]
+struct Declared<'a>(HashSet<&'a str>);
+
+impl<'a> Visitor<'a> for Declared<'a> {
+    fn visit(&mut self, arena: &'a Arena, id: NodeId) {
+        if let AstNode::Item(Item::FuncDecl(name, ..)) = &arena[id] {
+            self.0.insert(name.as_str());
+        }
+        walk(self, arena, id);
+    }
+}
+
+struct Function<'a> {
+    arena: &'a Arena,
+    hir: &'a Hir,
+    declared: &'a HashSet<&'a str>,
+    sites: Vec<NodeId>,
+    // What may have been stored in each variable.
+    flows: HashMap<SymbolId, Vec<Source>>,
+    roots: Vec<Source>,
+    // The bodies of the functions declared inside this one, with their names;
+    // anonymous ones have none.
+    nested: Vec<(String, NodeId)>,
+    captured: bool,
+}
+
+impl<'a> Function<'a> {
+    fn new(arena: &'a Arena, hir: &'a Hir, declared: &'a HashSet<&'a str>) -> Self {
+        Function { arena, hir, declared, sites: Vec::new(), flows: HashMap::new(), roots: Vec::new(), nested: Vec::new(), captured: false }
+    }
+
+    fn escapes(&mut self, sources: Vec<Source>) {
+        self.roots.extend(sources);
+    }
+
+    fn site(&mut self, id: NodeId) -> Vec<Source> {
+        self.sites.push(id);
+        vec![Source::Site(id)]
+    }
+
+    fn nested(&mut self, name: String, body: NodeId) {
+        self.captured |= self.hir.captured(body).is_some();
+        self.nested.push((name, body));
+    }
+
+    // The sites reachable from a root through the variables they flowed into.
+    fn escaping(&self) -> HashSet<NodeId> {
+        let mut escaping = HashSet::new();
+        let mut seen = HashSet::new();
+        let mut pending = self.roots.clone();
+        while let Some(source) = pending.pop() {
+            match source {
+                Source::Site(site) => {
+                    escaping.insert(site);
+                }
+                Source::Variable(symbol) => {
+                    if seen.insert(symbol) {
+                        pending.extend(self.flows.get(&symbol).into_iter().flatten().copied());
+                    }
+                }
+            }
+        }
+        escaping
+    }
+
+    fn is_builtin(&self, call: NodeId, name: &str) -> bool {
+        self.hir.resolve(call).is_none() && !self.declared.contains(name) && builtins::is_function(name)
+    }
+
+    // Each argument of a builtin escapes unless the result's type cannot
+    // hold it.
+    fn builtin(&mut self, name: &str, args: &[ExprId]) {
+        let types: Vec<Option<ViraType>> = args.iter().map(|arg| self.hir.type_of(arg.node()).cloned()).collect();
+        let ret = if builtins::mutates(name) { None } else { builtins::return_type(name, &types) };
+        for (&arg, typ) in args.iter().zip(&types) {
+            let value = self.value(arg.node());
+            match (&ret, typ) {
+                (Some(ret), Some(typ)) if !holds(ret, typ) => {}
+                _ => self.escapes(value),
+            }
+        }
+    }
+
+    fn all_escape(&mut self, nodes: impl IntoIterator<Item = ExprId>) {
+        for node in nodes {
+            let value = self.value(node.node());
+            self.escapes(value);
+        }
+    }
+
+    fn drop_all(&mut self, nodes: impl IntoIterator<Item = ExprId>) {
+        for node in nodes {
+            self.value(node.node());
+        }
+    }
+
+    // Walks the node, recording what escapes inside it, and returns what its
+    // value may be.
+    fn value(&mut self, id: NodeId) -> Vec<Source> {
+        let arena = self.arena;
+        match &arena[id] {
+            AstNode::Expr(expr) => match expr {
+                Expr::ArrayLiteral(items) => {
+                    self.all_escape(items.iter().copied());
+                    self.site(id)
+                }
+                Expr::Call(name, args) if self.is_builtin(id, name) && (name == "array_filled" || name == "array_generate") => {
+                    if let [len, rest @ ..] = args.as_slice() {
+                        self.value(len.node());
+                        self.all_escape(rest.iter().copied());
+                    }
+                    self.site(id)
+                }
+                Expr::Call(name, args) if self.is_builtin(id, name) => {
+                    self.builtin(name, args);
+                    Vec::new()
+                }
+                Expr::Call(_, args) | Expr::Tuple(args) | Expr::EnumConstructor(_, _, args) => {
+                    self.all_escape(args.iter().copied());
+                    Vec::new()
+                }
+                Expr::Apply(callee, args) => {
+                    self.all_escape(std::iter::once(*callee).chain(args.iter().copied()));
+                    Vec::new()
+                }
+                Expr::MethodCall(receiver, method, args) => {
+                    let builtin = self.hir.type_of(receiver.node()).and_then(Receiver::of_type).and_then(|receiver| builtins::method(receiver, method));
+                    // What a for-in loop over an array indexes is the array.
+                    if builtin.as_ref().is_some_and(|builtin| builtin.builtin == "array.items") {
+                        return self.value(receiver.node());
+                    }
+                    match builtin {
+                        Some(builtin) => self.builtin(builtin.builtin, &std::iter::once(*receiver).chain(args.iter().copied()).collect::<Vec<_>>()),
+                        None => self.all_escape(std::iter::once(*receiver).chain(args.iter().copied())),
+                    }
+                    Vec::new()
+                }
+                Expr::StructLiteral(_, fields) => {
+                    self.all_escape(fields.iter().map(|(_, value)| *value));
+                    Vec::new()
+                }
+                Expr::MapLiteral(entries) => {
+                    self.all_escape(entries.iter().flat_map(|&(key, value)| [key, value]));
+                    Vec::new()
+                }
+                Expr::Binary(left, op, right) => {
+                    self.drop_all([*left, *right]);
+                    if *op == BinOp::Add && self.hir.type_of(id) == Some(&ViraType::String) {
+                        self.site(id)
+                    } else {
+                        Vec::new()
+                    }
+                }
+                Expr::VarRef(_) => self.hir.resolve(id).map(Source::Variable).into_iter().collect(),
+                Expr::If(condition, then, otherwise) => {
+                    self.value(condition.node());
+                    let mut value = self.value(*then);
+                    value.extend(otherwise.map(|otherwise| self.value(otherwise)).unwrap_or_default());
+                    value
+                }
+                Expr::Coalesce(left, right) => {
+                    let mut value = self.value(left.node());
+                    value.extend(self.value(right.node()));
+                    value
+                }
+                Expr::Block(stmts) => {
+                    let mut value = Vec::new();
+                    for &stmt in stmts {
+                        value = self.value(stmt);
+                    }
+                    value
+                }
+                // The arms may bind parts of the scrutinee, which is not
+                // followed through the patterns.
+                Expr::Match(scrutinee, arms) => {
+                    self.all_escape([*scrutinee]);
+                    arms.iter().flat_map(|&(_, arm)| self.value(arm.node())).collect()
+                }
+                Expr::Lambda(_, _, body) => {
+                    self.nested(String::new(), body.node());
+                    Vec::new()
+                }
+                // Reading from a value does not keep it; the elements read
+                // out were stored, so they escaped already.
+                _ => {
+                    expr.for_each_child(&mut |child| {
+                        self.value(child);
+                    });
+                    Vec::new()
+                }
+            },
+            AstNode::Stmt(stmt) => {
+                match stmt {
+                    Stmt::VarDecl(name, _, init) => {
+                        let value = self.value(init.node());
+                        match self.hir.declared(id, name) {
+                            Some(symbol) => self.flows.entry(symbol).or_default().extend(value),
+                            None => self.escapes(value),
+                        }
+                    }
+                    Stmt::Assign(_, value) => {
+                        let value = self.value(value.node());
+                        match self.hir.resolve(id) {
+                            Some(symbol) => self.flows.entry(symbol).or_default().extend(value),
+                            None => self.escapes(value),
+                        }
+                    }
+                    Stmt::TupleDecl(_, _, value) | Stmt::Return(Some(value)) | Stmt::Throw(value) => self.all_escape([*value]),
+                    Stmt::Try(body, _, handler) => {
+                        let mut value = self.value(body.node());
+                        value.extend(self.value(handler.node()));
+                        return value;
+                    }
+                    _ => stmt.for_each_child(&mut |child| {
+                        self.value(child);
+                    }),
+                }
+                Vec::new()
+            }
+            AstNode::Item(item) => {
+                match item {
+                    Item::FuncDecl(name, _, _, _, body) => self.nested(name.clone(), *body),
+                    Item::ImplDecl(_, typ, methods) => {
+                        for &method in methods {
+                            if let Item::FuncDecl(name, _, _, _, body) = &arena[method] {
+                                self.nested(format!("{}::{}", typ, name), *body);
+                            }
+                        }
+                    }
+                    Item::Pub(item) | Item::Attribute(_, _, item) => {
+                        self.value(*item);
+                    }
+                    _ => {}
+                }
+                Vec::new()
+            }
+        }
+    }
+}
+
+// Whether a value of type `outer` can contain one of type `inner`.
+fn holds(outer: &ViraType, inner: &ViraType) -> bool {
+    outer == inner
+        || match outer {
+            ViraType::Array(element) | ViraType::Optional(element) => holds(element, inner),
+            ViraType::Map(key, value) => holds(key, inner) || holds(value, inner),
+            ViraType::Tuple(elements) => elements.iter().any(|element| holds(element, inner)),
+            ViraType::Function(..) | ViraType::Struct(_) | ViraType::Enum(_) | ViraType::TypeVar(_) => true,
+            _ => false,
+        }
+}
+
+// One line per allocation site: where it is, what it allocates and whether
+// it goes in its function's region or on the heap.
+pub fn report(sites: &[(String, &Site)]) -> String {
+    let width = sites.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max("Function".len());
+    let mut out = format!("\n{:<width$}  {:<9}  {:<20}  Storage\n", "Function", "Site", "Allocation");
+    for (name, site) in sites {
+        let at = site.at.map_or(String::new(), |(line, column)| format!("{}:{}", line, column));
+        out.push_str(&format!("{:<width$}  {:<9}  {:<20}  {}\n", name, at, site.what, if site.local { "region" } else { "heap" }));
+    }
+    out
+}
@@ -0,0 +1,135 @@
+use vira_syntax::arena::{Arena, ExprId, NodeId};
+use vira_syntax::ast::{self, AstNode, BinOp, Expr, Program, UnaryOp};
+use vira_syntax::visit::{walk_mut, VisitorMut};
+
+use crate::hir::{self, Hir};
+
+// Constant folding, for `-O`: operators whose operands are literals become
+// the literal they evaluate to, innermost first, so `2 * 3 + 1` is `7`.
+// A fold never changes what a program does. Whatever would fail or depend on
+// the profile at run time stays for the engine to evaluate: overflow, which
+// is an error or wraps, division by zero, and operands the engines reject:
+// they have no float arithmetic and do not concatenate strings with `+` yet,
+// so those stay too. An `if` on a literal condition, which is also what `&&`
+// and `||` lower to, folds when the branch it takes is a single literal.
+// So does `config.key`, unless `config` there is a variable of its own.
+
+pub fn fold(arena: &mut Arena, items: &[NodeId], hir: &Hir) {
+    let config = ast::config(arena, items).map(<[_]>::to_vec).unwrap_or_default();
+    let mut fold = Fold { hir, config };
+    for &id in items {
+        fold.visit_mut(arena, id);
+    }
+}
+
+// A loaded program keeps the tree but not the HIR its modules were checked
+// with, so this builds each module's once for its fold.
+pub fn fold_program(program: &mut Program) {
+    for (_, items) in &program.modules {
+        let hir = hir::build(&program.arena, items);
+        fold(&mut program.arena, items, &hir);
+    }
+}
+
+struct Fold<'a> {
+    hir: &'a Hir,
+    config: Vec<(String, ExprId)>,
+}
+
+impl VisitorMut for Fold<'_> {
+    fn visit_mut(&mut self, arena: &mut Arena, id: NodeId) {
+        walk_mut(self, arena, id);
+        let folded = match arena[id] {
+            AstNode::Expr(Expr::Binary(left, op, right)) => binary(&arena[left], op, &arena[right]),
+            AstNode::Expr(Expr::Unary(op, operand)) => unary(op, &arena[operand]),
+            AstNode::Expr(Expr::IntToFloat(operand)) => match arena[operand] {
+                Expr::Literal(v) => Some(Expr::FloatLiteral(v as f64)),
+                _ => None,
+            },
+            AstNode::Expr(Expr::FieldAccess(base, ref field)) if self.hir.resolve(base.node()).is_none() && matches!(&arena[base], Expr::VarRef(name) if name == "config") => {
+                self.config.iter().find(|(key, _)| key == field).and_then(|&(_, value)| literal(&arena[value]))
+            }
+            AstNode::Expr(Expr::If(condition, then, Some(otherwise))) => match arena[condition] {
+                Expr::BoolLiteral(taken) => literal_block(arena, if taken { then } else { otherwise }),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(folded) = folded {
+            arena[id] = AstNode::Expr(folded);
+        }
+    }
+}
+
+fn binary(left: &Expr, op: BinOp, right: &Expr) -> Option<Expr> {
+    let folded = match (left, right) {
+        (Expr::Literal(a), Expr::Literal(b)) => match op {
+            BinOp::Add => Expr::Literal(a.checked_add(*b)?),
+            BinOp::Sub => Expr::Literal(a.checked_sub(*b)?),
+            BinOp::Mul => Expr::Literal(a.checked_mul(*b)?),
+            // `checked_div` is None for a zero divisor too.
+            BinOp::Div => Expr::Literal(a.checked_div(*b)?),
+            BinOp::Mod => Expr::Literal(a.checked_rem(*b)?),
+            BinOp::And | BinOp::Or => return None,
+            _ => Expr::BoolLiteral(compare(a.partial_cmp(b), op)?),
+        },
+        (Expr::FloatLiteral(a), Expr::FloatLiteral(b)) => Expr::BoolLiteral(compare(a.partial_cmp(b), op)?),
+        (Expr::BoolLiteral(a), Expr::BoolLiteral(b)) => Expr::BoolLiteral(equality(a == b, op)?),
+        (Expr::StringLiteral(a), Expr::StringLiteral(b)) => Expr::BoolLiteral(equality(a == b, op)?),
+        _ => return None,
+    };
+    Some(folded)
+}
+
+// A comparison as the interpreter makes it: a NaN is unordered and unequal
+// to everything. None for the operators that do not compare.
+fn compare(ordering: Option<std::cmp::Ordering>, op: BinOp) -> Option<bool> {
+    let result = match op {
+        BinOp::Eq => ordering.is_some_and(|o| o.is_eq()),
+        BinOp::Neq => !ordering.is_some_and(|o| o.is_eq()),
+        BinOp::Lt => ordering.is_some_and(|o| o.is_lt()),
+        BinOp::Gt => ordering.is_some_and(|o| o.is_gt()),
+        BinOp::Le => ordering.is_some_and(|o| o.is_le()),
+        BinOp::Ge => ordering.is_some_and(|o| o.is_ge()),
+        _ => return None,
+    };
+    Some(result)
+}
+
+// Bools and strings only have `==` and `!=`.
+fn equality(equal: bool, op: BinOp) -> Option<bool> {
+    match op {
+        BinOp::Eq => Some(equal),
+        BinOp::Neq => Some(!equal),
+        _ => None,
+    }
+}
+
+fn unary(op: UnaryOp, operand: &Expr) -> Option<Expr> {
+    match (op, operand) {
+        (UnaryOp::Neg, Expr::Literal(v)) => v.checked_neg().map(Expr::Literal),
+        (UnaryOp::Neg, Expr::FloatLiteral(v)) => Some(Expr::FloatLiteral(-v)),
+        (UnaryOp::Not, Expr::BoolLiteral(v)) => Some(Expr::BoolLiteral(!v)),
+        _ => None,
+    }
+}
+
+fn literal_block(arena: &Arena, block: NodeId) -> Option<Expr> {
+    match &arena[block] {
+        AstNode::Expr(Expr::Block(items)) => match items.as_slice() {
+            [item] => match &arena[*item] {
+                AstNode::Expr(expr) => literal(expr),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn literal(expr: &Expr) -> Option<Expr> {
+    match expr {
+        literal @ (Expr::Literal(_) | Expr::FloatLiteral(_) | Expr::BoolLiteral(_) | Expr::StringLiteral(_)) => Some(literal.clone()),
+        _ => None,
+    }
+}
@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+
+use vira_rt::builtins::{self, Receiver};
+use vira_rt::numerics;
+use vira_syntax::arena::{Arena, ExprId, NodeId, TypeMap};
+use vira_syntax::ast::{literal_type, AstNode, BinOp, Expr, Item, MethodSig, Pattern, Stmt, Variant, ViraType};
+
+use crate::typecheck;
+
+// The typed HIR codegen compiles from: the tree of the program together with
+// the static type of its expressions and, for each variable a name refers
+// to, the declaration it resolves to. Both are worked out in one pass in
+// source order, before any code is emitted, so codegen never has to guess a
+// type from what it has translated so far. An expression without a type is
+// one whose type cannot be known statically; codegen reports it where it
+// needs one.
+//
+// Scoping follows compiled code: a function body sees its parameters and the
+// declarations around it but not the variables of the enclosing function,
+// which it would capture, and the variables declared in a branch, loop body
+// or handler end with it. The named functions of a block, or of the program,
+// are known from its start, so a call ahead of the declaration has a type.
+//
+// A field, element or method of an optional value has the type it has for
+// the value inside, as the type checker only lets one be read where the
+// value is known not to be nil.
+//
+// The HIR annotates the tree rather than replacing it: sugar such as `for
+// ... in`, interpolation and compound assignment is lowered in the tree
+// itself, by `lower`, before the HIR is built over it. It is built once for
+// each shape of the tree a module takes: by the type checker, which returns
+// it for a pass run straight after, by folding a loaded program, and by the
+// caller of codegen after the last pass that rewrites the tree, with codegen
+// and escape analysis sharing that one. The
+// interpreter runs the lowered tree without it, as it checks types on the
+// values it computes and has no use for static ones.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SymbolId(usize);
+
+pub struct Symbol {
+    pub typ: Option<ViraType>,
+}
+
+#[derive(Default)]
+pub struct Hir {
+    types: TypeMap,
+    // Variable references and assignments, by node.
+    resolved: HashMap<NodeId, SymbolId>,
+    // Keyed by the declaring node and name: the `let` itself, or the body
+    // that sees a parameter or a match, `for` or `catch` binding.
+    declared: HashMap<(NodeId, String), SymbolId>,
+    symbols: Vec<Symbol>,
+    // The first variable of an enclosing function each function body uses,
+    // keyed by the body.
+    captures: HashMap<NodeId, String>,
+}
+
+impl Hir {
+    pub fn type_of(&self, id: NodeId) -> Option<&ViraType> {
+        self.types.get(id)
+    }
+
+    pub fn resolve(&self, id: NodeId) -> Option<SymbolId> {
+        self.resolved.get(&id).copied()
+    }
+
+    pub fn declared(&self, at: NodeId, name: &str) -> Option<SymbolId> {
+        self.declared.get(&(at, name.to_string())).copied()
+    }
+
+    pub fn symbol(&self, id: SymbolId) -> &Symbol {
+        &self.symbols[id.0]
+    }
+
+    pub fn captured(&self, body: NodeId) -> Option<&str> {
+        self.captures.get(&body).map(|name| name.as_str())
+    }
+
+    // Types the `IntToFloat` node the checker put around an int operand.
+    pub fn converted(&mut self, id: ExprId) {
+        self.types.insert(id.node(), ViraType::Float);
+    }
+}
+
+pub fn build(arena: &Arena, program: &[NodeId]) -> Hir {
+    let mut builder = Builder { arena, hir: Hir::default(), scope: Scope::default(), outer: Vec::new() };
+    builder.hoist(program);
+    for &node in program {
+        builder.visit(node);
+    }
+    builder.hir
+}
+
+// What is declared at a point of the program.
+#[derive(Clone, Default)]
+struct Scope {
+    variables: HashMap<String, SymbolId>,
+    functions: HashMap<String, (Vec<ViraType>, ViraType)>,
+    structs: HashMap<String, Vec<(String, ViraType)>>,
+    enums: HashMap<String, Vec<Variant>>,
+    traits: HashMap<String, Vec<MethodSig>>,
+    // Keyed by implementing type and method name; the parameters exclude
+    // `self`.
+    methods: HashMap<(String, String), (Vec<ViraType>, ViraType)>,
+    consts: HashMap<String, ExprId>,
+    // The type of each data section.
+    data: HashMap<String, ViraType>,
+}
+
+struct Builder<'a> {
+    arena: &'a Arena,
+    hir: Hir,
+    scope: Scope,
+    // The variables of the enclosing functions, innermost last, with the
+    // body of the function each encloses.
+    outer: Vec<(HashMap<String, SymbolId>, NodeId)>,
+}
+
+impl Builder<'_> {
+    fn declare(&mut self, at: NodeId, name: &str, typ: Option<ViraType>) {
+        let id = SymbolId(self.hir.symbols.len());
+        self.hir.symbols.push(Symbol { typ });
+        self.hir.declared.insert((at, name.to_string()), id);
+        self.scope.variables.insert(name.to_string(), id);
+    }
+
+    // Resolves a variable of the current function. A variable of an
+    // enclosing one is recorded as captured by every function in between.
+    fn resolve(&mut self, id: NodeId, name: &str) {
+        if let Some(&symbol) = self.scope.variables.get(name) {
+            self.hir.resolved.insert(id, symbol);
+            return;
+        }
+        if let Some(depth) = self.outer.iter().rposition(|(variables, _)| variables.contains_key(name)) {
+            let bodies: Vec<NodeId> = self.outer.iter().skip(depth + 1).map(|(_, body)| *body).collect();
+            for body in bodies.into_iter().chain(self.current_body()) {
+                self.hir.captures.entry(body).or_insert_with(|| name.to_string());
+            }
+        }
+    }
+
+    fn current_body(&self) -> Option<NodeId> {
+        self.outer.last().map(|(_, body)| *body)
+    }
+
+    fn variable_type(&self, name: &str) -> Option<Option<&ViraType>> {
+        self.scope.variables.get(name).map(|&symbol| self.hir.symbol(symbol).typ.as_ref())
+    }
+
+    fn type_of(&self, id: NodeId) -> Option<ViraType> {
+        self.hir.types.get(id).cloned()
+    }
+
+    // The type of a value whose fields, elements or methods are read.
+    fn base_type(&self, id: NodeId) -> Option<ViraType> {
+        match self.type_of(id)? {
+            ViraType::Optional(inner) => Some(*inner),
+            typ => Some(typ),
+        }
+    }
+
+    fn is_trait(&self, typ: &ViraType) -> bool {
+        matches!(typ, ViraType::Struct(name) if self.scope.traits.contains_key(name))
+    }
+
+    // Runs `visit` with the variables as they are now restored afterwards.
+    fn nested(&mut self, visit: impl FnOnce(&mut Self)) {
+        let saved = self.scope.variables.clone();
+        visit(self);
+        self.scope.variables = saved;
+    }
+
+    // A function body sees its parameters and the declarations made so far;
+    // what it declares stays inside it.
+    fn function(&mut self, params: &[(String, ViraType)], body: NodeId) {
+        let saved = self.scope.clone();
+        let variables = std::mem::take(&mut self.scope.variables);
+        self.outer.push((variables, body));
+        for (name, typ) in params {
+            self.declare(body, name, Some(typ.clone()));
+        }
+        self.visit(body);
+        self.outer.pop();
+        self.scope = saved;
+    }
+
+    fn hoist(&mut self, stmts: &[NodeId]) {
+        for &stmt in stmts {
+            if let AstNode::Item(Item::FuncDecl(name, _, params, ret, _)) = &self.arena[self.arena.item(stmt)] {
+                self.scope.functions.insert(name.clone(), (params.iter().map(|(_, typ)| typ.clone()).collect(), ret.clone()));
+            }
+        }
+    }
+
+    fn visit(&mut self, id: NodeId) {
+        let arena = self.arena;
+        match &arena[id] {
+            AstNode::Expr(Expr::Block(stmts)) => {
+                self.hoist(stmts);
+                for &stmt in stmts {
+                    self.visit(stmt);
+                }
+            }
+            AstNode::Stmt(Stmt::VarDecl(name, typ, init)) => {
+                self.visit(init.node());
+                let typ = match typ {
+                    Some(typ) if self.is_trait(typ) || matches!(typ, ViraType::Optional(_)) => Some(typ.clone()),
+                    _ => self.type_of(init.node()).or_else(|| typ.clone()),
+                };
+                self.declare(id, name, typ);
+            }
+            AstNode::Stmt(Stmt::TupleDecl(names, typ, init)) => {
+                self.visit(init.node());
+                if let Some(ViraType::Tuple(elements)) = self.type_of(init.node()).or_else(|| typ.clone()) {
+                    if elements.len() == names.len() {
+                        for (name, element) in names.iter().zip(elements) {
+                            self.declare(id, name, Some(element));
+                        }
+                    }
+                }
+            }
+            AstNode::Item(Item::ConstDecl(name, _, value)) => {
+                self.visit(value.node());
+                self.scope.consts.insert(name.clone(), *value);
+            }
+            AstNode::Item(Item::ConfigDecl(entries)) => {
+                for (key, value) in entries {
+                    self.visit(value.node());
+                    self.scope.consts.insert(format!("config.{}", key), *value);
+                }
+            }
+            AstNode::Item(Item::DataDecl(name, typ, ..)) => {
+                self.scope.data.insert(name.clone(), typ.clone());
+            }
+            AstNode::Stmt(Stmt::Assign(name, value)) => {
+                self.visit(value.node());
+                self.resolve(id, name);
+            }
+            AstNode::Expr(Expr::VarRef(name) | Expr::Call(name, _)) => {
+                arena[id].for_each_child(&mut |child| self.visit(child));
+                self.resolve(id, name);
+            }
+            AstNode::Item(Item::FuncDecl(name, _, params, ret, body)) => {
+                self.scope.functions.insert(name.clone(), (params.iter().map(|(_, typ)| typ.clone()).collect(), ret.clone()));
+                self.function(params, *body);
+            }
+            AstNode::Expr(Expr::Lambda(params, _, body)) => self.function(params, body.node()),
+            AstNode::Item(Item::StructDecl(name, fields)) => {
+                self.scope.structs.insert(name.clone(), fields.clone());
+            }
+            AstNode::Item(Item::EnumDecl(name, variants)) => {
+                self.scope.enums.insert(name.clone(), variants.clone());
+            }
+            AstNode::Item(Item::TraitDecl(name, methods)) => {
+                self.scope.traits.insert(name.clone(), methods.clone());
+            }
+            // Methods are declared before any body, so they can call each
+            // other.
+            AstNode::Item(Item::ImplDecl(_, type_name, methods)) => {
+                for &method in methods {
+                    if let Item::FuncDecl(name, _, params, ret, _) = &arena[method] {
+                        let own_params = params.iter().skip(1).map(|(_, typ)| typ.clone()).collect();
+                        self.scope.methods.entry((type_name.clone(), name.clone())).or_insert((own_params, ret.clone()));
+                    }
+                }
+                for &method in methods {
+                    if let Item::FuncDecl(_, _, params, _, body) = &arena[method] {
+                        self.function(params, *body);
+                    }
+                }
+            }
+            AstNode::Expr(Expr::If(condition, then, otherwise)) => {
+                self.visit(condition.node());
+                self.nested(|builder| builder.visit(*then));
+                if let Some(otherwise) = otherwise {
+                    self.nested(|builder| builder.visit(*otherwise));
+                }
+            }
+            AstNode::Expr(Expr::Match(scrutinee, arms)) => {
+                self.visit(scrutinee.node());
+                let scrutinee = self.type_of(scrutinee.node());
+                for (pattern, body) in arms {
+                    self.nested(|builder| {
+                        builder.bind(pattern, scrutinee.clone(), body.node());
+                        builder.visit(body.node());
+                    });
+                }
+            }
+            AstNode::Stmt(Stmt::Try(body, name, handler)) => {
+                self.visit(body.node());
+                self.nested(|builder| {
+                    builder.declare(handler.node(), name, Some(ViraType::Int));
+                    builder.visit(handler.node());
+                });
+            }
+            // The arguments of an attribute are not compiled.
+            AstNode::Item(Item::Attribute(_, _, item)) => self.visit(*item),
+            node => node.for_each_child(&mut |child| self.visit(child)),
+        }
+        if let Some(typ) = self.compute_type(id) {
+            self.hir.types.insert(id, typ);
+        }
+    }
+
+    fn bind(&mut self, pattern: &Pattern, typ: Option<ViraType>, body: NodeId) {
+        match pattern {
+            Pattern::Binding(name) => self.declare(body, name, typ),
+            Pattern::EnumVariant(enum_name, variant, fields) => {
+                let payload = self.scope.enums.get(enum_name).and_then(|variants| variants.iter().find(|(v, ..)| v == variant));
+                let payload = payload.map(|(_, payload, _)| payload.clone()).unwrap_or_default();
+                for (i, field) in fields.iter().enumerate() {
+                    self.bind(field, payload.get(i).cloned(), body);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // The return type of `receiver.method(...)`.
+    fn method_type(&self, receiver: NodeId, method: &str, args: &[ExprId]) -> Option<ViraType> {
+        match self.base_type(receiver)? {
+            ViraType::Struct(name) | ViraType::Enum(name) => match self.scope.traits.get(&name) {
+                Some(methods) => methods.iter().find(|(m, _, _)| m == method).map(|(_, _, ret)| ret.clone()),
+                None => match self.scope.methods.get(&(name.clone(), method.to_string())) {
+                    Some((_, ret)) => Some(ret.clone()),
+                    None => match self.field(&name, method)? {
+                        ViraType::Function(_, ret) => Some(*ret),
+                        _ => None,
+                    },
+                },
+            },
+            typ => {
+                let builtin = builtins::method(Receiver::of_type(&typ)?, method)?;
+                let args: Vec<_> = std::iter::once(Some(typ)).chain(args.iter().map(|arg| self.type_of(arg.node()))).collect();
+                builtins::return_type(builtin.builtin, &args)
+            }
+        }
+    }
+
+    fn field(&self, struct_name: &str, field: &str) -> Option<ViraType> {
+        self.scope.structs.get(struct_name)?.iter().find(|(name, _)| name == field).map(|(_, typ)| typ.clone())
+    }
+
+    fn is_user_type(&self, id: NodeId) -> bool {
+        matches!(self.base_type(id), Some(ViraType::Struct(_) | ViraType::Enum(_)))
+    }
+
+    // The type of `id`, whose children already have theirs.
+    fn compute_type(&self, id: NodeId) -> Option<ViraType> {
+        let arena = self.arena;
+        match &arena[id] {
+            AstNode::Expr(Expr::Literal(_)) => Some(ViraType::Int),
+            AstNode::Expr(Expr::FloatLiteral(_)) => Some(ViraType::Float),
+            AstNode::Expr(Expr::BoolLiteral(_)) => Some(ViraType::Bool),
+            AstNode::Expr(Expr::StringLiteral(_)) => Some(ViraType::String),
+            AstNode::Expr(Expr::StructLiteral(name, _)) => Some(ViraType::Struct(name.clone())),
+            AstNode::Expr(Expr::EnumConstructor(name, member, _)) if name == numerics::MODULE && !self.scope.enums.contains_key(name) => {
+                numerics::return_type(&format!("{}::{}", name, member))
+            }
+            AstNode::Expr(Expr::EnumConstructor(name, _, _)) => Some(ViraType::Enum(name.clone())),
+            AstNode::Expr(Expr::Range(_, _)) => Some(ViraType::Range),
+            AstNode::Expr(Expr::If(_, then, Some(_))) => self.type_of(*then),
+            AstNode::Expr(Expr::Match(_, arms)) => arms.iter().find_map(|(_, body)| self.type_of(body.node())),
+            AstNode::Expr(Expr::Block(stmts)) => self.type_of(*stmts.last()?),
+            AstNode::Expr(Expr::Tuple(items)) => items.iter().map(|item| self.type_of(item.node())).collect::<Option<_>>().map(ViraType::Tuple),
+            AstNode::Expr(Expr::TupleIndex(base, index)) => match self.base_type(base.node())? {
+                ViraType::Tuple(elements) => elements.get(*index).cloned(),
+                _ => None,
+            },
+            AstNode::Expr(Expr::Lambda(params, ret, _)) => {
+                Some(ViraType::Function(params.iter().map(|(_, typ)| typ.clone()).collect(), Box::new(ret.clone())))
+            }
+            AstNode::Expr(Expr::Call(name, args)) => match self.variable_type(name) {
+                Some(Some(ViraType::Function(_, ret))) => Some((**ret).clone()),
+                None if self.scope.functions.contains_key(name) => self.scope.functions.get(name).map(|(_, ret)| ret.clone()),
+                _ => {
+                    let args: Vec<_> = args.iter().map(|arg| self.type_of(arg.node())).collect();
+                    builtins::return_type(name, &args)
+                }
+            },
+            AstNode::Expr(Expr::MapLiteral(entries)) => {
+                let (key, value) = entries.first()?;
+                Some(ViraType::Map(Box::new(self.type_of(key.node())?), Box::new(self.type_of(value.node())?)))
+            }
+            AstNode::Expr(Expr::ArrayLiteral(items)) => Some(ViraType::Array(Box::new(self.type_of(items.first()?.node())?))),
+            AstNode::Expr(Expr::Apply(callee, _)) => match self.type_of(callee.node())? {
+                ViraType::Function(_, ret) => Some(*ret),
+                _ => None,
+            },
+            AstNode::Expr(Expr::MethodCall(receiver, method, args)) => self.method_type(receiver.node(), method, args),
+            AstNode::Expr(Expr::Binary(left, op, right)) if self.is_user_type(left.node()) => match op {
+                BinOp::Eq | BinOp::Neq => Some(ViraType::Bool),
+                _ => self.method_type(left.node(), op.method()?, std::slice::from_ref(right)),
+            },
+            // Either side may be nil, which has no type of its own.
+            AstNode::Expr(Expr::Binary(_, BinOp::Eq | BinOp::Neq, _)) => Some(ViraType::Bool),
+            AstNode::Expr(Expr::Binary(left, op, right)) => typecheck::binary_type(*op, &self.type_of(left.node())?, &self.type_of(right.node())?).ok().flatten(),
+            AstNode::Expr(Expr::Unary(op, operand)) => typecheck::unary_type(*op, &self.type_of(operand.node())?).ok().flatten(),
+            AstNode::Expr(Expr::IntToFloat(_)) => Some(ViraType::Float),
+            AstNode::Expr(Expr::Index(base, index)) if self.is_user_type(base.node()) => self.method_type(base.node(), "index", std::slice::from_ref(index)),
+            AstNode::Expr(Expr::Index(base, _)) => match self.base_type(base.node())? {
+                ViraType::Array(element) => Some(*element),
+                ViraType::Range => Some(ViraType::Int),
+                _ => None,
+            },
+            AstNode::Expr(Expr::VarRef(name)) => match self.variable_type(name) {
+                Some(typ) => typ.cloned(),
+                None => match self.scope.functions.get(name) {
+                    Some((params, ret)) => Some(ViraType::Function(params.clone(), Box::new(ret.clone()))),
+                    None => match self.scope.data.get(name) {
+                        Some(typ) => Some(typ.clone()),
+                        None => self.scope.consts.get(name).and_then(|&value| literal_type(arena, value)),
+                    },
+                },
+            },
+            AstNode::Expr(Expr::FieldAccess(base, field)) => match (&arena[*base], self.base_type(base.node())) {
+                (_, Some(ViraType::Struct(name))) => self.field(&name, field),
+                // `config.key`, a constant of the config block.
+                (Expr::VarRef(name), None) => self.scope.consts.get(&format!("{}.{}", name, field)).and_then(|&value| literal_type(arena, value)),
+                _ => None,
+            },
+            AstNode::Expr(Expr::SafeAccess(base, field)) => match self.type_of(base.node())? {
+                ViraType::Optional(inner) => match *inner {
+                    ViraType::Struct(name) => match self.field(&name, field)? {
+                        typ @ ViraType::Optional(_) => Some(typ),
+                        typ => Some(ViraType::Optional(Box::new(typ))),
+                    },
+                    _ => None,
+                },
+                _ => None,
+            },
+            AstNode::Expr(Expr::Coalesce(left, right)) if matches!(arena[*right], Expr::Nil) => self.type_of(left.node()),
+            AstNode::Expr(Expr::Coalesce(left, right)) => match self.type_of(right.node()) {
+                fallback @ Some(ViraType::Optional(_)) => fallback,
+                fallback => match self.type_of(left.node()) {
+                    Some(ViraType::Optional(inner)) => Some(*inner),
+                    _ => fallback,
+                },
+            },
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,17 @@
+#![deny(clippy::unwrap_used)]
+
+// The middle end: the typed HIR the backend compiles from, the type checker,
+// the dead code warnings and the escape analysis that read it, and the
+// passes that rewrite a checked tree before it runs: constant folding,
+// monomorphization, whole-program optimization, the linking of a program's
+// modules into one tree and the stripping of tests from builds.
+
+pub mod deadcode;
+pub mod escape;
+pub mod fold;
+pub mod hir;
+pub mod link;
+pub mod mono;
+pub mod strip;
+pub mod typecheck;
+pub mod wpo;
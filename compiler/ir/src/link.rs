@@ -0,0 +1,140 @@
+use std::collections::{HashMap, HashSet};
+
+use vira_syntax::arena::{Arena, NodeId};
+use vira_syntax::ast::{AstNode, Expr, Item, Stmt};
+use vira_syntax::visit::{walk_mut, VisitorMut};
+
+use crate::hir::{self, Hir, SymbolId};
+use crate::typecheck;
+
+// Links the modules of a program into the one tree codegen compiles. The
+// top-level functions, variables, constants and data sections of each
+// imported module are renamed `module::name`, where they are declared and
+// where their module uses them, so that two modules can declare the same
+// name. `module::name` in another module, which parses as an enum
+// constructor, becomes a call of or a reference to that name. The entry
+// module, which comes last, keeps its names, and types are shared by every
+// module, as in the interpreter.
+
+struct Member {
+    public: bool,
+    function: bool,
+}
+
+// The top-level names of an imported module, and the symbols its own uses
+// of its variables resolve to.
+struct Members {
+    names: HashMap<String, Member>,
+    variables: HashSet<SymbolId>,
+}
+
+pub fn link(arena: &mut Arena, modules: Vec<(String, Vec<NodeId>)>) -> Result<Vec<NodeId>, String> {
+    let hirs: Vec<Hir> = modules.iter().map(|(_, nodes)| hir::build(arena, nodes)).collect();
+    let imported = modules.len().saturating_sub(1);
+    let members: HashMap<&str, Members> =
+        modules.iter().zip(&hirs).take(imported).map(|((module, nodes), hir)| (module.as_str(), members(arena, nodes, hir))).collect();
+    for (i, ((module, nodes), hir)) in modules.iter().zip(&hirs).enumerate() {
+        let own = members.get(module.as_str()).filter(|_| i < imported);
+        if own.is_some() {
+            for &node in nodes {
+                let id = arena.item(node);
+                qualify_declaration(arena, id, module);
+            }
+        }
+        let mut linker = Linker { module, own, members: &members, hir, error: None };
+        for &node in nodes {
+            linker.visit_mut(arena, node);
+        }
+        if let Some(error) = linker.error {
+            return Err(error);
+        }
+    }
+    let program: Vec<NodeId> = modules.into_iter().flat_map(|(_, nodes)| nodes).collect();
+    // Calls across modules are checked, and their int operands of float
+    // operators converted, now that their callee is known.
+    typecheck::check(arena, &program)?;
+    Ok(program)
+}
+
+fn members(arena: &Arena, nodes: &[NodeId], hir: &Hir) -> Members {
+    let mut members = Members { names: HashMap::new(), variables: HashSet::new() };
+    for &node in nodes {
+        let public = matches!(arena[node], AstNode::Item(Item::Pub(_)));
+        let id = arena.item(node);
+        let (names, function) = match &arena[id] {
+            AstNode::Item(Item::FuncDecl(name, ..)) => (vec![name.clone()], true),
+            AstNode::Item(Item::ConstDecl(name, ..) | Item::DataDecl(name, ..)) => (vec![name.clone()], false),
+            AstNode::Stmt(Stmt::VarDecl(name, ..)) => (vec![name.clone()], false),
+            AstNode::Stmt(Stmt::TupleDecl(names, ..)) => (names.clone(), false),
+            _ => continue,
+        };
+        for name in names {
+            members.variables.extend(hir.declared(id, &name));
+            members.names.insert(name, Member { public, function });
+        }
+    }
+    members
+}
+
+fn qualify_declaration(arena: &mut Arena, id: NodeId, module: &str) {
+    let qualify = |name: &mut String| *name = format!("{}::{}", module, name);
+    match &mut arena[id] {
+        AstNode::Item(Item::FuncDecl(name, ..) | Item::ConstDecl(name, ..) | Item::DataDecl(name, ..)) => qualify(name),
+        AstNode::Stmt(Stmt::VarDecl(name, ..)) => qualify(name),
+        AstNode::Stmt(Stmt::TupleDecl(names, ..)) => names.iter_mut().for_each(qualify),
+        _ => {}
+    }
+}
+
+struct Linker<'p> {
+    module: &'p str,
+    // The members of the module being linked, unless it is the entry module.
+    own: Option<&'p Members>,
+    members: &'p HashMap<&'p str, Members>,
+    hir: &'p Hir,
+    error: Option<String>,
+}
+
+impl<'p> Linker<'p> {
+    // Whether a use of `name` at `id` is of the module's own top-level
+    // `name`: a variable by the declaration it resolves to, and a function,
+    // constant or data section by a name no variable hides.
+    fn is_own(&self, id: NodeId, name: &str) -> bool {
+        let Some(own) = self.own else {
+            return false;
+        };
+        match self.hir.resolve(id) {
+            Some(symbol) => own.variables.contains(&symbol),
+            None => own.names.contains_key(name),
+        }
+    }
+
+    fn member(&mut self, module: &str, name: &str) -> Option<&'p Member> {
+        let member = self.members.get(module)?.names.get(name)?;
+        if !member.public && module != self.module {
+            self.error = Some(format!("'{}' is private to module '{}'; declare it pub to use it from other modules.", name, module));
+        }
+        Some(member)
+    }
+}
+
+impl VisitorMut for Linker<'_> {
+    fn visit_mut(&mut self, arena: &mut Arena, id: NodeId) {
+        walk_mut(self, arena, id);
+        let linked = match &arena[id] {
+            AstNode::Expr(Expr::VarRef(name)) if self.is_own(id, name) => AstNode::Expr(Expr::VarRef(format!("{}::{}", self.module, name))),
+            AstNode::Expr(Expr::Call(name, args)) if self.is_own(id, name) => AstNode::Expr(Expr::Call(format!("{}::{}", self.module, name), args.clone())),
+            AstNode::Stmt(Stmt::Assign(name, value)) if self.is_own(id, name) => AstNode::Stmt(Stmt::Assign(format!("{}::{}", self.module, name), *value)),
+            AstNode::Expr(Expr::EnumConstructor(module, name, args)) => {
+                let qualified = format!("{}::{}", module, name);
+                match self.member(module, name) {
+                    Some(Member { function: true, .. }) => AstNode::Expr(Expr::Call(qualified, args.clone())),
+                    Some(_) if args.is_empty() => AstNode::Expr(Expr::VarRef(qualified)),
+                    _ => return,
+                }
+            }
+            _ => return,
+        };
+        arena[id] = linked;
+    }
+}
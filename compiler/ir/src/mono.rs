@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+
+use vira_rt::builtins::{self, Receiver};
+use vira_rt::numerics;
+use vira_syntax::arena::{Arena, ExprId, NodeId};
+use vira_syntax::ast::{AstNode, BinOp, Expr, Item, Stmt, UnaryOp, ViraType};
+use vira_syntax::visit::{self, walk_mut, VisitorMut};
+
+// Monomorphization. Runs before codegen and replaces every generic function
+// with one specialized copy per distinct list of type arguments it is called
+// with; calls are renamed to the copy, e.g. `id<int>`. Type arguments are
+// inferred from the static types of the call's arguments. The interpreter is
+// dynamically typed and runs generic functions as written.
+//
+// Copies are made as calls are found, calls inside a copy included, and
+// reused for every later call with the same type arguments, so each distinct
+// instantiation is compiled once. They take the place of the generic
+// declaration in the output.
+
+const MAX_INSTANTIATION_DEPTH: usize = 64;
+
+struct Generic {
+    type_params: Vec<String>,
+    params: Vec<(String, ViraType)>,
+    ret: ViraType,
+    body: NodeId,
+}
+
+struct Monomorphizer<'a> {
+    arena: &'a mut Arena,
+    generics: HashMap<String, Generic>,
+    structs: HashMap<String, Vec<(String, ViraType)>>,
+    // Return types of methods, keyed by type and method name.
+    methods: HashMap<(String, String), ViraType>,
+    // Return types of concrete functions, including specialized copies.
+    returns: HashMap<String, ViraType>,
+    // Specialized copies of each generic function, in instantiation order.
+    copies: HashMap<String, Vec<NodeId>>,
+    depth: usize,
+}
+
+pub fn monomorphize(arena: &mut Arena, program: Vec<NodeId>) -> Result<Vec<NodeId>, String> {
+    let mut mono = Monomorphizer {
+        arena,
+        generics: HashMap::new(),
+        structs: HashMap::new(),
+        methods: HashMap::new(),
+        returns: HashMap::new(),
+        copies: HashMap::new(),
+        depth: 0,
+    };
+    let mut rest = Vec::new();
+    for id in program {
+        // Visibility and attributes have been checked by the time the program
+        // is compiled.
+        let id = mono.arena.item(id);
+        match &mono.arena[id] {
+            AstNode::Item(Item::FuncDecl(name, type_params, params, ret, body)) if !type_params.is_empty() => {
+                let name = name.clone();
+                let generic = Generic { type_params: type_params.clone(), params: params.clone(), ret: ret.clone(), body: *body };
+                mono.generics.insert(name.clone(), generic);
+                // Keeps the declaration's position for its specialized copies.
+                let body = mono.arena.alloc(Expr::Block(Vec::new()));
+                rest.push(mono.arena.alloc(Item::FuncDecl(name, Vec::new(), Vec::new(), ViraType::Int, body)));
+                continue;
+            }
+            AstNode::Item(Item::FuncDecl(name, _, _, ret, _)) => {
+                mono.returns.insert(name.clone(), ret.clone());
+            }
+            AstNode::Item(Item::StructDecl(name, fields)) => {
+                mono.structs.insert(name.clone(), fields.clone());
+            }
+            AstNode::Item(Item::ImplDecl(_, typ, methods)) => {
+                for &method in methods {
+                    if let Item::FuncDecl(name, _, _, ret, _) = &mono.arena[method] {
+                        mono.methods.insert((typ.clone(), name.clone()), ret.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+        rest.push(id);
+    }
+
+    let mut env = HashMap::new();
+    for &id in &rest {
+        if !matches!(&mono.arena[id], AstNode::Item(Item::FuncDecl(name, ..)) if mono.generics.contains_key(name)) {
+            mono.rewrite(id, &mut env)?;
+        }
+    }
+    let mut output = Vec::new();
+    for id in rest {
+        match &mono.arena[id] {
+            AstNode::Item(Item::FuncDecl(name, ..)) if mono.generics.contains_key(name) => {
+                output.extend(mono.copies.remove(name).unwrap_or_default());
+            }
+            _ => output.push(id),
+        }
+    }
+    Ok(output)
+}
+
+fn substitute(typ: &ViraType, bindings: &HashMap<String, ViraType>) -> ViraType {
+    match typ {
+        ViraType::TypeVar(name) => bindings.get(name).cloned().unwrap_or_else(|| typ.clone()),
+        ViraType::Array(inner) => ViraType::Array(Box::new(substitute(inner, bindings))),
+        ViraType::Optional(inner) => ViraType::Optional(Box::new(substitute(inner, bindings))),
+        ViraType::Function(params, ret) => ViraType::Function(
+            params.iter().map(|p| substitute(p, bindings)).collect(),
+            Box::new(substitute(ret, bindings)),
+        ),
+        ViraType::Tuple(elements) => ViraType::Tuple(elements.iter().map(|e| substitute(e, bindings)).collect()),
+        ViraType::Map(key, value) => ViraType::Map(Box::new(substitute(key, bindings)), Box::new(substitute(value, bindings))),
+        _ => typ.clone(),
+    }
+}
+
+// Replaces type parameters in the types written inside a copied body.
+struct Substitution<'b>(&'b HashMap<String, ViraType>);
+
+impl VisitorMut for Substitution<'_> {
+    fn visit_mut(&mut self, arena: &mut Arena, id: NodeId) {
+        match &mut arena[id] {
+            AstNode::Stmt(Stmt::VarDecl(_, Some(typ), _) | Stmt::TupleDecl(_, Some(typ), _)) => *typ = substitute(typ, self.0),
+            AstNode::Expr(Expr::Lambda(params, ret, _)) => {
+                for (_, typ) in params.iter_mut() {
+                    *typ = substitute(typ, self.0);
+                }
+                *ret = substitute(ret, self.0);
+            }
+            _ => {}
+        }
+        walk_mut(self, arena, id);
+    }
+}
+
+fn unify(param: &ViraType, arg: &ViraType, bindings: &mut HashMap<String, ViraType>) -> Result<(), String> {
+    match (param, arg) {
+        (ViraType::TypeVar(name), _) => match bindings.get(name) {
+            Some(bound) if bound != arg => Err(format!("Conflicting types for type parameter '{}': {} and {}.", name, bound, arg)),
+            _ => {
+                bindings.insert(name.clone(), arg.clone());
+                Ok(())
+            }
+        },
+        (ViraType::Array(p), ViraType::Array(a)) | (ViraType::Optional(p), ViraType::Optional(a)) => unify(p, a, bindings),
+        // A plain value passes for an optional one.
+        (ViraType::Optional(p), _) => unify(p, arg, bindings),
+        (ViraType::Map(pk, pv), ViraType::Map(ak, av)) => {
+            unify(pk, ak, bindings)?;
+            unify(pv, av, bindings)
+        }
+        (ViraType::Function(ps, pr), ViraType::Function(args, ar)) if ps.len() == args.len() => {
+            for (p, a) in ps.iter().zip(args) {
+                unify(p, a, bindings)?;
+            }
+            unify(pr, ar, bindings)
+        }
+        (ViraType::Tuple(ps), ViraType::Tuple(args)) if ps.len() == args.len() => {
+            for (p, a) in ps.iter().zip(args) {
+                unify(p, a, bindings)?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+impl Monomorphizer<'_> {
+    fn rewrite(&mut self, id: NodeId, env: &mut HashMap<String, ViraType>) -> Result<(), String> {
+        match self.arena[id].clone() {
+            AstNode::Stmt(Stmt::VarDecl(name, typ, init)) => {
+                self.rewrite(init.node(), env)?;
+                match self.infer(init.node(), env).or(typ) {
+                    Some(typ) => env.insert(name, typ),
+                    None => env.remove(&name),
+                };
+                return Ok(());
+            }
+            AstNode::Stmt(Stmt::TupleDecl(names, typ, init)) => {
+                self.rewrite(init.node(), env)?;
+                let elements = match self.infer(init.node(), env).or(typ) {
+                    Some(ViraType::Tuple(elements)) => elements,
+                    _ => Vec::new(),
+                };
+                for (i, name) in names.iter().enumerate() {
+                    env.insert(name.clone(), elements.get(i).cloned().unwrap_or(ViraType::Int));
+                }
+                return Ok(());
+            }
+            AstNode::Item(Item::FuncDecl(_, _, params, _, body)) => {
+                let mut inner = env.clone();
+                inner.extend(params);
+                return self.rewrite(body, &mut inner);
+            }
+            AstNode::Expr(Expr::Lambda(params, _, body)) => {
+                let mut inner = env.clone();
+                inner.extend(params);
+                return self.rewrite(body.node(), &mut inner);
+            }
+            _ => {}
+        }
+
+        for child in visit::children(self.arena, id) {
+            self.rewrite(child, env)?;
+        }
+
+        if let AstNode::Expr(Expr::Call(name, args)) = self.arena[id].clone() {
+            if self.generics.contains_key(name.as_str()) {
+                let mangled = self.instantiate(&name, &args, env)?;
+                self.arena[id] = AstNode::Expr(Expr::Call(mangled, args));
+            }
+        }
+        Ok(())
+    }
+
+    fn instantiate(&mut self, name: &str, args: &[ExprId], env: &HashMap<String, ViraType>) -> Result<String, String> {
+        let generic = self.generics.get(name).ok_or(format!("Undefined function '{}'.", name))?;
+        if generic.params.len() != args.len() {
+            return Err(format!("Function '{}' expects {} argument(s), got {}.", name, generic.params.len(), args.len()));
+        }
+        let mut bindings = HashMap::new();
+        for ((_, param), arg) in generic.params.iter().zip(args) {
+            if let Some(arg_type) = self.infer(arg.node(), env) {
+                unify(param, &arg_type, &mut bindings)?;
+            }
+        }
+        let mut type_args = Vec::new();
+        for type_param in &generic.type_params {
+            let typ = bindings
+                .get(type_param)
+                .ok_or(format!("Cannot infer type parameter '{}' of '{}'.", type_param, name))?;
+            type_args.push(typ.to_string());
+        }
+
+        let mangled = format!("{}<{}>", name, type_args.join(", "));
+        if self.returns.contains_key(&mangled) {
+            return Ok(mangled);
+        }
+        if self.depth >= MAX_INSTANTIATION_DEPTH {
+            return Err(format!("Instantiating '{}' recurses without end.", mangled));
+        }
+        let params: Vec<(String, ViraType)> = generic.params.iter().map(|(p, t)| (p.clone(), substitute(t, &bindings))).collect();
+        let ret = substitute(&generic.ret, &bindings);
+        let body = self.arena.deep_copy(generic.body);
+        Substitution(&bindings).visit_mut(self.arena, body);
+
+        // Registered before the body is rewritten so recursive calls resolve
+        // to this copy.
+        self.returns.insert(mangled.clone(), ret.clone());
+        let mut inner: HashMap<String, ViraType> = params.iter().cloned().collect();
+        self.depth += 1;
+        let rewritten = self.rewrite(body, &mut inner);
+        self.depth -= 1;
+        rewritten?;
+        let copy = self.arena.alloc(Item::FuncDecl(mangled.clone(), Vec::new(), params, ret, body));
+        self.copies.entry(name.to_string()).or_default().push(copy);
+        Ok(mangled)
+    }
+
+    fn field(&self, struct_name: &str, field: &str) -> Option<ViraType> {
+        self.structs.get(struct_name)?.iter().find(|(name, _)| name == field).map(|(_, typ)| typ.clone())
+    }
+
+    fn infer(&self, id: NodeId, env: &HashMap<String, ViraType>) -> Option<ViraType> {
+        match &self.arena[id] {
+            AstNode::Expr(Expr::Literal(_)) => Some(ViraType::Int),
+            AstNode::Expr(Expr::FloatLiteral(_)) => Some(ViraType::Float),
+            AstNode::Expr(Expr::BoolLiteral(_)) => Some(ViraType::Bool),
+            AstNode::Expr(Expr::StringLiteral(_)) => Some(ViraType::String),
+            AstNode::Expr(Expr::ArrayLiteral(items)) => Some(ViraType::Array(Box::new(self.infer(items.first()?.node(), env)?))),
+            AstNode::Expr(Expr::MapLiteral(entries)) => {
+                let (key, value) = entries.first()?;
+                Some(ViraType::Map(Box::new(self.infer(key.node(), env)?), Box::new(self.infer(value.node(), env)?)))
+            }
+            AstNode::Expr(Expr::Tuple(items)) => items.iter().map(|&item| self.infer(item.node(), env)).collect::<Option<_>>().map(ViraType::Tuple),
+            AstNode::Expr(Expr::TupleIndex(base, index)) => match self.infer(base.node(), env)? {
+                ViraType::Tuple(elements) => elements.get(*index).cloned(),
+                _ => None,
+            },
+            AstNode::Expr(Expr::StructLiteral(name, _)) => Some(ViraType::Struct(name.clone())),
+            AstNode::Expr(Expr::EnumConstructor(name, member, _)) if name == numerics::MODULE => numerics::return_type(&format!("{}::{}", name, member)),
+            AstNode::Expr(Expr::EnumConstructor(name, _, _)) => Some(ViraType::Enum(name.clone())),
+            AstNode::Expr(Expr::Range(_, _)) => Some(ViraType::Range),
+            AstNode::Expr(Expr::VarRef(name)) => env.get(name).cloned(),
+            AstNode::Expr(Expr::Call(name, args)) => env
+                .get(name)
+                .and_then(|typ| match typ {
+                    ViraType::Function(_, ret) => Some((**ret).clone()),
+                    _ => None,
+                })
+                .or_else(|| self.returns.get(name).cloned())
+                .or_else(|| {
+                    let args: Vec<_> = args.iter().map(|&arg| self.infer(arg.node(), env)).collect();
+                    builtins::return_type(name, &args)
+                }),
+            AstNode::Expr(Expr::Apply(callee, _)) => match self.infer(callee.node(), env)? {
+                ViraType::Function(_, ret) => Some(*ret),
+                _ => None,
+            },
+            AstNode::Expr(Expr::Lambda(params, ret, _)) => {
+                Some(ViraType::Function(params.iter().map(|(_, t)| t.clone()).collect(), Box::new(ret.clone())))
+            }
+            AstNode::Expr(Expr::Binary(left, op, _)) => match op {
+                BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod => self.infer(left.node(), env),
+                _ => Some(ViraType::Bool),
+            },
+            AstNode::Expr(Expr::Unary(UnaryOp::Neg, operand)) => self.infer(operand.node(), env),
+            AstNode::Expr(Expr::Unary(UnaryOp::Not, _)) => Some(ViraType::Bool),
+            AstNode::Expr(Expr::IntToFloat(_)) => Some(ViraType::Float),
+            AstNode::Expr(Expr::Index(base, index)) => match (self.infer(base.node(), env)?, self.infer(index.node(), env)) {
+                (typ, Some(ViraType::Range)) => Some(typ),
+                (ViraType::Array(inner), _) => Some(*inner),
+                (ViraType::Range, _) => Some(ViraType::Int),
+                (ViraType::String, _) => Some(ViraType::String),
+                _ => None,
+            },
+            AstNode::Expr(Expr::FieldAccess(base, field)) => match self.infer(base.node(), env)? {
+                ViraType::Struct(name) => self.field(&name, field),
+                _ => None,
+            },
+            AstNode::Expr(Expr::SafeAccess(base, field)) => match self.infer(base.node(), env)? {
+                ViraType::Optional(inner) => match *inner {
+                    ViraType::Struct(name) => match self.field(&name, field)? {
+                        typ @ ViraType::Optional(_) => Some(typ),
+                        typ => Some(ViraType::Optional(Box::new(typ))),
+                    },
+                    _ => None,
+                },
+                _ => None,
+            },
+            AstNode::Expr(Expr::MethodCall(receiver, method, args)) => match self.infer(receiver.node(), env)? {
+                ViraType::Struct(name) | ViraType::Enum(name) => self.methods.get(&(name, method.clone())).cloned(),
+                typ => {
+                    let builtin = builtins::method(Receiver::of_type(&typ)?, method)?;
+                    let args: Vec<_> = std::iter::once(Some(typ)).chain(args.iter().map(|&arg| self.infer(arg.node(), env))).collect();
+                    builtins::return_type(builtin.builtin, &args)
+                }
+            },
+            AstNode::Expr(Expr::If(_, then, Some(_))) => self.infer(*then, env),
+            AstNode::Expr(Expr::Match(_, arms)) => arms.iter().find_map(|&(_, body)| self.infer(body.node(), env)),
+            // The value of a block sees the variables it declares.
+            AstNode::Expr(Expr::Block(stmts)) => {
+                let (&last, rest) = stmts.split_last()?;
+                let mut inner = env.clone();
+                for &stmt in rest {
+                    if let AstNode::Stmt(Stmt::VarDecl(name, typ, init)) = &self.arena[stmt] {
+                        match self.infer(init.node(), &inner).or_else(|| typ.clone()) {
+                            Some(typ) => inner.insert(name.clone(), typ),
+                            None => inner.remove(name),
+                        };
+                    }
+                }
+                self.infer(last, &inner)
+            }
+            AstNode::Expr(Expr::Coalesce(left, right)) if matches!(self.arena[*right], Expr::Nil) => self.infer(left.node(), env),
+            AstNode::Expr(Expr::Coalesce(left, right)) => match self.infer(right.node(), env) {
+                fallback @ Some(ViraType::Optional(_)) => fallback,
+                fallback => match self.infer(left.node(), env) {
+                    Some(ViraType::Optional(inner)) => Some(*inner),
+                    _ => fallback,
+                },
+            },
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::{HashMap, HashSet};
+
+use vira_syntax::arena::{Arena, NodeId};
+use vira_syntax::ast::{self, AstNode, Expr, Item, Program};
+use vira_syntax::visit::{walk, Visitor};
+
+// Dead-code stripping of tests for `build`: the `@test` functions of every
+// module are left out, and so is each function that only tests reach. What
+// the rest of the program reaches stays, even a test it calls. Functions are
+// matched by name across modules, so a name that any kept code mentions keeps
+// every function of that name.
+pub fn strip_tests(program: &mut Program) {
+    let Program { arena, modules } = program;
+    let mut functions: HashMap<&str, Vec<NodeId>> = HashMap::new();
+    let mut tests = Vec::new();
+    for &item in modules.iter().flat_map(|(_, items)| items) {
+        if let AstNode::Item(Item::FuncDecl(name, ..)) = &arena[arena.item(item)] {
+            functions.entry(name.as_str()).or_default().push(item);
+            if ast::is_test(arena, item) {
+                tests.push(item);
+            }
+        }
+    }
+    if tests.is_empty() {
+        return;
+    }
+    let for_tests = reachable(arena, &functions, tests);
+    let roots = modules
+        .iter()
+        .flat_map(|(_, items)| items)
+        .copied()
+        .filter(|&item| !matches!(&arena[arena.item(item)], AstNode::Item(Item::FuncDecl(name, ..)) if for_tests.contains(name.as_str())))
+        .collect();
+    let live = reachable(arena, &functions, roots);
+    let stripped: HashSet<&str> = for_tests.difference(&live).copied().collect();
+    for (_, items) in modules.iter_mut() {
+        items.retain(|&item| !matches!(&arena[arena.item(item)], AstNode::Item(Item::FuncDecl(name, ..)) if stripped.contains(name.as_str())));
+    }
+}
+
+// The names of the functions declared at the top level that `roots` reach,
+// their own names included.
+fn reachable<'a>(arena: &'a Arena, functions: &HashMap<&'a str, Vec<NodeId>>, mut pending: Vec<NodeId>) -> HashSet<&'a str> {
+    let mut reached = HashSet::new();
+    while let Some(item) = pending.pop() {
+        let mut names = Names(Vec::new());
+        names.visit(arena, item);
+        if let AstNode::Item(Item::FuncDecl(name, ..)) = &arena[arena.item(item)] {
+            names.0.push(name);
+        }
+        for name in names.0 {
+            if let Some((&name, declared)) = functions.get_key_value(name) {
+                if reached.insert(name) {
+                    pending.extend(declared);
+                }
+            }
+        }
+    }
+    reached
+}
+
+// Every name a subtree refers to as a variable or calls.
+struct Names<'a>(Vec<&'a str>);
+
+impl<'a> Visitor<'a> for Names<'a> {
+    fn visit(&mut self, arena: &'a Arena, id: NodeId) {
+        if let AstNode::Expr(Expr::VarRef(name) | Expr::Call(name, _)) = &arena[id] {
+            self.0.push(name);
+        }
+        walk(self, arena, id);
+    }
+}
@@ -0,0 +1,662 @@
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+use vira_syntax::arena::{Arena, ExprId, NodeId, Position};
+use vira_syntax::ast::{AstNode, BinOp, Expr, Item, Pattern, Stmt, UnaryOp, ViraType};
+use vira_syntax::printer::{self, bin_op};
+use vira_syntax::visit::{walk, Visitor};
+
+use crate::hir::{self, Hir, SymbolId};
+
+// Static type checking, run on every module before it runs or compiles, so
+// a type error is reported up front rather than as a failure of whichever
+// engine reaches it first. The types are those the HIR infers. The checker
+// only reports what is certain: an operand whose type is unknown or a type
+// variable is not checked, and neither is one of a user type, whose
+// operators are its methods. Every error is reported, innermost first, each
+// with the position of the node at fault.
+//
+// A function has to produce a value of its return type on every path: each
+// ends in a `return`, a `throw` or a trailing value, and a `while true` loop,
+// which only a `return` or `throw` leaves, ends none. `return` outside a
+// function is an error. The type of what is returned is only checked for
+// named functions: a lambda without `-> T` reads as returning int, so its
+// return type cannot be told from an inferred one.
+//
+// A call of a named function has to pass as many arguments as it has
+// parameters, each of the parameter's type. Every function of the module is
+// known before any call is checked, so calls before the declaration and
+// recursive ones are checked too. A name declared as a function more than
+// once, or bound as a variable anywhere, is left alone, as its calls may
+// not reach the declaration.
+//
+// A value of an optional type may be nil, so it cannot have its fields,
+// elements or methods read, or be an operand of anything but `==` and `!=`,
+// until it is known not to be: `?.` and `??` handle the nil, or a check of
+// a variable against nil narrows it to the type inside. The narrowing
+// follows the flow of the function. `if x != nil` narrows `x` in its then
+// branch, `if x == nil` in its else branch and, when that branch cannot
+// fall through, after the `if`, and `&&`, `||` and `!` combine checks as
+// they would be evaluated. A variable is narrowed after it is given a value
+// that is not optional and no longer narrowed after any other assignment; a
+// loop or a `catch` starts without the narrowing of the variables it
+// assigns, as it may run after them. Only variables and their fields
+// narrow, fields not being assignable, so any other value has to be bound
+// to a variable to be checked.
+//
+// Every variable has a value from its declaration, as a `let` needs an
+// initializer, and a struct literal has to give each field of the struct,
+// once.
+//
+// An int and a float meet as floats: an arithmetic or comparison operator
+// with one of each converts the int, and the checker makes that explicit
+// with an `IntToFloat` node around it, for the engines to follow. A float
+// never becomes an int on its own.
+//
+// The HIR the checker builds is returned for the passes that follow, with a
+// type for each conversion it made, so they need not build it again.
+
+pub fn check(arena: &mut Arena, program: &[NodeId]) -> Result<Hir, String> {
+    let mut checker = TypeChecker::new(arena, program);
+    for &id in program {
+        checker.visit(arena, id);
+    }
+    if !checker.errors.is_empty() {
+        return Err(checker.errors.join("\n"));
+    }
+    for (binary, operand) in checker.conversions {
+        let converted = arena.alloc_expr(Expr::IntToFloat(operand));
+        if let Some(span) = arena.span(operand.node()) {
+            arena.set_span(converted.node(), span);
+        }
+        if let AstNode::Expr(Expr::Binary(left, _, right)) = &mut arena[binary] {
+            *(if *left == operand { left } else { right }) = converted;
+        }
+        checker.hir.converted(converted);
+    }
+    Ok(checker.hir)
+}
+
+pub struct TypeChecker {
+    hir: Hir,
+    traits: HashSet<String>,
+    // The position of the innermost node being checked that has one; the
+    // nodes passes make up have none.
+    at: Position,
+    // The function being checked, innermost last: its name, or None for a
+    // lambda, and its return type.
+    functions: Vec<(Option<String>, ViraType)>,
+    // The parameters of each named function that calls are checked against.
+    signatures: HashMap<String, Vec<(String, ViraType)>>,
+    // The fields of each struct, to check literals against.
+    structs: HashMap<String, Vec<String>>,
+    // The optional variables and fields known not to be nil where the
+    // checker is.
+    narrowed: HashSet<Place>,
+    // Binary operators and their int operand to convert to float.
+    conversions: Vec<(NodeId, ExprId)>,
+    errors: Vec<String>,
+}
+
+impl TypeChecker {
+    pub fn new(arena: &Arena, program: &[NodeId]) -> Self {
+        let traits = program
+            .iter()
+            .filter_map(|&id| match &arena[arena.item(id)] {
+                AstNode::Item(Item::TraitDecl(name, _)) => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+        let mut signatures = Signatures::default();
+        for &id in program {
+            signatures.visit(arena, id);
+        }
+        let structs = signatures.structs.into_iter().filter_map(|(name, fields)| Some((name.to_string(), fields?.iter().map(|(field, _)| field.clone()).collect()))).collect();
+        let signatures = signatures
+            .functions
+            .into_iter()
+            .filter(|(name, _)| !signatures.variables.contains(name))
+            .filter_map(|(name, params)| Some((name.to_string(), params?.to_vec())))
+            .collect();
+        TypeChecker {
+            hir: hir::build(arena, program),
+            traits,
+            at: (1, 1),
+            functions: Vec::new(),
+            signatures,
+            structs,
+            narrowed: HashSet::new(),
+            conversions: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, message: String) {
+        self.errors.push(format!("{}:{}: {}", self.at.0, self.at.1, message));
+    }
+
+    // The type of `id` where the checker is: that of the HIR, but without
+    // the optional for a narrowed variable or field.
+    fn type_of(&self, arena: &Arena, id: NodeId) -> Option<&ViraType> {
+        match self.hir.type_of(id)? {
+            ViraType::Optional(inner) if self.place(arena, id).is_some_and(|place| self.narrowed.contains(&place)) => Some(inner),
+            typ => Some(typ),
+        }
+    }
+
+    // The variable, and the fields of it, that `id` reads.
+    fn place(&self, arena: &Arena, id: NodeId) -> Option<Place> {
+        match &arena[id] {
+            AstNode::Expr(Expr::VarRef(_)) => Some((self.hir.resolve(id)?, Vec::new())),
+            AstNode::Expr(Expr::FieldAccess(base, field)) => {
+                let (symbol, mut fields) = self.place(arena, base.node())?;
+                fields.push(field.clone());
+                Some((symbol, fields))
+            }
+            _ => None,
+        }
+    }
+
+    // Reports `id` when it may be nil where a value is needed.
+    fn non_nil(&mut self, arena: &Arena, id: NodeId) {
+        let Some(ViraType::Optional(_)) = self.type_of(arena, id) else { return };
+        match self.place(arena, id).map(|_| printer::print(arena, id)) {
+            Some(place) => self.error(format!("'{}' may be nil here; check it against nil first, or use '?.' or '??'.", place)),
+            None => {
+                let typ = self.hir.type_of(id).map(ToString::to_string).unwrap_or_default();
+                self.error(format!("This {} may be nil; bind it to a variable and check that against nil, or use '?.' or '??'.", typ))
+            }
+        }
+    }
+
+    // The optional variables and fields `condition` shows not to be nil when
+    // it is true, and when it is false. `&&` and `||` come lowered to `if`s.
+    fn narrowing(&self, arena: &Arena, condition: NodeId) -> (Narrowing, Narrowing) {
+        let checked = |operand: ExprId, other: ExprId| match (&arena[other], self.hir.type_of(operand.node())) {
+            (Expr::Nil, Some(ViraType::Optional(_))) => self.place(arena, operand.node()),
+            _ => None,
+        };
+        match &arena[condition] {
+            AstNode::Expr(Expr::Binary(left, op @ (BinOp::Eq | BinOp::Neq), right)) => {
+                let narrowed = Some(checked(*left, *right).or_else(|| checked(*right, *left)).into_iter().collect());
+                if *op == BinOp::Neq {
+                    (narrowed, Some(HashSet::new()))
+                } else {
+                    (Some(HashSet::new()), narrowed)
+                }
+            }
+            AstNode::Expr(Expr::If(condition, then, Some(otherwise))) => {
+                let (when_true, when_false) = self.narrowing(arena, condition.node());
+                let (then_true, then_false) = self.narrowing(arena, *then);
+                let (otherwise_true, otherwise_false) = self.narrowing(arena, *otherwise);
+                (
+                    intersection(union(when_true.clone(), then_true), union(when_false.clone(), otherwise_true)),
+                    intersection(union(when_true, then_false), union(when_false, otherwise_false)),
+                )
+            }
+            AstNode::Expr(Expr::Block(stmts)) if stmts.len() == 1 => self.narrowing(arena, stmts[0]),
+            AstNode::Expr(Expr::Unary(UnaryOp::Not, operand)) => {
+                let (when_true, when_false) = self.narrowing(arena, operand.node());
+                (when_false, when_true)
+            }
+            AstNode::Expr(Expr::BoolLiteral(value)) => {
+                if *value {
+                    (Some(HashSet::new()), None)
+                } else {
+                    (None, Some(HashSet::new()))
+                }
+            }
+            _ => (Some(HashSet::new()), Some(HashSet::new())),
+        }
+    }
+
+    // Leaves out the narrowing of the variables assigned in `id`.
+    fn forget_assigned(&mut self, arena: &Arena, id: NodeId) {
+        let mut assigned = Assigned { hir: &self.hir, symbols: HashSet::new() };
+        assigned.visit(arena, id);
+        let assigned = assigned.symbols;
+        self.narrowed.retain(|(symbol, _)| !assigned.contains(symbol));
+    }
+
+    // Narrows `symbol` when `value` is not optional, and no longer its
+    // fields.
+    fn assign(&mut self, arena: &Arena, symbol: Option<SymbolId>, value: NodeId) {
+        let Some(symbol) = symbol else { return };
+        let narrowed = !matches!(self.type_of(arena, value), None | Some(ViraType::Optional(_))) && !matches!(arena[value], AstNode::Expr(Expr::Nil));
+        self.narrowed.retain(|(assigned, _)| *assigned != symbol);
+        if narrowed {
+            self.narrowed.insert((symbol, Vec::new()));
+        }
+    }
+
+    // Visits the children of `id`, following the flow of control for the
+    // narrowing.
+    fn flow(&mut self, arena: &Arena, id: NodeId) {
+        match &arena[id] {
+            AstNode::Expr(Expr::If(condition, then, otherwise)) => {
+                self.visit(arena, condition.node());
+                let (when_true, when_false) = self.narrowing(arena, condition.node());
+                let before = self.narrowed.clone();
+                self.narrowed.extend(when_true.unwrap_or_default());
+                self.visit(arena, *then);
+                let after_then = mem::replace(&mut self.narrowed, before);
+                self.narrowed.extend(when_false.unwrap_or_default());
+                if let Some(otherwise) = otherwise {
+                    self.visit(arena, *otherwise);
+                }
+                if otherwise.is_some_and(|otherwise| diverges(arena, otherwise)) {
+                    self.narrowed = after_then;
+                } else if !diverges(arena, *then) {
+                    self.narrowed.retain(|place| after_then.contains(place));
+                }
+            }
+            AstNode::Expr(Expr::Match(scrutinee, arms)) => {
+                self.visit(arena, scrutinee.node());
+                let before = self.narrowed.clone();
+                let mut after: Option<HashSet<Place>> = None;
+                for &(_, body) in arms {
+                    self.narrowed = before.clone();
+                    self.visit(arena, body.node());
+                    if !diverges(arena, body.node()) {
+                        let narrowed = mem::take(&mut self.narrowed);
+                        after = Some(match after {
+                            Some(after) => &after & &narrowed,
+                            None => narrowed,
+                        });
+                    }
+                }
+                self.narrowed = after.unwrap_or(before);
+            }
+            AstNode::Stmt(Stmt::While(condition, body)) => {
+                self.forget_assigned(arena, id);
+                self.visit(arena, condition.node());
+                let (when_true, when_false) = self.narrowing(arena, condition.node());
+                let before = self.narrowed.clone();
+                self.narrowed.extend(when_true.unwrap_or_default());
+                self.visit(arena, *body);
+                self.narrowed = before;
+                self.narrowed.extend(when_false.unwrap_or_default());
+            }
+            AstNode::Stmt(Stmt::Try(body, _, handler)) => {
+                let before = self.narrowed.clone();
+                self.visit(arena, body.node());
+                let after_body = mem::replace(&mut self.narrowed, before);
+                self.forget_assigned(arena, body.node());
+                self.visit(arena, handler.node());
+                if diverges(arena, handler.node()) {
+                    self.narrowed = after_body;
+                } else if !diverges(arena, body.node()) {
+                    self.narrowed.retain(|place| after_body.contains(place));
+                }
+            }
+            AstNode::Stmt(Stmt::Assign(_, value)) => {
+                self.visit(arena, value.node());
+                self.assign(arena, self.hir.resolve(id), value.node());
+            }
+            AstNode::Stmt(Stmt::VarDecl(name, _, init)) => {
+                self.visit(arena, init.node());
+                let symbol = self.hir.declared(id, name);
+                if let Some(ViraType::Optional(_)) = symbol.and_then(|symbol| self.hir.symbol(symbol).typ.as_ref()) {
+                    self.assign(arena, symbol, init.node());
+                }
+            }
+            // A function body runs later, when no narrowing is known.
+            AstNode::Item(Item::FuncDecl(..)) | AstNode::Expr(Expr::Lambda(..)) => {
+                let before = mem::take(&mut self.narrowed);
+                walk(self, arena, id);
+                self.narrowed = before;
+            }
+            _ => walk(self, arena, id),
+        }
+    }
+
+    // A let takes the type of its initializer unless it is annotated, in
+    // which case the initializer has to fit the annotation.
+    fn var_decl(&mut self, arena: &Arena, name: &str, declared: Option<&ViraType>, init: NodeId) {
+        let is_nil = matches!(arena[init], AstNode::Expr(Expr::Nil));
+        let declared = match declared {
+            None if is_nil => return self.error(format!("Cannot infer the type of '{}' from nil; declare it as an optional type.", name)),
+            None => return,
+            Some(ViraType::Optional(_)) if is_nil => return,
+            Some(declared) if is_nil => return self.error(format!("Cannot set '{}' of type {} to nil; declare it as {}?.", name, declared, declared)),
+            Some(declared) => declared,
+        };
+        let Some(actual) = self.type_of(arena, init) else { return };
+        if !self.assignable(declared, actual) {
+            let message = format!("Variable '{}' is declared as {} but its initializer is {}.", name, declared, actual);
+            self.error(message);
+        }
+    }
+
+    // How the function being checked is named in errors.
+    fn function_name(&self) -> String {
+        match self.functions.last() {
+            Some((Some(name), _)) => format!("Function '{}'", name),
+            _ => "The lambda".to_string(),
+        }
+    }
+
+    fn return_stmt(&mut self, arena: &Arena, value: Option<ExprId>) {
+        let Some((_, ret)) = self.functions.last() else {
+            return self.error("'return' outside a function.".to_string());
+        };
+        let Some(value) = value else {
+            return self.error(format!("{} must return a value of type {}.", self.function_name(), ret));
+        };
+        if self.functions.last().is_some_and(|(name, _)| name.is_none()) {
+            return;
+        }
+        if let Some(actual) = self.type_of(arena, value.node()) {
+            if !self.assignable(ret, actual) {
+                self.error(format!("{} returns {} but this returns {}.", self.function_name(), ret, actual));
+            }
+        }
+    }
+
+    // Checks the body of the function on top of `functions` once its
+    // statements have been.
+    fn body(&mut self, arena: &Arena, body: NodeId) {
+        let Some((name, ret)) = self.functions.last() else { return };
+        if !ends_in_value(arena, body) {
+            return self.error(format!("{} does not return a value on every path; end each one in a return of type {}.", self.function_name(), ret));
+        }
+        if name.is_none() {
+            return;
+        }
+        let last = match &arena[body] {
+            AstNode::Expr(Expr::Block(stmts)) => stmts.last().copied(),
+            _ => Some(body),
+        };
+        if let Some(actual) = last.filter(|&last| arena[last].is_expression()).and_then(|last| self.type_of(arena, last)) {
+            if !self.assignable(ret, actual) {
+                self.error(format!("{} returns {} but its body ends in {}.", self.function_name(), ret, actual));
+            }
+        }
+    }
+
+    fn call(&mut self, arena: &Arena, name: &str, args: &[ExprId]) {
+        let Some(params) = self.signatures.get(name) else { return };
+        if params.len() != args.len() {
+            return self.error(format!("Function '{}' expects {} argument(s), got {}.", name, params.len(), args.len()));
+        }
+        let mismatches: Vec<String> = params
+            .iter()
+            .zip(args)
+            .enumerate()
+            .filter_map(|(i, ((param, declared), arg))| {
+                let actual = self.type_of(arena, arg.node())?;
+                (!self.assignable(declared, actual)).then(|| format!("Argument {} of '{}' is {} but parameter '{}' is {}.", i + 1, name, actual, param, declared))
+            })
+            .collect();
+        for mismatch in mismatches {
+            self.error(mismatch);
+        }
+    }
+
+    fn struct_literal(&mut self, name: &str, fields: &[(String, ExprId)]) {
+        let Some(declared) = self.structs.get(name) else { return };
+        let mut errors = Vec::new();
+        for (i, (field, _)) in fields.iter().enumerate() {
+            if !declared.contains(field) {
+                errors.push(format!("Struct '{}' has no field '{}'.", name, field));
+            } else if fields[..i].iter().any(|(earlier, _)| earlier == field) {
+                errors.push(format!("Field '{}' is given twice in '{}' literal.", field, name));
+            }
+        }
+        for field in declared.iter().filter(|&field| !fields.iter().any(|(given, _)| given == field)) {
+            errors.push(format!("Missing field '{}' in '{}' literal.", field, name));
+        }
+        for error in errors {
+            self.error(error);
+        }
+    }
+
+    fn assignable(&self, declared: &ViraType, actual: &ViraType) -> bool {
+        match (declared, actual) {
+            (ViraType::TypeVar(_), _) | (_, ViraType::TypeVar(_)) => true,
+            (ViraType::Optional(declared), ViraType::Optional(actual)) => self.assignable(declared, actual),
+            (ViraType::Optional(declared), actual) => self.assignable(declared, actual),
+            // Whether the type implements the trait is for the engines to
+            // check.
+            (ViraType::Struct(name), ViraType::Struct(_) | ViraType::Enum(_)) if self.traits.contains(name) => true,
+            // Annotations name enums as structs.
+            (ViraType::Struct(declared), ViraType::Struct(actual) | ViraType::Enum(actual)) => declared == actual,
+            (ViraType::Array(declared), ViraType::Array(actual)) => self.assignable(declared, actual),
+            (ViraType::Map(key, value), ViraType::Map(actual_key, actual_value)) => self.assignable(key, actual_key) && self.assignable(value, actual_value),
+            (ViraType::Tuple(declared), ViraType::Tuple(actual)) => {
+                declared.len() == actual.len() && declared.iter().zip(actual).all(|(declared, actual)| self.assignable(declared, actual))
+            }
+            (ViraType::Function(params, ret), ViraType::Function(actual_params, actual_ret)) => {
+                params.len() == actual_params.len()
+                    && params.iter().zip(actual_params).all(|(param, actual)| self.assignable(param, actual))
+                    && self.assignable(ret, actual_ret)
+            }
+            (declared, actual) => declared == actual,
+        }
+    }
+}
+
+impl<'a> Visitor<'a> for TypeChecker {
+    fn visit(&mut self, arena: &'a Arena, id: NodeId) {
+        let outer = self.at;
+        if let Some((start, _)) = arena.span(id) {
+            self.at = start;
+        }
+        let function = match &arena[id] {
+            AstNode::Item(Item::FuncDecl(name, _, _, ret, body)) => Some((Some(name.clone()), ret, *body)),
+            AstNode::Expr(Expr::Lambda(_, ret, body)) => Some((None, ret, body.node())),
+            _ => None,
+        };
+        if let Some((name, ret, _)) = &function {
+            self.functions.push((name.clone(), (*ret).clone()));
+        }
+        self.flow(arena, id);
+        if let Some((_, _, body)) = function {
+            self.body(arena, body);
+            self.functions.pop();
+        }
+        match &arena[id] {
+            AstNode::Expr(Expr::Binary(left, op, right)) => {
+                if !matches!(op, BinOp::Eq | BinOp::Neq) {
+                    self.non_nil(arena, left.node());
+                    self.non_nil(arena, right.node());
+                }
+                if let (Some(left_type), Some(right_type)) = (self.type_of(arena, left.node()), self.type_of(arena, right.node())) {
+                    match (binary_type(*op, left_type, right_type), left_type, right_type) {
+                        (Err(e), _, _) => self.error(e),
+                        (Ok(_), ViraType::Int, ViraType::Float) => self.conversions.push((id, *left)),
+                        (Ok(_), ViraType::Float, ViraType::Int) => self.conversions.push((id, *right)),
+                        _ => {}
+                    }
+                }
+            }
+            AstNode::Expr(Expr::Unary(op, operand)) => {
+                self.non_nil(arena, operand.node());
+                if let Some(operand) = self.type_of(arena, operand.node()) {
+                    if let Err(e) = unary_type(*op, operand) {
+                        self.error(e);
+                    }
+                }
+            }
+            AstNode::Expr(
+                Expr::FieldAccess(base, _) | Expr::Index(base, _) | Expr::TupleIndex(base, _) | Expr::MethodCall(base, ..) | Expr::Apply(base, _),
+            ) => self.non_nil(arena, base.node()),
+            AstNode::Expr(Expr::StructLiteral(name, fields)) => self.struct_literal(name, fields),
+            AstNode::Stmt(Stmt::VarDecl(name, declared, init)) => self.var_decl(arena, name, declared.as_ref(), init.node()),
+            AstNode::Stmt(Stmt::Return(value)) => self.return_stmt(arena, *value),
+            AstNode::Expr(Expr::Call(name, args)) => self.call(arena, name, args),
+            AstNode::Stmt(Stmt::TupleDecl(names, declared, init)) => self.var_decl(arena, &format!("({})", names.join(", ")), declared.as_ref(), init.node()),
+            _ => {}
+        }
+        self.at = outer;
+    }
+}
+
+// The variables assigned anywhere in a subtree.
+struct Assigned<'h> {
+    hir: &'h Hir,
+    symbols: HashSet<SymbolId>,
+}
+
+impl<'a> Visitor<'a> for Assigned<'_> {
+    fn visit(&mut self, arena: &'a Arena, id: NodeId) {
+        if let AstNode::Stmt(Stmt::Assign(..)) = arena[id] {
+            self.symbols.extend(self.hir.resolve(id));
+        }
+        walk(self, arena, id);
+    }
+}
+
+// The parameters of the named functions of a program and the fields of its
+// structs, None for a name declared more than once, and every name bound as
+// a variable.
+#[derive(Default)]
+struct Signatures<'a> {
+    functions: HashMap<&'a str, Option<&'a [(String, ViraType)]>>,
+    structs: HashMap<&'a str, Option<&'a [(String, ViraType)]>>,
+    variables: HashSet<&'a str>,
+}
+
+impl<'a> Visitor<'a> for Signatures<'a> {
+    fn visit(&mut self, arena: &'a Arena, id: NodeId) {
+        match &arena[id] {
+            AstNode::Item(Item::FuncDecl(name, _, params, ..)) => {
+                let first = !self.functions.contains_key(name.as_str());
+                self.functions.insert(name, first.then_some(params.as_slice()));
+                self.variables.extend(params.iter().map(|(name, _)| name.as_str()));
+            }
+            AstNode::Item(Item::StructDecl(name, fields)) => {
+                let first = !self.structs.contains_key(name.as_str());
+                self.structs.insert(name, first.then_some(fields.as_slice()));
+            }
+            // Methods are called on a value, not by name.
+            AstNode::Item(Item::ImplDecl(_, _, methods)) => {
+                for &method in methods {
+                    if let Item::FuncDecl(_, _, params, _, body) = &arena[method] {
+                        self.variables.extend(params.iter().map(|(name, _)| name.as_str()));
+                        self.visit(arena, *body);
+                    }
+                }
+                return;
+            }
+            AstNode::Expr(Expr::Lambda(params, ..)) => self.variables.extend(params.iter().map(|(name, _)| name.as_str())),
+            AstNode::Stmt(Stmt::VarDecl(name, ..) | Stmt::Try(_, name, _)) => {
+                self.variables.insert(name);
+            }
+            AstNode::Stmt(Stmt::TupleDecl(names, ..)) => self.variables.extend(names.iter().map(|name| name.as_str())),
+            AstNode::Expr(Expr::Match(_, arms)) => arms.iter().for_each(|(pattern, _)| bindings(pattern, &mut self.variables)),
+            _ => {}
+        }
+        walk(self, arena, id);
+    }
+}
+
+fn bindings<'a>(pattern: &'a Pattern, names: &mut HashSet<&'a str>) {
+    match pattern {
+        Pattern::Binding(name) => {
+            names.insert(name);
+        }
+        Pattern::EnumVariant(_, _, fields) => fields.iter().for_each(|field| bindings(field, names)),
+        _ => {}
+    }
+}
+
+// A variable, or a field of it, by the names of the fields.
+type Place = (SymbolId, Vec<String>);
+
+// What a condition narrows, or None when it cannot come out that way.
+type Narrowing = Option<HashSet<Place>>;
+
+fn union(a: Narrowing, b: Narrowing) -> Narrowing {
+    Some(&a? | &b?)
+}
+
+fn intersection(a: Narrowing, b: Narrowing) -> Narrowing {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(&a & &b),
+        (a, b) => a.or(b),
+    }
+}
+
+// Whether control never reaches the end of `id`.
+pub fn diverges(arena: &Arena, id: NodeId) -> bool {
+    match &arena[id] {
+        AstNode::Stmt(Stmt::Return(_) | Stmt::Throw(_)) => true,
+        AstNode::Stmt(Stmt::While(condition, _)) => matches!(arena[*condition], Expr::BoolLiteral(true)),
+        AstNode::Expr(Expr::Block(stmts)) => stmts.iter().any(|&stmt| diverges(arena, stmt)),
+        AstNode::Expr(Expr::If(_, then, Some(otherwise))) => diverges(arena, *then) && diverges(arena, *otherwise),
+        AstNode::Expr(Expr::Match(_, arms)) => !arms.is_empty() && arms.iter().all(|&(_, body)| diverges(arena, body.node())),
+        AstNode::Stmt(Stmt::Try(body, _, handler)) => diverges(arena, body.node()) && diverges(arena, handler.node()),
+        _ => false,
+    }
+}
+
+// Whether every path through `id` either diverges or ends in a value.
+fn ends_in_value(arena: &Arena, id: NodeId) -> bool {
+    if diverges(arena, id) {
+        return true;
+    }
+    match &arena[id] {
+        AstNode::Expr(Expr::Block(stmts)) => stmts.last().is_some_and(|&last| ends_in_value(arena, last)),
+        AstNode::Expr(Expr::If(_, then, Some(otherwise))) => ends_in_value(arena, *then) && ends_in_value(arena, *otherwise),
+        AstNode::Expr(Expr::Match(_, arms)) => arms.iter().all(|&(_, body)| ends_in_value(arena, body.node())),
+        AstNode::Stmt(Stmt::Try(body, _, handler)) => ends_in_value(arena, body.node()) && ends_in_value(arena, handler.node()),
+        node => node.is_expression(),
+    }
+}
+
+// Whether the operators of a value of this type are left unchecked.
+fn unchecked(typ: &ViraType) -> bool {
+    matches!(typ, ViraType::TypeVar(_) | ViraType::Struct(_) | ViraType::Enum(_) | ViraType::Optional(_))
+}
+
+// The type of `left op right` for operands of these types; None when the
+// checker does not tell, and an error when no engine could evaluate it.
+pub fn binary_type(op: BinOp, left: &ViraType, right: &ViraType) -> Result<Option<ViraType>, String> {
+    if unchecked(left) || unchecked(right) {
+        return Ok(matches!(op, BinOp::Eq | BinOp::Neq).then_some(ViraType::Bool));
+    }
+    if matches!((left, right), (ViraType::Decimal, ViraType::Float) | (ViraType::Float, ViraType::Decimal)) {
+        return Err("Cannot mix decimal and float; make the float a decimal(\"...\").".to_string());
+    }
+    let numeric = numeric_type(left, right);
+    let typ = match op {
+        BinOp::And | BinOp::Or => (*left == ViraType::Bool && *right == ViraType::Bool).then_some(ViraType::Bool),
+        BinOp::Eq | BinOp::Neq => (numeric.is_some() || mem::discriminant(left) == mem::discriminant(right)).then_some(ViraType::Bool),
+        BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge => match numeric {
+            Some(ViraType::Complex) => return Err("Complex numbers have no ordering.".to_string()),
+            numeric => numeric.map(|_| ViraType::Bool),
+        },
+        BinOp::Add if *left == ViraType::String && *right == ViraType::String => Some(ViraType::String),
+        BinOp::Mod if numeric == Some(ViraType::Complex) => return Err("Complex numbers have no remainder.".to_string()),
+        _ => numeric,
+    };
+    typ.map(Some).ok_or_else(|| format!("Operator '{}' cannot be applied to {} and {}.", bin_op(op), left, right))
+}
+
+// The type of arithmetic on two numbers: the same as theirs when they are
+// the same. Ints mix with floats and decimals, and ints and floats with
+// complex numbers.
+fn numeric_type(left: &ViraType, right: &ViraType) -> Option<ViraType> {
+    match (left, right) {
+        (ViraType::Int, ViraType::Int) => Some(ViraType::Int),
+        (ViraType::Float, ViraType::Float) => Some(ViraType::Float),
+        (ViraType::Decimal | ViraType::Int, ViraType::Decimal | ViraType::Int) => Some(ViraType::Decimal),
+        (ViraType::Int, ViraType::Float) | (ViraType::Float, ViraType::Int) => Some(ViraType::Float),
+        (ViraType::Complex | ViraType::Float | ViraType::Int, ViraType::Complex | ViraType::Float | ViraType::Int) if matches!((left, right), (ViraType::Complex, _) | (_, ViraType::Complex)) => {
+            Some(ViraType::Complex)
+        }
+        _ => None,
+    }
+}
+
+pub fn unary_type(op: UnaryOp, operand: &ViraType) -> Result<Option<ViraType>, String> {
+    if unchecked(operand) {
+        return Ok(None);
+    }
+    match (op, operand) {
+        (UnaryOp::Neg, ViraType::Int | ViraType::Float | ViraType::Decimal | ViraType::Complex) | (UnaryOp::Not, ViraType::Bool) => Ok(Some(operand.clone())),
+        (UnaryOp::Neg, _) => Err(format!("Operator '-' cannot be applied to {}.", operand)),
+        (UnaryOp::Not, _) => Err(format!("Operator '!' cannot be applied to {}.", operand)),
+    }
+}
@@ -0,0 +1,563 @@
+use std::fs;
+use std::io::{self, BufRead};
+use std::process::Command;
+
+use vira_syntax::ast::ViraType;
+
+use crate::compress;
+#[cfg(feature = "config-formats")]
+use crate::config_formats;
+use crate::decimal::{Decimal, Rounding};
+use crate::digest;
+use crate::encoding;
+use crate::interpreter::{Map, MapKey, Value};
+use crate::sorting;
+#[cfg(feature = "sqlite")]
+use crate::sqlite;
+use crate::template;
+
+// Functions every program can call without declaring them. A user function
+// or function value with the same name takes precedence.
+
+pub fn expect_args(name: &str, args: &[Value], count: usize) -> Result<(), String> {
+    if args.len() != count {
+        return Err(format!("Function '{}' expects {} argument(s), got {}.", name, count, args.len()));
+    }
+    Ok(())
+}
+
+// `array_filled(len, value)` and `array_generate(len, f)` allocate the whole
+// array up front instead of growing it a push at a time. A length the
+// machine cannot hold is an error rather than an abort.
+pub fn array_storage(name: &str, len: &Value) -> Result<(usize, Vec<Value>), String> {
+    let Value::Int(len) = len else {
+        return Err(format!("'{}' expects an int length.", name));
+    };
+    let len = usize::try_from(*len).map_err(|_| format!("'{}' expects a length of 0 or more, not {}.", name, len))?;
+    let mut items = Vec::new();
+    items.try_reserve_exact(len).map_err(|_| format!("Cannot allocate an array of {} elements.", len))?;
+    Ok((len, items))
+}
+
+// The bytes a string or an array of ints from 0 to 255 stands for, for the
+// builtins that work on bytes. A string is its UTF-8.
+pub fn bytes(name: &str, data: &Value) -> Result<Vec<u8>, String> {
+    match data {
+        Value::String(s) => Ok(s.as_bytes().to_vec()),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                Value::Int(v) => u8::try_from(*v).map_err(|_| format!("'{}' expects bytes from 0 to 255, not {}.", name, v)),
+                other => Err(format!("'{}' expects an array of ints, not one with a {}.", name, other.type_name())),
+            })
+            .collect(),
+        other => Err(format!("'{}' expects a string or an array of bytes, not {}.", name, other.type_name())),
+    }
+}
+
+// `hash(a, b, ...)` mixes one word per argument with FNV-1a. The JIT inlines
+// the same mix, so both engines agree on every hash.
+pub const HASH_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+pub const HASH_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+pub fn mix(hash: u64, word: u64) -> u64 {
+    (hash ^ word).wrapping_mul(HASH_PRIME)
+}
+
+fn hash_word(value: &Value) -> Result<u64, String> {
+    match value {
+        Value::Int(v) => Ok(*v as u64),
+        Value::Bool(b) => Ok(*b as u64),
+        Value::String(s) => Ok(s.bytes().fold(HASH_BASIS, |hash, byte| mix(hash, byte as u64))),
+        Value::Tuple(items) => hash_all(items),
+        _ => Err(format!("Cannot hash {}.", value.type_name())),
+    }
+}
+
+fn hash_all(values: &[Value]) -> Result<u64, String> {
+    values.iter().try_fold(HASH_BASIS, |hash, value| Ok(mix(hash, hash_word(value)?)))
+}
+
+// What builtins that change the world outside the program do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effects {
+    Perform,
+    // `run --dry-run`: only log what would have been done.
+    DryRun,
+    // Compile-time evaluation: fail.
+    Forbid,
+}
+
+pub fn effect(effects: Effects, description: String, perform: impl FnOnce() -> Result<Value, String>) -> Result<Value, String> {
+    match effects {
+        Effects::Perform => perform(),
+        Effects::DryRun => {
+            eprintln!("dry-run: would {}", description);
+            Ok(Value::Int(0))
+        }
+        Effects::Forbid => Err(format!("Cannot {} at compile time.", description)),
+    }
+}
+
+// Reading changes nothing, so a dry run reads for real.
+pub fn read(effects: Effects, description: String, perform: impl FnOnce() -> Result<Value, String>) -> Result<Value, String> {
+    match effects {
+        Effects::Perform | Effects::DryRun => perform(),
+        Effects::Forbid => Err(format!("Cannot {} at compile time.", description)),
+    }
+}
+
+// How the lines of a file end, the last argument of `read_file` and
+// `write_file`. A program only ever sees `\n`: reading turns `\r\n` into
+// `\n`, whatever the file has, and writing turns each `\n` into the ending
+// asked for. `native` is `crlf` on Windows and `lf` elsewhere, and `keep`
+// leaves the text alone both ways. Without one, files are written with
+// `lf`, so a script writes the same bytes on every platform, as `write` does
+// to standard output. Paths are used as written; `/` separates directories
+// on every platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Newlines {
+    Lf,
+    Crlf,
+    Keep,
+}
+
+impl Newlines {
+    fn parse(mode: &str) -> Result<Newlines, String> {
+        match mode {
+            "lf" => Ok(Newlines::Lf),
+            "crlf" => Ok(Newlines::Crlf),
+            "native" if cfg!(windows) => Ok(Newlines::Crlf),
+            "native" => Ok(Newlines::Lf),
+            "keep" => Ok(Newlines::Keep),
+            _ => Err(format!("Unknown newline mode '{}'; expected lf, crlf, native or keep.", mode)),
+        }
+    }
+
+    fn from_args(name: &str, args: &[Value], count: usize) -> Result<Newlines, String> {
+        match args.get(count) {
+            _ if args.len() != count && args.len() != count + 1 => {
+                Err(format!("Function '{}' expects {} or {} argument(s), got {}.", name, count, count + 1, args.len()))
+            }
+            None => Ok(Newlines::Lf),
+            Some(Value::String(mode)) => Newlines::parse(mode),
+            Some(_) => Err(format!("'{}' expects the newline mode as a string.", name)),
+        }
+    }
+
+    fn read(self, text: String) -> String {
+        match self {
+            Newlines::Keep => text,
+            _ => text.replace("\r\n", "\n"),
+        }
+    }
+
+    fn write(self, text: &str) -> String {
+        match self {
+            Newlines::Lf => text.replace("\r\n", "\n"),
+            Newlines::Crlf => text.replace("\r\n", "\n").replace('\n', "\r\n"),
+            Newlines::Keep => text.to_string(),
+        }
+    }
+}
+
+fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) { Command::new("cmd") } else { Command::new("sh") };
+    shell.arg(if cfg!(windows) { "/C" } else { "-c" }).arg(command);
+    shell
+}
+
+// The built-in types that have methods.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Receiver {
+    Range,
+    Array,
+    String,
+    Map,
+    Decimal,
+    Complex,
+}
+
+impl Receiver {
+    pub fn of_type(typ: &ViraType) -> Option<Receiver> {
+        match typ {
+            ViraType::Range => Some(Receiver::Range),
+            ViraType::Array(_) => Some(Receiver::Array),
+            ViraType::String => Some(Receiver::String),
+            ViraType::Map(..) => Some(Receiver::Map),
+            ViraType::Decimal => Some(Receiver::Decimal),
+            ViraType::Complex => Some(Receiver::Complex),
+            _ => None,
+        }
+    }
+
+    pub fn of_value(value: &Value) -> Option<Receiver> {
+        match value {
+            Value::Range(..) => Some(Receiver::Range),
+            Value::Array(_) => Some(Receiver::Array),
+            Value::String(_) => Some(Receiver::String),
+            Value::Map(_) => Some(Receiver::Map),
+            Value::Decimal(_) => Some(Receiver::Decimal),
+            Value::Complex(..) => Some(Receiver::Complex),
+            _ => None,
+        }
+    }
+}
+
+// A method of a built-in type: the builtin that implements it, which takes
+// the receiver as its first argument, and the number of other arguments.
+pub struct Method {
+    pub builtin: &'static str,
+    pub params: usize,
+}
+
+// Resolves `receiver.name(...)` for both engines and the passes that need
+// its type. A user method with the same name takes precedence. Builtins
+// named with a dot can only be reached this way.
+pub fn method(receiver: Receiver, name: &str) -> Option<Method> {
+    let (builtin, params) = match (receiver, name) {
+        (Receiver::Range, "len") => ("range.len", 0),
+        (Receiver::Range, "items") => ("range.items", 0),
+        (Receiver::Array, "len") => ("array.len", 0),
+        (Receiver::Array, "items") => ("array.items", 0),
+        (Receiver::Array, "is_empty") => ("array.is_empty", 0),
+        (Receiver::Array, "push") => ("array.push", 1),
+        (Receiver::Array, "contains") => ("array.contains", 1),
+        (Receiver::Array, "join") => ("array.join", 1),
+        (Receiver::String, "len") => ("string.len", 0),
+        (Receiver::String, "is_empty") => ("string.is_empty", 0),
+        (Receiver::String, "contains") => ("string.contains", 1),
+        (Receiver::String, "starts_with") => ("string.starts_with", 1),
+        (Receiver::String, "ends_with") => ("string.ends_with", 1),
+        (Receiver::String, "split") => ("string.split", 1),
+        (Receiver::String, "trim") => ("string.trim", 0),
+        (Receiver::String, "to_upper") => ("string.to_upper", 0),
+        (Receiver::String, "to_lower") => ("string.to_lower", 0),
+        (Receiver::String, "items") => ("string.items", 0),
+        (Receiver::Map, "len") => ("map.len", 0),
+        (Receiver::Map, "items") => ("map.items", 0),
+        (Receiver::Map, "get") => ("get", 1),
+        (Receiver::Map, "contains") => ("contains", 1),
+        (Receiver::Decimal, "round") => ("decimal.round", 2),
+        (Receiver::Decimal, "scale") => ("decimal.scale", 0),
+        (Receiver::Complex, "re") => ("complex.re", 0),
+        (Receiver::Complex, "im") => ("complex.im", 0),
+        (Receiver::Complex, "abs") => ("complex.abs", 0),
+        (Receiver::Complex, "arg") => ("complex.arg", 0),
+        (Receiver::Complex, "conj") => ("complex.conj", 0),
+        _ => return None,
+    };
+    Some(Method { builtin, params })
+}
+
+// Methods that update the variable they are called on, e.g. `xs.push(1)`.
+// The builtin returns the new value.
+pub fn mutates(builtin: &str) -> bool {
+    builtin == "array.push"
+}
+
+// The builtins called by name, as `f(x)`, for the passes that resolve
+// names. `format`, `clone`, `json`, `discriminant` and `array_generate` are
+// implemented by the engines themselves, the sorting functions by `sorting`,
+// and the log functions by `logging`, as their fields may need `to_json`.
+pub fn is_function(name: &str) -> bool {
+    matches!(
+        name,
+        "get" | "contains" | "set" | "ordered_map" | "sorted_keys" | "array_filled" | "array_generate" | "hash" | "format" | "clone" | "json"
+            | "discriminant" | "checked_add" | "checked_sub" | "checked_mul" | "checked_div" | "checked_rem" | "decimal" | "complex"
+            | "read_file" | "read_bytes" | "readline" | "write_file" | "remove_file" | "exec" | "render" | "md5" | "sha256" | "crc32"
+            | "base64_encode" | "base64_decode" | "hex_encode" | "hex_decode" | "from_utf8" | "gzip" | "gunzip" | "deflate" | "inflate"
+            | "db_open" | "db_exec" | "db_query" | "toml_parse" | "yaml_parse" | "log_info" | "log_warn" | "log_error"
+    )
+}
+
+// Returns None when `name` is not a builtin.
+pub fn call(name: &str, args: Vec<Value>, effects: Effects) -> Option<Result<Value, String>> {
+    let result = match name {
+        "get" => expect_args(name, &args, 2).and_then(|()| match args.as_slice() {
+            [Value::Map(map), key] => {
+                let key = MapKey::from_value(key)?;
+                map.get(&key).cloned().ok_or(format!("Key {} is not in the map.", key))
+            }
+            _ => Err("'get' expects a map as its first argument.".to_string()),
+        }),
+        "contains" => expect_args(name, &args, 2).and_then(|()| match args.as_slice() {
+            [Value::Map(map), key] => Ok(Value::Bool(map.contains_key(&MapKey::from_value(key)?))),
+            _ => Err("'contains' expects a map as its first argument.".to_string()),
+        }),
+        // Maps are values like everything else, so `set` returns an updated
+        // copy: `m = set(m, "k", 1)`.
+        "set" => expect_args(name, &args, 3).and_then(|()| match args.as_slice() {
+            [Value::Map(map), key, value] => {
+                let mut map = map.clone();
+                map.insert(MapKey::from_value(key)?, value.clone());
+                Ok(Value::Map(map))
+            }
+            _ => Err("'set' expects a map as its first argument.".to_string()),
+        }),
+        // An empty map that iterates in insertion order, for output that
+        // should follow the order it was built in.
+        "ordered_map" => expect_args(name, &args, 0).map(|()| Value::Map(Map::ordered())),
+        // The keys in key order whatever order the map iterates in; struct
+        // and enum keys come in the order of their hashes.
+        "sorted_keys" => expect_args(name, &args, 1).and_then(|()| match args.as_slice() {
+            [Value::Map(map)] => Ok(Value::Array(map.sorted_entries().into_iter().map(|(key, _)| key.to_value()).collect())),
+            _ => Err("'sorted_keys' expects a map.".to_string()),
+        }),
+        "array_filled" => match args.as_slice() {
+            [len, value] => array_storage(name, len).map(|(len, mut items)| {
+                items.resize(len, value.clone());
+                Value::Array(items)
+            }),
+            _ => Err(format!("Function '{}' expects 2 argument(s), got {}.", name, args.len())),
+        },
+        // Structs and enums were replaced by what their `hash` method returns.
+        "hash" => hash_all(&args).map(|hash| Value::Int(hash as i64)),
+        // Return nil instead of wrapping or trapping, so callers handle
+        // overflow with `??`.
+        "checked_add" | "checked_sub" | "checked_mul" | "checked_div" | "checked_rem" => {
+            expect_args(name, &args, 2).and_then(|()| match args.as_slice() {
+                [Value::Int(a), Value::Int(b)] => Ok(checked(name, *a, *b).map_or(Value::Nil, Value::Int)),
+                _ => Err(format!("'{}' expects two ints.", name)),
+            })
+        }
+        "array.len" | "array.is_empty" | "array.push" | "array.contains" | "array.join" => match args.as_slice() {
+            [Value::Array(items)] if name == "array.len" => Ok(Value::Int(items.len() as i64)),
+            [Value::Array(items)] if name == "array.is_empty" => Ok(Value::Bool(items.is_empty())),
+            [Value::Array(items), value] if name == "array.push" => {
+                let mut items = items.clone();
+                items.push(value.clone());
+                Ok(Value::Array(items))
+            }
+            [Value::Array(items), value] if name == "array.contains" => Ok(Value::Bool(items.contains(value))),
+            [Value::Array(items), Value::String(separator)] if name == "array.join" => {
+                let parts: Vec<String> = items
+                    .iter()
+                    .map(|item| match item {
+                        Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    })
+                    .collect();
+                Ok(Value::String(parts.join(separator)))
+            }
+            // The receiver is always an array, so only the separator can be wrong.
+            _ => Err("'join' expects a string separator.".to_string()),
+        },
+        "string.len" | "string.is_empty" | "string.trim" | "string.to_upper" | "string.to_lower" => match args.as_slice() {
+            [Value::String(s)] => Ok(match name {
+                "string.len" => Value::Int(s.chars().count() as i64),
+                "string.is_empty" => Value::Bool(s.is_empty()),
+                "string.trim" => Value::String(s.trim().to_string()),
+                "string.to_upper" => Value::String(s.to_uppercase()),
+                _ => Value::String(s.to_lowercase()),
+            }),
+            _ => Err(format!("Invalid arguments to '{}'.", name)),
+        },
+        "string.contains" | "string.starts_with" | "string.ends_with" | "string.split" => match args.as_slice() {
+            [Value::String(s), Value::String(other)] => Ok(match name {
+                "string.contains" => Value::Bool(s.contains(other.as_str())),
+                "string.starts_with" => Value::Bool(s.starts_with(other.as_str())),
+                "string.ends_with" => Value::Bool(s.ends_with(other.as_str())),
+                _ => Value::Array(s.split(other.as_str()).map(|part| Value::String(part.to_string())).collect()),
+            }),
+            _ => Err(format!("'{}' expects a string argument.", name.trim_start_matches("string."))),
+        },
+        "map.len" => match args.as_slice() {
+            [Value::Map(map)] => Ok(Value::Int(map.len() as i64)),
+            _ => Err(format!("Invalid arguments to '{}'.", name)),
+        },
+        "range.len" => match args.as_slice() {
+            [Value::Range(start, end)] => Ok(Value::Int(end.saturating_sub(*start).max(0))),
+            _ => Err(format!("Invalid arguments to '{}'.", name)),
+        },
+        // What `for x in value` visits, which `lower` indexes from 0 to its
+        // `len`: a range stands for its ints without making them, a map for
+        // its keys in iteration order and a string for its characters.
+        "range.items" | "array.items" | "map.items" | "string.items" => match args.as_slice() {
+            [items @ (Value::Range(..) | Value::Array(_))] => Ok(items.clone()),
+            [Value::Map(map)] => Ok(Value::Array(map.entries().into_iter().map(|(key, _)| key.to_value()).collect())),
+            [Value::String(s)] => Ok(Value::Array(s.chars().map(|c| Value::String(c.to_string())).collect())),
+            _ => Err(format!("Invalid arguments to '{}'.", name)),
+        },
+        // Floats are not accepted: by the time one gets here it has already
+        // been rounded to binary.
+        "decimal" => expect_args(name, &args, 1).and_then(|()| match args.as_slice() {
+            [Value::String(text)] => Decimal::parse(text).map(Value::Decimal),
+            [Value::Int(v)] => Ok(Value::Decimal(Decimal::from_int(*v))),
+            [Value::Decimal(d)] => Ok(Value::Decimal(*d)),
+            _ => Err("'decimal' expects a string such as \"12.50\", or an int.".to_string()),
+        }),
+        "decimal.round" => match args.as_slice() {
+            [Value::Decimal(d), Value::Int(places), Value::String(mode)] => u32::try_from(*places)
+                .map_err(|_| "'round' expects a number of places of 0 or more.".to_string())
+                .and_then(|places| d.round(places, Rounding::parse(mode)?))
+                .map(Value::Decimal),
+            _ => Err("'round' expects a number of places and a rounding mode, e.g. d.round(2, \"half_even\").".to_string()),
+        },
+        "decimal.scale" => match args.as_slice() {
+            [Value::Decimal(d)] => Ok(Value::Int(d.scale() as i64)),
+            _ => Err(format!("Invalid arguments to '{}'.", name)),
+        },
+        "complex" => expect_args(name, &args, 2).and_then(|()| match args.as_slice() {
+            [Value::Int(re), Value::Int(im)] => Ok(Value::Complex(*re as f64, *im as f64)),
+            [Value::Int(re), Value::Float(im)] => Ok(Value::Complex(*re as f64, *im)),
+            [Value::Float(re), Value::Int(im)] => Ok(Value::Complex(*re, *im as f64)),
+            [Value::Float(re), Value::Float(im)] => Ok(Value::Complex(*re, *im)),
+            _ => Err("'complex' expects a real and an imaginary part.".to_string()),
+        }),
+        "complex.re" | "complex.im" | "complex.abs" | "complex.arg" | "complex.conj" => match args.as_slice() {
+            [Value::Complex(re, im)] => Ok(match name {
+                "complex.re" => Value::Float(*re),
+                "complex.im" => Value::Float(*im),
+                "complex.abs" => Value::Float(re.hypot(*im)),
+                "complex.arg" => Value::Float(im.atan2(*re)),
+                _ => Value::Complex(*re, -im),
+            }),
+            _ => Err(format!("Invalid arguments to '{}'.", name)),
+        },
+        "write_file" => Newlines::from_args(name, &args, 2).and_then(|newlines| match args.as_slice() {
+            [Value::String(path), Value::String(text), ..] => {
+                let text = newlines.write(text);
+                effect(effects, format!("write {} byte(s) to {}", text.len(), path), || {
+                    fs::write(path, &text).map_err(|e| format!("Cannot write {}: {}", path, e))?;
+                    Ok(Value::Int(0))
+                })
+            }
+            // Bytes, such as those of `gzip`, are written as they are.
+            [Value::String(path), data @ Value::Array(_)] => {
+                let data = bytes(name, data)?;
+                effect(effects, format!("write {} byte(s) to {}", data.len(), path), || {
+                    fs::write(path, &data).map_err(|e| format!("Cannot write {}: {}", path, e))?;
+                    Ok(Value::Int(0))
+                })
+            }
+            _ => Err("'write_file' expects a path and a string, or an array of bytes.".to_string()),
+        }),
+        "read_file" => Newlines::from_args(name, &args, 1).and_then(|newlines| match args.as_slice() {
+            [Value::String(path), ..] => read(effects, format!("read {}", path), || {
+                let text = fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+                Ok(Value::String(newlines.read(text)))
+            }),
+            _ => Err("'read_file' expects a path.".to_string()),
+        }),
+        // The file as it is, one int per byte, for what is not text.
+        "read_bytes" => expect_args(name, &args, 1).and_then(|()| match args.as_slice() {
+            [Value::String(path)] => read(effects, format!("read {}", path), || {
+                let bytes = fs::read(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+                Ok(Value::Array(bytes.into_iter().map(|byte| Value::Int(byte as i64)).collect()))
+            }),
+            _ => Err("'read_bytes' expects a path.".to_string()),
+        }),
+        // The next line of standard input without its `\n` or `\r\n`, or nil
+        // at the end of the input.
+        "readline" => expect_args(name, &args, 0).and_then(|()| {
+            read(effects, "read standard input".to_string(), || {
+                let mut line = String::new();
+                if io::stdin().lock().read_line(&mut line).map_err(|e| format!("Cannot read standard input: {}", e))? == 0 {
+                    return Ok(Value::Nil);
+                }
+                let line = line.strip_suffix('\n').unwrap_or(&line);
+                Ok(Value::String(line.strip_suffix('\r').unwrap_or(line).to_string()))
+            })
+        }),
+        "remove_file" => expect_args(name, &args, 1).and_then(|()| match args.as_slice() {
+            [Value::String(path)] => effect(effects, format!("remove {}", path), || {
+                fs::remove_file(path).map_err(|e| format!("Cannot remove {}: {}", path, e))?;
+                Ok(Value::Int(0))
+            }),
+            _ => Err("'remove_file' expects a path.".to_string()),
+        }),
+        "render" => template::render(&args),
+        "md5" | "sha256" | "crc32" => digest::digest(name, &args),
+        "base64_encode" | "base64_decode" | "hex_encode" | "hex_decode" | "from_utf8" => encoding::call(name, &args),
+        "gzip" | "gunzip" | "deflate" | "inflate" => compress::call(name, &args),
+        #[cfg(feature = "sqlite")]
+        "db_open" | "db_exec" | "db_query" => sqlite::call(name, &args, effects),
+        #[cfg(not(feature = "sqlite"))]
+        "db_open" | "db_exec" | "db_query" => Err(format!("'{}' needs a vira-compiler built with the sqlite feature.", name)),
+        #[cfg(feature = "config-formats")]
+        "toml_parse" | "yaml_parse" => config_formats::call(name, &args),
+        #[cfg(not(feature = "config-formats"))]
+        "toml_parse" | "yaml_parse" => Err(format!("'{}' needs a vira-compiler built with the config-formats feature.", name)),
+        // Runs a shell command and returns its exit code.
+        "exec" => expect_args(name, &args, 1).and_then(|()| match args.as_slice() {
+            [Value::String(command)] => effect(effects, format!("run `{}`", command), || {
+                let status = shell(command).status().map_err(|e| format!("Cannot run `{}`: {}", command, e))?;
+                Ok(Value::Int(status.code().unwrap_or(-1) as i64))
+            }),
+            _ => Err("'exec' expects a command string.".to_string()),
+        }),
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn checked(name: &str, a: i64, b: i64) -> Option<i64> {
+    match name {
+        "checked_add" => a.checked_add(b),
+        "checked_sub" => a.checked_sub(b),
+        "checked_mul" => a.checked_mul(b),
+        "checked_div" => a.checked_div(b),
+        _ => a.checked_rem(b),
+    }
+}
+
+// Builtins that reach outside the program, for the effect analysis.
+pub fn has_effects(name: &str) -> bool {
+    matches!(
+        name,
+        "read_file" | "read_bytes" | "readline" | "write_file" | "remove_file" | "exec" | "db_open" | "db_exec" | "db_query" | "log_info" | "log_warn" | "log_error"
+    )
+}
+
+// Builtins that build a new collection.
+pub fn allocates(name: &str) -> bool {
+    matches!(
+        name,
+        "set" | "ordered_map" | "sorted_keys" | "array.push" | "array_filled" | "array_generate" | "string.split" | "map.items" | "string.items" | "sort" | "sort_by" | "clone"
+    )
+}
+
+// The static type of a builtin call, for the passes that need one.
+pub fn return_type(name: &str, args: &[Option<ViraType>]) -> Option<ViraType> {
+    match (name, args) {
+        ("get", [Some(ViraType::Map(_, value)), _]) => Some((**value).clone()),
+        ("contains", [Some(ViraType::Map(..)), _]) => Some(ViraType::Bool),
+        ("set", [Some(typ @ ViraType::Map(..)), _, _]) => Some(typ.clone()),
+        ("sorted_keys", [Some(ViraType::Map(key, _))]) => Some(ViraType::Array(key.clone())),
+        ("checked_add" | "checked_sub" | "checked_mul" | "checked_div" | "checked_rem", [_, _]) => {
+            Some(ViraType::Optional(Box::new(ViraType::Int)))
+        }
+        ("range.len" | "array.len" | "string.len" | "map.len", [_]) => Some(ViraType::Int),
+        ("range.items" | "array.items", [typ]) => typ.clone(),
+        ("map.items", [Some(ViraType::Map(key, _))]) => Some(ViraType::Array(key.clone())),
+        ("string.items", [_]) => Some(ViraType::Array(Box::new(ViraType::String))),
+        ("array.is_empty" | "string.is_empty", [_]) => Some(ViraType::Bool),
+        ("array.push", [Some(typ), _]) => Some(typ.clone()),
+        ("array.contains" | "string.contains" | "string.starts_with" | "string.ends_with", [_, _]) => Some(ViraType::Bool),
+        ("array.join", [_, _]) | ("string.trim" | "string.to_upper" | "string.to_lower", [_]) => Some(ViraType::String),
+        ("string.split", [_, _]) => Some(ViraType::Array(Box::new(ViraType::String))),
+        ("decimal", [_]) | ("decimal.round", [_, _, _]) => Some(ViraType::Decimal),
+        ("decimal.scale", [_]) => Some(ViraType::Int),
+        ("complex", [_, _]) | ("complex.conj", [_]) => Some(ViraType::Complex),
+        ("complex.re" | "complex.im" | "complex.abs" | "complex.arg", [_]) => Some(ViraType::Float),
+        ("write_file", [_, _] | [_, _, _]) | ("remove_file", [_]) | ("exec", [_]) => Some(ViraType::Int),
+        ("read_file", [_] | [_, _]) | ("md5" | "sha256" | "crc32", [_]) => Some(ViraType::String),
+        ("read_bytes", [_]) => Some(ViraType::Array(Box::new(ViraType::Int))),
+        ("base64_encode", [_] | [_, _]) | ("hex_encode", [_]) => Some(ViraType::String),
+        ("base64_decode", [_] | [_, _]) | ("hex_decode", [_]) => Some(ViraType::Optional(Box::new(ViraType::Array(Box::new(ViraType::Int))))),
+        ("from_utf8", [_]) => Some(ViraType::Optional(Box::new(ViraType::String))),
+        ("gzip" | "deflate", [_]) => Some(ViraType::Array(Box::new(ViraType::Int))),
+        ("gunzip" | "inflate", [_]) => Some(ViraType::Optional(Box::new(ViraType::Array(Box::new(ViraType::Int))))),
+        // The columns of a query's rows depend on the query.
+        ("db_open", [_]) | ("db_exec", [_, _] | [_, _, _]) => Some(ViraType::Int),
+        ("readline", []) => Some(ViraType::Optional(Box::new(ViraType::String))),
+        ("format", [_]) => Some(ViraType::String),
+        ("array_filled", [_, Some(element)]) => Some(ViraType::Array(Box::new(element.clone()))),
+        ("array_generate", [_, Some(ViraType::Function(_, element))]) => Some(ViraType::Array(element.clone())),
+        ("hash", _) | ("discriminant", [_]) => Some(ViraType::Int),
+        ("json", [_]) | ("render", [_, _] | [_, _, _]) => Some(ViraType::String),
+        ("clone", [typ]) => typ.clone(),
+        _ => sorting::return_type(name, args),
+    }
+}
@@ -0,0 +1,381 @@
+use crate::builtins;
+use crate::digest;
+use crate::encoding;
+use crate::interpreter::Value;
+
+// Compression, for logs and build artifacts without shelling out:
+//
+//   gzip(data)       gunzip(bytes)
+//   deflate(data)    inflate(bytes)
+//
+// `data` is a string, taken as its UTF-8, or an array of ints from 0 to 255,
+// and the result an array of bytes. `gzip` makes a .gz file's contents
+// (RFC 1952) and `deflate` the raw stream inside it (RFC 1951). The
+// decompressors read anything other tools write, and give nil when the bytes
+// are not valid, so that callers handle bad input with `??`; `gunzip` also
+// checks the CRC and takes the concatenated members some tools append.
+//
+// The compressor finds repeats with hash chains and codes them with the fixed
+// Huffman codes, falling back to storing data that does not compress.
+
+pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+    let [data] = args else {
+        return Err(format!("Function '{}' expects 1 argument(s), got {}.", name, args.len()));
+    };
+    let data = builtins::bytes(name, data)?;
+    Ok(match name {
+        "gzip" => bytes(gzip(&data)),
+        "deflate" => bytes(deflate(&data)),
+        "gunzip" => encoding::decoded(gunzip(&data)),
+        _ => encoding::decoded(inflate(&data).map(|(bytes, _)| bytes)),
+    })
+}
+
+fn bytes(bytes: Vec<u8>) -> Value {
+    Value::Array(bytes.into_iter().map(|byte| Value::Int(byte as i64)).collect())
+}
+
+// The first length and distance of each code and how many extra bits follow
+// it, for length codes 257 to 285 and distance codes 0 to 29.
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// The order the code length code lengths of a dynamic block come in.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+const WINDOW: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    // No flags, no time, and an unknown OS.
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+    out.extend(deflate(data));
+    out.extend(digest::crc32(data).to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out
+}
+
+fn gunzip(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut rest = data;
+    loop {
+        let (member, used) = gunzip_member(rest)?;
+        out.extend(member);
+        rest = &rest[used..];
+        if rest.is_empty() {
+            return Some(out);
+        }
+    }
+}
+
+// One member and the number of bytes it took.
+fn gunzip_member(data: &[u8]) -> Option<(Vec<u8>, usize)> {
+    const FHCRC: u8 = 2;
+    const FEXTRA: u8 = 4;
+    const FNAME: u8 = 8;
+    const FCOMMENT: u8 = 16;
+    let (&[0x1f, 0x8b, 8, flags], _) = data.split_first_chunk::<4>()? else {
+        return None;
+    };
+    let mut at = 10;
+    if flags & FEXTRA != 0 {
+        let len = u16::from_le_bytes(*data.get(at..)?.first_chunk::<2>()?) as usize;
+        at += 2 + len;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            at += data.get(at..)?.iter().position(|&byte| byte == 0)? + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        at += 2;
+    }
+    let (bytes, used) = inflate(data.get(at..)?)?;
+    at += used;
+    let trailer = data.get(at..at + 8)?;
+    let crc = u32::from_le_bytes(trailer[..4].try_into().ok()?);
+    let size = u32::from_le_bytes(trailer[4..].try_into().ok()?);
+    (crc == digest::crc32(&bytes) && size == bytes.len() as u32).then_some((bytes, at + 8))
+}
+
+// Writes bits from the least significant up, as DEFLATE packs them.
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= (value as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes go most significant bit first.
+    fn code(&mut self, code: u32, len: u32) {
+        self.write(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+
+    fn literal(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xc0 + symbol - 280, 8),
+        }
+    }
+
+    fn repeat(&mut self, len: usize, distance: usize) {
+        let code = LENGTH_BASE.iter().rposition(|&base| base as usize <= len).unwrap_or(0);
+        self.literal(257 + code as u32);
+        self.write((len - LENGTH_BASE[code] as usize) as u32, LENGTH_EXTRA[code] as u32);
+        let code = DISTANCE_BASE.iter().rposition(|&base| base as usize <= distance).unwrap_or(0);
+        self.code(code as u32, 5);
+        self.write((distance - DISTANCE_BASE[code] as usize) as u32, DISTANCE_EXTRA[code] as u32);
+    }
+}
+
+// The latest position of each hash of three bytes, and the one before each
+// position with the same hash.
+struct Chains {
+    head: Vec<usize>,
+    previous: Vec<usize>,
+}
+
+impl Chains {
+    fn insert(&mut self, data: &[u8], at: usize) {
+        if at + MIN_MATCH <= data.len() {
+            let hash = hash(data, at);
+            self.previous[at] = self.head[hash];
+            self.head[hash] = at;
+        }
+    }
+}
+
+fn hash(data: &[u8], at: usize) -> usize {
+    ((data[at] as usize) << 10 ^ (data[at + 1] as usize) << 5 ^ data[at + 2] as usize) & 0x7fff
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    // The last block, with the fixed codes.
+    writer.write(1, 1);
+    writer.write(1, 2);
+    let mut chains = Chains { head: vec![usize::MAX; 1 << 15], previous: vec![usize::MAX; data.len()] };
+    let mut at = 0;
+    while at < data.len() {
+        let (mut len, mut distance) = (0, 0);
+        if at + MIN_MATCH <= data.len() {
+            let mut candidate = chains.head[hash(data, at)];
+            let mut chain = 0;
+            while candidate != usize::MAX && at - candidate <= WINDOW && chain < MAX_CHAIN {
+                let longest = data[candidate..].iter().zip(&data[at..]).take(MAX_MATCH).take_while(|(a, b)| a == b).count();
+                if longest > len {
+                    (len, distance) = (longest, at - candidate);
+                }
+                candidate = chains.previous[candidate];
+                chain += 1;
+            }
+        }
+        if len >= MIN_MATCH {
+            writer.repeat(len, distance);
+            for i in at..at + len {
+                chains.insert(data, i);
+            }
+            at += len;
+        } else {
+            writer.literal(data[at] as u32);
+            chains.insert(data, at);
+            at += 1;
+        }
+    }
+    writer.literal(256);
+    let compressed = writer.finish();
+    // Stored blocks cost five bytes for each 64 KiB.
+    if compressed.len() <= data.len() + 5 * data.len().div_ceil(65535).max(1) {
+        return compressed;
+    }
+    stored(data)
+}
+
+fn stored(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut chunks = data.chunks(65535).peekable();
+    if chunks.peek().is_none() {
+        return vec![1, 0, 0, 0xff, 0xff];
+    }
+    while let Some(chunk) = chunks.next() {
+        out.push(chunks.peek().is_none() as u8);
+        out.extend((chunk.len() as u16).to_le_bytes());
+        out.extend((!(chunk.len() as u16)).to_le_bytes());
+        out.extend(chunk);
+    }
+    out
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    // In bits.
+    at: usize,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> Option<u32> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = *self.data.get(self.at / 8)?;
+            value |= ((byte >> (self.at % 8)) as u32 & 1) << i;
+            self.at += 1;
+        }
+        Some(value)
+    }
+
+    fn align(&mut self) {
+        self.at = self.at.div_ceil(8) * 8;
+    }
+
+    // A symbol of the code, read a bit at a time: the codes of each length
+    // are consecutive, after those of the lengths before.
+    fn symbol(&mut self, huffman: &Huffman) -> Option<u16> {
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for &count in &huffman.counts[1..] {
+            code |= self.bits(1)? as usize;
+            if code < first + count {
+                return huffman.symbols.get(index + code - first).copied();
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        None
+    }
+}
+
+// A canonical Huffman code: how many codes have each length, and the symbols
+// in code order.
+struct Huffman {
+    counts: [usize; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    // None when the lengths describe more codes than there are.
+    fn new(lengths: &[u8]) -> Option<Huffman> {
+        let mut counts = [0; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut left: isize = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as isize;
+            if left < 0 {
+                return None;
+            }
+        }
+        let mut symbols: Vec<u16> = (0..lengths.len() as u16).filter(|&symbol| lengths[symbol as usize] != 0).collect();
+        symbols.sort_by_key(|&symbol| lengths[symbol as usize]);
+        Some(Huffman { counts, symbols })
+    }
+}
+
+// The data and the number of bytes of the stream, so that a gzip trailer can
+// be found after it.
+fn inflate(data: &[u8]) -> Option<(Vec<u8>, usize)> {
+    let mut reader = BitReader { data, at: 0 };
+    let mut out = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let start = reader.at / 8;
+                let header = data.get(start..start + 4)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                if len != !u16::from_le_bytes([header[2], header[3]]) {
+                    return None;
+                }
+                out.extend(data.get(start + 4..start + 4 + len as usize)?);
+                reader.at = (start + 4 + len as usize) * 8;
+            }
+            1 => {
+                let mut lengths = [8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                codes(&mut reader, &mut out, &Huffman::new(&lengths)?, &Huffman::new(&[5; 30])?)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic(&mut reader)?;
+                codes(&mut reader, &mut out, &literals, &distances)?;
+            }
+            _ => return None,
+        }
+        if last {
+            return Some((out, reader.at.div_ceil(8)));
+        }
+    }
+}
+
+// The literal and distance codes of a dynamic block, themselves coded.
+fn dynamic(reader: &mut BitReader) -> Option<(Huffman, Huffman)> {
+    let literals = reader.bits(5)? as usize + 257;
+    let distances = reader.bits(5)? as usize + 1;
+    let code_lengths = reader.bits(4)? as usize + 4;
+    let mut lengths = [0; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[symbol] = reader.bits(3)? as u8;
+    }
+    let code = Huffman::new(&lengths)?;
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let (len, times) = match reader.symbol(&code)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last()?, 3 + reader.bits(2)?),
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(len, times as usize));
+    }
+    if lengths.len() > literals + distances || lengths[256] == 0 {
+        return None;
+    }
+    Some((Huffman::new(&lengths[..literals])?, Huffman::new(&lengths[literals..])?))
+}
+
+fn codes(reader: &mut BitReader, out: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Option<()> {
+    loop {
+        match reader.symbol(literals)? {
+            symbol @ 0..=255 => out.push(symbol as u8),
+            256 => return Some(()),
+            symbol => {
+                let code = symbol as usize - 257;
+                let len = *LENGTH_BASE.get(code)? as usize + reader.bits(*LENGTH_EXTRA.get(code)? as u32)? as usize;
+                let code = reader.symbol(distances)? as usize;
+                let distance = *DISTANCE_BASE.get(code)? as usize + reader.bits(*DISTANCE_EXTRA.get(code)? as u32)? as usize;
+                let start = out.len().checked_sub(distance)?;
+                // The repeat may overlap what it writes.
+                for i in start..start + len {
+                    out.push(out[i]);
+                }
+            }
+        }
+    }
+}
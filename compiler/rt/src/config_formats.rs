@@ -0,0 +1,85 @@
+use toml::Value as Toml;
+use yaml_rust2::{Yaml, YamlLoader};
+
+use crate::interpreter::{Map, MapKey, Value};
+
+// `toml_parse(text)` and `yaml_parse(text)`: the values of a configuration
+// file, as the maps, arrays, strings, ints, floats and bools Vira has, so a
+// script reads them as it would its own. Tables and mappings keep the order
+// of the file. TOML dates and times come as their text, and YAML's null as
+// nil. Of a YAML file with several documents, the first is read; an empty
+// one is nil. Text that is not valid is an error that says where.
+//
+// Built only with the `config-formats` feature, as the parsers add to the
+// size of the binary.
+
+pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+    let [text] = args else {
+        return Err(format!("Function '{}' expects 1 argument(s), got {}.", name, args.len()));
+    };
+    let Value::String(text) = text else {
+        return Err(format!("'{}' expects a string.", name));
+    };
+    match name {
+        "toml_parse" => text.parse::<toml::Table>().map(|table| table_value(&table)).map_err(|e| {
+            let at = e.span().map_or(String::new(), |span| {
+                let before = &text[..span.start];
+                let line = before.matches('\n').count() + 1;
+                let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+                format!(" at line {} column {}", line, column)
+            });
+            let message = e.message().trim_end().replace('\n', "; ");
+            format!("Invalid TOML{}: {}", at, if message.is_empty() { "unexpected text" } else { &message })
+        }),
+        _ => {
+            let documents = YamlLoader::load_from_str(text).map_err(|e| format!("Invalid YAML: {}", e))?;
+            documents.first().map_or(Ok(Value::Nil), yaml_value)
+        }
+    }
+}
+
+fn table_value(table: &toml::Table) -> Value {
+    let mut map = Map::ordered();
+    for (key, value) in table {
+        map.insert(MapKey::String(key.clone()), toml_value(value));
+    }
+    Value::Map(map)
+}
+
+fn toml_value(value: &Toml) -> Value {
+    match value {
+        Toml::String(s) => Value::String(s.clone()),
+        Toml::Integer(v) => Value::Int(*v),
+        Toml::Float(v) => Value::Float(*v),
+        Toml::Boolean(b) => Value::Bool(*b),
+        Toml::Datetime(datetime) => Value::String(datetime.to_string()),
+        Toml::Array(items) => Value::Array(items.iter().map(toml_value).collect()),
+        Toml::Table(table) => table_value(table),
+    }
+}
+
+fn yaml_value(value: &Yaml) -> Result<Value, String> {
+    Ok(match value {
+        Yaml::String(s) => Value::String(s.clone()),
+        Yaml::Integer(v) => Value::Int(*v),
+        Yaml::Real(_) => Value::Float(value.as_f64().ok_or("Invalid YAML: a float out of range.")?),
+        Yaml::Boolean(b) => Value::Bool(*b),
+        Yaml::Null => Value::Nil,
+        Yaml::Array(items) => Value::Array(items.iter().map(yaml_value).collect::<Result<_, _>>()?),
+        Yaml::Hash(entries) => {
+            let mut map = Map::ordered();
+            for (key, value) in entries {
+                let key = match key {
+                    Yaml::String(s) => MapKey::String(s.clone()),
+                    Yaml::Integer(v) => MapKey::Int(*v),
+                    Yaml::Boolean(b) => MapKey::Bool(*b),
+                    _ => return Err("Invalid YAML: map keys have to be strings, ints or bools.".to_string()),
+                };
+                map.insert(key, yaml_value(value)?);
+            }
+            Value::Map(map)
+        }
+        // The loader replaces each alias with what its anchor holds.
+        Yaml::Alias(_) | Yaml::BadValue => return Err("Invalid YAML: an alias without an anchor.".to_string()),
+    })
+}
@@ -0,0 +1,227 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use vira_syntax::ast::BinOp;
+
+// Exact base-10 numbers for money: `units / 10^scale`. Addition and
+// subtraction keep the larger scale and multiplication adds the scales, so
+// none of them round. Division rounds half-even, and `round` rounds in the
+// mode it is given.
+
+pub const MAX_SCALE: u32 = 18;
+// Places a quotient keeps beyond the larger scale of its operands.
+const DIVISION_PLACES: u32 = 6;
+
+#[derive(Clone, Copy)]
+pub struct Decimal {
+    units: i128,
+    scale: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    HalfEven,
+    HalfUp,
+    // Towards zero.
+    Down,
+    // Away from zero.
+    Up,
+    Floor,
+    Ceiling,
+}
+
+impl Rounding {
+    pub fn parse(name: &str) -> Result<Rounding, String> {
+        match name {
+            "half_even" => Ok(Rounding::HalfEven),
+            "half_up" => Ok(Rounding::HalfUp),
+            "down" => Ok(Rounding::Down),
+            "up" => Ok(Rounding::Up),
+            "floor" => Ok(Rounding::Floor),
+            "ceiling" => Ok(Rounding::Ceiling),
+            _ => Err(format!("Unknown rounding mode '{}'; expected half_even, half_up, down, up, floor or ceiling.", name)),
+        }
+    }
+}
+
+fn overflow() -> String {
+    "Decimal overflow.".to_string()
+}
+
+fn power_of_ten(exponent: u32) -> Result<i128, String> {
+    10i128.checked_pow(exponent).ok_or_else(overflow)
+}
+
+// `n / d` rounded to an integer in `mode`.
+fn divide(n: i128, d: i128, mode: Rounding) -> i128 {
+    let quotient = n / d;
+    let remainder = n % d;
+    if remainder == 0 {
+        return quotient;
+    }
+    let negative = (n < 0) != (d < 0);
+    let away = match mode {
+        Rounding::Down => false,
+        Rounding::Up => true,
+        Rounding::Floor => negative,
+        Rounding::Ceiling => !negative,
+        Rounding::HalfUp | Rounding::HalfEven => match (remainder.unsigned_abs() * 2).cmp(&d.unsigned_abs()) {
+            Ordering::Greater => true,
+            Ordering::Less => false,
+            Ordering::Equal => mode == Rounding::HalfUp || quotient % 2 != 0,
+        },
+    };
+    match (away, negative) {
+        (false, _) => quotient,
+        (true, true) => quotient - 1,
+        (true, false) => quotient + 1,
+    }
+}
+
+// The arithmetic can fail, so it returns a Result rather than implementing
+// the operator traits.
+#[allow(clippy::should_implement_trait)]
+impl Decimal {
+    pub fn from_int(value: i64) -> Decimal {
+        Decimal { units: value as i128, scale: 0 }
+    }
+
+    // `12.50`, `-3`, `.5`. The written scale is kept, so "1.50" prints as
+    // 1.50.
+    pub fn parse(text: &str) -> Result<Decimal, String> {
+        let invalid = || format!("'{}' is not a decimal number.", text);
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if whole.is_empty() && fraction.is_empty() || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        if fraction.len() > MAX_SCALE as usize {
+            return Err(format!("'{}' has more than {} decimal places.", text, MAX_SCALE));
+        }
+        let units: i128 = format!("{}{}", whole, fraction).parse().map_err(|_| overflow())?;
+        Ok(Decimal { units: if negative { -units } else { units }, scale: fraction.len() as u32 })
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    fn with_scale(self, scale: u32) -> Result<i128, String> {
+        self.units.checked_mul(power_of_ten(scale - self.scale)?).ok_or_else(overflow)
+    }
+
+    fn align(self, other: Decimal) -> Result<(i128, i128, u32), String> {
+        let scale = self.scale.max(other.scale);
+        Ok((self.with_scale(scale)?, other.with_scale(scale)?, scale))
+    }
+
+    pub fn neg(self) -> Result<Decimal, String> {
+        Ok(Decimal { units: self.units.checked_neg().ok_or_else(overflow)?, scale: self.scale })
+    }
+
+    pub fn add(self, other: Decimal) -> Result<Decimal, String> {
+        let (a, b, scale) = self.align(other)?;
+        Ok(Decimal { units: a.checked_add(b).ok_or_else(overflow)?, scale })
+    }
+
+    pub fn sub(self, other: Decimal) -> Result<Decimal, String> {
+        let (a, b, scale) = self.align(other)?;
+        Ok(Decimal { units: a.checked_sub(b).ok_or_else(overflow)?, scale })
+    }
+
+    pub fn mul(self, other: Decimal) -> Result<Decimal, String> {
+        let product = Decimal { units: self.units.checked_mul(other.units).ok_or_else(overflow)?, scale: self.scale + other.scale };
+        if product.scale > MAX_SCALE {
+            return product.round(MAX_SCALE, Rounding::HalfEven);
+        }
+        Ok(product)
+    }
+
+    pub fn div(self, other: Decimal) -> Result<Decimal, String> {
+        if other.units == 0 {
+            return Err("Division by zero.".to_string());
+        }
+        let scale = (self.scale.max(other.scale) + DIVISION_PLACES).min(MAX_SCALE);
+        // units / 10^scale = (a / 10^sa) / (b / 10^sb)
+        let numerator = self.units.checked_mul(power_of_ten(scale + other.scale - self.scale)?).ok_or_else(overflow)?;
+        Ok(Decimal { units: divide(numerator, other.units, Rounding::HalfEven), scale })
+    }
+
+    pub fn rem(self, other: Decimal) -> Result<Decimal, String> {
+        if other.units == 0 {
+            return Err("Division by zero.".to_string());
+        }
+        let (a, b, scale) = self.align(other)?;
+        Ok(Decimal { units: a % b, scale })
+    }
+
+    // To `places` decimal places; more places than the value has only pads.
+    pub fn round(self, places: u32, mode: Rounding) -> Result<Decimal, String> {
+        if places > MAX_SCALE {
+            return Err(format!("A decimal has at most {} places.", MAX_SCALE));
+        }
+        if places >= self.scale {
+            return Ok(Decimal { units: self.with_scale(places)?, scale: places });
+        }
+        Ok(Decimal { units: divide(self.units, power_of_ten(self.scale - places)?, mode), scale: places })
+    }
+}
+
+// `a op b` for the arithmetic operators; None for the others.
+pub fn arith(a: Decimal, op: BinOp, b: Decimal) -> Option<Result<Decimal, String>> {
+    match op {
+        BinOp::Add => Some(a.add(b)),
+        BinOp::Sub => Some(a.sub(b)),
+        BinOp::Mul => Some(a.mul(b)),
+        BinOp::Div => Some(a.div(b)),
+        BinOp::Mod => Some(a.rem(b)),
+        _ => None,
+    }
+}
+
+// Equal values compare equal whatever their scales: 1.5 == 1.50.
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Whole parts first, then the fractions at the largest scale, which
+        // cannot overflow.
+        let whole = |d: &Decimal| d.units / 10i128.pow(d.scale);
+        let fraction = |d: &Decimal| d.units % 10i128.pow(d.scale) * 10i128.pow(MAX_SCALE - d.scale);
+        whole(self).cmp(&whole(other)).then_with(|| fraction(self).cmp(&fraction(other)))
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = format!("{:0>width$}", self.units.unsigned_abs(), width = self.scale as usize + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - self.scale as usize);
+        let sign = if self.units < 0 { "-" } else { "" };
+        if fraction.is_empty() {
+            write!(f, "{}{}", sign, whole)
+        } else {
+            write!(f, "{}{}.{}", sign, whole, fraction)
+        }
+    }
+}
+
+impl fmt::Debug for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
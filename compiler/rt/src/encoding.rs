@@ -0,0 +1,98 @@
+use crate::builtins;
+use crate::interpreter::Value;
+
+// Text forms of bytes, for web APIs and binary protocols:
+//
+//   base64_encode(data)         base64_encode(data, "url")
+//   base64_decode(text)         base64_decode(text, "url")
+//   hex_encode(data)            hex_decode(text)
+//   from_utf8(bytes)
+//
+// `data` is a string, taken as its UTF-8, or an array of ints from 0 to 255.
+// The decoders give back such an array, or nil when the text is not valid,
+// so that callers handle bad input with `??`. `from_utf8` makes a string of
+// bytes, or nil when they are not UTF-8.
+//
+// The "url" alphabet uses `-` and `_` for `+` and `/` and leaves out the `=`
+// padding, as JWTs do; the decoder takes text with or without the padding.
+
+const STANDARD: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+    match (name, args) {
+        ("base64_encode", [data]) => Ok(Value::String(base64_encode(&builtins::bytes(name, data)?, false))),
+        ("base64_encode", [data, Value::String(alphabet)]) => Ok(Value::String(base64_encode(&builtins::bytes(name, data)?, url(alphabet)?))),
+        ("base64_decode", [Value::String(text)]) => Ok(decoded(base64_decode(text, false))),
+        ("base64_decode", [Value::String(text), Value::String(alphabet)]) => Ok(decoded(base64_decode(text, url(alphabet)?))),
+        ("hex_encode", [data]) => Ok(Value::String(builtins::bytes(name, data)?.iter().map(|byte| format!("{:02x}", byte)).collect())),
+        ("hex_decode", [Value::String(text)]) => Ok(decoded(hex_decode(text))),
+        ("from_utf8", [data @ Value::Array(_)]) => Ok(String::from_utf8(builtins::bytes(name, data)?).map_or(Value::Nil, Value::String)),
+        ("base64_encode", [_, _]) => Err("'base64_encode' expects its alphabet as a string.".to_string()),
+        ("base64_decode", [_] | [_, _]) => Err("'base64_decode' expects a string, and an alphabet string.".to_string()),
+        ("hex_decode", [_]) => Err("'hex_decode' expects a string.".to_string()),
+        ("from_utf8", [_]) => Err("'from_utf8' expects an array of bytes.".to_string()),
+        ("base64_encode" | "base64_decode", _) => Err(format!("Function '{}' expects 1 or 2 argument(s), got {}.", name, args.len())),
+        _ => Err(format!("Function '{}' expects 1 argument(s), got {}.", name, args.len())),
+    }
+}
+
+fn url(alphabet: &str) -> Result<bool, String> {
+    match alphabet {
+        "standard" => Ok(false),
+        "url" => Ok(true),
+        _ => Err(format!("Unknown base64 alphabet '{}'; expected standard or url.", alphabet)),
+    }
+}
+
+// An array of the bytes, or nil.
+pub fn decoded(bytes: Option<Vec<u8>>) -> Value {
+    bytes.map_or(Value::Nil, |bytes| Value::Array(bytes.into_iter().map(|byte| Value::Int(byte as i64)).collect()))
+}
+
+fn base64_encode(bytes: &[u8], url: bool) -> String {
+    let alphabet = if url { URL } else { STANDARD };
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| group | (byte as u32) << (16 - 8 * i));
+        // Three bytes make four characters, and a short chunk one more
+        // character than it has bytes.
+        for i in 0..=chunk.len() {
+            text.push(alphabet[(group >> (18 - 6 * i) & 63) as usize] as char);
+        }
+        if !url {
+            text.push_str(&"=".repeat(3 - chunk.len()));
+        }
+    }
+    text
+}
+
+fn base64_decode(text: &str, url: bool) -> Option<Vec<u8>> {
+    let alphabet = if url { URL } else { STANDARD };
+    let unpadded = text.trim_end_matches('=');
+    // Padding only ever completes the last group of four.
+    if text.len() - unpadded.len() > 2 || (unpadded.len() != text.len() && !text.len().is_multiple_of(4)) || unpadded.len() % 4 == 1 {
+        return None;
+    }
+    let digits = unpadded.bytes().map(|c| alphabet.iter().position(|&digit| digit == c).map(|digit| digit as u32)).collect::<Option<Vec<_>>>()?;
+    let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+    for chunk in digits.chunks(4) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &digit)| group | digit << (18 - 6 * i));
+        let count = chunk.len() - 1;
+        // The bits after the last byte have to be zero, so that every
+        // string of bytes has one encoding.
+        if group & ((1 << (24 - 8 * count)) - 1) != 0 {
+            return None;
+        }
+        bytes.extend((0..count).map(|i| (group >> (16 - 8 * i)) as u8));
+    }
+    Some(bytes)
+}
+
+fn hex_decode(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    let digits = text.chars().map(|c| c.to_digit(16).map(|digit| digit as u8)).collect::<Option<Vec<_>>>()?;
+    Some(digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect())
+}
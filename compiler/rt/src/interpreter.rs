@@ -0,0 +1,1703 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
+use vira_syntax::arena::{Arena, ExprId, ItemId, NodeId, Position};
+use vira_syntax::ast::{AstNode, BinOp, Expr, Item, Pattern, Stmt, UnaryOp, Variant, ViraType};
+use vira_syntax::printer;
+use vira_syntax::visit::{walk, Visitor};
+
+use crate::builtins::{self, Effects, Receiver};
+use crate::decimal::{self, Decimal};
+use crate::interrupt;
+use crate::json;
+use crate::logging;
+use crate::numerics;
+use crate::profile::Profile;
+use crate::sorting;
+
+// Values behave as copies: assigning, passing or returning one never lets
+// two variables see each other's changes, so arrays and maps cannot alias.
+// `clone(v)` is still useful on user types, whose `clone` method it calls.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Decimal(Decimal),
+    // Real and imaginary parts.
+    Complex(f64, f64),
+    Bool(bool),
+    String(String),
+    Array(Vec<Value>),
+    Struct(String, Vec<(String, Value)>),
+    EnumVariant(String, String, Vec<Value>),
+    Range(i64, i64),
+    Function(Rc<Closure>),
+    Tuple(Vec<Value>),
+    Map(Map),
+    // The absent value of an optional. A present one is the value itself.
+    Nil,
+}
+
+// Map keys are limited to values with a total equality.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MapKey {
+    Int(i64),
+    Bool(bool),
+    String(String),
+    User(UserKey),
+}
+
+// A struct or enum key. Its `hash` method picks a bucket and its `eq` method
+// tells the keys of a bucket apart, which takes running the program, so the
+// interpreter resolves a key to its bucket and slot before the lookup.
+#[derive(Clone)]
+pub struct UserKey {
+    hash: i64,
+    slot: usize,
+    value: Box<Value>,
+}
+
+impl fmt::Debug for UserKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.value)
+    }
+}
+
+impl PartialEq for UserKey {
+    fn eq(&self, other: &Self) -> bool {
+        (self.hash, self.slot) == (other.hash, other.slot)
+    }
+}
+
+impl Eq for UserKey {}
+
+impl std::hash::Hash for UserKey {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        (self.hash, self.slot).hash(state);
+    }
+}
+
+impl PartialOrd for UserKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for UserKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.hash, self.slot).cmp(&(other.hash, other.slot))
+    }
+}
+
+impl MapKey {
+    pub fn from_value(value: &Value) -> Result<MapKey, String> {
+        match value {
+            Value::Int(v) => Ok(MapKey::Int(*v)),
+            Value::Bool(v) => Ok(MapKey::Bool(*v)),
+            Value::String(s) => Ok(MapKey::String(s.clone())),
+            _ => Err(format!("Map keys must be int, bool, string or a type with 'hash' and 'eq' methods, not {}.", value.type_name())),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            MapKey::Int(v) => Value::Int(*v),
+            MapKey::Bool(v) => Value::Bool(*v),
+            MapKey::String(s) => Value::String(s.clone()),
+            MapKey::User(key) => (*key.value).clone(),
+        }
+    }
+}
+
+impl fmt::Display for MapKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_value())
+    }
+}
+
+// The entries of a map. Maps print and iterate in key order, so output
+// built from them is the same on every run; one made by `ordered_map()`
+// keeps its keys in the order they were first set instead.
+#[derive(Clone, Default)]
+pub struct Map {
+    entries: HashMap<MapKey, Value>,
+    // The keys in insertion order, for an ordered map.
+    order: Option<Vec<MapKey>>,
+}
+
+impl fmt::Debug for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.entries()).finish()
+    }
+}
+
+// Two maps with the same entries are equal however they iterate.
+impl PartialEq for Map {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries
+    }
+}
+
+impl Map {
+    pub fn ordered() -> Map {
+        Map { entries: HashMap::new(), order: Some(Vec::new()) }
+    }
+
+    pub fn get(&self, key: &MapKey) -> Option<&Value> {
+        self.entries.get(key)
+    }
+
+    pub fn contains_key(&self, key: &MapKey) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn insert(&mut self, key: MapKey, value: Value) {
+        if let Some(order) = &mut self.order {
+            if !self.entries.contains_key(&key) {
+                order.push(key.clone());
+            }
+        }
+        self.entries.insert(key, value);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // In no particular order.
+    pub fn keys(&self) -> impl Iterator<Item = &MapKey> {
+        self.entries.keys()
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+        self.entries.values_mut()
+    }
+
+    // In iteration order.
+    pub fn entries(&self) -> Vec<(&MapKey, &Value)> {
+        match &self.order {
+            Some(order) => order.iter().filter_map(|key| self.entries.get_key_value(key)).collect(),
+            None => self.sorted_entries(),
+        }
+    }
+
+    pub fn sorted_entries(&self) -> Vec<(&MapKey, &Value)> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries
+    }
+}
+
+// A function value. Anonymous functions capture a snapshot of the variables
+// visible where they were created; named functions, nested ones included,
+// capture nothing. `module` is the namespace the body resolves names in.
+#[derive(Clone)]
+pub struct Closure {
+    params: Vec<String>,
+    body: NodeId,
+    captured: Option<HashMap<String, Value>>,
+    module: String,
+    // `@requires` and `@ensures` conditions, in source order.
+    contracts: Vec<(String, NodeId)>,
+}
+
+// A point in a run: the function being executed, the position reached in it
+// and the variables it saw. The REPL keeps one for each call a failed
+// evaluation unwound, and the debugger one for each statement executed.
+pub struct Frame {
+    pub function: String,
+    // How many calls deep the function is, the top level being 0.
+    pub depth: usize,
+    pub position: Option<Position>,
+    pub locals: Vec<(String, Value)>,
+}
+
+impl Value {
+    pub fn type_name(&self) -> String {
+        match self {
+            Value::Int(_) => "int".to_string(),
+            Value::Float(_) => "float".to_string(),
+            Value::Decimal(_) => "decimal".to_string(),
+            Value::Complex(..) => "complex".to_string(),
+            Value::Bool(_) => "bool".to_string(),
+            Value::String(_) => "string".to_string(),
+            Value::Array(items) => match items.first() {
+                Some(item) => format!("array<{}>", item.type_name()),
+                None => "array".to_string(),
+            },
+            Value::Struct(name, _) | Value::EnumVariant(name, _, _) => name.clone(),
+            Value::Range(_, _) => "range".to_string(),
+            Value::Function(_) => "func".to_string(),
+            Value::Tuple(values) if values.len() == 1 => format!("({},)", values[0].type_name()),
+            Value::Tuple(values) => format!("({})", values.iter().map(|v| v.type_name()).collect::<Vec<_>>().join(", ")),
+            Value::Map(map) => match map.entries().first() {
+                Some((key, value)) => format!("map<{}, {}>", key.to_value().type_name(), value.type_name()),
+                None => "map".to_string(),
+            },
+            Value::Nil => "nil".to_string(),
+        }
+    }
+}
+
+// Ints mix with decimals exactly.
+fn decimal_operand(value: &Value) -> Result<Decimal, String> {
+    match value {
+        Value::Decimal(d) => Ok(*d),
+        Value::Int(v) => Ok(Decimal::from_int(*v)),
+        _ => Err("Type mismatch in binary op.".to_string()),
+    }
+}
+
+// Ints and floats are complex numbers with no imaginary part.
+fn complex_operand(value: &Value) -> Result<(f64, f64), String> {
+    match value {
+        Value::Complex(re, im) => Ok((*re, *im)),
+        Value::Float(v) => Ok((*v, 0.0)),
+        Value::Int(v) => Ok((*v as f64, 0.0)),
+        _ => Err("Type mismatch in binary op.".to_string()),
+    }
+}
+
+fn compare(ordering: Ordering, op: BinOp) -> bool {
+    match op {
+        BinOp::Lt => ordering.is_lt(),
+        BinOp::Gt => ordering.is_gt(),
+        BinOp::Le => ordering.is_le(),
+        _ => ordering.is_ge(),
+    }
+}
+
+fn join(values: &[Value]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(v) => write!(f, "{}", v),
+            Value::Float(v) => write!(f, "{:?}", v),
+            Value::Decimal(v) => write!(f, "{}", v),
+            Value::Complex(re, im) if *im < 0.0 => write!(f, "{:?}-{:?}i", re, -im),
+            Value::Complex(re, im) => write!(f, "{:?}+{:?}i", re, im),
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::String(s) => write!(f, "\"{}\"", s),
+            Value::Array(items) => write!(f, "[{}]", join(items)),
+            Value::Struct(name, fields) => {
+                let fields: Vec<String> = fields.iter().map(|(field, value)| format!("{}: {}", field, value)).collect();
+                write!(f, "{} {{ {} }}", name, fields.join(", "))
+            }
+            Value::EnumVariant(name, variant, payload) if payload.is_empty() => write!(f, "{}::{}", name, variant),
+            Value::EnumVariant(name, variant, payload) => write!(f, "{}::{}({})", name, variant, join(payload)),
+            Value::Range(start, end) => write!(f, "{}..{}", start, end),
+            Value::Function(closure) => write!(f, "{:?}", closure),
+            Value::Tuple(values) if values.len() == 1 => write!(f, "({},)", values[0]),
+            Value::Tuple(values) => write!(f, "({})", join(values)),
+            Value::Map(map) => {
+                let entries: Vec<String> = map.entries().iter().map(|(key, value)| format!("{}: {}", key, value)).collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            Value::Nil => write!(f, "nil"),
+        }
+    }
+}
+
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "func({})", self.params.join(", "))
+    }
+}
+
+// Function values are only equal to themselves.
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+pub struct Interpreter {
+    variables: HashMap<String, Value>,
+    functions: HashMap<String, Rc<Closure>>,
+    structs: HashMap<String, Vec<(String, ViraType)>>,
+    enums: HashMap<String, Vec<Variant>>,
+    // Each trait's methods with their parameter counts, not counting `self`.
+    traits: HashMap<String, Vec<(String, usize)>>,
+    // Methods by implementing type and name. Calls are dispatched on the
+    // receiver's type at run time.
+    methods: HashMap<(String, String), Rc<Closure>>,
+    // The program's nodes. Shared so that `execute` can hold a node while
+    // it mutates the interpreter.
+    arena: Rc<Arena>,
+    profile: Profile,
+    // Set by `return` and taken by the enclosing call; blocks and loops stop
+    // executing while it is set.
+    returning: Option<Value>,
+    // Set by `throw`, which also fails with an "uncaught" error so that
+    // everything between it and the nearest `try` unwinds like any other
+    // error. The `try` takes the value back out.
+    throwing: Option<Value>,
+    // The module being executed ("" for the entry file) and the top-level
+    // variables of every imported module. Functions declared in a module are
+    // registered as `module::name`.
+    module: String,
+    module_globals: HashMap<String, HashMap<String, Value>>,
+    constants: HashSet<String>,
+    // Qualified names of the module items declared `pub`. Other modules can
+    // only reach those.
+    public: HashSet<String>,
+    effects: Effects,
+    // Set when evaluating at compile time, where a runaway program has to
+    // become an error instead of hanging or overflowing the compiler's stack.
+    limits: Option<Limits>,
+    steps: u64,
+    depth: usize,
+    // The lines `write` has printed, when they are kept instead, as doctests
+    // do to compare them with what the example expects.
+    output: Option<Vec<String>>,
+    // The frames the last failed evaluation unwound, innermost first, when
+    // they are kept, and where the error happened in the frame being left.
+    failed: Option<Vec<Frame>>,
+    failed_at: Option<Position>,
+    // Each statement of the entry module executed, with the number of lines
+    // written before it, when the run is recorded for the debugger.
+    trace: Option<Vec<(Frame, usize)>>,
+    function: String,
+    // Whether `readline` may read standard input.
+    stdin: bool,
+    // What the nodes visited and not yet used came to, innermost last; see
+    // `visit`.
+    evaluated: Vec<Result<Value, String>>,
+}
+
+// How many statements a recorded run keeps. The rest of the run still
+// executes, unrecorded.
+pub const TRACE_LIMIT: usize = 100_000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub steps: u64,
+    pub depth: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter::with_profile(Profile::debug(), Arena::new())
+    }
+
+    pub fn with_profile(profile: Profile, arena: Arena) -> Self {
+        Interpreter {
+            variables: HashMap::new(),
+            functions: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            traits: HashMap::new(),
+            methods: HashMap::new(),
+            arena: Rc::new(arena),
+            profile,
+            returning: None,
+            throwing: None,
+            module: String::new(),
+            module_globals: HashMap::new(),
+            constants: HashSet::new(),
+            public: HashSet::new(),
+            effects: Effects::Perform,
+            limits: None,
+            steps: 0,
+            depth: 0,
+            output: None,
+            failed: None,
+            failed_at: None,
+            trace: None,
+            function: "<top level>".to_string(),
+            stdin: true,
+            evaluated: Vec::new(),
+        }
+    }
+
+    // An interpreter for compile-time evaluation: effects and output are
+    // errors, and so is exceeding `limits`.
+    pub fn compile_time(limits: Limits, arena: Arena) -> Self {
+        let mut interp = Interpreter::with_profile(Profile::debug(), arena);
+        interp.effects = Effects::Forbid;
+        interp.limits = Some(limits);
+        interp
+    }
+
+    // The variables and functions declared so far, which the REPL's next
+    // line can use.
+    pub fn defined(&self) -> HashSet<String> {
+        self.variables.keys().chain(self.functions.keys()).cloned().collect()
+    }
+
+    // Where the REPL parses each new line. Functions declared so far only hold
+    // ids, which stay valid as nodes are added.
+    pub fn arena_mut(&mut self) -> &mut Arena {
+        Rc::make_mut(&mut self.arena)
+    }
+
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.effects = if dry_run { Effects::DryRun } else { Effects::Perform };
+    }
+
+    // Keeps what `write` prints from now on for `take_output` instead.
+    pub fn capture_output(&mut self) {
+        self.output.get_or_insert_with(Vec::new);
+    }
+
+    pub fn take_output(&mut self) -> Vec<String> {
+        self.output.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    // Keeps the frames an evaluation that fails unwinds, for `backtrace`.
+    // Off by default, as each one copies the variables of its call.
+    pub fn keep_failed_frames(&mut self) {
+        self.failed.get_or_insert_with(Vec::new);
+    }
+
+    // The frames of the last evaluation, innermost first, the top level
+    // last. Empty unless it failed.
+    pub fn backtrace(&self) -> &[Frame] {
+        self.failed.as_deref().unwrap_or_default()
+    }
+
+    // Makes `readline` fail, for a run whose standard input is not its own,
+    // as under `dap`, where it carries the protocol.
+    pub fn close_stdin(&mut self) {
+        self.stdin = false;
+    }
+
+    // Records each statement of the entry module from now on, for
+    // `take_trace`. Output is captured as well, to be shown as the trace is
+    // stepped through.
+    pub fn record_trace(&mut self) {
+        self.trace.get_or_insert_with(Vec::new);
+        self.capture_output();
+    }
+
+    // Ends the recording.
+    pub fn take_trace(&mut self) -> Vec<(Frame, usize)> {
+        self.trace.take().unwrap_or_default()
+    }
+
+    // Runs `program` with `locals` as its variables, as the debugger does
+    // for a breakpoint's condition at a recorded step.
+    pub fn interpret_with(&mut self, locals: &[(String, Value)], program: &[NodeId]) -> Result<Option<Value>, String> {
+        let saved = std::mem::replace(&mut self.variables, locals.iter().cloned().collect());
+        let result = self.interpret(program);
+        self.variables = saved;
+        result
+    }
+
+    fn frame(&self, position: Option<Position>) -> Frame {
+        let mut locals: Vec<(String, Value)> = self.variables.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+        locals.sort_by(|a, b| a.0.cmp(&b.0));
+        Frame { function: self.function.clone(), depth: self.depth, position, locals }
+    }
+
+    // Records that `stmt` is about to run. Declarations of functions and
+    // types are not steps, and modules other than the entry one have their
+    // positions in other files.
+    fn step(&mut self, stmt: NodeId) {
+        let Some(trace) = &self.trace else { return };
+        if trace.len() >= TRACE_LIMIT || !self.module.is_empty() {
+            return;
+        }
+        let arena = Rc::clone(&self.arena);
+        let (AstNode::Expr(_) | AstNode::Stmt(_) | AstNode::Item(Item::ConstDecl(..) | Item::ConfigDecl(_) | Item::DataDecl(..))) = &arena[arena.item(stmt)] else {
+            return;
+        };
+        let Some((position, _)) = arena.span(stmt) else { return };
+        let frame = self.frame(Some(position));
+        let written = self.output.as_ref().map_or(0, Vec::len);
+        if let Some(trace) = &mut self.trace {
+            trace.push((frame, written));
+        }
+    }
+
+    // Records the frame being left with an error, with what it can see.
+    fn unwind(&mut self, function: &str) {
+        let position = self.failed_at.take();
+        if self.failed.is_some() {
+            let frame = Frame { function: function.to_string(), ..self.frame(position) };
+            if let Some(failed) = &mut self.failed {
+                failed.push(frame);
+            }
+        }
+    }
+
+    // Runs a block's deferred statements, latest first, however the block
+    // exited. A pending return value survives them, and so does the first
+    // error, along with what it threw.
+    fn run_deferred(&mut self, deferred: &[NodeId], mut result: Result<Value, String>) -> Result<Value, String> {
+        let returning = self.returning.take();
+        let throwing = self.throwing.take();
+        let failed = result.is_err();
+        for &stmt in deferred.iter().rev() {
+            if let Err(e) = self.execute(stmt) {
+                result = result.and(Err(e));
+            }
+        }
+        self.returning = returning;
+        if failed {
+            self.throwing = throwing;
+        }
+        result
+    }
+
+    fn int_arith(&self, a: i64, b: i64, op: BinOp) -> Result<Value, String> {
+        let checked = match op {
+            BinOp::Add => a.checked_add(b),
+            BinOp::Sub => a.checked_sub(b),
+            BinOp::Mul => a.checked_mul(b),
+            BinOp::Div | BinOp::Mod if b == 0 => return Err("Division by zero.".to_string()),
+            BinOp::Div => a.checked_div(b),
+            BinOp::Mod => a.checked_rem(b),
+            _ => return Err("Type mismatch in binary op.".to_string()),
+        };
+        match checked {
+            Some(v) => Ok(Value::Int(v)),
+            None if self.profile.overflow_checks => Err("Integer overflow.".to_string()),
+            None => Ok(Value::Int(match op {
+                BinOp::Add => a.wrapping_add(b),
+                BinOp::Sub => a.wrapping_sub(b),
+                BinOp::Mul => a.wrapping_mul(b),
+                BinOp::Div => a.wrapping_div(b),
+                _ => a.wrapping_rem(b),
+            })),
+        }
+    }
+
+    // Returns the value of the last top-level node when it is an expression.
+    pub fn interpret(&mut self, ast: &[NodeId]) -> Result<Option<Value>, String> {
+        if let Some(failed) = &mut self.failed {
+            failed.clear();
+        }
+        self.failed_at = None;
+        self.hoist(ast)?;
+        let mut last = None;
+        for &node in ast {
+            interrupt::check()?;
+            self.step(node);
+            let value = match self.execute(node) {
+                Ok(value) => value,
+                Err(e) => {
+                    let module = if self.module.is_empty() { "<top level>".to_string() } else { self.module.clone() };
+                    self.unwind(&module);
+                    return Err(e);
+                }
+            };
+            if self.returning.take().is_some() {
+                return Ok(None);
+            }
+            last = self.arena[node].is_expression().then_some(value);
+        }
+        Ok(last)
+    }
+
+    // Defines the named functions among the statements before any of them
+    // runs, so that they can be called ahead of their declarations. Running
+    // a declaration again when it is reached defines the same function.
+    fn hoist(&mut self, stmts: &[NodeId]) -> Result<(), String> {
+        for &stmt in stmts {
+            if matches!(self.arena[self.arena.item(stmt)], AstNode::Item(Item::FuncDecl(..))) {
+                self.execute(stmt)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Runs an imported module in its own namespace. Its top-level variables are
+    // kept for `module::name` lookups and for its functions.
+    pub fn interpret_module(&mut self, name: &str, ast: &[NodeId]) -> Result<(), String> {
+        let saved = std::mem::take(&mut self.variables);
+        let saved_module = std::mem::replace(&mut self.module, name.to_string());
+        let result = self.interpret(ast);
+        let globals = std::mem::replace(&mut self.variables, saved);
+        self.module = saved_module;
+        self.module_globals.insert(name.to_string(), globals);
+        result.map(|_| ())
+    }
+
+    fn qualify(&self, name: &str) -> String {
+        if self.module.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}::{}", self.module, name)
+        }
+    }
+
+    fn call_function(&mut self, name: &str, func: &Closure, args: Vec<Value>) -> Result<Value, String> {
+        let env = self.environment(func);
+        self.call(name, func, args, env)
+    }
+
+    // What a function's body sees besides its parameters. Named functions see
+    // the caller's variables when called from their own module, and their
+    // module's top-level variables otherwise.
+    fn environment(&self, closure: &Closure) -> HashMap<String, Value> {
+        match &closure.captured {
+            Some(captured) => captured.clone(),
+            None if closure.module == self.module => self.variables.clone(),
+            None => self.module_globals.get(&closure.module).cloned().unwrap_or_default(),
+        }
+    }
+
+    // Calls a function value, from `f(x)(y)` or a builtin that takes one.
+    fn apply(&mut self, callee: &Value, args: Vec<Value>) -> Result<Value, String> {
+        let Value::Function(closure) = callee else {
+            return Err("Cannot call a non-function value.".to_string());
+        };
+        let env = self.environment(closure);
+        self.call("<anonymous>", closure, args, env)
+    }
+
+    // Runs `closure` in `env` extended with its parameters. The callee's
+    // locals are discarded afterwards, so they never leak into the caller.
+    fn call(&mut self, name: &str, closure: &Closure, args: Vec<Value>, mut env: HashMap<String, Value>) -> Result<Value, String> {
+        if closure.params.len() != args.len() {
+            return Err(format!("Function '{}' expects {} argument(s), got {}.", name, closure.params.len(), args.len()));
+        }
+        if self.limits.is_some_and(|limits| self.depth >= limits.depth) {
+            return Err(format!("Calls nested more than {} deep.", self.depth));
+        }
+        let checked = self.profile.contracts && !closure.contracts.is_empty();
+        let call = if checked { format!("{}({})", name, join(&args)) } else { String::new() };
+        env.extend(closure.params.iter().cloned().zip(args));
+        let saved = std::mem::replace(&mut self.variables, env);
+        let saved_module = std::mem::replace(&mut self.module, closure.module.clone());
+        let saved_function = std::mem::replace(&mut self.function, name.to_string());
+        self.depth += 1;
+        let precondition = if checked { self.check_contracts(closure, &call, None) } else { Ok(()) };
+        let result = precondition.and_then(|()| self.execute(closure.body));
+        let returned = self.returning.take();
+        let mut result = result.map(|value| returned.unwrap_or(value));
+        if let (true, Ok(value)) = (checked, &result) {
+            let value = value.clone();
+            self.variables.insert("result".to_string(), value.clone());
+            result = self.check_contracts(closure, &call, Some(&value)).map(|()| value);
+        }
+        if result.is_err() {
+            self.unwind(name);
+        }
+        self.depth -= 1;
+        self.variables = saved;
+        self.module = saved_module;
+        self.function = saved_function;
+        result
+    }
+
+    // Checks the `@requires` conditions before the body runs, or the
+    // `@ensures` ones once it has returned `returned`. Both see the
+    // parameters; `@ensures` also sees `result`.
+    fn check_contracts(&mut self, closure: &Closure, call: &str, returned: Option<&Value>) -> Result<(), String> {
+        let kind = if returned.is_some() { "ensures" } else { "requires" };
+        for (_, condition) in closure.contracts.iter().filter(|(contract, _)| contract == kind) {
+            match self.execute(*condition)? {
+                Value::Bool(true) => {}
+                Value::Bool(false) => {
+                    return Err(match returned {
+                        None => format!("Contract @requires({}) failed for the call {}.", printer::print(&self.arena, *condition), call),
+                        Some(value) => format!("Contract @ensures({}) failed: {} returned {}.", printer::print(&self.arena, *condition), call, value),
+                    })
+                }
+                other => return Err(format!("Contract @{}({}) must be a bool, got {}.", kind, printer::print(&self.arena, *condition), other.type_name())),
+            }
+        }
+        Ok(())
+    }
+
+    fn match_pattern(pattern: &Pattern, value: &Value, bindings: &mut Vec<(String, Value)>) -> bool {
+        match (pattern, value) {
+            (Pattern::Wildcard, _) => true,
+            (Pattern::Binding(name), _) => {
+                bindings.push((name.clone(), value.clone()));
+                true
+            }
+            (Pattern::Int(p), Value::Int(v)) => p == v,
+            (Pattern::Float(p), Value::Float(v)) => p == v,
+            (Pattern::Bool(p), Value::Bool(v)) => p == v,
+            (Pattern::String(p), Value::String(v)) => p == v,
+            (Pattern::EnumVariant(enum_name, variant, fields), Value::EnumVariant(value_enum, value_variant, payload)) => {
+                enum_name == value_enum
+                    && variant == value_variant
+                    && fields.len() == payload.len()
+                    && fields.iter().zip(payload).all(|(p, v)| Self::match_pattern(p, v, bindings))
+            }
+            _ => false,
+        }
+    }
+
+    fn field(value: Value, field: &str) -> Result<Value, String> {
+        match value {
+            Value::Struct(name, fields) => fields
+                .into_iter()
+                .find(|(f, _)| f == field)
+                .map(|(_, v)| v)
+                .ok_or(format!("Struct '{}' has no field '{}'.", name, field)),
+            Value::Nil => Err(format!("Cannot access field '{}' of nil; use '?.' to pass nil through.", field)),
+            _ => Err(format!("Cannot access field '{}' on non-struct value.", field)),
+        }
+    }
+
+    fn slice(value: Value, start: i64, end: i64) -> Result<Value, String> {
+        let len = match &value {
+            Value::Array(vec) => vec.len(),
+            Value::String(s) => s.chars().count(),
+            _ => return Err("Cannot slice non-array.".to_string()),
+        };
+        if start < 0 || end < start || end as usize > len {
+            return Err(format!("Slice {}..{} out of bounds for length {}.", start, end, len));
+        }
+        let (start, end) = (start as usize, end as usize);
+        match value {
+            Value::Array(vec) => Ok(Value::Array(vec.into_iter().skip(start).take(end - start).collect())),
+            Value::String(s) => Ok(Value::String(s.chars().skip(start).take(end - start).collect())),
+            _ => Err("Cannot slice non-array.".to_string()),
+        }
+    }
+
+    fn execute(&mut self, id: NodeId) -> Result<Value, String> {
+        let arena = Rc::clone(&self.arena);
+        self.visit(&arena, id);
+        self.evaluated.pop().unwrap_or(Ok(Value::Nil))
+    }
+
+    // The values of a node's children, evaluated in order up to the first
+    // that fails.
+    fn operands(&mut self, id: NodeId) -> Result<Vec<Value>, String> {
+        let arena = Rc::clone(&self.arena);
+        let from = self.evaluated.len();
+        walk(self, &arena, id);
+        self.evaluated.split_off(from).into_iter().collect()
+    }
+
+    fn evaluate(&mut self, arena: &Arena, id: NodeId) -> Result<Value, String> {
+        if let Some(limits) = self.limits {
+            self.steps += 1;
+            if self.steps > limits.steps {
+                return Err(format!("Gave up after {} evaluation steps.", limits.steps));
+            }
+        }
+        let result = match &arena[id] {
+            AstNode::Expr(expr) => self.expr(id, expr),
+            AstNode::Stmt(stmt) => self.stmt(stmt),
+            AstNode::Item(item) => self.item(item),
+        };
+        // The innermost node with a position is where the error happened.
+        if result.is_err() && self.failed_at.is_none() {
+            self.failed_at = arena.span(id).map(|(start, _)| start);
+        }
+        result
+    }
+
+    fn expr(&mut self, id: NodeId, expr: &Expr) -> Result<Value, String> {
+        let arena = Rc::clone(&self.arena);
+        match expr {
+            Expr::Literal(val) => Ok(Value::Int(*val)),
+            Expr::FloatLiteral(val) => Ok(Value::Float(*val)),
+            Expr::BoolLiteral(val) => Ok(Value::Bool(*val)),
+            Expr::StringLiteral(s) => Ok(Value::String(s.clone())),
+            Expr::Nil => Ok(Value::Nil),
+            Expr::Coalesce(left, right) => match self.execute(left.node())? {
+                Value::Nil => self.execute(right.node()),
+                value => Ok(value),
+            },
+            Expr::Binary(left, op, right) => {
+                let l = self.execute(left.node())?;
+                let r = self.execute(right.node())?;
+                // A comparison with nil checks for nil, not with `eq`.
+                if let (Value::Struct(..) | Value::EnumVariant(..), Some(method)) = (&l, op.method().filter(|_| !matches!(r, Value::Nil))) {
+                    let result = self.call_operator(l, method, r)?;
+                    return match (result, op) {
+                        (Value::Bool(equal), BinOp::Neq) => Ok(Value::Bool(!equal)),
+                        (result, _) => Ok(result),
+                    };
+                }
+                // Where the checker could not tell the operand types, as for
+                // a type variable, an int meets a float as the conversion it
+                // inserts would have it.
+                let (l, r) = match (l, r) {
+                    (Value::Int(a), r @ Value::Float(_)) => (Value::Float(a as f64), r),
+                    (l @ Value::Float(_), Value::Int(b)) => (l, Value::Float(b as f64)),
+                    operands => operands,
+                };
+                match (l, r, op) {
+                    (Value::Int(a), Value::Int(b), BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod) => {
+                        self.int_arith(a, b, *op)
+                    }
+                    (Value::Float(a), Value::Float(b), BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod) => Ok(Value::Float(match op {
+                        BinOp::Add => a + b,
+                        BinOp::Sub => a - b,
+                        BinOp::Mul => a * b,
+                        BinOp::Div => a / b,
+                        _ => a % b,
+                    })),
+                    (Value::String(a), Value::String(b), BinOp::Add) => Ok(Value::String(a + &b)),
+                    (l @ (Value::Decimal(_) | Value::Int(_)), r @ (Value::Decimal(_) | Value::Int(_)), op)
+                        if matches!(l, Value::Decimal(_)) || matches!(r, Value::Decimal(_)) =>
+                    {
+                        let (a, b) = (decimal_operand(&l)?, decimal_operand(&r)?);
+                        match decimal::arith(a, *op, b) {
+                            Some(result) => result.map(Value::Decimal),
+                            None => match op {
+                                BinOp::Eq => Ok(Value::Bool(a == b)),
+                                BinOp::Neq => Ok(Value::Bool(a != b)),
+                                BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge => Ok(Value::Bool(compare(a.cmp(&b), *op))),
+                                _ => Err("Type mismatch in binary op.".to_string()),
+                            },
+                        }
+                    }
+                    (Value::Decimal(_), Value::Float(_), _) | (Value::Float(_), Value::Decimal(_), _) => {
+                        Err("Cannot mix decimal and float; make the float a decimal(\"...\").".to_string())
+                    }
+                    (l @ (Value::Complex(..) | Value::Float(_) | Value::Int(_)), r @ (Value::Complex(..) | Value::Float(_) | Value::Int(_)), op)
+                        if matches!(l, Value::Complex(..)) || matches!(r, Value::Complex(..)) =>
+                    {
+                        let (a, b) = (complex_operand(&l)?, complex_operand(&r)?);
+                        match (numerics::complex_arith(a, *op, b), op) {
+                            (Some((re, im)), _) => Ok(Value::Complex(re, im)),
+                            (None, BinOp::Eq) => Ok(Value::Bool(a == b)),
+                            (None, BinOp::Neq) => Ok(Value::Bool(a != b)),
+                            (None, BinOp::Mod) => Err("Complex numbers have no remainder.".to_string()),
+                            _ => Err("Complex numbers have no ordering.".to_string()),
+                        }
+                    }
+                    (Value::Int(a), Value::Int(b), BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge) => Ok(Value::Bool(compare(a.cmp(&b), *op))),
+                    (Value::Float(a), Value::Float(b), BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge) => {
+                        Ok(Value::Bool(a.partial_cmp(&b).is_some_and(|ordering| compare(ordering, *op))))
+                    }
+                    (l, r, BinOp::Eq | BinOp::Neq) if l.type_name() == r.type_name() || l == Value::Nil || r == Value::Nil => Ok(Value::Bool((l == r) == matches!(op, BinOp::Eq))),
+                    _ => Err("Type mismatch in binary op.".to_string()),
+                }
+            }
+            Expr::Unary(op, right) => {
+                let r = self.execute(right.node())?;
+                match (op, r) {
+                    (UnaryOp::Neg, Value::Int(v)) => match v.checked_neg() {
+                        Some(n) => Ok(Value::Int(n)),
+                        None if self.profile.overflow_checks => Err("Integer overflow.".to_string()),
+                        None => Ok(Value::Int(v.wrapping_neg())),
+                    },
+                    (UnaryOp::Neg, Value::Float(v)) => Ok(Value::Float(-v)),
+                    (UnaryOp::Neg, Value::Decimal(v)) => v.neg().map(Value::Decimal),
+                    (UnaryOp::Neg, Value::Complex(re, im)) => Ok(Value::Complex(-re, -im)),
+                    (UnaryOp::Not, Value::Bool(v)) => Ok(Value::Bool(!v)),
+                    _ => Err("Invalid unary op.".to_string()),
+                }
+            }
+            Expr::IntToFloat(operand) => match self.execute(operand.node())? {
+                Value::Int(v) => Ok(Value::Float(v as f64)),
+                other => Err(format!("Cannot convert {} to float.", other.type_name())),
+            },
+            // A named function used as a value.
+            Expr::VarRef(name) => match self.variables.get(name) {
+                Some(value) => Ok(value.clone()),
+                None => match self.functions.get(&self.qualify(name)).or_else(|| self.functions.get(name)) {
+                    Some(func) => Ok(Value::Function(func.clone())),
+                    None => Err("Undefined variable.".to_string()),
+                },
+            },
+            Expr::Call(name, _) => {
+                let values = self.operands(id)?;
+                if let Some(Value::Function(closure)) = self.variables.get(name).cloned() {
+                    return self.call_function(name, &closure, values);
+                }
+                let func = self.functions.get(&self.qualify(name)).or_else(|| self.functions.get(name)).cloned();
+                match func {
+                    Some(func) => self.call_function(name, &func, values),
+                    None if name == "format" => match values.as_slice() {
+                        [value] => self.format(value).map(Value::String),
+                        _ => Err(format!("Function 'format' expects 1 argument(s), got {}.", values.len())),
+                    },
+                    None if name == "clone" => match <[Value; 1]>::try_from(values) {
+                        Ok([value]) => self.clone_value(value),
+                        Err(values) => Err(format!("Function 'clone' expects 1 argument(s), got {}.", values.len())),
+                    },
+                    None if name == "json" => match values.as_slice() {
+                        [value] => json::encode(value, &mut |value| self.user_json(value)).map(Value::String),
+                        _ => Err(format!("Function 'json' expects 1 argument(s), got {}.", values.len())),
+                    },
+                    None if name == "discriminant" => match values.as_slice() {
+                        [Value::EnumVariant(type_name, variant, _)] => self
+                            .enums
+                            .get(type_name)
+                            .and_then(|variants| variants.iter().find(|(v, ..)| v == variant))
+                            .map(|&(.., discriminant)| Value::Int(discriminant))
+                            .ok_or(format!("Enum '{}' has no variant '{}'.", type_name, variant)),
+                        [value] => Err(format!("'discriminant' expects an enum value, not {}.", value.type_name())),
+                        _ => Err(format!("Function 'discriminant' expects 1 argument(s), got {}.", values.len())),
+                    },
+                    None if name == "hash" => {
+                        let mut words = Vec::new();
+                        for value in values {
+                            words.push(match &value {
+                                Value::Struct(type_name, _) | Value::EnumVariant(type_name, _, _) => Value::Int(self.user_hash(&type_name.clone(), value)?),
+                                _ => value,
+                            });
+                        }
+                        builtins::call(name, words, self.effects).unwrap_or(Err(format!("Undefined function '{}'.", name)))
+                    }
+                    None if matches!(name.as_str(), "get" | "contains" | "set") && matches!(values.get(1), Some(Value::Struct(..) | Value::EnumVariant(..))) => {
+                        self.user_keyed(name, values)
+                    }
+                    None if name == "array_generate" => self.array_generate(values),
+                    None if logging::is_function(name) => logging::call(name, &values, self.effects, &mut |value| self.user_json(value)),
+                    None if sorting::is_function(name) => sorting::call(name, values, &mut |function, args| self.apply(function, args)),
+                    None if name == "readline" && !self.stdin => Err("'readline' cannot read here; standard input is not the program's.".to_string()),
+                    None => builtins::call(name, values, self.effects).unwrap_or(Err(format!("Undefined function '{}'.", name))),
+                }
+            }
+            Expr::Lambda(params, _, body) => Ok(Value::Function(Rc::new(Closure {
+                params: params.iter().map(|(p, _)| p.clone()).collect(),
+                body: body.node(),
+                captured: Some(self.variables.clone()),
+                module: self.module.clone(),
+                contracts: Vec::new(),
+            }))),
+            // Lowered to a concatenation before anything runs.
+            Expr::Interpolation(_) => Err("Unexpected string interpolation.".to_string()),
+            Expr::Apply(callee, args) => {
+                let callee = self.execute(callee.node())?;
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.execute(arg.node())?);
+                }
+                self.apply(&callee, values)
+            }
+            Expr::If(cond, then, else_) => {
+                if let Value::Bool(true) = self.execute(cond.node())? {
+                    self.execute(*then)
+                } else if let Some(e) = else_ {
+                    self.execute(*e)
+                } else {
+                    Ok(Value::Int(0))
+                }
+            }
+            Expr::Block(stmts) => {
+                self.hoist(stmts)?;
+                let mut result = Ok(Value::Int(0));
+                let mut deferred = Vec::new();
+                // The variables the block's own `let`s hide, with what they
+                // held, for when the block ends.
+                let mut shadowed: Vec<(String, Option<Value>)> = Vec::new();
+                for &stmt in stmts {
+                    let names = match &arena[stmt] {
+                        AstNode::Stmt(Stmt::Defer(stmt)) => {
+                            deferred.push(*stmt);
+                            result = Ok(Value::Int(0));
+                            continue;
+                        }
+                        AstNode::Stmt(Stmt::VarDecl(name, ..)) => std::slice::from_ref(name),
+                        AstNode::Stmt(Stmt::TupleDecl(names, ..)) => names.as_slice(),
+                        _ => &[],
+                    };
+                    for name in names {
+                        if !shadowed.iter().any(|(shadowed, _)| shadowed == name) {
+                            shadowed.push((name.clone(), self.variables.get(name).cloned()));
+                        }
+                    }
+                    self.step(stmt);
+                    result = interrupt::check().and_then(|_| self.execute(stmt));
+                    if result.is_err() || self.returning.is_some() {
+                        break;
+                    }
+                }
+                let result = self.run_deferred(&deferred, result);
+                for (name, previous) in shadowed.into_iter().rev() {
+                    match previous {
+                        Some(v) => self.variables.insert(name, v),
+                        None => self.variables.remove(&name),
+                    };
+                }
+                result
+            }
+            Expr::ArrayLiteral(_) => Ok(Value::Array(self.operands(id)?)),
+            Expr::Index(arr, idx) => {
+                let a = self.execute(arr.node())?;
+                let i = self.execute(idx.node())?;
+                if let Value::Struct(..) | Value::EnumVariant(..) = a {
+                    return self.call_operator(a, "index", i);
+                }
+                if let Value::Range(start, end) = i {
+                    return Self::slice(a, start, end);
+                }
+                if let Value::Map(map) = a {
+                    let key = self.map_key(&map, &i)?;
+                    return map.get(&key).cloned().ok_or(format!("Key {} is not in the map.", key));
+                }
+                // The ints of a range are worked out, not stored.
+                if let (Value::Range(start, end), Value::Int(index)) = (&a, &i) {
+                    return start.checked_add(*index).filter(|item| *index >= 0 && item < end).map(Value::Int).ok_or("Index out of bounds.".to_string());
+                }
+                if let Value::Array(vec) = a {
+                    if let Value::Int(index) = i {
+                        vec.get(index as usize).cloned().ok_or("Index out of bounds.".to_string())
+                    } else {
+                        Err("Index must be int.".to_string())
+                    }
+                } else {
+                    Err("Cannot index non-array.".to_string())
+                }
+            }
+            Expr::StructLiteral(name, inits) => {
+                let decl = self.structs.get(name).cloned().ok_or(format!("Undefined struct '{}'.", name))?;
+                for (field, _) in inits {
+                    if !decl.iter().any(|(f, _)| f == field) {
+                        return Err(format!("Struct '{}' has no field '{}'.", name, field));
+                    }
+                }
+                let mut fields = Vec::new();
+                for (field, _) in &decl {
+                    let init = inits
+                        .iter()
+                        .find(|(f, _)| f == field)
+                        .ok_or(format!("Missing field '{}' in '{}' literal.", field, name))?;
+                    fields.push((field.clone(), self.execute(init.1.node())?));
+                }
+                Ok(Value::Struct(name.clone(), fields))
+            }
+            Expr::FieldAccess(base, field) => {
+                let value = self.execute(base.node())?;
+                Self::field(value, field)
+            }
+            Expr::SafeAccess(base, field) => match self.execute(base.node())? {
+                Value::Nil => Ok(Value::Nil),
+                value => Self::field(value, field),
+            },
+            Expr::Tuple(_) => Ok(Value::Tuple(self.operands(id)?)),
+            Expr::MapLiteral(entries) => {
+                let mut map = Map::default();
+                for (key, value) in entries {
+                    let key = self.execute(key.node())?;
+                    let key = self.map_key(&map, &key)?;
+                    map.insert(key, self.execute(value.node())?);
+                }
+                Ok(Value::Map(map))
+            }
+            Expr::TupleIndex(base, index) => match self.execute(base.node())? {
+                Value::Tuple(values) => {
+                    let len = values.len();
+                    values
+                        .into_iter()
+                        .nth(*index)
+                        .ok_or(format!("Tuple index {} is out of range for a tuple of {} element(s).", index, len))
+                }
+                _ => Err(format!("Cannot take element {} of a non-tuple value.", index)),
+            },
+            Expr::Match(scrutinee, arms) => {
+                let value = self.execute(scrutinee.node())?;
+                for (pattern, body) in arms {
+                    let mut bindings = Vec::new();
+                    if !Self::match_pattern(pattern, &value, &mut bindings) {
+                        continue;
+                    }
+                    // Pattern bindings are only visible inside their arm.
+                    let mut shadowed = Vec::new();
+                    for (name, bound) in bindings {
+                        shadowed.push((name.clone(), self.variables.insert(name, bound)));
+                    }
+                    let result = self.execute(body.node());
+                    for (name, previous) in shadowed.into_iter().rev() {
+                        match previous {
+                            Some(v) => self.variables.insert(name, v),
+                            None => self.variables.remove(&name),
+                        };
+                    }
+                    return result;
+                }
+                Err(format!("No match arm matched value {:?}.", value))
+            }
+            Expr::Range(start, end) => match (self.execute(start.node())?, self.execute(end.node())?) {
+                (Value::Int(s), Value::Int(e)) => Ok(Value::Range(s, e)),
+                _ => Err("Range bounds must be int.".to_string()),
+            },
+            Expr::MethodCall(target, method, args) => {
+                let receiver = self.execute(target.node())?;
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.execute(arg.node())?);
+                }
+                let type_name = match &receiver {
+                    Value::Struct(name, _) | Value::EnumVariant(name, _, _) => name.clone(),
+                    other => other.type_name(),
+                };
+                if let Some(func) = self.methods.get(&(type_name.clone(), method.clone())).cloned() {
+                    if func.params.len() != values.len() + 1 {
+                        return Err(format!("Method '{}' expects {} argument(s), got {}.", method, func.params.len() - 1, values.len()));
+                    }
+                    values.insert(0, receiver);
+                    return self.call_function(method, &func, values);
+                }
+                if let Some(builtin) = Receiver::of_value(&receiver).and_then(|r| builtins::method(r, method)) {
+                    if builtin.params != values.len() {
+                        return Err(format!("Method '{}' expects {} argument(s), got {}.", method, builtin.params, values.len()));
+                    }
+                    values.insert(0, receiver);
+                    let result = builtins::call(builtin.builtin, values, self.effects).unwrap_or_else(|| Err(format!("Type '{}' has no method '{}'.", type_name, method)))?;
+                    if !builtins::mutates(builtin.builtin) {
+                        return Ok(result);
+                    }
+                    let Expr::VarRef(name) = &arena[*target] else {
+                        return Err(format!("'{}' updates its receiver, which must be a variable.", method));
+                    };
+                    if self.constants.contains(&self.qualify(name)) {
+                        return Err(format!("Cannot assign to constant '{}'.", name));
+                    }
+                    self.variables.insert(name.clone(), result);
+                    return Ok(Value::Int(0));
+                }
+                // A struct field holding a function value.
+                if let Value::Struct(_, fields) = &receiver {
+                    if let Some((_, Value::Function(closure))) = fields.iter().find(|(f, _)| f == method) {
+                        return self.call_function(method, closure, values);
+                    }
+                }
+                Err(format!("Type '{}' has no method '{}'.", type_name, method))
+            }
+            Expr::EnumConstructor(name, variant, args) if !self.enums.contains_key(name) && self.module_globals.contains_key(name) => {
+                self.module_member(name, variant, args)
+            }
+            Expr::EnumConstructor(name, variant, _) if !self.enums.contains_key(name) && name == numerics::MODULE => {
+                let values = self.operands(id)?;
+                numerics::call(&format!("{}::{}", name, variant), &values).unwrap_or_else(|| Err(format!("Module '{}' has no member '{}'.", name, variant)))
+            }
+            Expr::EnumConstructor(name, variant, args) => {
+                let variants = self.enums.get(name).ok_or(format!("Undefined enum '{}'.", name))?;
+                let (_, payload, _) = variants
+                    .iter()
+                    .find(|(v, ..)| v == variant)
+                    .ok_or(format!("Enum '{}' has no variant '{}'.", name, variant))?;
+                if payload.len() != args.len() {
+                    return Err(format!(
+                        "Variant '{}::{}' expects {} value(s), got {}.",
+                        name,
+                        variant,
+                        payload.len(),
+                        args.len()
+                    ));
+                }
+                Ok(Value::EnumVariant(name.clone(), variant.clone(), self.operands(id)?))
+            }
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Result<Value, String> {
+        match stmt {
+            Stmt::VarDecl(name, typ, init) => {
+                if self.constants.contains(&self.qualify(name)) {
+                    return Err(format!("Cannot redeclare constant '{}'.", name));
+                }
+                let value = self.execute(init.node())?;
+                match typ {
+                    Some(ViraType::Optional(_)) => {}
+                    Some(typ) if value == Value::Nil => return Err(format!("Cannot set '{}' of type {} to nil; declare it as {}?.", name, typ, typ)),
+                    None if value == Value::Nil => return Err(format!("Cannot infer the type of '{}' from nil; declare it as an optional type.", name)),
+                    _ => {}
+                }
+                self.variables.insert(name.clone(), value);
+                Ok(Value::Int(0))
+            }
+            Stmt::TupleDecl(names, _, init) => {
+                if let Some(name) = names.iter().find(|name| self.constants.contains(&self.qualify(name))) {
+                    return Err(format!("Cannot redeclare constant '{}'.", name));
+                }
+                match self.execute(init.node())? {
+                    Value::Tuple(values) if values.len() == names.len() => {
+                        for (name, value) in names.iter().zip(values) {
+                            self.variables.insert(name.clone(), value);
+                        }
+                        Ok(Value::Int(0))
+                    }
+                    Value::Tuple(values) => Err(format!(
+                        "Cannot destructure a tuple of {} element(s) into {} name(s).",
+                        values.len(),
+                        names.len()
+                    )),
+                    _ => Err("Cannot destructure a non-tuple value.".to_string()),
+                }
+            }
+            Stmt::Assign(name, value) => {
+                if self.constants.contains(&self.qualify(name)) {
+                    return Err(format!("Cannot assign to constant '{}'.", name));
+                }
+                if !self.variables.contains_key(name) {
+                    return Err(format!("Undefined variable '{}'.", name));
+                }
+                let value = self.execute(value.node())?;
+                self.variables.insert(name.clone(), value);
+                Ok(Value::Int(0))
+            }
+            Stmt::While(cond, body) => {
+                while if let Value::Bool(c) = self.execute(cond.node())? { c } else { false } {
+                    interrupt::check()?;
+                    self.execute(*body)?;
+                    if self.returning.is_some() {
+                        break;
+                    }
+                }
+                Ok(Value::Int(0))
+            }
+            // Lowered before anything runs.
+            Stmt::For(..) => Err("Unexpected 'for' loop.".to_string()),
+            Stmt::ForIn(..) => Err("Unexpected 'for-in' loop.".to_string()),
+            Stmt::CompoundAssign(..) => Err("Unexpected compound assignment.".to_string()),
+            Stmt::Return(expr) => {
+                let value = if let Some(e) = expr { self.execute(e.node())? } else { Value::Int(0) };
+                self.returning = Some(value.clone());
+                Ok(value)
+            }
+            Stmt::Defer(_) => Err("'defer' is only allowed directly inside a block.".to_string()),
+            Stmt::Throw(value) => {
+                let value = self.execute(value.node())?;
+                let message = format!("Uncaught throw of {}.", value);
+                self.throwing = Some(value);
+                Err(message)
+            }
+            // Only thrown values are caught; runtime errors still end the run.
+            Stmt::Try(body, name, handler) => {
+                self.throwing = None;
+                let error = match self.execute(body.node()) {
+                    Ok(_) => return Ok(Value::Int(0)),
+                    Err(e) => e,
+                };
+                let Some(thrown) = self.throwing.take() else {
+                    return Err(error);
+                };
+                // Caught, so nothing failed.
+                if let Some(failed) = &mut self.failed {
+                    failed.clear();
+                }
+                self.failed_at = None;
+                let shadowed = self.variables.insert(name.clone(), thrown);
+                let result = self.execute(handler.node());
+                match shadowed {
+                    Some(v) => self.variables.insert(name.clone(), v),
+                    None => self.variables.remove(name),
+                };
+                result.map(|_| Value::Int(0))
+            }
+            Stmt::Write(expr) => {
+                let value = self.execute(expr.node())?;
+                if self.effects == Effects::Forbid {
+                    return Err("Cannot write output at compile time.".to_string());
+                }
+                let text = match self.render(&value)? {
+                    Some(text) => text,
+                    None => format!("{:?}", value),
+                };
+                match &mut self.output {
+                    Some(output) => output.push(text),
+                    None => println!("{}", text),
+                }
+                Ok(Value::Int(0))
+            }
+        }
+    }
+
+    fn item(&mut self, item: &Item) -> Result<Value, String> {
+        let arena = Rc::clone(&self.arena);
+        match item {
+            Item::ConstDecl(name, _, value) => {
+                let value = self.execute(value.node())?;
+                self.constants.insert(self.qualify(name));
+                self.variables.insert(name.clone(), value);
+                Ok(Value::Int(0))
+            }
+            Item::DataDecl(name, typ, _, contents) => {
+                let value = match typ {
+                    ViraType::Array(_) => Value::Array(contents.bytes().map(|byte| Value::Int(byte as i64)).collect()),
+                    _ => Value::String(contents.clone()),
+                };
+                self.constants.insert(self.qualify(name));
+                self.variables.insert(name.clone(), value);
+                Ok(Value::Int(0))
+            }
+            // The block is a constant struct named `config`.
+            Item::ConfigDecl(entries) => {
+                let mut fields = Vec::new();
+                for (key, value) in entries {
+                    fields.push((key.clone(), self.execute(value.node())?));
+                }
+                self.constants.insert(self.qualify("config"));
+                self.variables.insert("config".to_string(), Value::Struct("config".to_string(), fields));
+                Ok(Value::Int(0))
+            }
+            Item::FuncDecl(name, _, params, _, body) => {
+                let closure = Rc::new(Closure {
+                    params: params.iter().map(|(p, _)| p.clone()).collect(),
+                    body: *body,
+                    captured: None,
+                    module: self.module.clone(),
+                    contracts: Vec::new(),
+                });
+                // One declared inside a function is a local of that call.
+                if self.depth > 0 {
+                    self.variables.insert(name.clone(), Value::Function(closure));
+                } else {
+                    self.functions.insert(self.qualify(name), closure);
+                }
+                Ok(Value::Int(0))
+            }
+            // `@pure` is checked before the program runs. Contracts are
+            // attached to the function and checked on each call.
+            Item::Attribute(name, args, item) => {
+                let result = self.execute(*item)?;
+                if let ("requires" | "ensures", [condition], AstNode::Item(Item::FuncDecl(func, ..))) = (name.as_str(), args.as_slice(), &arena[arena.item(*item)]) {
+                    let qualified = self.qualify(func);
+                    let closure = match self.variables.get_mut(func) {
+                        Some(Value::Function(closure)) if self.depth > 0 => Some(closure),
+                        _ => self.functions.get_mut(&qualified),
+                    };
+                    if let Some(closure) = closure {
+                        Rc::make_mut(closure).contracts.insert(0, (name.clone(), condition.node()));
+                    }
+                }
+                Ok(result)
+            }
+            Item::StructDecl(name, fields) => {
+                self.structs.insert(name.clone(), fields.clone());
+                Ok(Value::Int(0))
+            }
+            Item::EnumDecl(name, variants) => {
+                self.enums.insert(name.clone(), variants.clone());
+                Ok(Value::Int(0))
+            }
+            Item::TraitDecl(name, methods) => {
+                let methods = methods.iter().map(|(method, params, _)| (method.clone(), params.len())).collect();
+                self.traits.insert(name.clone(), methods);
+                Ok(Value::Int(0))
+            }
+            Item::ImplDecl(trait_name, type_name, methods) => {
+                self.implement(trait_name.as_deref(), type_name, methods)?;
+                Ok(Value::Int(0))
+            }
+            Item::Pub(item) => {
+                if let AstNode::Item(Item::FuncDecl(name, ..)) | AstNode::Stmt(Stmt::VarDecl(name, ..)) | AstNode::Item(Item::ConstDecl(name, ..)) = &arena[arena.item(*item)] {
+                    self.public.insert(self.qualify(name));
+                }
+                self.execute(*item)
+            }
+            // Modules are loaded before the program runs, so an import has nothing
+            // left to do here. Aliases were replaced while parsing.
+            Item::Import(_) | Item::TypeAlias(..) => Ok(Value::Int(0)),
+        }
+    }
+
+    // Registers the methods of an impl block. Without a trait there is nothing
+    // to check them against.
+    fn implement(&mut self, trait_name: Option<&str>, type_name: &str, methods: &[ItemId]) -> Result<(), String> {
+        let required = match trait_name {
+            Some(trait_name) => Some(self.traits.get(trait_name).ok_or(format!("Undefined trait '{}'.", trait_name))?),
+            None => None,
+        };
+        if !self.structs.contains_key(type_name) && !self.enums.contains_key(type_name) {
+            return Err(format!("Undefined type '{}'.", type_name));
+        }
+        let mut closures = Vec::new();
+        for &method in methods {
+            let Item::FuncDecl(name, _, params, _, body) = &self.arena[method] else {
+                continue;
+            };
+            if let (Some(trait_name), Some(required)) = (trait_name, required) {
+                match required.iter().find(|(m, _)| m == name) {
+                    None => return Err(format!("Method '{}' is not part of trait '{}'.", name, trait_name)),
+                    Some((_, arity)) if params.len() != arity + 1 => {
+                        return Err(format!("Method '{}' of trait '{}' takes {} parameter(s) after self.", name, trait_name, arity))
+                    }
+                    Some(_) => {}
+                }
+            }
+            if self.methods.contains_key(&(type_name.to_string(), name.clone())) {
+                return Err(format!("Type '{}' already has a method '{}'.", type_name, name));
+            }
+            let closure = Closure {
+                params: params.iter().map(|(p, _)| p.clone()).collect(),
+                body: *body,
+                captured: None,
+                module: self.module.clone(),
+                contracts: Vec::new(),
+            };
+            closures.push((name.clone(), Rc::new(closure)));
+        }
+        if let (Some(trait_name), Some(required)) = (trait_name, required) {
+            if let Some((missing, _)) = required.iter().find(|(m, _)| !closures.iter().any(|(name, _)| name == m)) {
+                return Err(format!("Impl of '{}' for '{}' is missing method '{}'.", trait_name, type_name, missing));
+            }
+        }
+        for (name, closure) in closures {
+            self.methods.insert((type_name.to_string(), name), closure);
+        }
+        Ok(())
+    }
+
+    // How `write` and `format` show a value: structs and enums that define
+    // `to_string` show what it returns, at any depth. None when no such
+    // method is involved, which leaves the value to the default rendering.
+    fn render(&mut self, value: &Value) -> Result<Option<String>, String> {
+        match value {
+            Value::Struct(name, _) | Value::EnumVariant(name, _, _) if self.methods.contains_key(&(name.clone(), "to_string".to_string())) => {
+                let func = self.methods.get(&(name.clone(), "to_string".to_string())).cloned().ok_or("Missing 'to_string'.")?;
+                match self.call_function("to_string", &func, vec![value.clone()])? {
+                    Value::String(text) => Ok(Some(text)),
+                    other => Err(format!("'{}.to_string' returned {}, not a string.", name, other.type_name())),
+                }
+            }
+            Value::Array(items) => Ok(self.render_all(items)?.map(|parts| format!("[{}]", parts.join(", ")))),
+            Value::Tuple(items) if items.len() == 1 => Ok(self.render_all(items)?.map(|parts| format!("({},)", parts.join("")))),
+            Value::Tuple(items) => Ok(self.render_all(items)?.map(|parts| format!("({})", parts.join(", ")))),
+            Value::Struct(name, fields) => {
+                let values: Vec<Value> = fields.iter().map(|(_, value)| value.clone()).collect();
+                Ok(self.render_all(&values)?.map(|parts| {
+                    let fields: Vec<String> = fields.iter().zip(parts).map(|((field, _), part)| format!("{}: {}", field, part)).collect();
+                    format!("{} {{ {} }}", name, fields.join(", "))
+                }))
+            }
+            Value::EnumVariant(name, variant, payload) => Ok(self.render_all(payload)?.map(|parts| format!("{}::{}({})", name, variant, parts.join(", ")))),
+            Value::Map(map) => {
+                let (keys, values): (Vec<MapKey>, Vec<Value>) = map.entries().into_iter().map(|(key, value)| (key.clone(), value.clone())).unzip();
+                Ok(self.render_all(&values)?.map(|parts| {
+                    let entries: Vec<String> = keys.iter().zip(parts).map(|(key, part)| format!("{}: {}", key, part)).collect();
+                    format!("{{{}}}", entries.join(", "))
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // Every value rendered, or None when none of them has a rendering of its own.
+    fn render_all(&mut self, values: &[Value]) -> Result<Option<Vec<String>>, String> {
+        let mut parts = Vec::new();
+        let mut custom = false;
+        for value in values {
+            match self.render(value)? {
+                Some(part) => {
+                    custom = true;
+                    parts.push(part);
+                }
+                None => parts.push(value.to_string()),
+            }
+        }
+        Ok(custom.then_some(parts))
+    }
+
+    // `format(value)`: a string as it is, anything else as `render` or the
+    // default rendering shows it.
+    fn format(&mut self, value: &Value) -> Result<String, String> {
+        match value {
+            Value::String(text) => Ok(text.clone()),
+            _ => Ok(self.render(value)?.unwrap_or_else(|| value.to_string())),
+        }
+    }
+
+    // `array_generate(len, f)`: element `i` is `f(i)`.
+    fn array_generate(&mut self, values: Vec<Value>) -> Result<Value, String> {
+        let [len, function] = <[Value; 2]>::try_from(values)
+            .map_err(|values| format!("Function 'array_generate' expects 2 argument(s), got {}.", values.len()))?;
+        let (len, mut items) = builtins::array_storage("array_generate", &len)?;
+        for i in 0..len {
+            items.push(self.apply(&function, vec![Value::Int(i as i64)])?);
+        }
+        Ok(Value::Array(items))
+    }
+
+    // A copy of `value` in which every struct and enum with a `clone` method,
+    // however deeply nested, is replaced by what that method returns.
+    fn clone_value(&mut self, value: Value) -> Result<Value, String> {
+        let type_name = match &value {
+            Value::Struct(name, _) | Value::EnumVariant(name, _, _) => name.clone(),
+            _ => String::new(),
+        };
+        if let Some(func) = self.methods.get(&(type_name, "clone".to_string())).cloned() {
+            return self.call_function("clone", &func, vec![value]);
+        }
+        let mut each = |items: Vec<Value>| items.into_iter().map(|item| self.clone_value(item)).collect::<Result<Vec<_>, _>>();
+        Ok(match value {
+            Value::Array(items) => Value::Array(each(items)?),
+            Value::Tuple(items) => Value::Tuple(each(items)?),
+            Value::EnumVariant(name, variant, payload) => Value::EnumVariant(name, variant, each(payload)?),
+            Value::Struct(name, fields) => {
+                let (names, values): (Vec<String>, Vec<Value>) = fields.into_iter().unzip();
+                Value::Struct(name, names.into_iter().zip(each(values)?).collect())
+            }
+            Value::Map(mut map) => {
+                for value in map.values_mut() {
+                    *value = self.clone_value(std::mem::replace(value, Value::Nil))?;
+                }
+                Value::Map(map)
+            }
+            other => other,
+        })
+    }
+
+    // What the `to_json` method of a struct or enum returns.
+    fn user_json(&mut self, value: &Value) -> Result<String, String> {
+        let (Value::Struct(type_name, _) | Value::EnumVariant(type_name, _, _)) = value else {
+            return Err(format!("Cannot encode {} as JSON.", value.type_name()));
+        };
+        let func = self
+            .methods
+            .get(&(type_name.clone(), "to_json".to_string()))
+            .cloned()
+            .ok_or(format!("Type '{}' has no method 'to_json' to encode it as JSON; add @derive(json) to it.", type_name))?;
+        match self.call_function("to_json", &func, vec![value.clone()])? {
+            Value::String(text) => Ok(text),
+            other => Err(format!("Method 'to_json' must return a string, not {}.", other.type_name())),
+        }
+    }
+
+    // What the `hash` method of a struct or enum returns.
+    fn user_hash(&mut self, type_name: &str, value: Value) -> Result<i64, String> {
+        let func = self.key_method(type_name, "hash")?;
+        match self.call_function("hash", &func, vec![value])? {
+            Value::Int(hash) => Ok(hash),
+            other => Err(format!("Method 'hash' must return an int, not {}.", other.type_name())),
+        }
+    }
+
+    fn key_method(&self, type_name: &str, method: &str) -> Result<Rc<Closure>, String> {
+        self.methods
+            .get(&(type_name.to_string(), method.to_string()))
+            .cloned()
+            .ok_or(format!("Type '{}' needs a '{}' method to be hashed or used as a map key; add @derive(hash, eq) to it.", type_name, method))
+    }
+
+    // The key of `map` that `key` is equal to, found by its `hash` and `eq`
+    // methods when it is a struct or enum. A key not in the map gets the
+    // next free slot of its bucket.
+    fn map_key(&mut self, map: &Map, key: &Value) -> Result<MapKey, String> {
+        let (Value::Struct(name, _) | Value::EnumVariant(name, _, _)) = key else {
+            return MapKey::from_value(key);
+        };
+        let eq = self.key_method(name, "eq")?;
+        let hash = self.user_hash(name, key.clone())?;
+        let mut slot = 0;
+        for existing in map.keys() {
+            let MapKey::User(user) = existing else { continue };
+            if user.hash != hash {
+                continue;
+            }
+            if self.call_function("eq", &eq, vec![key.clone(), (*user.value).clone()])? == Value::Bool(true) {
+                return Ok(existing.clone());
+            }
+            slot = slot.max(user.slot + 1);
+        }
+        Ok(MapKey::User(UserKey { hash, slot, value: Box::new(key.clone()) }))
+    }
+
+    // `get`, `contains` and `set` with a struct or enum key.
+    fn user_keyed(&mut self, name: &str, values: Vec<Value>) -> Result<Value, String> {
+        match (name, values.as_slice()) {
+            ("get", [Value::Map(map), key]) => {
+                let key = self.map_key(map, key)?;
+                map.get(&key).cloned().ok_or(format!("Key {} is not in the map.", key))
+            }
+            ("contains", [Value::Map(map), key]) => Ok(Value::Bool(map.contains_key(&self.map_key(map, key)?))),
+            ("set", [Value::Map(map), key, value]) => {
+                let key = self.map_key(map, key)?;
+                let mut map = map.clone();
+                map.insert(key, value.clone());
+                Ok(Value::Map(map))
+            }
+            _ => builtins::call(name, values, self.effects).unwrap_or(Err(format!("Undefined function '{}'.", name))),
+        }
+    }
+
+    // An operator whose left operand is a struct or enum calls the method of
+    // that name on it.
+    fn call_operator(&mut self, receiver: Value, method: &str, operand: Value) -> Result<Value, String> {
+        let type_name = match &receiver {
+            Value::Struct(name, _) | Value::EnumVariant(name, _, _) => name.clone(),
+            other => other.type_name(),
+        };
+        let func = self
+            .methods
+            .get(&(type_name.clone(), method.to_string()))
+            .cloned()
+            .ok_or(format!("Type '{}' has no method '{}' for this operator.", type_name, method))?;
+        self.call_function(method, &func, vec![receiver, operand])
+    }
+
+    // `module::name(args)` calls a function of an imported module;
+    // `module::name` reads one of its top-level variables.
+    fn module_member(&mut self, module: &str, member: &str, args: &[ExprId]) -> Result<Value, String> {
+        let qualified = format!("{}::{}", module, member);
+        let exists = self.functions.contains_key(&qualified) || self.module_globals.get(module).is_some_and(|globals| globals.contains_key(member));
+        if exists && module != self.module && !self.public.contains(&qualified) {
+            return Err(format!("'{}' is private to module '{}'; declare it pub to use it from other modules.", member, module));
+        }
+        if let Some(func) = self.functions.get(&qualified).cloned() {
+            let mut values = Vec::new();
+            for arg in args {
+                values.push(self.execute(arg.node())?);
+            }
+            return self.call_function(&qualified, &func, values);
+        }
+        match self.module_globals.get(module).and_then(|globals| globals.get(member)) {
+            Some(value) if args.is_empty() => Ok(value.clone()),
+            _ => Err(format!("Module '{}' has no member '{}'.", module, member)),
+        }
+    }
+}
+
+// Evaluation is a visit: it leaves the node's value, or the error it failed
+// with, on `evaluated`, where `execute` and `operands` take it from. Within
+// a walk, once a child has failed the rest are not evaluated. A walk only
+// ever sees its own siblings on top, so `execute` never finds a failure
+// there that is not its own.
+impl<'a> Visitor<'a> for Interpreter {
+    fn visit(&mut self, arena: &'a Arena, id: NodeId) {
+        if self.evaluated.last().is_some_and(Result::is_err) {
+            return;
+        }
+        let result = self.evaluate(arena, id);
+        self.evaluated.push(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vira_syntax::parser::Parser;
+    use vira_syntax::tokenizer::tokenize;
+
+    use super::*;
+
+    const SAY: &str = "func say(n: int) -> int {\n    write n\n    return n\n}\n";
+
+    // What running `source` after `SAY` returns, and the lines it wrote.
+    fn run(source: &str) -> (Result<Option<Value>, String>, Vec<String>) {
+        let mut arena = Arena::new();
+        let program = match Parser::new(tokenize(&format!("{}{}", SAY, source)), &mut arena).parse() {
+            Ok(program) => program,
+            Err(errors) => return (Err(vira_syntax::diagnostic::render(&errors)), Vec::new()),
+        };
+        let mut interp = Interpreter::with_profile(Profile::debug(), arena);
+        interp.capture_output();
+        let result = interp.interpret(&program);
+        (result, interp.take_output())
+    }
+
+    #[test]
+    fn operands_are_evaluated_in_order() {
+        let (result, output) = run("(say(1), [say(2), say(3)])");
+        assert_eq!(result, Ok(Some(Value::Tuple(vec![Value::Int(1), Value::Array(vec![Value::Int(2), Value::Int(3)])]))));
+        assert_eq!(output, vec!["Int(1)", "Int(2)", "Int(3)"]);
+    }
+
+    #[test]
+    fn operands_stop_at_the_first_failure() {
+        let (result, output) = run("[say(1), 1 / 0, say(2)]");
+        assert_eq!(result, Err("Division by zero.".to_string()));
+        assert_eq!(output, vec!["Int(1)"]);
+    }
+}
@@ -0,0 +1,42 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Ctrl-C handling for running programs. The SIGINT handler only sets a flag;
+// the interpreter polls it at statement boundaries and loop iterations, and
+// compiled code polls it at loop back-edges.
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+const SIGINT: i32 = 2;
+
+extern "C" {
+    fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+}
+
+extern "C" fn on_sigint(_: i32) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+// Replaces the default SIGINT behaviour, which kills the process, for the
+// rest of the run.
+pub fn install() {
+    unsafe {
+        signal(SIGINT, on_sigint);
+    }
+}
+
+// Forgets a Ctrl-C pressed while nothing was running, e.g. at the REPL prompt.
+pub fn clear() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+pub fn check() -> Result<(), String> {
+    if INTERRUPTED.swap(false, Ordering::SeqCst) {
+        return Err("Interrupted.".to_string());
+    }
+    Ok(())
+}
+
+// The flag's address, which compiled code loads as a byte.
+pub fn flag_address() -> i64 {
+    INTERRUPTED.as_ptr() as i64
+}
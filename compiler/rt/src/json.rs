@@ -0,0 +1,306 @@
+use crate::interpreter::{MapKey, Value};
+
+// `json(value)` encodes a value as JSON text. Arrays and tuples become
+// arrays, maps with string keys become objects, whose members come in the
+// order the map iterates in, and nil becomes null.
+// Structs and enums are encoded by their `to_json` method, which `user`
+// calls; `@derive(json)` writes one from the fields.
+
+// Encodes a struct or enum.
+pub type User<'a> = dyn FnMut(&Value) -> Result<String, String> + 'a;
+
+pub fn encode(value: &Value, user: &mut User) -> Result<String, String> {
+    match value {
+        Value::Int(v) => Ok(v.to_string()),
+        Value::Float(v) if v.is_finite() => Ok(format!("{:?}", v)),
+        Value::Float(_) => Err("JSON has no NaN or infinity.".to_string()),
+        Value::Decimal(d) => Ok(d.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::String(s) => Ok(string(s)),
+        Value::Nil => Ok("null".to_string()),
+        Value::Array(items) | Value::Tuple(items) => {
+            let items = items.iter().map(|item| encode(item, user)).collect::<Result<Vec<_>, _>>()?;
+            Ok(format!("[{}]", items.join(", ")))
+        }
+        Value::Map(map) => {
+            let mut members = Vec::new();
+            for (key, value) in map.entries() {
+                let MapKey::String(key) = key else {
+                    return Err(format!("JSON object keys are strings, not {}.", key.to_value().type_name()));
+                };
+                members.push(format!("{}: {}", string(key), encode(value, user)?));
+            }
+            Ok(format!("{{{}}}", members.join(", ")))
+        }
+        Value::Struct(..) | Value::EnumVariant(..) => user(value),
+        _ => Err(format!("Cannot encode {} as JSON.", value.type_name())),
+    }
+}
+
+pub fn string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// A JSON document the compiler writes for other tools, such as `--emit ast`,
+// or reads from them, such as the requests of a debugger front end.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+pub fn object(members: Vec<(&str, Json)>) -> Json {
+    Json::Object(members.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+}
+
+impl Json {
+    // Two spaces per level, one member or element per line.
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, 0);
+        out
+    }
+
+    fn write(&self, out: &mut String, depth: usize) {
+        let indent = |out: &mut String, depth: usize| out.push_str(&"  ".repeat(depth));
+        match self {
+            Json::Null => out.push_str("null"),
+            Json::Bool(b) => out.push_str(&b.to_string()),
+            Json::Int(v) => out.push_str(&v.to_string()),
+            Json::Float(v) if v.is_finite() => out.push_str(&format!("{:?}", v)),
+            Json::Float(_) => out.push_str("null"),
+            Json::String(s) => out.push_str(&string(s)),
+            Json::Array(items) if items.is_empty() => out.push_str("[]"),
+            Json::Object(members) if members.is_empty() => out.push_str("{}"),
+            Json::Array(items) => {
+                out.push_str("[\n");
+                for (i, item) in items.iter().enumerate() {
+                    indent(out, depth + 1);
+                    item.write(out, depth + 1);
+                    out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                indent(out, depth);
+                out.push(']');
+            }
+            Json::Object(members) => {
+                out.push_str("{\n");
+                for (i, (key, value)) in members.iter().enumerate() {
+                    indent(out, depth + 1);
+                    out.push_str(&format!("{}: ", string(key)));
+                    value.write(out, depth + 1);
+                    out.push_str(if i + 1 < members.len() { ",\n" } else { "\n" });
+                }
+                indent(out, depth);
+                out.push('}');
+            }
+        }
+    }
+}
+
+impl Json {
+    // A member of an object.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Json::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> &[Json] {
+        match self {
+            Json::Array(items) => items,
+            _ => &[],
+        }
+    }
+
+    // On one line, as a protocol that frames each message by its length
+    // sends it.
+    pub fn compact(&self) -> String {
+        match self {
+            Json::Array(items) => format!("[{}]", items.iter().map(Json::compact).collect::<Vec<_>>().join(",")),
+            Json::Object(members) => {
+                let members: Vec<String> = members.iter().map(|(key, value)| format!("{}:{}", string(key), value.compact())).collect();
+                format!("{{{}}}", members.join(","))
+            }
+            scalar => scalar.pretty(),
+        }
+    }
+}
+
+// Reads a JSON document. Numbers without a fraction or exponent that fit an
+// i64 are ints.
+pub fn parse(text: &str) -> Result<Json, String> {
+    let mut reader = Reader { chars: text.char_indices().peekable(), text };
+    let value = reader.value()?;
+    reader.skip_whitespace();
+    match reader.chars.next() {
+        None => Ok(value),
+        Some((at, _)) => Err(format!("Unexpected text after the JSON value at offset {}.", at)),
+    }
+}
+
+struct Reader<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    text: &'a str,
+}
+
+impl Reader<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_ascii_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.chars.next() {
+            Some((_, c)) if c == expected => Ok(()),
+            Some((at, c)) => Err(format!("Expected '{}' but found '{}' at offset {}.", expected, c, at)),
+            None => Err(format!("Expected '{}' but the JSON ended.", expected)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        let Some(&(at, c)) = self.chars.peek() else {
+            return Err("Expected a JSON value but the text ended.".to_string());
+        };
+        match c {
+            '{' => {
+                self.chars.next();
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.chars.next_if(|(_, c)| *c == '}').is_some() {
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    self.expect(':')?;
+                    members.push((key, self.value()?));
+                    self.skip_whitespace();
+                    if self.chars.next_if(|(_, c)| *c == ',').is_none() {
+                        self.expect('}')?;
+                        return Ok(Json::Object(members));
+                    }
+                }
+            }
+            '[' => {
+                self.chars.next();
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.chars.next_if(|(_, c)| *c == ']').is_some() {
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    if self.chars.next_if(|(_, c)| *c == ',').is_none() {
+                        self.expect(']')?;
+                        return Ok(Json::Array(items));
+                    }
+                }
+            }
+            '"' => self.string().map(Json::String),
+            '-' | '0'..='9' => {
+                let mut end = at;
+                while let Some((i, c)) = self.chars.next_if(|(_, c)| matches!(c, '-' | '+' | '.' | 'e' | 'E' | '0'..='9')) {
+                    end = i + c.len_utf8();
+                }
+                let number = &self.text[at..end];
+                match number.parse::<i64>() {
+                    Ok(v) => Ok(Json::Int(v)),
+                    Err(_) => number.parse::<f64>().map(Json::Float).map_err(|_| format!("Malformed number '{}' at offset {}.", number, at)),
+                }
+            }
+            _ => {
+                for (word, value) in [("true", Json::Bool(true)), ("false", Json::Bool(false)), ("null", Json::Null)] {
+                    if self.text[at..].starts_with(word) {
+                        self.chars.nth(word.len() - 1);
+                        return Ok(value);
+                    }
+                }
+                Err(format!("Unexpected '{}' at offset {}.", c, at))
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                None => return Err("Unterminated JSON string.".to_string()),
+                Some((_, '"')) => return Ok(s),
+                Some((at, '\\')) => match self.chars.next().map(|(_, c)| c) {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => {
+                        let high = self.hex4(at)?;
+                        // A character outside the basic plane comes as a
+                        // surrogate pair.
+                        let code = if (0xD800..0xDC00).contains(&high) {
+                            self.expect('\\')?;
+                            self.expect('u')?;
+                            let low = self.hex4(at)?;
+                            0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF)
+                        } else {
+                            high
+                        };
+                        s.push(char::from_u32(code).ok_or(format!("Invalid escape at offset {}.", at))?);
+                    }
+                    _ => return Err(format!("Invalid escape at offset {}.", at)),
+                },
+                Some((_, c)) => s.push(c),
+            }
+        }
+    }
+
+    fn hex4(&mut self, at: usize) -> Result<u32, String> {
+        let digits: String = (0..4).filter_map(|_| self.chars.next().map(|(_, c)| c)).collect();
+        u32::from_str_radix(&digits, 16).map_err(|_| format!("Invalid escape at offset {}.", at))
+    }
+}
@@ -0,0 +1,26 @@
+#![deny(clippy::unwrap_used)]
+
+// What runs a Vira program: the tree-walking interpreter with its values and
+// builtins, the numeric types, the profiles that set runtime checks, and the
+// functions and counters that JIT-compiled code calls into. Nothing here
+// depends on a backend.
+
+pub mod builtins;
+pub mod compress;
+#[cfg(feature = "config-formats")]
+pub mod config_formats;
+pub mod decimal;
+pub mod digest;
+pub mod encoding;
+pub mod interpreter;
+pub mod interrupt;
+pub mod json;
+pub mod logging;
+pub mod numerics;
+pub mod perf;
+pub mod profile;
+pub mod runtime;
+pub mod sorting;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod template;
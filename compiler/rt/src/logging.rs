@@ -0,0 +1,118 @@
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::builtins::{self, Effects};
+use crate::interpreter::{Map, MapKey, Value};
+use crate::json;
+
+// `log_info(message)`, `log_warn(message)` and `log_error(message)` write a
+// line to standard error, away from what the program writes, with the time
+// in UTC and the level:
+//
+//   log_info("listening", {"port": 8080, "host": "localhost"})
+//   2024-05-01T12:00:00.000Z INFO  listening port=8080 host="localhost"
+//
+// The optional map gives fields to write after the message, in the order
+// the map iterates in. Their values are written as JSON, as `json` would
+// write them, so a string stays on one line and a struct needs a `to_json`
+// method.
+//
+// `VIRA_LOG` sets the least level written: `info`, the default, `warn`,
+// `error`, or `off` for none. With `VIRA_LOG_FORMAT=json` each line is a
+// JSON object instead, with `time`, `level` and `message` members before the
+// fields, for tools that collect logs.
+//
+// Logging is an effect like writing a file: a dry run says what it would
+// log, and compile-time evaluation rejects it.
+
+const LEVELS: [&str; 3] = ["info", "warn", "error"];
+
+pub fn is_function(name: &str) -> bool {
+    matches!(name, "log_info" | "log_warn" | "log_error")
+}
+
+pub fn call(name: &str, args: &[Value], effects: Effects, user: &mut json::User) -> Result<Value, String> {
+    let (message, fields) = match args {
+        [Value::String(message)] => (message, None),
+        [Value::String(message), Value::Map(fields)] => (message, Some(fields)),
+        [_] | [_, _] => return Err(format!("'{}' expects a string message and a map of fields.", name)),
+        _ => return Err(format!("Function '{}' expects 1 or 2 argument(s), got {}.", name, args.len())),
+    };
+    let level = name.trim_start_matches("log_");
+    builtins::effect(effects, format!("log \"{}\" at level {}", message, level), || write(level, message, fields, user))
+}
+
+fn write(level: &str, message: &str, fields: Option<&Map>, user: &mut json::User) -> Result<Value, String> {
+    if !enabled(level)? {
+        return Ok(Value::Int(0));
+    }
+    let mut members = Vec::new();
+    for (key, value) in fields.iter().flat_map(|fields| fields.entries()) {
+        let MapKey::String(key) = key else {
+            return Err(format!("Log fields are named by strings, not {}.", key.to_value().type_name()));
+        };
+        members.push((key.clone(), json::encode(value, user)?));
+    }
+    let time = timestamp();
+    let line = match env::var("VIRA_LOG_FORMAT").as_deref() {
+        Ok("json") => {
+            let head = [("time", json::string(&time)), ("level", json::string(level)), ("message", json::string(message))];
+            let members: Vec<String> = head
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .chain(members)
+                .map(|(key, value)| format!("{}: {}", json::string(&key), value))
+                .collect();
+            format!("{{{}}}", members.join(", "))
+        }
+        Ok("text") | Err(_) => {
+            let fields: String = members.iter().map(|(key, value)| format!(" {}={}", key, value)).collect();
+            format!("{} {:<5} {}{}", time, level.to_uppercase(), message, fields)
+        }
+        Ok(format) => return Err(format!("Unknown VIRA_LOG_FORMAT '{}'; expected text or json.", format)),
+    };
+    eprintln!("{}", line);
+    Ok(Value::Int(0))
+}
+
+// Whether `VIRA_LOG` lets lines of the level through.
+fn enabled(level: &str) -> Result<bool, String> {
+    let least = match env::var("VIRA_LOG") {
+        Ok(least) if least.is_empty() => return Ok(true),
+        Ok(least) => least.to_lowercase(),
+        Err(_) => return Ok(true),
+    };
+    if least == "off" {
+        return Ok(false);
+    }
+    let rank = |level: &str| LEVELS.iter().position(|&known| known == level);
+    let least = rank(&least).ok_or(format!("Unknown VIRA_LOG level '{}'; expected info, warn, error or off.", least))?;
+    Ok(rank(level).is_some_and(|rank| rank >= least))
+}
+
+// The time now as RFC 3339 in UTC, to the millisecond.
+fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let (days, seconds) = ((now.as_secs() / 86_400) as i64, now.as_secs() % 86_400);
+    // The civil date of a day count, after Howard Hinnant's `civil_from_days`,
+    // with years starting in March so that the leap day comes last.
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds / 3_600,
+        seconds / 60 % 60,
+        seconds % 60,
+        now.subsec_millis()
+    )
+}
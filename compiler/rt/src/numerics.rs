@@ -0,0 +1,139 @@
+use vira_syntax::ast::{BinOp, ViraType};
+
+use crate::interpreter::Value;
+
+// The `numerics` module every program can use as `numerics::dot(a, b)`
+// without a numerics.vira of its own, plus complex arithmetic. The array
+// functions copy their arguments into flat f64 buffers and run native loops
+// instead of interpreted ones.
+
+pub const MODULE: &str = "numerics";
+
+// `a op b` for complex numbers (re, im); None for the operators that do not
+// apply.
+pub fn complex_arith(a: (f64, f64), op: BinOp, b: (f64, f64)) -> Option<(f64, f64)> {
+    let ((ar, ai), (br, bi)) = (a, b);
+    match op {
+        BinOp::Add => Some((ar + br, ai + bi)),
+        BinOp::Sub => Some((ar - br, ai - bi)),
+        BinOp::Mul => Some((ar * br - ai * bi, ar * bi + ai * br)),
+        BinOp::Div => {
+            let denominator = br * br + bi * bi;
+            Some(((ar * br + ai * bi) / denominator, (ai * br - ar * bi) / denominator))
+        }
+        _ => None,
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Float(v) => Some(*v),
+        Value::Int(v) => Some(*v as f64),
+        _ => None,
+    }
+}
+
+fn vector(value: &Value, function: &str) -> Result<Vec<f64>, String> {
+    match value {
+        Value::Array(items) => items.iter().map(number).collect::<Option<_>>(),
+        _ => None,
+    }
+    .ok_or(format!("'{}' expects arrays of numbers.", function))
+}
+
+// A row-major matrix.
+struct Matrix {
+    rows: usize,
+    columns: usize,
+    cells: Vec<f64>,
+}
+
+impl Matrix {
+    fn from_value(value: &Value, function: &str) -> Result<Matrix, String> {
+        let invalid = || format!("'{}' expects matrices as array<array<float>>.", function);
+        let Value::Array(rows) = value else {
+            return Err(invalid());
+        };
+        let mut cells = Vec::new();
+        let mut columns = None;
+        for row in rows {
+            let row = vector(row, function).map_err(|_| invalid())?;
+            if *columns.get_or_insert(row.len()) != row.len() {
+                return Err(format!("'{}' expects every row of a matrix to have the same length.", function));
+            }
+            cells.extend(row);
+        }
+        Ok(Matrix { rows: rows.len(), columns: columns.unwrap_or(0), cells })
+    }
+
+    fn to_value(&self) -> Value {
+        let row = |r: usize| self.cells.get(r * self.columns..(r + 1) * self.columns).unwrap_or(&[]);
+        Value::Array((0..self.rows).map(|r| Value::Array(row(r).iter().map(|v| Value::Float(*v)).collect())).collect())
+    }
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn matmul(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut cells = vec![0.0; a.rows * b.columns];
+    // i-k-j order walks both `b` and the result row by row.
+    for (a_row, out_row) in a.cells.chunks(a.columns.max(1)).zip(cells.chunks_mut(b.columns.max(1))) {
+        for (a_cell, b_row) in a_row.iter().zip(b.cells.chunks(b.columns.max(1))) {
+            for (out, b_cell) in out_row.iter_mut().zip(b_row) {
+                *out += a_cell * b_cell;
+            }
+        }
+    }
+    Matrix { rows: a.rows, columns: b.columns, cells }
+}
+
+fn transpose(m: &Matrix) -> Matrix {
+    let mut cells = Vec::with_capacity(m.cells.len());
+    for column in 0..m.columns {
+        cells.extend(m.cells.iter().skip(column).step_by(m.columns).copied());
+    }
+    Matrix { rows: m.columns, columns: m.rows, cells }
+}
+
+// Returns None when `name` is not a member of the module.
+pub fn call(name: &str, args: &[Value]) -> Option<Result<Value, String>> {
+    let function = name.strip_prefix("numerics::")?;
+    let result = match (function, args) {
+        ("dot", [a, b]) => vector(a, function).and_then(|a| {
+            let b = vector(b, function)?;
+            if a.len() != b.len() {
+                return Err(format!("'dot' expects vectors of the same length, got {} and {}.", a.len(), b.len()));
+            }
+            Ok(Value::Float(dot(&a, &b)))
+        }),
+        ("norm", [v]) => vector(v, function).map(|v| Value::Float(dot(&v, &v).sqrt())),
+        ("transpose", [m]) => Matrix::from_value(m, function).map(|m| transpose(&m).to_value()),
+        ("matmul", [a, b]) => Matrix::from_value(a, function).and_then(|a| {
+            let b = Matrix::from_value(b, function)?;
+            if a.columns != b.rows {
+                return Err(format!("Cannot multiply a {}x{} matrix by a {}x{} one.", a.rows, a.columns, b.rows, b.columns));
+            }
+            Ok(matmul(&a, &b).to_value())
+        }),
+        ("dot" | "matmul", _) => Err(format!("'numerics::{}' expects 2 argument(s), got {}.", function, args.len())),
+        ("norm" | "transpose", _) => Err(format!("'numerics::{}' expects 1 argument(s), got {}.", function, args.len())),
+        _ => return None,
+    };
+    Some(result)
+}
+
+// Functions that build a new array.
+pub fn allocates(function: &str) -> bool {
+    matches!(function, "transpose" | "matmul")
+}
+
+pub fn return_type(name: &str) -> Option<ViraType> {
+    let matrix = || ViraType::Array(Box::new(ViraType::Array(Box::new(ViraType::Float))));
+    match name.strip_prefix("numerics::")? {
+        "dot" | "norm" => Some(ViraType::Float),
+        "transpose" | "matmul" => Some(matrix()),
+        _ => None,
+    }
+}
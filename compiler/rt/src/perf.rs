@@ -0,0 +1,56 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Software performance counters for `run --perf-counters`. Every compiled
+// function gets a row that its own code updates as it runs, so no external
+// profiler or hardware counter support is needed. Instruction counts are
+// Cranelift IR instructions, an estimate of the machine code executed;
+// allocations are the aggregates, maps and arrays the runtime allocates.
+
+pub const CALLS: usize = 0;
+pub const INSTRUCTIONS: usize = 1;
+pub const BRANCHES: usize = 2;
+pub const ALLOCATIONS: usize = 3;
+pub const COUNTERS: usize = 4;
+
+pub struct Row {
+    pub function: String,
+    // Boxed so the addresses compiled code writes to never move.
+    counts: Box<[AtomicU64; COUNTERS]>,
+}
+
+impl Row {
+    pub fn new(function: String) -> Row {
+        Row { function, counts: Box::default() }
+    }
+
+    // Where compiled code increments `counter`.
+    pub fn address(&self, counter: usize) -> i64 {
+        self.counts.as_ptr().wrapping_add(counter) as i64
+    }
+
+    fn get(&self, counter: usize) -> u64 {
+        self.counts.get(counter).map_or(0, |count| count.load(Ordering::Relaxed))
+    }
+}
+
+// One line per function that ran, busiest first.
+pub fn report(rows: &[Row]) -> String {
+    let mut rows: Vec<&Row> = rows.iter().filter(|row| row.get(CALLS) > 0).collect();
+    rows.sort_by(|a, b| b.get(INSTRUCTIONS).cmp(&a.get(INSTRUCTIONS)).then_with(|| a.function.cmp(&b.function)));
+    let width = rows.iter().map(|row| row.function.len()).max().unwrap_or(0).max("Function".len());
+    let mut out = format!(
+        "\n{:<width$}  {:>10}  {:>14}  {:>10}  {:>11}\n",
+        "Function", "Calls", "Instructions", "Branches", "Allocations"
+    );
+    for row in rows {
+        out.push_str(&format!(
+            "{:<width$}  {:>10}  {:>14}  {:>10}  {:>11}\n",
+            row.function,
+            row.get(CALLS),
+            row.get(INSTRUCTIONS),
+            row.get(BRANCHES),
+            row.get(ALLOCATIONS)
+        ));
+    }
+    out
+}
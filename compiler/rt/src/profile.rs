@@ -0,0 +1,160 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub name: String,
+    pub opt_level: u8,
+    // Fail on integer overflow instead of wrapping, in both engines.
+    // `checked_*` reports overflow as nil either way.
+    pub overflow_checks: bool,
+    // Check `@requires` and `@ensures` on every call.
+    pub contracts: bool,
+}
+
+impl Profile {
+    pub fn debug() -> Self {
+        Profile {
+            name: "debug".to_string(),
+            opt_level: 0,
+            overflow_checks: true,
+            contracts: true,
+        }
+    }
+
+    pub fn release() -> Self {
+        Profile {
+            name: "release".to_string(),
+            opt_level: 2,
+            overflow_checks: false,
+            contracts: false,
+        }
+    }
+
+    // Starts from the built-in defaults and applies the `[profile.<name>]`
+    // section of the nearest bytes.yml, if there is one.
+    pub fn load(start_dir: &Path, release: bool) -> Result<Self, String> {
+        let mut profile = if release { Profile::release() } else { Profile::debug() };
+        if let Some(manifest) = find_manifest(start_dir) {
+            let source = fs::read_to_string(&manifest).map_err(|e| e.to_string())?;
+            let header = format!("[profile.{}]", profile.name);
+            let mut in_section = false;
+            for (line_no, line) in source.lines().enumerate() {
+                let line = line.split('#').next().unwrap_or("").trim();
+                if line.starts_with('[') {
+                    in_section = line == header;
+                    continue;
+                }
+                if !in_section || line.is_empty() {
+                    continue;
+                }
+                let (key, value) = line
+                    .split_once(':')
+                    .ok_or_else(|| format!("{}:{}: expected 'key: value' in {}", manifest.display(), line_no + 1, header))?;
+                profile
+                    .set(key.trim(), value.trim())
+                    .map_err(|e| format!("{}:{}: {}", manifest.display(), line_no + 1, e))?;
+            }
+        }
+        Ok(profile)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "opt-level" => {
+                self.opt_level = match value.parse() {
+                    Ok(level) if level <= 2 => level,
+                    _ => return Err(format!("opt-level must be 0, 1 or 2, got '{}'", value)),
+                }
+            }
+            "overflow-checks" => self.overflow_checks = parse_bool(key, value)?,
+            "contracts" => self.contracts = parse_bool(key, value)?,
+            // Indexing is always checked, neither engine has debug info to
+            // emit, and there is no collector to stress.
+            "bounds-checks" | "debug-info" | "gc-stress" => return Err(format!("profile key '{}' is not supported yet", key)),
+            _ => return Err(format!("unknown profile key '{}'", key)),
+        }
+        Ok(())
+    }
+
+    pub fn cranelift_opt_level(&self) -> &'static str {
+        match self.opt_level {
+            0 => "none",
+            1 => "speed",
+            _ => "speed_and_size",
+        }
+    }
+}
+
+// A runnable example of the project, declared in its manifest as
+//
+//   [[example]]
+//   name: fizzbuzz
+//   path: examples/fizzbuzz.vira
+//
+// The path is relative to the manifest and defaults to
+// `examples/<name>.vira`.
+#[derive(Debug, Clone)]
+pub struct Example {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+// The examples declared in the nearest bytes.yml, in order; none without one.
+pub fn examples(start_dir: &Path) -> Result<Vec<Example>, String> {
+    let Some(manifest) = find_manifest(start_dir) else { return Ok(Vec::new()) };
+    let source = fs::read_to_string(&manifest).map_err(|e| e.to_string())?;
+    let root = manifest.parent().unwrap_or(Path::new("."));
+    // Name and path of each entry, and the line it starts at.
+    let mut entries: Vec<(Option<String>, Option<PathBuf>, usize)> = Vec::new();
+    let mut in_entry = false;
+    for (line_no, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.starts_with('[') {
+            in_entry = line == "[[example]]";
+            if in_entry {
+                entries.push((None, None, line_no + 1));
+            }
+            continue;
+        }
+        let Some(entry) = entries.last_mut().filter(|_| in_entry && !line.is_empty()) else { continue };
+        let at = format!("{}:{}", manifest.display(), line_no + 1);
+        let (key, value) = line.split_once(':').ok_or_else(|| format!("{}: expected 'key: value' in [[example]]", at))?;
+        match (key.trim(), value.trim()) {
+            ("name", name) if !name.is_empty() => entry.0 = Some(name.to_string()),
+            ("path", path) if !path.is_empty() => entry.1 = Some(root.join(path)),
+            ("name" | "path", _) => return Err(format!("{}: '{}' needs a value", at, key.trim())),
+            (key, _) => return Err(format!("{}: unknown example key '{}'", at, key)),
+        }
+    }
+    let mut examples: Vec<Example> = Vec::new();
+    for (name, path, line_no) in entries {
+        let name = name.ok_or_else(|| format!("{}:{}: [[example]] needs a name", manifest.display(), line_no))?;
+        if examples.iter().any(|example| example.name == name) {
+            return Err(format!("{}:{}: example '{}' is declared twice", manifest.display(), line_no, name));
+        }
+        let path = path.unwrap_or_else(|| root.join("examples").join(format!("{}.vira", name)));
+        examples.push(Example { name, path });
+    }
+    Ok(examples)
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("{} must be true or false, got '{}'", key, value)),
+    }
+}
+
+fn find_manifest(start_dir: &Path) -> Option<PathBuf> {
+    let mut current = Some(start_dir);
+    while let Some(dir) = current {
+        let candidate = dir.join("bytes.yml");
+        if candidate.exists() {
+            return Some(candidate);
+        }
+        current = dir.parent();
+    }
+    None
+}
@@ -0,0 +1,289 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use crate::interpreter::Value;
+
+// Runtime support called from JIT-compiled code. Values cross the boundary as
+// I64: ints and bools as themselves, floats by their bits, aggregates and
+// strings by address. Every function returns a value.
+
+// Aggregates, maps, arrays and strings made by compiled code are never freed; there
+// is no collector yet. The exception is an array the escape analysis finds cannot
+// outlive the function that makes it: it goes in the function's region,
+// which is freed when the function returns.
+type Map = HashMap<i64, i64>;
+type Array = Vec<i64>;
+
+pub const ALLOC: &str = "vira_alloc";
+pub const MAP_NEW: &str = "vira_map_new";
+pub const MAP_INSERT: &str = "vira_map_insert";
+pub const MAP_WITH: &str = "vira_map_with";
+pub const MAP_GET: &str = "vira_map_get";
+pub const MAP_CONTAINS: &str = "vira_map_contains";
+pub const MAP_LEN: &str = "vira_map_len";
+pub const ARRAY_FILLED: &str = "vira_array_filled";
+pub const ARRAY_FILLED_LOCAL: &str = "vira_array_filled_local";
+pub const ARRAY_FROM_BYTES: &str = "vira_array_from_bytes";
+pub const ARRAY_SET: &str = "vira_array_set";
+pub const ARRAY_GET: &str = "vira_array_get";
+pub const ARRAY_LEN: &str = "vira_array_len";
+pub const STRING_FROM_BYTES: &str = "vira_string_from_bytes";
+pub const STRING_CONCAT: &str = "vira_string_concat";
+pub const STRING_EQ: &str = "vira_string_eq";
+pub const FLOAT_REM: &str = "vira_float_rem";
+pub const WRITE_INT: &str = "vira_write_int";
+pub const WRITE_FLOAT: &str = "vira_write_float";
+pub const WRITE_BOOL: &str = "vira_write_bool";
+pub const WRITE_STRING: &str = "vira_write_string";
+pub const OVERFLOW: &str = "vira_overflow";
+pub const DIVISION_BY_ZERO: &str = "vira_division_by_zero";
+pub const OUT_OF_BOUNDS: &str = "vira_out_of_bounds";
+pub const INTERRUPTED: &str = "vira_interrupted";
+pub const UNCAUGHT: &str = "vira_uncaught";
+pub const REGION_ENTER: &str = "vira_region_enter";
+pub const REGION_LEAVE: &str = "vira_region_leave";
+
+// Where a `throw` that leaves its function puts the value: a flag word, then
+// the value. Compiled code checks the flag after each call.
+#[repr(C)]
+struct Thrown {
+    set: AtomicI64,
+    value: AtomicI64,
+}
+
+static THROWN: Thrown = Thrown { set: AtomicI64::new(0), value: AtomicI64::new(0) };
+
+pub fn thrown_address() -> i64 {
+    &THROWN as *const Thrown as i64
+}
+
+// Runtime functions that allocate a new aggregate, map, array or string.
+pub fn allocates(name: &str) -> bool {
+    [ALLOC, MAP_NEW, MAP_WITH, ARRAY_FILLED, ARRAY_FILLED_LOCAL, ARRAY_FROM_BYTES, STRING_FROM_BYTES, STRING_CONCAT].contains(&name)
+}
+
+// Every runtime function by name with its address, for the backend to link
+// compiled code against.
+pub fn symbols() -> [(&'static str, *const u8); 28] {
+    [
+        (ALLOC, vira_alloc as *const u8),
+        (MAP_NEW, vira_map_new as *const u8),
+        (MAP_INSERT, vira_map_insert as *const u8),
+        (MAP_WITH, vira_map_with as *const u8),
+        (MAP_GET, vira_map_get as *const u8),
+        (MAP_CONTAINS, vira_map_contains as *const u8),
+        (MAP_LEN, vira_map_len as *const u8),
+        (ARRAY_FILLED, vira_array_filled as *const u8),
+        (ARRAY_FILLED_LOCAL, vira_array_filled_local as *const u8),
+        (ARRAY_FROM_BYTES, vira_array_from_bytes as *const u8),
+        (ARRAY_SET, vira_array_set as *const u8),
+        (ARRAY_GET, vira_array_get as *const u8),
+        (ARRAY_LEN, vira_array_len as *const u8),
+        (STRING_FROM_BYTES, vira_string_from_bytes as *const u8),
+        (STRING_CONCAT, vira_string_concat as *const u8),
+        (STRING_EQ, vira_string_eq as *const u8),
+        (FLOAT_REM, vira_float_rem as *const u8),
+        (WRITE_INT, vira_write_int as *const u8),
+        (WRITE_FLOAT, vira_write_float as *const u8),
+        (WRITE_BOOL, vira_write_bool as *const u8),
+        (WRITE_STRING, vira_write_string as *const u8),
+        (OVERFLOW, vira_overflow as *const u8),
+        (DIVISION_BY_ZERO, vira_division_by_zero as *const u8),
+        (OUT_OF_BOUNDS, vira_out_of_bounds as *const u8),
+        (INTERRUPTED, vira_interrupted as *const u8),
+        (UNCAUGHT, vira_uncaught as *const u8),
+        (REGION_ENTER, vira_region_enter as *const u8),
+        (REGION_LEAVE, vira_region_leave as *const u8),
+    ]
+}
+
+// Zeroed memory of `size` bytes, a whole number of 8-byte slots, for a
+// struct, tuple, enum value, optional, range or trait object, so that it
+// outlives the function that makes it.
+extern "C" fn vira_alloc(size: i64) -> *mut i64 {
+    let words = usize::try_from(size).unwrap_or(0).div_ceil(8);
+    Box::into_raw(vec![0i64; words].into_boxed_slice()).cast()
+}
+
+extern "C" fn vira_map_new() -> *mut Map {
+    Box::into_raw(Box::default())
+}
+
+// Fills a map that is still being built from a literal, returning it.
+unsafe extern "C" fn vira_map_insert(map: *mut Map, key: i64, value: i64) -> *mut Map {
+    (*map).insert(key, value);
+    map
+}
+
+// `set(m, k, v)`: maps are values, so this returns an updated copy.
+unsafe extern "C" fn vira_map_with(map: *const Map, key: i64, value: i64) -> *mut Map {
+    let mut copy = (*map).clone();
+    copy.insert(key, value);
+    Box::into_raw(Box::new(copy))
+}
+
+unsafe extern "C" fn vira_map_get(map: *const Map, key: i64) -> i64 {
+    match (*map).get(&key) {
+        Some(value) => *value,
+        None => {
+            eprintln!("Run error: Key {} is not in the map.", key);
+            std::process::exit(1);
+        }
+    }
+}
+
+unsafe extern "C" fn vira_map_contains(map: *const Map, key: i64) -> i64 {
+    (*map).contains_key(&key) as i64
+}
+
+unsafe extern "C" fn vira_map_len(map: *const Map) -> i64 {
+    (*map).len() as i64
+}
+
+// One allocation for the whole array; a zero fill comes from the allocator
+// already zeroed.
+extern "C" fn vira_array_filled(len: i64, value: i64) -> *mut Array {
+    let Ok(len) = usize::try_from(len) else {
+        eprintln!("Run error: 'array_filled' expects a length of 0 or more, not {}.", len);
+        std::process::exit(1);
+    };
+    let mut items = Array::new();
+    if items.try_reserve_exact(len).is_err() {
+        eprintln!("Run error: Cannot allocate an array of {} elements.", len);
+        std::process::exit(1);
+    }
+    items.resize(len, value);
+    Box::into_raw(Box::new(items))
+}
+
+thread_local! {
+    // The arrays in the regions of the functions running, oldest first.
+    static REGIONS: RefCell<Vec<*mut Array>> = const { RefCell::new(Vec::new()) };
+}
+
+// `array_filled` for an array that does not escape its function.
+extern "C" fn vira_array_filled_local(len: i64, value: i64) -> *mut Array {
+    let array = vira_array_filled(len, value);
+    REGIONS.with(|regions| regions.borrow_mut().push(array));
+    array
+}
+
+// A function with local arrays starts a region on entry, and ends it before
+// it returns, freeing every local array made since, its callees' included.
+// A callee that returns early for a throw leaves its region open, for the
+// caller's to free.
+extern "C" fn vira_region_enter() -> i64 {
+    REGIONS.with(|regions| regions.borrow().len() as i64)
+}
+
+unsafe extern "C" fn vira_region_leave(mark: i64) -> i64 {
+    let freed = REGIONS.with(|regions| regions.borrow_mut().split_off(mark as usize));
+    for array in freed {
+        drop(Box::from_raw(array));
+    }
+    0
+}
+
+// An array<int> of the `len` bytes at `bytes`, a data section of the
+// compiled program.
+unsafe extern "C" fn vira_array_from_bytes(bytes: *const u8, len: i64) -> *mut Array {
+    let bytes = std::slice::from_raw_parts(bytes, len as usize);
+    Box::into_raw(Box::new(bytes.iter().map(|&byte| byte as i64).collect()))
+}
+
+// Fills an array that is still being built, returning it.
+unsafe extern "C" fn vira_array_set(array: *mut Array, index: i64, value: i64) -> *mut Array {
+    if let Some(slot) = usize::try_from(index).ok().and_then(|i| (&mut *array).get_mut(i)) {
+        *slot = value;
+    }
+    array
+}
+
+unsafe extern "C" fn vira_array_get(array: *const Array, index: i64) -> i64 {
+    match usize::try_from(index).ok().and_then(|i| (&*array).get(i)) {
+        Some(value) => *value,
+        None => {
+            eprintln!("Run error: Index out of bounds.");
+            std::process::exit(1);
+        }
+    }
+}
+
+unsafe extern "C" fn vira_array_len(array: *const Array) -> i64 {
+    (*array).len() as i64
+}
+
+// A string of the `len` bytes at `bytes`, a literal or data section of the
+// compiled program. The empty string comes with no bytes at all.
+unsafe extern "C" fn vira_string_from_bytes(bytes: *const u8, len: i64) -> *mut String {
+    let bytes = if len == 0 { &[] } else { std::slice::from_raw_parts(bytes, len as usize) };
+    Box::into_raw(Box::new(String::from_utf8_lossy(bytes).into_owned()))
+}
+
+unsafe extern "C" fn vira_string_concat(a: *const String, b: *const String) -> *mut String {
+    Box::into_raw(Box::new(format!("{}{}", *a, *b)))
+}
+
+unsafe extern "C" fn vira_string_eq(a: *const String, b: *const String) -> i64 {
+    (*a == *b) as i64
+}
+
+// Cranelift has no float remainder instruction.
+extern "C" fn vira_float_rem(a: i64, b: i64) -> i64 {
+    (f64::from_bits(a as u64) % f64::from_bits(b as u64)).to_bits() as i64
+}
+
+// `write` prints what the interpreter prints for the same value.
+fn write(value: Value) -> i64 {
+    println!("{:?}", value);
+    0
+}
+
+extern "C" fn vira_write_int(value: i64) -> i64 {
+    write(Value::Int(value))
+}
+
+extern "C" fn vira_write_float(bits: i64) -> i64 {
+    write(Value::Float(f64::from_bits(bits as u64)))
+}
+
+extern "C" fn vira_write_bool(value: i64) -> i64 {
+    write(Value::Bool(value != 0))
+}
+
+unsafe extern "C" fn vira_write_string(text: *const String) -> i64 {
+    write(Value::String((*text).clone()))
+}
+
+// The checks compiled arithmetic and indexing make end the run the way the
+// interpreter's errors do.
+fn fail(message: &str) -> i64 {
+    eprintln!("Run error: {}", message);
+    std::process::exit(1);
+}
+
+extern "C" fn vira_overflow() -> i64 {
+    fail("Integer overflow.")
+}
+
+extern "C" fn vira_division_by_zero() -> i64 {
+    fail("Division by zero.")
+}
+
+extern "C" fn vira_out_of_bounds() -> i64 {
+    fail("Index out of bounds.")
+}
+
+// Called when a loop back-edge sees Ctrl-C; compiled code has no way to
+// unwind, so this ends the run.
+extern "C" fn vira_interrupted() -> i64 {
+    eprintln!("Run error: Interrupted.");
+    std::process::exit(130);
+}
+
+// Called when a thrown value reaches the top level without a `try`.
+extern "C" fn vira_uncaught() -> i64 {
+    eprintln!("Run error: Uncaught throw of {}.", THROWN.value.load(Ordering::SeqCst));
+    std::process::exit(1);
+}
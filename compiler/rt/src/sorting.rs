@@ -0,0 +1,200 @@
+use std::cmp::Ordering;
+
+use vira_syntax::ast::ViraType;
+
+use crate::builtins::expect_args;
+use crate::interpreter::Value;
+
+// Sorting and searching builtins. The `_by` variants take a comparator
+// `func(a, b) -> int` that returns a negative number, zero or a positive
+// number when `a` orders before, with or after `b`. The loops run natively;
+// only the comparator calls go back into the program.
+//
+// Sorting is stable: elements the comparator calls equal keep their order.
+// `min_by` returns the first of several minimums and `max_by` the last of
+// several maximums, which are where a stable sort puts them.
+
+// Calls a function value with arguments.
+pub type Apply<'a> = dyn FnMut(&Value, Vec<Value>) -> Result<Value, String> + 'a;
+
+pub fn is_function(name: &str) -> bool {
+    matches!(name, "sort" | "sort_by" | "binary_search" | "min_by" | "max_by")
+}
+
+// The functions that call back into the program.
+pub fn calls_back(name: &str, args: usize) -> bool {
+    matches!((name, args), ("sort_by" | "min_by" | "max_by", _) | ("binary_search", 3))
+}
+
+// The order `sort` and `binary_search` use without a comparator.
+fn natural(a: &Value, b: &Value) -> Result<Ordering, String> {
+    match (a, b) {
+        (Value::Int(a), Value::Int(b)) => Ok(a.cmp(b)),
+        (Value::Float(a), Value::Float(b)) => a.partial_cmp(b).ok_or("Cannot order NaN.".to_string()),
+        (Value::Decimal(a), Value::Decimal(b)) => Ok(a.cmp(b)),
+        (Value::String(a), Value::String(b)) => Ok(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Ok(a.cmp(b)),
+        (Value::Tuple(a), Value::Tuple(b)) if a.len() == b.len() => {
+            for (a, b) in a.iter().zip(b) {
+                let ordering = natural(a, b)?;
+                if ordering.is_ne() {
+                    return Ok(ordering);
+                }
+            }
+            Ok(Ordering::Equal)
+        }
+        _ => Err(format!("Cannot order {} and {}; pass a comparator.", a.type_name(), b.type_name())),
+    }
+}
+
+// Orders `a` and `b` by calling the comparator `function`.
+fn compare_by(name: &str, function: &Value, apply: &mut Apply, a: &Value, b: &Value) -> Result<Ordering, String> {
+    match apply(function, vec![a.clone(), b.clone()])? {
+        Value::Int(n) => Ok(n.cmp(&0)),
+        other => Err(format!("The comparator of '{}' must return an int, not {}.", name, other.type_name())),
+    }
+}
+
+// A merge sort rather than `slice::sort_by`: the comparator can fail, and the
+// standard sort may panic on one that is not a total order.
+fn merge_sort(mut items: Vec<Value>, cmp: &mut dyn FnMut(&Value, &Value) -> Result<Ordering, String>) -> Result<Vec<Value>, String> {
+    if items.len() < 2 {
+        return Ok(items);
+    }
+    let right = items.split_off(items.len() / 2);
+    let left = merge_sort(items, cmp)?;
+    let right = merge_sort(right, cmp)?;
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let (mut left, mut right) = (left.into_iter().peekable(), right.into_iter().peekable());
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        // Ties take from the left, which keeps the sort stable.
+        let next = if cmp(r, l)?.is_lt() { right.next() } else { left.next() };
+        merged.extend(next);
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+// The first index whose element orders equal to `value`.
+fn binary_search(items: &[Value], value: &Value, cmp: &mut dyn FnMut(&Value, &Value) -> Result<Ordering, String>) -> Result<Option<usize>, String> {
+    let (mut low, mut high) = (0, items.len());
+    while low < high {
+        let middle = low + (high - low) / 2;
+        match items.get(middle) {
+            Some(item) if cmp(item, value)?.is_lt() => low = middle + 1,
+            _ => high = middle,
+        }
+    }
+    match items.get(low) {
+        Some(item) if cmp(item, value)?.is_eq() => Ok(Some(low)),
+        _ => Ok(None),
+    }
+}
+
+// The element `wanted` picks over every other, or nil for an empty array.
+fn extreme(items: Vec<Value>, cmp: &mut dyn FnMut(&Value, &Value) -> Result<Ordering, String>, wanted: Ordering) -> Result<Value, String> {
+    let mut best: Option<Value> = None;
+    for item in items {
+        let replace = match &best {
+            Some(current) => {
+                let ordering = cmp(&item, current)?;
+                // `max_by` also replaces on ties, so that it finds the last maximum.
+                ordering == wanted || wanted.is_gt() && ordering.is_eq()
+            }
+            None => true,
+        };
+        if replace {
+            best = Some(item);
+        }
+    }
+    Ok(best.unwrap_or(Value::Nil))
+}
+
+pub fn call(name: &str, mut args: Vec<Value>, apply: &mut Apply) -> Result<Value, String> {
+    match (name, args.len()) {
+        ("sort", _) => expect_args(name, &args, 1).and_then(|()| match args.pop() {
+            Some(Value::Array(items)) => merge_sort(items, &mut natural).map(Value::Array),
+            _ => Err("'sort' expects an array.".to_string()),
+        }),
+        ("binary_search", 2) => match args.as_slice() {
+            [Value::Array(items), value] => binary_search(items, value, &mut natural).map(|index| index.map_or(Value::Nil, |i| Value::Int(i as i64))),
+            _ => Err("'binary_search' expects a sorted array and a value.".to_string()),
+        },
+        ("binary_search", 3) => match args.as_slice() {
+            [Value::Array(items), value, function @ Value::Function(_)] => {
+                binary_search(items, value, &mut |a, b| compare_by(name, function, apply, a, b)).map(|index| index.map_or(Value::Nil, |i| Value::Int(i as i64)))
+            }
+            _ => Err("'binary_search' expects a sorted array, a value and a comparator.".to_string()),
+        },
+        ("binary_search", n) => Err(format!("Function 'binary_search' expects 2 or 3 argument(s), got {}.", n)),
+        _ => expect_args(name, &args, 2).and_then(|()| {
+            let (Some(function @ Value::Function(_)), Some(Value::Array(items))) = (args.pop(), args.pop()) else {
+                return Err(format!("'{}' expects an array and a comparator.", name));
+            };
+            let mut cmp = |a: &Value, b: &Value| compare_by(name, &function, apply, a, b);
+            match name {
+                "sort_by" => merge_sort(items, &mut cmp).map(Value::Array),
+                "min_by" => extreme(items, &mut cmp, Ordering::Less),
+                _ => extreme(items, &mut cmp, Ordering::Greater),
+            }
+        }),
+    }
+}
+
+pub fn return_type(name: &str, args: &[Option<ViraType>]) -> Option<ViraType> {
+    match (name, args) {
+        ("sort", [Some(typ @ ViraType::Array(_))]) | ("sort_by", [Some(typ @ ViraType::Array(_)), _]) => Some(typ.clone()),
+        ("binary_search", [_, _] | [_, _, _]) => Some(ViraType::Optional(Box::new(ViraType::Int))),
+        ("min_by" | "max_by", [Some(ViraType::Array(element)), _]) => Some(ViraType::Optional(element.clone())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Records of a key and their position, ordered by the key alone.
+    fn records(keys: &[i64]) -> Vec<Value> {
+        keys.iter().enumerate().map(|(i, &key)| Value::Tuple(vec![Value::Int(key), Value::Int(i as i64)])).collect()
+    }
+
+    fn by_key(a: &Value, b: &Value) -> Result<Ordering, String> {
+        match (a, b) {
+            (Value::Tuple(a), Value::Tuple(b)) => natural(&a[0], &b[0]),
+            _ => Err("Expected records.".to_string()),
+        }
+    }
+
+    fn labels(items: &[Value]) -> Vec<i64> {
+        items
+            .iter()
+            .map(|item| match item {
+                Value::Tuple(fields) => match fields.as_slice() {
+                    [_, Value::Int(label)] => *label,
+                    _ => -1,
+                },
+                _ => -1,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sorting_keeps_the_order_of_equal_keys() -> Result<(), String> {
+        let keys = [3, 1, 2, 1, 3, 2, 1, 0, 3, 2, 2, 1, 0, 3, 1, 2, 0];
+        let sorted = merge_sort(records(&keys), &mut by_key)?;
+        let mut expected: Vec<usize> = (0..keys.len()).collect();
+        expected.sort_by_key(|&i| keys[i]);
+        assert_eq!(labels(&sorted), expected.iter().map(|&i| i as i64).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn min_by_and_max_by_pick_where_a_stable_sort_puts_ties() -> Result<(), String> {
+        let keys = [2, 0, 3, 0, 3, 1];
+        assert_eq!(labels(&[extreme(records(&keys), &mut by_key, Ordering::Less)?]), [1]);
+        assert_eq!(labels(&[extreme(records(&keys), &mut by_key, Ordering::Greater)?]), [4]);
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use std::cell::RefCell;
+
+use rusqlite::types::{Value as Sql, ValueRef};
+use rusqlite::{params_from_iter, Connection, OpenFlags};
+
+use crate::builtins::{self, Effects};
+use crate::interpreter::{Map, MapKey, Value};
+
+// SQLite databases, for scripts that keep their data in one file:
+//
+//   let db = db_open("app.db")
+//   db_exec(db, "insert into users (name, age) values (?, ?)", ["ada", 36])
+//   db_query(db, "select name from users where age > ?", [30])
+//
+// `db_open` gives a handle that stays open until the program ends.
+// `db_exec` runs a statement and gives the number of rows it changed, and
+// `db_query` gives one map per row, from column name to value, in the order
+// of the columns. The parameters, bound to the `?`s in order, are optional.
+//
+// Ints, floats, strings and nil go in and come out as themselves, a bool goes
+// in as 0 or 1, and a blob is an array of bytes. Errors from SQLite are
+// errors of the call.
+//
+// A dry run opens the database read-only and logs each `db_exec` instead of
+// running it; queries still run. Built only with the `sqlite` feature.
+
+thread_local! {
+    static DATABASES: RefCell<Vec<Connection>> = const { RefCell::new(Vec::new()) };
+}
+
+pub fn call(name: &str, args: &[Value], effects: Effects) -> Result<Value, String> {
+    match (name, args) {
+        ("db_open", [Value::String(path)]) => builtins::read(effects, format!("open {}", path), || open(path, effects)),
+        ("db_exec", [db, Value::String(sql), params @ ..]) if params.len() <= 1 => {
+            let params = parameters(params)?;
+            builtins::effect(effects, format!("run `{}` on database {}", sql, handle(db)?), || {
+                with(db, sql, |connection| connection.execute(sql, params_from_iter(params))).map(|changed| Value::Int(changed as i64))
+            })
+        }
+        ("db_query", [db, Value::String(sql), params @ ..]) if params.len() <= 1 => {
+            let params = parameters(params)?;
+            builtins::read(effects, format!("query database {}", handle(db)?), || with(db, sql, |connection| query(connection, sql, params)))
+        }
+        ("db_open", [_]) => Err("'db_open' expects a path.".to_string()),
+        ("db_exec" | "db_query", [_, _] | [_, _, _]) => Err(format!("'{}' expects a database, an SQL string and an array of parameters.", name)),
+        ("db_open", _) => Err(format!("Function '{}' expects 1 argument(s), got {}.", name, args.len())),
+        _ => Err(format!("Function '{}' expects 2 or 3 argument(s), got {}.", name, args.len())),
+    }
+}
+
+fn open(path: &str, effects: Effects) -> Result<Value, String> {
+    let connection = match effects {
+        Effects::DryRun => Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY),
+        _ => Connection::open(path),
+    }
+    .map_err(|e| format!("Cannot open {}: {}", path, e))?;
+    DATABASES.with(|databases| {
+        let mut databases = databases.borrow_mut();
+        databases.push(connection);
+        Ok(Value::Int(databases.len() as i64 - 1))
+    })
+}
+
+fn handle(db: &Value) -> Result<usize, String> {
+    match db {
+        Value::Int(handle) if *handle >= 0 && (*handle as usize) < DATABASES.with(|databases| databases.borrow().len()) => Ok(*handle as usize),
+        _ => Err(format!("{} is not a database from 'db_open'.", db)),
+    }
+}
+
+fn with<T>(db: &Value, sql: &str, run: impl FnOnce(&Connection) -> rusqlite::Result<T>) -> Result<T, String> {
+    let handle = handle(db)?;
+    DATABASES.with(|databases| run(&databases.borrow()[handle])).map_err(|e| format!("Cannot run `{}`: {}", sql, e))
+}
+
+fn parameters(params: &[Value]) -> Result<Vec<Sql>, String> {
+    let items = match params {
+        [] => return Ok(Vec::new()),
+        [Value::Array(items)] => items,
+        _ => return Err("Parameters of an SQL statement go in an array.".to_string()),
+    };
+    items
+        .iter()
+        .map(|item| match item {
+            Value::Int(v) => Ok(Sql::Integer(*v)),
+            Value::Bool(b) => Ok(Sql::Integer(*b as i64)),
+            Value::Float(v) => Ok(Sql::Real(*v)),
+            Value::String(s) => Ok(Sql::Text(s.clone())),
+            Value::Nil => Ok(Sql::Null),
+            bytes @ Value::Array(_) => builtins::bytes("db_exec", bytes).map(Sql::Blob),
+            other => Err(format!("Cannot bind {} to an SQL parameter.", other.type_name())),
+        })
+        .collect()
+}
+
+fn query(connection: &Connection, sql: &str, params: Vec<Sql>) -> rusqlite::Result<Value> {
+    let mut statement = connection.prepare(sql)?;
+    let columns: Vec<String> = statement.column_names().into_iter().map(str::to_string).collect();
+    let mut rows = statement.query(params_from_iter(params))?;
+    let mut out = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut map = Map::ordered();
+        for (i, column) in columns.iter().enumerate() {
+            let value = match row.get_ref(i)? {
+                ValueRef::Null => Value::Nil,
+                ValueRef::Integer(v) => Value::Int(v),
+                ValueRef::Real(v) => Value::Float(v),
+                ValueRef::Text(text) => Value::String(String::from_utf8_lossy(text).into_owned()),
+                ValueRef::Blob(bytes) => Value::Array(bytes.iter().map(|&byte| Value::Int(byte as i64)).collect()),
+            };
+            map.insert(MapKey::String(column.clone()), value);
+        }
+        out.push(Value::Map(map));
+    }
+    Ok(Value::Array(out))
+}
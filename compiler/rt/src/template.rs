@@ -0,0 +1,232 @@
+use crate::interpreter::{MapKey, Value};
+
+// `render(template, values)` and `render(template, values, escape)`: text
+// from a template, for generation scripts. `values` is a map with string
+// keys, or a struct, whose fields are the names.
+//
+//   {{name}}                          the value of `name`
+//   {{raw name}}                      the same, never escaped
+//   {{for x in items}} ... {{end}}    the body once per element of an array
+//   {{if flag}} ... {{else}} ... {{end}}
+//
+// A name is a key of `values` or the variable of an enclosing `for`, and
+// `a.b` reads the field `b` of a struct or the key "b" of a map. A string is
+// inserted as its text and any other value as the REPL shows it. An `if`
+// takes a bool, or any other value, which counts as true unless it is nil.
+// The escape is `none`, the default, or `html`, which escapes `&`, `<`, `>`
+// and quotes in what is inserted. A tag other than an insertion alone on its line takes
+// the line with it, so templates can lay out their blocks one per line.
+
+enum Node {
+    Text(String),
+    Insert(Vec<String>, bool),
+    For(String, Vec<String>, Vec<Node>),
+    If(Vec<String>, Vec<Node>, Vec<Node>),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Escape {
+    None,
+    Html,
+}
+
+pub fn render(args: &[Value]) -> Result<Value, String> {
+    let (template, values, escape) = match args {
+        [Value::String(template), values] => (template, values, Escape::None),
+        [Value::String(template), values, Value::String(escape)] => {
+            let escape = match escape.as_str() {
+                "none" => Escape::None,
+                "html" => Escape::Html,
+                _ => return Err(format!("Unknown escape '{}'; expected none or html.", escape)),
+            };
+            (template, values, escape)
+        }
+        [_, _] | [_, _, _] => return Err("'render' expects a template string, values and an escape string.".to_string()),
+        _ => return Err(format!("Function 'render' expects 2 or 3 argument(s), got {}.", args.len())),
+    };
+    if !matches!(values, Value::Map(_) | Value::Struct(..)) {
+        return Err(format!("'render' expects its values as a map or struct, not {}.", values.type_name()));
+    }
+    let mut tags = tags(template)?.into_iter();
+    let (nodes, end) = parse(&mut tags)?;
+    if let Some((tag, line)) = end {
+        return Err(format!("'{{{{{}}}}}' at line {} of the template has nothing to close.", tag, line));
+    }
+    let mut out = String::new();
+    Renderer { values, scope: Vec::new(), escape }.nodes(&nodes, &mut out)?;
+    Ok(Value::String(out))
+}
+
+// A piece of the template: text, or a tag's contents and line.
+enum Piece {
+    Text(String),
+    Tag(String, usize),
+}
+
+// Splits the template into text and tags, dropping the lines of standalone
+// block tags.
+fn tags(template: &str) -> Result<Vec<Piece>, String> {
+    let mut pieces = Vec::new();
+    for (i, line) in template.split_inclusive('\n').enumerate() {
+        let mut rest = line;
+        let mut line_pieces = Vec::new();
+        while let Some(start) = rest.find("{{") {
+            let end = rest[start..].find("}}").ok_or(format!("Unclosed '{{{{' at line {} of the template.", i + 1))?;
+            line_pieces.push(Piece::Text(rest[..start].to_string()));
+            line_pieces.push(Piece::Tag(rest[start + 2..start + end].trim().to_string(), i + 1));
+            rest = &rest[start + end + 2..];
+        }
+        line_pieces.push(Piece::Text(rest.to_string()));
+        let standalone = line_pieces.iter().all(|piece| match piece {
+            Piece::Text(text) => text.trim().is_empty(),
+            Piece::Tag(tag, _) => is_block(tag),
+        });
+        if standalone && line_pieces.iter().any(|piece| matches!(piece, Piece::Tag(..))) {
+            line_pieces.retain(|piece| matches!(piece, Piece::Tag(..)));
+        }
+        pieces.extend(line_pieces);
+    }
+    Ok(pieces)
+}
+
+fn is_block(tag: &str) -> bool {
+    tag.starts_with("for ") || tag.starts_with("if ") || tag == "else" || tag == "end"
+}
+
+fn path(name: &str, line: usize) -> Result<Vec<String>, String> {
+    let path: Vec<String> = name.split('.').map(|part| part.trim().to_string()).collect();
+    if path.iter().any(|part| part.is_empty() || part.contains(char::is_whitespace)) {
+        return Err(format!("'{{{{{}}}}}' at line {} of the template is not a name.", name, line));
+    }
+    Ok(path)
+}
+
+// The `else` or `end` that ends a block, and its line.
+type Closing = Option<(String, usize)>;
+
+// The nodes up to the end of the template or a closing tag, which is
+// returned with them.
+fn parse(pieces: &mut impl Iterator<Item = Piece>) -> Result<(Vec<Node>, Closing), String> {
+    let mut nodes = Vec::new();
+    while let Some(piece) = pieces.next() {
+        let (tag, line) = match piece {
+            Piece::Text(text) => {
+                nodes.push(Node::Text(text));
+                continue;
+            }
+            Piece::Tag(tag, line) => (tag, line),
+        };
+        let unclosed = |what: &str| format!("'{{{{{}}}}}' at line {} of the template has no '{{{{end}}}}'.", what, line);
+        if let Some(header) = tag.strip_prefix("for ") {
+            let (var, items) = header.split_once(" in ").ok_or(format!("Expected '{{{{for x in items}}}}' at line {} of the template.", line))?;
+            let (body, end) = parse(pieces)?;
+            match end {
+                Some((end, _)) if end == "end" => nodes.push(Node::For(var.trim().to_string(), path(items, line)?, body)),
+                Some((other, line)) => return Err(format!("'{{{{{}}}}}' at line {} of the template is not allowed in a 'for'.", other, line)),
+                None => return Err(unclosed(&tag)),
+            }
+        } else if let Some(condition) = tag.strip_prefix("if ") {
+            let condition = path(condition, line)?;
+            let (then, end) = parse(pieces)?;
+            let otherwise = match end {
+                Some((end, _)) if end == "end" => Vec::new(),
+                Some((end, _)) if end == "else" => match parse(pieces)? {
+                    (otherwise, Some((end, _))) if end == "end" => otherwise,
+                    (_, Some((other, line))) => return Err(format!("'{{{{{}}}}}' at line {} of the template follows an 'else'.", other, line)),
+                    (_, None) => return Err(unclosed(&tag)),
+                },
+                Some((other, line)) => return Err(format!("'{{{{{}}}}}' at line {} of the template is not allowed in an 'if'.", other, line)),
+                None => return Err(unclosed(&tag)),
+            };
+            nodes.push(Node::If(condition, then, otherwise));
+        } else if tag == "end" || tag == "else" {
+            return Ok((nodes, Some((tag, line))));
+        } else if let Some(name) = tag.strip_prefix("raw ") {
+            nodes.push(Node::Insert(path(name, line)?, true));
+        } else {
+            nodes.push(Node::Insert(path(&tag, line)?, false));
+        }
+    }
+    Ok((nodes, None))
+}
+
+struct Renderer<'a> {
+    values: &'a Value,
+    // The variables of the enclosing `for`s, innermost last.
+    scope: Vec<(String, Value)>,
+    escape: Escape,
+}
+
+impl Renderer<'_> {
+    fn nodes(&mut self, nodes: &[Node], out: &mut String) -> Result<(), String> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Insert(path, raw) => {
+                    let text = match self.lookup(path)? {
+                        Value::String(s) => s,
+                        value => value.to_string(),
+                    };
+                    match self.escape {
+                        Escape::Html if !raw => out.push_str(&html(&text)),
+                        _ => out.push_str(&text),
+                    }
+                }
+                Node::For(var, items, body) => {
+                    let Value::Array(items) = self.lookup(items)? else {
+                        return Err(format!("'{}' in the template is not an array.", items.join(".")));
+                    };
+                    for item in items {
+                        self.scope.push((var.clone(), item));
+                        let result = self.nodes(body, out);
+                        self.scope.pop();
+                        result?;
+                    }
+                }
+                Node::If(condition, then, otherwise) => {
+                    let taken = match self.lookup(condition)? {
+                        Value::Bool(taken) => taken,
+                        value => value != Value::Nil,
+                    };
+                    self.nodes(if taken { then } else { otherwise }, out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, path: &[String]) -> Result<Value, String> {
+        let (first, rest) = path.split_first().ok_or("An empty name in the template.")?;
+        let mut value = match self.scope.iter().rev().find(|(var, _)| var == first) {
+            Some((_, value)) => value.clone(),
+            None => member(self.values, first).ok_or(format!("'{}' is not among the values of the template.", first))?,
+        };
+        for (i, name) in rest.iter().enumerate() {
+            value = member(&value, name).ok_or(format!("'{}' in the template has no '{}'.", path[..=i].join("."), name))?;
+        }
+        Ok(value)
+    }
+}
+
+fn member(value: &Value, name: &str) -> Option<Value> {
+    match value {
+        Value::Map(map) => map.get(&MapKey::String(name.to_string())).cloned(),
+        Value::Struct(_, fields) => fields.iter().find(|(field, _)| field == name).map(|(_, value)| value.clone()),
+        _ => None,
+    }
+}
+
+fn html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::ops::{Index, IndexMut};
+
+use crate::ast::{AstNode, Expr, Item, Stmt, ViraType};
+
+// Every node of a program lives in one arena and refers to its children by
+// `NodeId`. Passing a subtree around copies its id, not its nodes, and the
+// nodes of a program sit together in one vector. Nodes are never freed; a
+// pass that replaces a node leaves the old one unreachable.
+//
+// Ids are handed out in allocation order and never reused, so parsing the
+// same source always gives the same ids, and tools can attach what they
+// know about a node to its id in a `NodeMap`.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(u32);
+
+// Ids of nodes known to be an expression, a statement or an item, for the
+// children that can only be one of them. Indexing the arena with one gives
+// the `Expr`, `Stmt` or `Item` itself. The arena only hands them out for
+// nodes of their kind, and a pass that replaces such a child replaces it
+// with a node of the same kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(NodeId);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StmtId(NodeId);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ItemId(NodeId);
+
+#[derive(Debug, Clone, Default)]
+pub struct Arena {
+    nodes: Vec<AstNode>,
+    // Where the parser found each node. Nodes a pass makes up have none.
+    spans: SpanMap,
+    // The initializer each constant declaration was folded from, which
+    // still tells what the constant uses.
+    folded: NodeMap<NodeId>,
+}
+
+// A line and column in the source.
+pub type Position = (usize, usize);
+
+// The first and last token of a node.
+pub type Span = (Position, Position);
+
+// Something known about nodes, kept beside the tree rather than in it.
+#[derive(Debug, Clone)]
+pub struct NodeMap<T> {
+    entries: HashMap<NodeId, T>,
+}
+
+pub type SpanMap = NodeMap<Span>;
+
+pub type TypeMap = NodeMap<ViraType>;
+
+impl<T> Default for NodeMap<T> {
+    fn default() -> Self {
+        NodeMap { entries: HashMap::new() }
+    }
+}
+
+impl<T> NodeMap<T> {
+    pub fn insert(&mut self, id: NodeId, value: T) {
+        self.entries.insert(id, value);
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        self.entries.get(&id)
+    }
+}
+
+impl NodeId {
+    // The id as a number, for tools outside the compiler.
+    pub fn index(self) -> u32 {
+        self.0
+    }
+}
+
+impl ExprId {
+    pub fn node(self) -> NodeId {
+        self.0
+    }
+
+    // For passes that rewrite children in place.
+    pub fn node_mut(&mut self) -> &mut NodeId {
+        &mut self.0
+    }
+}
+
+impl StmtId {
+    pub fn node(self) -> NodeId {
+        self.0
+    }
+
+    pub fn node_mut(&mut self) -> &mut NodeId {
+        &mut self.0
+    }
+}
+
+impl ItemId {
+    pub fn node(self) -> NodeId {
+        self.0
+    }
+
+    pub fn node_mut(&mut self) -> &mut NodeId {
+        &mut self.0
+    }
+}
+
+impl From<ExprId> for NodeId {
+    fn from(id: ExprId) -> NodeId {
+        id.0
+    }
+}
+
+impl From<StmtId> for NodeId {
+    fn from(id: StmtId) -> NodeId {
+        id.0
+    }
+}
+
+impl From<ItemId> for NodeId {
+    fn from(id: ItemId) -> NodeId {
+        id.0
+    }
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Arena { nodes: Vec::new(), spans: SpanMap::default(), folded: NodeMap::default() }
+    }
+
+    pub fn alloc(&mut self, node: impl Into<AstNode>) -> NodeId {
+        let id = NodeId(self.nodes.len() as u32);
+        self.nodes.push(node.into());
+        id
+    }
+
+    pub fn alloc_expr(&mut self, expr: Expr) -> ExprId {
+        ExprId(self.alloc(expr))
+    }
+
+    pub fn alloc_stmt(&mut self, stmt: Stmt) -> StmtId {
+        StmtId(self.alloc(stmt))
+    }
+
+    pub fn alloc_item(&mut self, item: Item) -> ItemId {
+        ItemId(self.alloc(item))
+    }
+
+    // The node at `id` as an expression, statement or item, if it is one.
+    pub fn expr_id(&self, id: NodeId) -> Option<ExprId> {
+        matches!(self[id], AstNode::Expr(_)).then_some(ExprId(id))
+    }
+
+    pub fn stmt_id(&self, id: NodeId) -> Option<StmtId> {
+        matches!(self[id], AstNode::Stmt(_)).then_some(StmtId(id))
+    }
+
+    pub fn item_id(&self, id: NodeId) -> Option<ItemId> {
+        matches!(self[id], AstNode::Item(_)).then_some(ItemId(id))
+    }
+
+    pub fn set_span(&mut self, id: NodeId, span: Span) {
+        self.spans.insert(id, span);
+    }
+
+    pub fn span(&self, id: NodeId) -> Option<Span> {
+        self.spans.get(id).copied()
+    }
+
+    pub fn set_folded_from(&mut self, decl: NodeId, init: NodeId) {
+        self.folded.insert(decl, init);
+    }
+
+    pub fn folded_from(&self, decl: NodeId) -> Option<NodeId> {
+        self.folded.get(decl).copied()
+    }
+
+    // A copy of the subtree at `id`, for passes that specialize or duplicate
+    // one. Each node keeps a single parent; copies keep their spans.
+    pub fn deep_copy(&mut self, id: NodeId) -> NodeId {
+        let mut node = self[id].clone();
+        node.for_each_child_mut(&mut |child| *child = self.deep_copy(*child));
+        let copy = self.alloc(node);
+        if let Some(span) = self.span(id) {
+            self.set_span(copy, span);
+        }
+        copy
+    }
+
+    pub fn deep_copy_expr(&mut self, id: ExprId) -> ExprId {
+        ExprId(self.deep_copy(id.0))
+    }
+
+    // A copy of the subtree at `id` of another arena.
+    pub fn copy_from(&mut self, other: &Arena, id: NodeId) -> NodeId {
+        let mut node = other[id].clone();
+        node.for_each_child_mut(&mut |child| *child = self.copy_from(other, *child));
+        let copy = self.alloc(node);
+        if let Some(span) = other.span(id) {
+            self.set_span(copy, span);
+        }
+        copy
+    }
+
+    pub fn copy_expr_from(&mut self, other: &Arena, id: ExprId) -> ExprId {
+        ExprId(self.copy_from(other, id.0))
+    }
+
+    // The declaration inside any `pub` and attribute wrappers.
+    pub fn item(&self, id: NodeId) -> NodeId {
+        match &self[id] {
+            AstNode::Item(Item::Pub(item) | Item::Attribute(_, _, item)) => self.item(*item),
+            _ => id,
+        }
+    }
+}
+
+impl Index<NodeId> for Arena {
+    type Output = AstNode;
+
+    fn index(&self, id: NodeId) -> &AstNode {
+        &self.nodes[id.0 as usize]
+    }
+}
+
+impl IndexMut<NodeId> for Arena {
+    fn index_mut(&mut self, id: NodeId) -> &mut AstNode {
+        &mut self.nodes[id.0 as usize]
+    }
+}
+
+// A typed id whose node is of another kind is a bug in the pass that made
+// it, like an id out of range.
+impl Index<ExprId> for Arena {
+    type Output = Expr;
+
+    fn index(&self, id: ExprId) -> &Expr {
+        match &self[id.0] {
+            AstNode::Expr(expr) => expr,
+            node => panic!("Node {} is one of the {}, not an expression.", id.0 .0, node.kind()),
+        }
+    }
+}
+
+impl IndexMut<ExprId> for Arena {
+    fn index_mut(&mut self, id: ExprId) -> &mut Expr {
+        match &mut self[id.0] {
+            AstNode::Expr(expr) => expr,
+            node => panic!("Node {} is one of the {}, not an expression.", id.0 .0, node.kind()),
+        }
+    }
+}
+
+impl Index<StmtId> for Arena {
+    type Output = Stmt;
+
+    fn index(&self, id: StmtId) -> &Stmt {
+        match &self[id.0] {
+            AstNode::Stmt(stmt) => stmt,
+            node => panic!("Node {} is one of the {}, not a statement.", id.0 .0, node.kind()),
+        }
+    }
+}
+
+impl IndexMut<StmtId> for Arena {
+    fn index_mut(&mut self, id: StmtId) -> &mut Stmt {
+        match &mut self[id.0] {
+            AstNode::Stmt(stmt) => stmt,
+            node => panic!("Node {} is one of the {}, not a statement.", id.0 .0, node.kind()),
+        }
+    }
+}
+
+impl Index<ItemId> for Arena {
+    type Output = Item;
+
+    fn index(&self, id: ItemId) -> &Item {
+        match &self[id.0] {
+            AstNode::Item(item) => item,
+            node => panic!("Node {} is one of the {}, not an item.", id.0 .0, node.kind()),
+        }
+    }
+}
+
+impl IndexMut<ItemId> for Arena {
+    fn index_mut(&mut self, id: ItemId) -> &mut Item {
+        match &mut self[id.0] {
+            AstNode::Item(item) => item,
+            node => panic!("Node {} is one of the {}, not an item.", id.0 .0, node.kind()),
+        }
+    }
+}
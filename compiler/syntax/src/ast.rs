@@ -0,0 +1,538 @@
+use crate::arena::{Arena, ExprId, ItemId, NodeId};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ViraType {
+    Int,
+    Float,
+    Decimal,
+    Complex,
+    Bool,
+    String,
+    Array(Box<ViraType>),
+    Struct(String),
+    Enum(String),
+    Range,
+    Function(Vec<ViraType>, Box<ViraType>),
+    TypeVar(String),
+    Tuple(Vec<ViraType>),
+    Map(Box<ViraType>, Box<ViraType>),
+    // `int?`: a value of the inner type, or nil.
+    Optional(Box<ViraType>),
+}
+
+// A trait method: name, parameters after `self`, return type.
+pub type MethodSig = (String, Vec<(String, ViraType)>, ViraType);
+
+// An enum variant: name, payload types, discriminant. The discriminant is
+// the constant after `=`, or one more than the previous variant's.
+pub type Variant = (String, Vec<ViraType>, i64);
+
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub name: String,
+    pub typ: ViraType,
+}
+
+// Children are ids of nodes in the program's `Arena`. Each node is an
+// expression, a statement or an item. A child that can only be one of them,
+// such as the operand of a binary operator, has an `ExprId` or `ItemId`, so
+// a pass gets the `Expr` or `Item` itself; one that can be any node, such as
+// a statement of a block, has a `NodeId`.
+#[derive(Debug, Clone)]
+pub enum AstNode {
+    Expr(Expr),
+    Stmt(Stmt),
+    Item(Item),
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Literal(i64),
+    FloatLiteral(f64),
+    BoolLiteral(bool),
+    StringLiteral(String),
+    Nil,
+    Binary(ExprId, BinOp, ExprId),
+    Unary(UnaryOp, ExprId),
+    // An int operand of an arithmetic or comparison operator whose other
+    // operand is a float, as that float. The type checker inserts these;
+    // there is no syntax for them.
+    IntToFloat(ExprId),
+    VarRef(String),
+    Call(String, Vec<ExprId>),
+    // As a statement the branches may be any statement; as an expression
+    // they are blocks or another `if`.
+    If(ExprId, NodeId, Option<NodeId>),
+    Block(Vec<NodeId>),
+    ArrayLiteral(Vec<ExprId>),
+    Index(ExprId, ExprId),
+    StructLiteral(String, Vec<(String, ExprId)>),
+    FieldAccess(ExprId, String),
+    // `a?.field`: nil when `a` is nil.
+    SafeAccess(ExprId, String),
+    // `a ?? b`: `a` unless it is nil, in which case `b`, which is only
+    // evaluated then.
+    Coalesce(ExprId, ExprId),
+    Tuple(Vec<ExprId>),
+    TupleIndex(ExprId, usize),
+    MapLiteral(Vec<(ExprId, ExprId)>),
+    EnumConstructor(String, String, Vec<ExprId>),
+    MethodCall(ExprId, String, Vec<ExprId>),
+    Match(ExprId, Vec<(Pattern, ExprId)>),
+    Range(ExprId, ExprId),
+    Lambda(Vec<(String, ViraType)>, ViraType, ExprId),
+    Apply(ExprId, Vec<ExprId>),
+    // `"text ${expr} text"`: the text as string literals between the
+    // expressions, which `lower` turns into a concatenation.
+    Interpolation(Vec<ExprId>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    // `let x: T = ...`, or `let x = ...`, whose type is the initializer's.
+    VarDecl(String, Option<ViraType>, ExprId),
+    // `let (a, b): (int, string) = ...`, the annotation likewise optional.
+    TupleDecl(Vec<String>, Option<ViraType>, ExprId),
+    Assign(String, ExprId),
+    // `x += e`, and likewise `-=`, `*=`, `/=` and `%=`, which `lower` turns
+    // into `x = x + e`.
+    CompoundAssign(String, BinOp, ExprId),
+    While(ExprId, NodeId),
+    // `for init; cond; incr body`
+    For(NodeId, ExprId, NodeId, NodeId),
+    ForIn(String, ExprId, NodeId),
+    Return(Option<ExprId>),
+    Write(ExprId),
+    // `defer stmt`: runs `stmt` when the enclosing block exits, latest first.
+    Defer(NodeId),
+    // `throw expr`: unwinds to the nearest enclosing `try`.
+    Throw(ExprId),
+    // `try { ... } catch (e) { ... }`: body, name bound to the thrown value,
+    // handler.
+    Try(ExprId, String, ExprId),
+}
+
+// Declarations. Functions, types and impls may also appear in blocks; the
+// rest only at the top level.
+#[derive(Debug, Clone)]
+pub enum Item {
+    // Name, type parameters, parameters, return type, body.
+    FuncDecl(String, Vec<String>, Vec<(String, ViraType)>, ViraType, NodeId),
+    StructDecl(String, Vec<(String, ViraType)>),
+    EnumDecl(String, Vec<Variant>),
+    // `trait Shape { func area(self) -> float }`
+    TraitDecl(String, Vec<MethodSig>),
+    // `impl Shape for Circle { ... }`, or `impl Circle { ... }` without a
+    // trait: trait, type and methods. The methods are function declarations
+    // whose first parameter is `self`.
+    ImplDecl(Option<String>, String, Vec<ItemId>),
+    ConstDecl(String, ViraType, ExprId),
+    // `config { threads = 4, verbose = true }` at the top level: keys and
+    // their values, folded to literals like those of constants.
+    ConfigDecl(Vec<(String, ExprId)>),
+    // `data name = <<<END ... END` at the top level: name, type, which is
+    // string or array<int> for the bytes, delimiter and contents.
+    DataDecl(String, ViraType, String, String),
+    // `type Name = T`. Uses of the alias are replaced by `T` while parsing.
+    TypeAlias(String, ViraType),
+    Import(String),
+    // `pub func`, `pub let` or `pub const` at the top level of a module.
+    Pub(NodeId),
+    // `@name` or `@name(args)` before a declaration: name, arguments, item.
+    Attribute(String, Vec<ExprId>, NodeId),
+}
+
+impl From<Expr> for AstNode {
+    fn from(expr: Expr) -> Self {
+        AstNode::Expr(expr)
+    }
+}
+
+impl From<Stmt> for AstNode {
+    fn from(stmt: Stmt) -> Self {
+        AstNode::Stmt(stmt)
+    }
+}
+
+impl From<Item> for AstNode {
+    fn from(item: Item) -> Self {
+        AstNode::Item(item)
+    }
+}
+
+// A parsed program: the top-level nodes of each module, imported modules
+// first, with every node in one arena.
+pub struct Program {
+    pub arena: Arena,
+    pub modules: Vec<(String, Vec<NodeId>)>,
+}
+
+// The entries of the config block among a module's top-level items, for
+// passes and for tools, such as the test runner, that read the settings
+// without running the program.
+pub fn config<'a>(arena: &'a Arena, items: &[NodeId]) -> Option<&'a [(String, ExprId)]> {
+    items.iter().find_map(|&item| match &arena[item] {
+        AstNode::Item(Item::ConfigDecl(entries)) => Some(entries.as_slice()),
+        _ => None,
+    })
+}
+
+// Whether the top-level item is a function marked `@test`.
+pub fn is_test(arena: &Arena, mut item: NodeId) -> bool {
+    loop {
+        match &arena[item] {
+            AstNode::Item(Item::Attribute(name, ..)) if name == "test" => return true,
+            AstNode::Item(Item::Pub(inner) | Item::Attribute(_, _, inner)) => item = *inner,
+            _ => return false,
+        }
+    }
+}
+
+// The names of the `@test` functions among a module's top-level items, in
+// the order they are declared.
+pub fn tests(arena: &Arena, items: &[NodeId]) -> Vec<String> {
+    items
+        .iter()
+        .filter(|&&item| is_test(arena, item))
+        .filter_map(|&item| match &arena[arena.item(item)] {
+            AstNode::Item(Item::FuncDecl(name, ..)) => Some(name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+// The type of a folded constant, or None when `id` is not one.
+pub fn literal_type(arena: &Arena, id: ExprId) -> Option<ViraType> {
+    match &arena[id] {
+        Expr::Literal(_) => Some(ViraType::Int),
+        Expr::FloatLiteral(_) => Some(ViraType::Float),
+        Expr::BoolLiteral(_) => Some(ViraType::Bool),
+        Expr::StringLiteral(_) => Some(ViraType::String),
+        // Decimals have no literal syntax; a folded one is the call that
+        // makes it from its text.
+        Expr::Call(name, args) if name == "decimal" => match args.as_slice() {
+            [arg] => matches!(arena[*arg], Expr::StringLiteral(_)).then_some(ViraType::Decimal),
+            _ => None,
+        },
+        // Arrays need a first element to have a type, and every element has to match it.
+        Expr::ArrayLiteral(items) => {
+            let typ = literal_type(arena, *items.first()?)?;
+            items.iter().all(|&item| literal_type(arena, item).as_ref() == Some(&typ)).then(|| ViraType::Array(Box::new(typ)))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+    Binding(String),
+    EnumVariant(String, String, Vec<Pattern>),
+    Wildcard,
+}
+
+impl AstNode {
+    // Whether the node produces a value worth showing, as opposed to a
+    // declaration or statement that is run for its effect.
+    pub fn is_expression(&self) -> bool {
+        !matches!(self, AstNode::Expr(Expr::If(_, _, None) | Expr::Block(_)) | AstNode::Stmt(_) | AstNode::Item(_))
+    }
+
+    // The construct a node belongs to, phrased for diagnostics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AstNode::Expr(expr) => expr.kind(),
+            AstNode::Stmt(stmt) => stmt.kind(),
+            AstNode::Item(item) => item.kind(),
+        }
+    }
+
+    pub fn for_each_child(&self, f: &mut dyn FnMut(NodeId)) {
+        match self {
+            AstNode::Expr(expr) => expr.for_each_child(f),
+            AstNode::Stmt(stmt) => stmt.for_each_child(f),
+            AstNode::Item(item) => item.for_each_child(f),
+        }
+    }
+
+    pub fn for_each_child_mut(&mut self, f: &mut dyn FnMut(&mut NodeId)) {
+        match self {
+            AstNode::Expr(expr) => expr.for_each_child_mut(f),
+            AstNode::Stmt(stmt) => stmt.for_each_child_mut(f),
+            AstNode::Item(item) => item.for_each_child_mut(f),
+        }
+    }
+}
+
+impl Expr {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Expr::Literal(_) => "int literals",
+            Expr::FloatLiteral(_) => "float literals",
+            Expr::BoolLiteral(_) => "bool literals",
+            Expr::StringLiteral(_) => "strings",
+            Expr::Nil => "nil",
+            Expr::Binary(..) => "binary operators",
+            Expr::Unary(..) => "unary operators",
+            Expr::IntToFloat(_) => "int to float conversions",
+            Expr::VarRef(_) => "variables",
+            Expr::Call(..) => "function calls",
+            Expr::If(..) => "if expressions",
+            Expr::Block(_) => "blocks",
+            Expr::ArrayLiteral(_) => "arrays",
+            Expr::Index(..) => "indexing",
+            Expr::StructLiteral(..) => "struct literals",
+            Expr::FieldAccess(..) => "field access",
+            Expr::SafeAccess(..) => "optional field access",
+            Expr::Coalesce(..) => "'??' operators",
+            Expr::Tuple(_) => "tuples",
+            Expr::TupleIndex(..) => "tuple indexing",
+            Expr::MapLiteral(_) => "maps",
+            Expr::EnumConstructor(..) => "enum constructors",
+            Expr::MethodCall(..) => "method calls",
+            Expr::Match(..) => "match expressions",
+            Expr::Range(..) => "ranges",
+            Expr::Lambda(..) => "anonymous functions",
+            Expr::Apply(..) => "calls of function values",
+            Expr::Interpolation(_) => "string interpolation",
+        }
+    }
+
+    pub fn for_each_child(&self, f: &mut dyn FnMut(NodeId)) {
+        match self {
+            Expr::Binary(l, _, r) | Expr::Index(l, r) | Expr::Range(l, r) | Expr::Coalesce(l, r) => {
+                f(l.node());
+                f(r.node());
+            }
+            Expr::Unary(_, e) | Expr::IntToFloat(e) | Expr::Lambda(_, _, e) => f(e.node()),
+            Expr::Block(items) => items.iter().copied().for_each(f),
+            Expr::Call(_, items) | Expr::ArrayLiteral(items) | Expr::Tuple(items) | Expr::EnumConstructor(_, _, items) | Expr::Interpolation(items) => {
+                items.iter().for_each(|item| f(item.node()))
+            }
+            Expr::Apply(callee, args) | Expr::MethodCall(callee, _, args) => {
+                f(callee.node());
+                args.iter().for_each(|arg| f(arg.node()));
+            }
+            Expr::StructLiteral(_, fields) => fields.iter().for_each(|(_, value)| f(value.node())),
+            Expr::MapLiteral(entries) => entries.iter().for_each(|(key, value)| {
+                f(key.node());
+                f(value.node());
+            }),
+            Expr::FieldAccess(base, _) | Expr::SafeAccess(base, _) | Expr::TupleIndex(base, _) => f(base.node()),
+            Expr::Match(scrutinee, arms) => {
+                f(scrutinee.node());
+                arms.iter().for_each(|(_, body)| f(body.node()));
+            }
+            Expr::If(c, t, e) => {
+                f(c.node());
+                f(*t);
+                if let Some(e) = e {
+                    f(*e);
+                }
+            }
+            Expr::Literal(_) | Expr::FloatLiteral(_) | Expr::BoolLiteral(_) | Expr::StringLiteral(_) | Expr::Nil | Expr::VarRef(_) => {}
+        }
+    }
+
+    pub fn for_each_child_mut(&mut self, f: &mut dyn FnMut(&mut NodeId)) {
+        match self {
+            Expr::Binary(l, _, r) | Expr::Index(l, r) | Expr::Range(l, r) | Expr::Coalesce(l, r) => {
+                f(l.node_mut());
+                f(r.node_mut());
+            }
+            Expr::Unary(_, e) | Expr::IntToFloat(e) | Expr::Lambda(_, _, e) => f(e.node_mut()),
+            Expr::Block(items) => items.iter_mut().for_each(f),
+            Expr::Call(_, items) | Expr::ArrayLiteral(items) | Expr::Tuple(items) | Expr::EnumConstructor(_, _, items) | Expr::Interpolation(items) => {
+                items.iter_mut().for_each(|item| f(item.node_mut()))
+            }
+            Expr::Apply(callee, args) | Expr::MethodCall(callee, _, args) => {
+                f(callee.node_mut());
+                args.iter_mut().for_each(|arg| f(arg.node_mut()));
+            }
+            Expr::StructLiteral(_, fields) => fields.iter_mut().for_each(|(_, value)| f(value.node_mut())),
+            Expr::MapLiteral(entries) => entries.iter_mut().for_each(|(key, value)| {
+                f(key.node_mut());
+                f(value.node_mut());
+            }),
+            Expr::FieldAccess(base, _) | Expr::SafeAccess(base, _) | Expr::TupleIndex(base, _) => f(base.node_mut()),
+            Expr::Match(scrutinee, arms) => {
+                f(scrutinee.node_mut());
+                arms.iter_mut().for_each(|(_, body)| f(body.node_mut()));
+            }
+            Expr::If(c, t, e) => {
+                f(c.node_mut());
+                f(t);
+                if let Some(e) = e {
+                    f(e);
+                }
+            }
+            Expr::Literal(_) | Expr::FloatLiteral(_) | Expr::BoolLiteral(_) | Expr::StringLiteral(_) | Expr::Nil | Expr::VarRef(_) => {}
+        }
+    }
+}
+
+impl Stmt {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Stmt::VarDecl(..) => "let declarations",
+            Stmt::TupleDecl(..) => "tuple destructuring",
+            Stmt::Assign(..) => "assignments",
+            Stmt::CompoundAssign(..) => "compound assignments",
+            Stmt::While(..) => "while loops",
+            Stmt::For(..) => "for loops",
+            Stmt::ForIn(..) => "for-in loops",
+            Stmt::Return(_) => "return statements",
+            Stmt::Write(_) => "write statements",
+            Stmt::Defer(_) => "defer statements",
+            Stmt::Throw(_) => "throw statements",
+            Stmt::Try(..) => "try statements",
+        }
+    }
+
+    pub fn for_each_child(&self, f: &mut dyn FnMut(NodeId)) {
+        match self {
+            Stmt::While(cond, body) | Stmt::ForIn(_, cond, body) => {
+                f(cond.node());
+                f(*body);
+            }
+            Stmt::Try(body, _, handler) => {
+                f(body.node());
+                f(handler.node());
+            }
+            Stmt::VarDecl(_, _, e) | Stmt::TupleDecl(_, _, e) | Stmt::Assign(_, e) | Stmt::CompoundAssign(_, _, e) | Stmt::Write(e) | Stmt::Throw(e) => f(e.node()),
+            Stmt::Defer(stmt) => f(*stmt),
+            Stmt::For(init, cond, incr, body) => {
+                f(*init);
+                f(cond.node());
+                f(*incr);
+                f(*body);
+            }
+            Stmt::Return(e) => {
+                if let Some(e) = e {
+                    f(e.node());
+                }
+            }
+        }
+    }
+
+    pub fn for_each_child_mut(&mut self, f: &mut dyn FnMut(&mut NodeId)) {
+        match self {
+            Stmt::While(cond, body) | Stmt::ForIn(_, cond, body) => {
+                f(cond.node_mut());
+                f(body);
+            }
+            Stmt::Try(body, _, handler) => {
+                f(body.node_mut());
+                f(handler.node_mut());
+            }
+            Stmt::VarDecl(_, _, e) | Stmt::TupleDecl(_, _, e) | Stmt::Assign(_, e) | Stmt::CompoundAssign(_, _, e) | Stmt::Write(e) | Stmt::Throw(e) => f(e.node_mut()),
+            Stmt::Defer(stmt) => f(stmt),
+            Stmt::For(init, cond, incr, body) => {
+                f(init);
+                f(cond.node_mut());
+                f(incr);
+                f(body);
+            }
+            Stmt::Return(e) => {
+                if let Some(e) = e {
+                    f(e.node_mut());
+                }
+            }
+        }
+    }
+}
+
+impl Item {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Item::FuncDecl(..) => "function declarations",
+            Item::StructDecl(..) => "struct declarations",
+            Item::EnumDecl(..) => "enum declarations",
+            Item::TraitDecl(..) => "trait declarations",
+            Item::ImplDecl(..) => "impl blocks",
+            Item::ConstDecl(..) => "constants",
+            Item::ConfigDecl(_) => "config blocks",
+            Item::DataDecl(..) => "data sections",
+            Item::TypeAlias(..) => "type aliases",
+            Item::Import(_) => "imports",
+            Item::Pub(_) => "visibility modifiers",
+            Item::Attribute(..) => "attributes",
+        }
+    }
+
+    pub fn for_each_child(&self, f: &mut dyn FnMut(NodeId)) {
+        match self {
+            Item::FuncDecl(_, _, _, _, body) | Item::Pub(body) => f(*body),
+            Item::ConstDecl(_, _, e) => f(e.node()),
+            Item::ImplDecl(_, _, methods) => methods.iter().for_each(|method| f(method.node())),
+            Item::ConfigDecl(entries) => entries.iter().for_each(|(_, value)| f(value.node())),
+            Item::Attribute(_, args, item) => {
+                args.iter().for_each(|arg| f(arg.node()));
+                f(*item);
+            }
+            Item::StructDecl(..) | Item::EnumDecl(..) | Item::TraitDecl(..) | Item::TypeAlias(..) | Item::DataDecl(..) | Item::Import(_) => {}
+        }
+    }
+
+    pub fn for_each_child_mut(&mut self, f: &mut dyn FnMut(&mut NodeId)) {
+        match self {
+            Item::FuncDecl(_, _, _, _, body) | Item::Pub(body) => f(body),
+            Item::ConstDecl(_, _, e) => f(e.node_mut()),
+            Item::ImplDecl(_, _, methods) => methods.iter_mut().for_each(|method| f(method.node_mut())),
+            Item::ConfigDecl(entries) => entries.iter_mut().for_each(|(_, value)| f(value.node_mut())),
+            Item::Attribute(_, args, item) => {
+                args.iter_mut().for_each(|arg| f(arg.node_mut()));
+                f(item);
+            }
+            Item::StructDecl(..) | Item::EnumDecl(..) | Item::TraitDecl(..) | Item::TypeAlias(..) | Item::DataDecl(..) | Item::Import(_) => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    Neq,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    And,
+    Or,
+}
+
+// Methods that operators call when their left operand is a struct or enum,
+// each with the right operand as its only argument. `index` serves `x[i]`.
+pub const OPERATOR_METHODS: [&str; 7] = ["add", "sub", "mul", "div", "rem", "eq", "index"];
+
+// What `@derive(...)` can generate; vira-check's `derive` expands them.
+pub const DERIVABLE: [&str; 5] = ["hash", "eq", "to_string", "clone", "json"];
+
+impl BinOp {
+    // `!=` calls `eq` and negates the result.
+    pub fn method(self) -> Option<&'static str> {
+        match self {
+            BinOp::Add => Some("add"),
+            BinOp::Sub => Some("sub"),
+            BinOp::Mul => Some("mul"),
+            BinOp::Div => Some("div"),
+            BinOp::Mod => Some("rem"),
+            BinOp::Eq | BinOp::Neq => Some("eq"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use unicode_security::confusable_detection::skeleton;
+
+use crate::tokenizer::{Token, TokenType};
+
+// Two different identifiers that render alike (e.g. Latin `a` and Cyrillic
+// `а`) are almost always a mistake or an attack, so the driver warns about
+// every identifier whose confusable skeleton matches an earlier, different one.
+pub fn confusable_warnings(tokens: &[Token]) -> Vec<String> {
+    let mut first_seen: HashMap<String, &Token> = HashMap::new();
+    let mut warnings = Vec::new();
+    let mut reported: Vec<(&str, &str)> = Vec::new();
+    for token in tokens.iter().filter(|t| t.typ == TokenType::Identifier) {
+        let key: String = skeleton(&token.lexeme).collect();
+        match first_seen.get(&key) {
+            Some(original) if original.lexeme != token.lexeme => {
+                let pair = (original.lexeme.as_str(), token.lexeme.as_str());
+                if !reported.contains(&pair) {
+                    reported.push(pair);
+                    warnings.push(format!(
+                        "{}:{}: warning: identifier '{}' is confusable with '{}' (first used at {}:{})",
+                        token.line, token.col, token.lexeme, original.lexeme, original.line, original.col
+                    ));
+                }
+            }
+            Some(_) => {}
+            None => {
+                first_seen.insert(key, token);
+            }
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::tokenize;
+
+    fn warnings(source: &str) -> Vec<String> {
+        confusable_warnings(&tokenize(source))
+    }
+
+    #[test]
+    fn letters_of_other_scripts_are_confusable() {
+        // Cyrillic `а` and Greek `ο`.
+        assert_eq!(warnings("let a = 1\nlet \u{430} = 2"), vec!["2:5: warning: identifier '\u{430}' is confusable with 'a' (first used at 1:5)"]);
+        assert_eq!(warnings("let foo = 1\nfo\u{3bf}"), vec!["2:1: warning: identifier 'fo\u{3bf}' is confusable with 'foo' (first used at 1:5)"]);
+    }
+
+    #[test]
+    fn lookalikes_in_one_script_are_confusable() {
+        assert_eq!(warnings("let rn = 1\nlet m = 2"), vec!["2:5: warning: identifier 'm' is confusable with 'rn' (first used at 1:5)"]);
+        assert_eq!(warnings("let l = 1\nlet I = 2"), vec!["2:5: warning: identifier 'I' is confusable with 'l' (first used at 1:5)"]);
+    }
+
+    #[test]
+    fn each_pair_is_reported_once() {
+        assert_eq!(warnings("a \u{430} \u{430} a \u{430}").len(), 1);
+    }
+
+    #[test]
+    fn distinct_and_equivalent_names_are_not_confusable() {
+        assert_eq!(warnings("let a = 1\nlet b = a + a\nlet ab = b"), Vec::<String>::new());
+        // Composed and decomposed `é` are one identifier after NFC.
+        assert_eq!(warnings("let caf\u{e9} = 1\ncafe\u{301}"), Vec::<String>::new());
+        // A string is not an identifier.
+        assert_eq!(warnings("let \u{430} = \"a\""), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,23 @@
+use std::fmt;
+
+use crate::arena::Span;
+
+// An error in the source: where it is and what is wrong. It shows as
+// `line:col: message`, the form the other stages report errors in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ((line, col), _) = self.span;
+        write!(f, "{}:{}: {}", line, col, self.message)
+    }
+}
+
+// Several diagnostics, one per line.
+pub fn render(diagnostics: &[Diagnostic]) -> String {
+    diagnostics.iter().map(Diagnostic::to_string).collect::<Vec<_>>().join("\n")
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::arena::{Arena, NodeId};
+use crate::ast::{AstNode, Item, Stmt};
+
+// Structural diff of two versions of a program, for tools that redo work
+// only for what changed: the items of each are matched by what they
+// declare, `func f` with `func f`, and a matched pair counts as changed when
+// their trees differ, `pub` and attributes included. Where an item sits in
+// the file and how it is spelled do not count, so moving a function or
+// reformatting it is no change. Top-level statements declare nothing and
+// are matched in order.
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ItemKey {
+    pub kind: &'static str,
+    pub name: String,
+    // Which of the items with this kind and name, counting from 0, since a
+    // program that redeclares one still parses.
+    pub occurrence: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    // The new item.
+    Added(ItemKey, NodeId),
+    // The old item.
+    Removed(ItemKey, NodeId),
+    // The old item, then the new one.
+    Changed(ItemKey, NodeId, NodeId),
+}
+
+// The changes from `old` to `new`: the added and changed items in the order
+// of `new`, then the removed ones in the order of `old`. Unchanged items are
+// left out, so two versions that are the same give no changes.
+pub fn diff(old_arena: &Arena, old: &[NodeId], new_arena: &Arena, new: &[NodeId]) -> Vec<Change> {
+    let mut unmatched: HashMap<ItemKey, NodeId> = keyed(old_arena, old).into_iter().collect();
+    let mut changes = Vec::new();
+    for (key, item) in keyed(new_arena, new) {
+        match unmatched.remove(&key) {
+            Some(before) if same_tree(old_arena, before, new_arena, item) => {}
+            Some(before) => changes.push(Change::Changed(key, before, item)),
+            None => changes.push(Change::Added(key, item)),
+        }
+    }
+    for (key, item) in keyed(old_arena, old) {
+        if unmatched.contains_key(&key) {
+            changes.push(Change::Removed(key, item));
+        }
+    }
+    changes
+}
+
+fn keyed(arena: &Arena, items: &[NodeId]) -> Vec<(ItemKey, NodeId)> {
+    let mut seen: HashMap<(&'static str, String), usize> = HashMap::new();
+    items
+        .iter()
+        .map(|&item| {
+            let (kind, name) = declares(arena, arena.item(item));
+            let count = seen.entry((kind, name.clone())).or_default();
+            let key = ItemKey { kind, name, occurrence: *count };
+            *count += 1;
+            (key, item)
+        })
+        .collect()
+}
+
+// The kind of item and the name it declares.
+fn declares(arena: &Arena, item: NodeId) -> (&'static str, String) {
+    match &arena[item] {
+        AstNode::Item(Item::FuncDecl(name, ..)) => ("func", name.clone()),
+        AstNode::Item(Item::StructDecl(name, _)) => ("struct", name.clone()),
+        AstNode::Item(Item::EnumDecl(name, _)) => ("enum", name.clone()),
+        AstNode::Item(Item::TraitDecl(name, _)) => ("trait", name.clone()),
+        AstNode::Item(Item::ImplDecl(Some(trait_name), type_name, _)) => ("impl", format!("{} for {}", trait_name, type_name)),
+        AstNode::Item(Item::ImplDecl(None, type_name, _)) => ("impl", type_name.clone()),
+        AstNode::Item(Item::ConstDecl(name, ..)) => ("const", name.clone()),
+        AstNode::Item(Item::ConfigDecl(_)) => ("config", String::new()),
+        AstNode::Item(Item::DataDecl(name, ..)) => ("data", name.clone()),
+        AstNode::Stmt(Stmt::VarDecl(name, ..)) => ("let", name.clone()),
+        AstNode::Stmt(Stmt::TupleDecl(names, ..)) => ("let", format!("({})", names.join(", "))),
+        AstNode::Item(Item::TypeAlias(name, _)) => ("type", name.clone()),
+        AstNode::Item(Item::Import(module)) => ("import", module.clone()),
+        _ => ("statement", String::new()),
+    }
+}
+
+// Whether the subtrees at `x` and `y` are the same, comparing the nodes but
+// not where they sit in their arenas.
+pub fn same_tree(a: &Arena, x: NodeId, b: &Arena, y: NodeId) -> bool {
+    let (mut left, mut right) = (a[x].clone(), b[y].clone());
+    let (mut left_children, mut right_children) = (Vec::new(), Vec::new());
+    left.for_each_child_mut(&mut |child| left_children.push(std::mem::replace(child, x)));
+    right.for_each_child_mut(&mut |child| right_children.push(std::mem::replace(child, x)));
+    format!("{:?}", left) == format!("{:?}", right)
+        && left_children.len() == right_children.len()
+        && left_children.into_iter().zip(right_children).all(|(x, y)| same_tree(a, x, b, y))
+}
+
+impl fmt::Display for ItemKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.kind, self.occurrence) {
+            ("statement", n) => write!(f, "statement {}", n + 1),
+            ("config", _) => write!(f, "config"),
+            (kind, 0) => write!(f, "{} {}", kind, self.name),
+            (kind, n) => write!(f, "{} {} #{}", kind, self.name, n + 1),
+        }
+    }
+}
@@ -0,0 +1,20 @@
+// The public part of the Vira front end: tokens and the lexer's warnings,
+// the parser and its diagnostics, the syntax tree with its arena and side
+// tables, traversals, the lowering of sugar, structural diffs, and the
+// printer that turns a tree back into source. Linters and code generators can depend on this crate
+// without pulling in the interpreter or the backend.
+//
+// Anything public here is covered by semver: a breaking change, a new node
+// kind or token type included, bumps the minor version while the crate is
+// 0.x and the major version after that.
+
+pub mod arena;
+pub mod ast;
+pub mod confusables;
+pub mod diagnostic;
+pub mod diff;
+pub mod lower;
+pub mod parser;
+pub mod printer;
+pub mod tokenizer;
+pub mod visit;
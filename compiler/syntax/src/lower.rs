@@ -0,0 +1,126 @@
+use crate::arena::{Arena, ExprId, NodeId};
+use crate::ast::{AstNode, BinOp, Expr, Stmt};
+use crate::visit::{walk_mut, VisitorMut};
+
+// Desugaring. Runs right after parsing, so every later pass, the engines and
+// `--emit` see only the core nodes:
+//
+//   for (init; cond; incr) body   =>  { init  while cond { body  incr } }
+//   for x in e body               =>  { let _x_items = e.items()  let _x_count = _x_items.len()  let _x_index = 0
+//                                       while _x_index < _x_count { let x = _x_items[_x_index]  _x_index = _x_index + 1  body } }
+//   a && b                        =>  if a { b } else { false }
+//   a || b                        =>  if a { true } else { b }
+//   x += e                        =>  x = x + e
+//   "a ${e} b"                    =>  "a " + format(e) + " b"
+//
+// The operators therefore short-circuit. What a for-in loop visits, a
+// range, array, map or string, is only known at run time, so the loop
+// indexes whatever `items` gives for it; for a range that is the range
+// itself, not its ints. The loop's own variables start with `_`, so they are
+// never reported as unused or shadowing, and print as names that parse back.
+// `fmt` parses without lowering, so formatted code keeps its sugar.
+
+pub fn lower(arena: &mut Arena, program: &[NodeId]) {
+    for &id in program {
+        Lower.visit_mut(arena, id);
+    }
+}
+
+struct Lower;
+
+impl VisitorMut for Lower {
+    fn visit_mut(&mut self, arena: &mut Arena, id: NodeId) {
+        walk_mut(self, arena, id);
+        let lowered = match arena[id] {
+            AstNode::Stmt(Stmt::For(init, cond, incr, body)) => {
+                let body = arena.alloc(Expr::Block(vec![body, incr]));
+                let while_loop = arena.alloc(Stmt::While(cond, body));
+                AstNode::Expr(Expr::Block(vec![init, while_loop]))
+            }
+            AstNode::Expr(Expr::Binary(left, BinOp::And, right)) => {
+                let then = arena.alloc(Expr::Block(vec![right.node()]));
+                let otherwise = block_of(arena, AstNode::Expr(Expr::BoolLiteral(false)));
+                AstNode::Expr(Expr::If(left, then, Some(otherwise)))
+            }
+            AstNode::Expr(Expr::Binary(left, BinOp::Or, right)) => {
+                let then = block_of(arena, AstNode::Expr(Expr::BoolLiteral(true)));
+                let otherwise = arena.alloc(Expr::Block(vec![right.node()]));
+                AstNode::Expr(Expr::If(left, then, Some(otherwise)))
+            }
+            AstNode::Stmt(Stmt::ForIn(ref name, iterable, body)) => {
+                let name = name.clone();
+                for_in(arena, id, &name, iterable, body)
+            }
+            AstNode::Stmt(Stmt::CompoundAssign(ref name, op, value)) => {
+                let name = name.clone();
+                let current = arena.alloc_expr(Expr::VarRef(name.clone()));
+                let updated = arena.alloc_expr(Expr::Binary(current, op, value));
+                AstNode::Stmt(Stmt::Assign(name, updated))
+            }
+            AstNode::Expr(Expr::Interpolation(ref parts)) => {
+                let parts = parts.clone();
+                concat(arena, &parts)
+            }
+            _ => return,
+        };
+        arena[id] = lowered;
+    }
+}
+
+fn for_in(arena: &mut Arena, id: NodeId, name: &str, iterable: ExprId, body: NodeId) -> AstNode {
+    let [items, count, index] = ["items", "count", "index"].map(|part| format!("_{}_{}", name, part));
+    let all = arena.alloc_expr(Expr::MethodCall(iterable, "items".to_string(), Vec::new()));
+    let declare_items = arena.alloc(Stmt::VarDecl(items.clone(), None, all));
+    let items_ref = arena.alloc_expr(Expr::VarRef(items.clone()));
+    let len = arena.alloc_expr(Expr::MethodCall(items_ref, "len".to_string(), Vec::new()));
+    let declare_count = arena.alloc(Stmt::VarDecl(count.clone(), None, len));
+    let zero = arena.alloc_expr(Expr::Literal(0));
+    let declare_index = arena.alloc(Stmt::VarDecl(index.clone(), None, zero));
+
+    let index_ref = arena.alloc_expr(Expr::VarRef(index.clone()));
+    let count_ref = arena.alloc_expr(Expr::VarRef(count));
+    let condition = arena.alloc_expr(Expr::Binary(index_ref, BinOp::Lt, count_ref));
+    let items_ref = arena.alloc_expr(Expr::VarRef(items));
+    let index_ref = arena.alloc_expr(Expr::VarRef(index.clone()));
+    let item = arena.alloc_expr(Expr::Index(items_ref, index_ref));
+    let declare = arena.alloc(Stmt::VarDecl(name.to_string(), None, item));
+    let index_ref = arena.alloc_expr(Expr::VarRef(index.clone()));
+    let one = arena.alloc_expr(Expr::Literal(1));
+    let next = arena.alloc_expr(Expr::Binary(index_ref, BinOp::Add, one));
+    let advance = arena.alloc(Stmt::Assign(index, next));
+    // Errors in the made-up nodes, and warnings about `x`, point at the loop.
+    if let Some(span) = arena.span(id) {
+        for node in [all.node(), declare, item.node()] {
+            arena.set_span(node, span);
+        }
+    }
+    let loop_body = arena.alloc(Expr::Block(vec![declare, advance, body]));
+    let while_loop = arena.alloc(Stmt::While(condition, loop_body));
+    AstNode::Expr(Expr::Block(vec![declare_items, declare_count, declare_index, while_loop]))
+}
+
+// The branches of an `if` that is an expression are blocks.
+fn block_of(arena: &mut Arena, node: AstNode) -> NodeId {
+    let node = arena.alloc(node);
+    arena.alloc(Expr::Block(vec![node]))
+}
+
+// The parts of an interpolated string joined with `+`, with `format` around
+// those that are not text.
+fn concat(arena: &mut Arena, parts: &[ExprId]) -> AstNode {
+    let mut strings = Vec::new();
+    for &part in parts {
+        strings.push(match arena[part] {
+            Expr::StringLiteral(_) => part,
+            _ => arena.alloc_expr(Expr::Call("format".to_string(), vec![part])),
+        });
+    }
+    match strings.split_last() {
+        Some((&last, [first, rest @ ..])) => {
+            let left = rest.iter().fold(*first, |text, &part| arena.alloc_expr(Expr::Binary(text, BinOp::Add, part)));
+            AstNode::Expr(Expr::Binary(left, BinOp::Add, last))
+        }
+        Some((&only, [])) => AstNode::Expr(arena[only].clone()),
+        None => AstNode::Expr(Expr::StringLiteral(String::new())),
+    }
+}
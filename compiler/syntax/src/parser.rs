@@ -0,0 +1,1568 @@
+#![deny(clippy::indexing_slicing)]
+
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+
+use crate::arena::{Arena, ExprId, NodeId, Position, Span};
+use crate::ast::{literal_type, AstNode, BinOp, Expr, Item, MethodSig, Pattern, Stmt, UnaryOp, Variant, ViraType, DERIVABLE, OPERATOR_METHODS};
+use crate::diagnostic::Diagnostic;
+use crate::tokenizer::{tokenize, Token, TokenType};
+
+// Folds a constant expression to a literal node, given the constants declared
+// so far and the top-level functions and types a call may use. `const`
+// initializers, config values, `[value; count]` sizes and discriminants are
+// folded while parsing. Evaluating a call takes the interpreter, which this
+// crate does not link, so the parser leaves folding to its caller; without
+// `fold_with` only literals, earlier constants, negated numbers and arrays
+// of those are constant.
+pub type Fold = dyn Fn(&mut Arena, ExprId, &HashMap<String, ExprId>, &[NodeId]) -> Result<ExprId, String>;
+
+// A comment in the source, which the parser skips but keeps for `fmt` and
+// doctests, with the text as written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub span: Span,
+    pub text: String,
+}
+
+thread_local! {
+    // The token the parser last moved past, for crash reports.
+    static AT: Cell<Position> = const { Cell::new((0, 0)) };
+}
+
+// Where the parser on this thread last was, (0, 0) before it starts.
+pub fn position() -> Position {
+    AT.with(Cell::get)
+}
+
+pub struct Parser<'a> {
+    tokens: Vec<Token>,
+    // Where parsed nodes are stored.
+    arena: &'a mut Arena,
+    current: usize,
+    // Values of the constants declared so far, already folded to literals.
+    // The keys of the config block count as constants named `config.key`.
+    consts: HashMap<String, ExprId>,
+    // Whether the module has had its config block.
+    configured: bool,
+    // Top-level functions and types declared so far, which constant
+    // initializers can use.
+    declarations: Vec<NodeId>,
+    // Type parameters of the generic function being parsed.
+    type_params: Vec<String>,
+    // Type aliases declared so far, substituted wherever a type is parsed.
+    aliases: HashMap<String, ViraType>,
+    // For `fmt`: aliases stay as written, as types of their name.
+    keep_aliases: bool,
+    // Names parsed as struct, enum or trait types, so an alias cannot be
+    // declared after a use that would have meant it.
+    named_types: HashSet<String>,
+    // The token the node being parsed starts at, innermost last; see
+    // `spanned`.
+    starts: Vec<usize>,
+    fold: &'a Fold,
+    comments: Vec<Comment>,
+}
+
+#[derive(Clone, Copy)]
+enum Infix {
+    Binary(BinOp),
+    Range,
+    Coalesce,
+}
+
+// Infix operators and their binding power; higher binds tighter. All are left
+// associative except ranges, which do not chain, and `??`, which groups to
+// the right. Unary operators bind tighter than any of these.
+const OPERATORS: [(TokenType, u8, Infix); 15] = [
+    (TokenType::DotDot, 1, Infix::Range),
+    (TokenType::Or, 2, Infix::Binary(BinOp::Or)),
+    (TokenType::And, 3, Infix::Binary(BinOp::And)),
+    (TokenType::EqualEqual, 4, Infix::Binary(BinOp::Eq)),
+    (TokenType::BangEqual, 4, Infix::Binary(BinOp::Neq)),
+    (TokenType::Less, 5, Infix::Binary(BinOp::Lt)),
+    (TokenType::Greater, 5, Infix::Binary(BinOp::Gt)),
+    (TokenType::LessEqual, 5, Infix::Binary(BinOp::Le)),
+    (TokenType::GreaterEqual, 5, Infix::Binary(BinOp::Ge)),
+    (TokenType::QuestionQuestion, 6, Infix::Coalesce),
+    (TokenType::Plus, 7, Infix::Binary(BinOp::Add)),
+    (TokenType::Minus, 7, Infix::Binary(BinOp::Sub)),
+    (TokenType::Star, 8, Infix::Binary(BinOp::Mul)),
+    (TokenType::Slash, 8, Infix::Binary(BinOp::Div)),
+    (TokenType::Mod, 8, Infix::Binary(BinOp::Mod)),
+];
+
+fn infix(typ: &TokenType) -> Option<(u8, Infix)> {
+    OPERATORS.iter().find(|(op, _, _)| op == typ).map(|&(_, power, infix)| (power, infix))
+}
+
+// The operator of a compound assignment such as `+=`.
+fn compound(typ: &TokenType) -> Option<BinOp> {
+    match typ {
+        TokenType::PlusEquals => Some(BinOp::Add),
+        TokenType::MinusEquals => Some(BinOp::Sub),
+        TokenType::StarEquals => Some(BinOp::Mul),
+        TokenType::SlashEquals => Some(BinOp::Div),
+        TokenType::ModEquals => Some(BinOp::Mod),
+        _ => None,
+    }
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(tokens: Vec<Token>, arena: &'a mut Arena) -> Self {
+        let (comments, tokens): (Vec<Token>, Vec<Token>) = tokens.into_iter().partition(|t| t.typ == TokenType::Comment);
+        let comments = comments.into_iter().map(|t| Comment { span: ((t.line, t.col), t.end), text: t.lexeme }).collect();
+        AT.with(|at| at.set((0, 0)));
+        Parser {
+            tokens,
+            arena,
+            current: 0,
+            consts: HashMap::new(),
+            configured: false,
+            declarations: Vec::new(),
+            type_params: Vec::new(),
+            aliases: HashMap::new(),
+            keep_aliases: false,
+            named_types: HashSet::new(),
+            starts: Vec::new(),
+            fold: &literals,
+            comments,
+        }
+    }
+
+    pub fn fold_with(&mut self, fold: &'a Fold) {
+        self.fold = fold;
+    }
+
+    // Parses type aliases where they are used as types named by the alias,
+    // so a printed tree spells them as the source does.
+    pub fn keep_aliases(&mut self) {
+        self.keep_aliases = true;
+    }
+
+    // The comments of the source, in order.
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    pub fn parse(&mut self) -> Result<Vec<NodeId>, Vec<Diagnostic>> {
+        let lex_errors: Vec<Diagnostic> = self
+            .tokens
+            .iter()
+            .filter(|t| t.typ == TokenType::Error)
+            .map(|t| Diagnostic { span: ((t.line, t.col), t.end), message: t.lexeme.clone() })
+            .collect();
+        if !lex_errors.is_empty() {
+            return Err(lex_errors);
+        }
+
+        let mut statements = Vec::new();
+        while !self.is_at_end() {
+            match self.item() {
+                Ok(stmt) => {
+                    let item = self.arena.item(stmt);
+                    if let AstNode::Item(Item::FuncDecl(..) | Item::StructDecl(..) | Item::EnumDecl(..) | Item::TraitDecl(..) | Item::ImplDecl(..)) = self.arena[item] {
+                        self.declarations.push(item);
+                    }
+                    statements.push(stmt);
+                }
+                Err(message) => {
+                    let at = self.peek();
+                    return Err(vec![Diagnostic { span: ((at.line, at.col), (at.line, at.col)), message }]);
+                }
+            }
+        }
+        Ok(statements)
+    }
+
+    // Every node records its span in the arena: from the token the
+    // innermost `spanned` parse started at to the last token consumed.
+    fn alloc(&mut self, node: impl Into<AstNode>) -> NodeId {
+        let start = self.starts.last().copied().unwrap_or(0);
+        self.alloc_from(start, node)
+    }
+
+    // For nodes that do not start where the parse that makes them started,
+    // such as the operators of a chain.
+    fn alloc_from(&mut self, start: usize, node: impl Into<AstNode>) -> NodeId {
+        let id = self.arena.alloc(node);
+        self.span_from(start, id);
+        id
+    }
+
+    fn expr(&mut self, expr: Expr) -> ExprId {
+        let start = self.starts.last().copied().unwrap_or(0);
+        self.expr_from(start, expr)
+    }
+
+    fn expr_from(&mut self, start: usize, expr: Expr) -> ExprId {
+        let id = self.arena.alloc_expr(expr);
+        self.span_from(start, id.node());
+        id
+    }
+
+    fn span_from(&mut self, start: usize, id: NodeId) {
+        let (first, last) = (self.token_at(Some(start)), self.previous());
+        self.arena.set_span(id, ((first.line, first.col), (last.line, last.col)));
+    }
+
+    fn spanned<T>(&mut self, parse: fn(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        self.starts.push(self.current);
+        let node = parse(self);
+        self.starts.pop();
+        node
+    }
+
+    fn item(&mut self) -> Result<NodeId, String> {
+        self.spanned(Self::item_node)
+    }
+
+    // A top-level statement, which may be marked `pub` to export it from its
+    // module.
+    fn item_node(&mut self) -> Result<NodeId, String> {
+        if self.check(TokenType::At) {
+            return self.attributed(Self::item);
+        }
+        if self.at_type_alias() {
+            return self.type_alias();
+        }
+        if self.at_config() {
+            return self.config_decl();
+        }
+        if self.at_data() {
+            return self.data_decl();
+        }
+        if !self.match_token(TokenType::Pub) {
+            return self.statement();
+        }
+        let item = self.statement()?;
+        match self.arena[self.arena.item(item)] {
+            AstNode::Item(Item::FuncDecl(..)) | AstNode::Stmt(Stmt::VarDecl(..)) | AstNode::Item(Item::ConstDecl(..)) => Ok(self.alloc(Item::Pub(item))),
+            _ => Err("Only functions, lets and constants can be pub.".to_string()),
+        }
+    }
+
+    fn statement(&mut self) -> Result<NodeId, String> {
+        self.spanned(Self::statement_node)
+    }
+
+    fn statement_node(&mut self) -> Result<NodeId, String> {
+        if self.check(TokenType::Pub) {
+            return Err("'pub' is only allowed on top-level items.".to_string());
+        }
+        if self.check(TokenType::Defer) {
+            return Err("'defer' is only allowed directly inside a block.".to_string());
+        }
+        if self.at_type_alias() {
+            return Err("Type aliases are only allowed at the top level.".to_string());
+        }
+        if self.at_config() {
+            return Err("A config block is only allowed at the top level.".to_string());
+        }
+        if self.at_data() {
+            return Err("Data sections are only allowed at the top level.".to_string());
+        }
+        if self.check(TokenType::At) {
+            if self.tokens.get(self.current + 1).is_some_and(|t| t.lexeme == "derive") {
+                return Err("'@derive' is only allowed on top-level structs.".to_string());
+            }
+            if self.tokens.get(self.current + 1).is_some_and(|t| t.lexeme == "test") {
+                return Err("'@test' is only allowed on top-level functions.".to_string());
+            }
+            return self.attributed(Self::statement);
+        }
+        // `func(` starts an anonymous function expression rather than a declaration.
+        if self.check(TokenType::Func) && !matches!(self.tokens.get(self.current + 1).map(|t| &t.typ), Some(TokenType::LeftParen)) {
+            self.advance();
+            self.func_decl()
+        } else if self.match_token(TokenType::Struct) {
+            self.struct_decl()
+        } else if self.match_token(TokenType::Enum) {
+            self.enum_decl()
+        } else if self.match_token(TokenType::Trait) {
+            self.trait_decl()
+        } else if self.match_token(TokenType::Impl) {
+            self.impl_decl()
+        } else if self.match_token(TokenType::Let) {
+            self.var_decl()
+        } else if self.match_token(TokenType::Const) {
+            self.const_decl()
+        } else if self.match_token(TokenType::Import) {
+            self.import()
+        } else if self.match_token(TokenType::If) {
+            self.if_stmt()
+        } else if self.match_token(TokenType::While) {
+            self.while_stmt()
+        } else if self.match_token(TokenType::For) {
+            self.for_stmt()
+        } else if self.match_token(TokenType::Return) {
+            self.return_stmt()
+        } else if self.match_token(TokenType::Write) {
+            self.write_stmt()
+        } else if self.match_token(TokenType::Throw) {
+            let value = self.expression()?;
+            Ok(self.alloc(Stmt::Throw(value)))
+        } else if self.match_token(TokenType::Try) {
+            self.try_stmt()
+        } else if self.match_token(TokenType::LeftBrace) {
+            Ok(self.block()?.node())
+        } else if self.at_assignment() {
+            self.assignment()
+        } else {
+            self.expression_stmt()
+        }
+    }
+
+    // `@name` or `@name(args)`, then the declaration it applies to, which
+    // `declaration` parses.
+    fn attributed(&mut self, declaration: fn(&mut Self) -> Result<NodeId, String>) -> Result<NodeId, String> {
+        self.consume(TokenType::At, "Expect '@'.")?;
+        let name = self.consume(TokenType::Identifier, "Expect attribute name after '@'.")?.lexeme;
+        let args = if self.at_call() {
+            self.advance();
+            self.arguments()?
+        } else {
+            Vec::new()
+        };
+        let item = declaration(self)?;
+        match name.as_str() {
+            "pure" | "test" if !args.is_empty() => Err(format!("'@{}' takes no arguments.", name)),
+            "requires" | "ensures" if args.len() != 1 => Err(format!("'@{}' takes one condition.", name)),
+            "pure" | "requires" | "ensures" if !matches!(self.arena[self.arena.item(item)], AstNode::Item(Item::FuncDecl(..))) => {
+                Err(format!("'@{}' only applies to functions.", name))
+            }
+            "derive" if args.is_empty() => Err(format!("'@derive' takes the methods to derive: {}.", DERIVABLE.join(", "))),
+            "derive" if !matches!(self.arena[self.arena.item(item)], AstNode::Item(Item::StructDecl(..))) => Err("'@derive' only applies to structs.".to_string()),
+            "derive" => {
+                for &arg in &args {
+                    match &self.arena[arg] {
+                        Expr::VarRef(method) if DERIVABLE.contains(&method.as_str()) => {}
+                        _ => return Err(format!("'@derive' takes the methods to derive: {}.", DERIVABLE.join(", "))),
+                    }
+                }
+                Ok(self.alloc(Item::Attribute(name, args, item)))
+            }
+            "test" => match &self.arena[self.arena.item(item)] {
+                AstNode::Item(Item::FuncDecl(_, type_params, params, ..)) if type_params.is_empty() && params.is_empty() => Ok(self.alloc(Item::Attribute(name, args, item))),
+                AstNode::Item(Item::FuncDecl(test, ..)) => Err(format!("Test '{}' must take no parameters and have no type parameters.", test)),
+                _ => Err("'@test' only applies to functions.".to_string()),
+            },
+            "pure" | "requires" | "ensures" => Ok(self.alloc(Item::Attribute(name, args, item))),
+            _ => Err(format!("Unknown attribute '@{}'.", name)),
+        }
+    }
+
+    fn func_decl(&mut self) -> Result<NodeId, String> {
+        let name = self.consume(TokenType::Identifier, "Expect function name.")?.lexeme;
+        let mut type_params = Vec::new();
+        if self.match_token(TokenType::Less) {
+            loop {
+                let param = self.consume(TokenType::Identifier, "Expect type parameter name.")?.lexeme;
+                if type_params.contains(&param) {
+                    return Err(format!("Duplicate type parameter '{}'.", param));
+                }
+                type_params.push(param);
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+            self.consume(TokenType::Greater, "Expect '>' after type parameters.")?;
+        }
+        let outer = std::mem::replace(&mut self.type_params, type_params.clone());
+        let parsed = self.signature().and_then(|(params, return_type)| Ok((params, return_type, self.statement()?)));
+        self.type_params = outer;
+        let (params, return_type, body) = parsed?;
+        Ok(self.alloc(Item::FuncDecl(name, type_params, params, return_type, body)))
+    }
+
+    fn signature(&mut self) -> Result<(Vec<(String, ViraType)>, ViraType), String> {
+        self.consume(TokenType::LeftParen, "Expect '(' after name.")?;
+        let params = self.params()?;
+        if !self.match_token(TokenType::Arrow) {
+            return Err("Missing '->' in function declaration.".to_string());
+        }
+        let return_type = self.parse_type()?;
+        Ok((params, return_type))
+    }
+
+    fn params(&mut self) -> Result<Vec<(String, ViraType)>, String> {
+        let mut params = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                let param_name = self.consume(TokenType::Identifier, "Expect param name.")?.lexeme;
+                self.consume(TokenType::Colon, "Expect ':' after param name.")?;
+                let param_type = self.parse_type()?;
+                params.push((param_name, param_type));
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after params.")?;
+        Ok(params)
+    }
+
+    // `func(x: int) -> int { ... }` or the shorthand `|x: int| -> int x + 1`.
+    // Parameter and return types are required, as they are for `func`.
+    fn lambda(&mut self) -> Result<ExprId, String> {
+        if self.previous().typ == TokenType::Func {
+            self.consume(TokenType::LeftParen, "Expect '(' after 'func'.")?;
+            let params = self.params()?;
+            let return_type = self.lambda_return()?;
+            self.consume(TokenType::LeftBrace, "Expect '{' before function body.")?;
+            let body = self.block()?;
+            return Ok(self.expr(Expr::Lambda(params, return_type, body)));
+        }
+        let mut params = Vec::new();
+        if self.previous().typ == TokenType::Pipe {
+            while !self.check(TokenType::Pipe) && !self.is_at_end() {
+                let param_name = self.consume(TokenType::Identifier, "Expect param name.")?.lexeme;
+                self.consume(TokenType::Colon, "Expect ':' after param name.")?;
+                params.push((param_name, self.parse_type()?));
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+            self.consume(TokenType::Pipe, "Expect '|' after params.")?;
+        }
+        let return_type = self.lambda_return()?;
+        let body = if self.match_token(TokenType::LeftBrace) {
+            self.block()?
+        } else {
+            self.expression()?
+        };
+        Ok(self.expr(Expr::Lambda(params, return_type, body)))
+    }
+
+    fn lambda_return(&mut self) -> Result<ViraType, String> {
+        if !self.match_token(TokenType::Arrow) {
+            return Err("Missing '->' in anonymous function.".to_string());
+        }
+        self.parse_type()
+    }
+
+    // Parses call arguments after the opening '('.
+    fn arguments(&mut self) -> Result<Vec<ExprId>, String> {
+        let mut args = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                args.push(self.expression()?);
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+        Ok(args)
+    }
+
+    // `type` is only a keyword in `type Name = ...`, so it stays usable as a
+    // name elsewhere.
+    fn at_type_alias(&self) -> bool {
+        let typ = |offset: usize| self.tokens.get(self.current + offset).map(|t| &t.typ);
+        self.peek().typ == TokenType::Identifier
+            && self.peek().lexeme == "type"
+            && matches!(typ(1), Some(TokenType::Identifier | TokenType::IntType | TokenType::FloatType | TokenType::BoolType | TokenType::StringType))
+            && typ(2) == Some(&TokenType::Equals)
+    }
+
+    fn type_alias(&mut self) -> Result<NodeId, String> {
+        self.advance();
+        let name = self.advance().lexeme;
+        self.advance();
+        let typ = self.parse_type()?;
+        if matches!(name.as_str(), "int" | "float" | "decimal" | "complex" | "bool" | "string" | "range" | "array" | "map") {
+            return Err(format!("'{}' is a built-in type.", name));
+        }
+        if self.aliases.contains_key(&name) {
+            return Err(format!("Type alias '{}' is already declared.", name));
+        }
+        self.check_type_name(&name)?;
+        if self.named_types.contains(&name) {
+            return Err(format!("Type alias '{}' is used before it is declared.", name));
+        }
+        self.aliases.insert(name.clone(), typ.clone());
+        Ok(self.alloc(Item::TypeAlias(name, typ)))
+    }
+
+    // `config` is an ordinary name except where it starts a config block.
+    fn at_config(&self) -> bool {
+        let typ = |offset: usize| self.tokens.get(self.current + offset).map(|t| &t.typ);
+        self.peek().typ == TokenType::Identifier
+            && self.peek().lexeme == "config"
+            && typ(1) == Some(&TokenType::LeftBrace)
+            && match typ(2) {
+                Some(TokenType::RightBrace) => true,
+                Some(TokenType::Identifier) => typ(3) == Some(&TokenType::Equals),
+                _ => false,
+            }
+    }
+
+    // Config values are constant expressions, folded like constants, so that
+    // `config.key` can be used wherever a constant can.
+    fn config_decl(&mut self) -> Result<NodeId, String> {
+        if self.configured {
+            return Err("A module can have only one config block.".to_string());
+        }
+        self.configured = true;
+        self.advance();
+        self.advance();
+        let mut entries = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let key = self.consume(TokenType::Identifier, "Expect config key.")?.lexeme;
+            self.consume(TokenType::Equals, "Expect '=' after config key.")?;
+            let init = self.expression()?;
+            let qualified = format!("config.{}", key);
+            if self.consts.contains_key(&qualified) {
+                return Err(format!("Config key '{}' is already set.", key));
+            }
+            let value = (self.fold)(self.arena, init, &self.consts, &self.declarations)?;
+            if literal_type(self.arena, value).is_none() {
+                return Err(format!("Config value of '{}' is not a constant expression.", key));
+            }
+            self.consts.insert(qualified, value);
+            entries.push((key, value));
+            if !self.match_token(TokenType::Comma) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after config entries.")?;
+        Ok(self.alloc(Item::ConfigDecl(entries)))
+    }
+
+    // Like `config`, `data` is an ordinary name except before a declaration.
+    fn at_data(&self) -> bool {
+        let typ = |offset: usize| self.tokens.get(self.current + offset).map(|t| &t.typ);
+        self.peek().typ == TokenType::Identifier
+            && self.peek().lexeme == "data"
+            && typ(1) == Some(&TokenType::Identifier)
+            && matches!(typ(2), Some(TokenType::Equals | TokenType::Colon))
+    }
+
+    fn data_decl(&mut self) -> Result<NodeId, String> {
+        self.advance();
+        let name = self.advance().lexeme;
+        let typ = if self.match_token(TokenType::Colon) { self.parse_type()? } else { ViraType::String };
+        if !matches!(&typ, ViraType::String) && typ != ViraType::Array(Box::new(ViraType::Int)) {
+            return Err(format!("Data section '{}' must be a string or an array<int> of its bytes, not {}.", name, typ));
+        }
+        self.consume(TokenType::Equals, "Expect '=' after data section name.")?;
+        let heredoc = self.consume(TokenType::Heredoc, "Expect '<<<' and a delimiter after '='.")?.lexeme;
+        let (delimiter, contents) = heredoc.split_once('\n').unwrap_or((&heredoc, ""));
+        Ok(self.alloc(Item::DataDecl(name, typ, delimiter.to_string(), contents.to_string())))
+    }
+
+    fn check_type_name(&self, name: &str) -> Result<(), String> {
+        let declared = self.declarations.iter().any(|&d| {
+            matches!(&self.arena[d], AstNode::Item(Item::StructDecl(n, _) | Item::EnumDecl(n, _) | Item::TraitDecl(n, _)) if n == name)
+        });
+        if declared || self.aliases.contains_key(name) {
+            return Err(format!("Type '{}' is already declared.", name));
+        }
+        Ok(())
+    }
+
+    fn struct_decl(&mut self) -> Result<NodeId, String> {
+        let name = self.consume(TokenType::Identifier, "Expect struct name.")?.lexeme;
+        self.check_type_name(&name)?;
+        self.consume(TokenType::LeftBrace, "Expect '{' after struct name.")?;
+        let mut fields: Vec<(String, ViraType)> = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let field_name = self.consume(TokenType::Identifier, "Expect field name.")?.lexeme;
+            if fields.iter().any(|(f, _)| *f == field_name) {
+                return Err(format!("Duplicate field '{}' in struct '{}'.", field_name, name));
+            }
+            self.consume(TokenType::Colon, "Expect ':' after field name.")?;
+            let field_type = self.parse_type()?;
+            fields.push((field_name, field_type));
+            if !self.match_token(TokenType::Comma) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after struct fields.")?;
+        Ok(self.alloc(Item::StructDecl(name, fields)))
+    }
+
+    fn enum_decl(&mut self) -> Result<NodeId, String> {
+        let name = self.consume(TokenType::Identifier, "Expect enum name.")?.lexeme;
+        self.check_type_name(&name)?;
+        self.consume(TokenType::LeftBrace, "Expect '{' after enum name.")?;
+        let mut variants: Vec<Variant> = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let variant = self.consume(TokenType::Identifier, "Expect variant name.")?.lexeme;
+            if variants.iter().any(|(v, ..)| *v == variant) {
+                return Err(format!("Duplicate variant '{}' in enum '{}'.", variant, name));
+            }
+            let mut payload = Vec::new();
+            if self.match_token(TokenType::LeftParen) {
+                if !self.check(TokenType::RightParen) {
+                    loop {
+                        payload.push(self.parse_type()?);
+                        if !self.match_token(TokenType::Comma) {
+                            break;
+                        }
+                    }
+                }
+                self.consume(TokenType::RightParen, "Expect ')' after variant payload.")?;
+            }
+            let discriminant = if self.match_token(TokenType::Equals) {
+                let init = self.expression()?;
+                self.const_int(init).map_err(|e| format!("Discriminant of '{}::{}': {}", name, variant, e))?
+            } else {
+                match variants.last() {
+                    Some((previous, _, discriminant)) => {
+                        discriminant.checked_add(1).ok_or(format!("The discriminant after '{}::{}' overflows; give '{}' one.", name, previous, variant))?
+                    }
+                    None => 0,
+                }
+            };
+            if let Some((other, ..)) = variants.iter().find(|&&(_, _, d)| d == discriminant) {
+                return Err(format!("'{}::{}' and '{}::{}' have the same discriminant, {}.", name, other, name, variant, discriminant));
+            }
+            variants.push((variant, payload, discriminant));
+            if !self.match_token(TokenType::Comma) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after enum variants.")?;
+        Ok(self.alloc(Item::EnumDecl(name, variants)))
+    }
+
+    fn trait_decl(&mut self) -> Result<NodeId, String> {
+        let name = self.consume(TokenType::Identifier, "Expect trait name.")?.lexeme;
+        self.check_type_name(&name)?;
+        self.consume(TokenType::LeftBrace, "Expect '{' after trait name.")?;
+        let mut methods: Vec<MethodSig> = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            self.consume(TokenType::Func, "Expect 'func' in trait body.")?;
+            let method = self.consume(TokenType::Identifier, "Expect method name.")?.lexeme;
+            if methods.iter().any(|(m, _, _)| *m == method) {
+                return Err(format!("Duplicate method '{}' in trait '{}'.", method, name));
+            }
+            let (params, return_type) = self.method_signature()?;
+            methods.push((method, params, return_type));
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after trait methods.")?;
+        Ok(self.alloc(Item::TraitDecl(name, methods)))
+    }
+
+    fn impl_decl(&mut self) -> Result<NodeId, String> {
+        let name = self.consume(TokenType::Identifier, "Expect trait or type name after 'impl'.")?.lexeme;
+        let (trait_name, type_name) = if self.match_token(TokenType::For) {
+            (Some(name), self.consume(TokenType::Identifier, "Expect type name after 'for'.")?.lexeme)
+        } else {
+            (None, name)
+        };
+        let header = match &trait_name {
+            Some(trait_name) => format!("impl of '{}' for '{}'", trait_name, type_name),
+            None => format!("impl of '{}'", type_name),
+        };
+        self.consume(TokenType::LeftBrace, "Expect '{' after impl header.")?;
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let start = self.current;
+            self.consume(TokenType::Func, "Expect 'func' in impl body.")?;
+            let method = self.consume(TokenType::Identifier, "Expect method name.")?.lexeme;
+            if methods.iter().any(|&m| matches!(&self.arena[m], Item::FuncDecl(name, ..) if *name == method)) {
+                return Err(format!("Duplicate method '{}' in {}.", method, header));
+            }
+            let (mut params, return_type) = self.method_signature()?;
+            if OPERATOR_METHODS.contains(&method.as_str()) && params.len() != 1 {
+                return Err(format!("Method '{}' is called by an operator and must take one parameter after self.", method));
+            }
+            if method == "eq" && return_type != ViraType::Bool {
+                return Err("Method 'eq' is called by '==' and must return bool.".to_string());
+            }
+            if method == "to_json" && (!params.is_empty() || return_type != ViraType::String) {
+                return Err("Method 'to_json' is called by 'json' and must take only self and return string.".to_string());
+            }
+            if method == "hash" && (!params.is_empty() || return_type != ViraType::Int) {
+                return Err("Method 'hash' is called by maps and must take only self and return int.".to_string());
+            }
+            if method == "to_string" && (!params.is_empty() || return_type != ViraType::String) {
+                return Err("Method 'to_string' is called by 'write' and 'format' and must take only self and return string.".to_string());
+            }
+            params.insert(0, ("self".to_string(), ViraType::Struct(type_name.clone())));
+            let body = self.statement()?;
+            let method = self.arena.alloc_item(Item::FuncDecl(method, Vec::new(), params, return_type, body));
+            self.span_from(start, method.node());
+            methods.push(method);
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after impl methods.")?;
+        Ok(self.alloc(Item::ImplDecl(trait_name, type_name, methods)))
+    }
+
+    // `(self, x: int) -> int`; returns the parameters after `self`.
+    fn method_signature(&mut self) -> Result<(Vec<(String, ViraType)>, ViraType), String> {
+        self.consume(TokenType::LeftParen, "Expect '(' after method name.")?;
+        if !self.check(TokenType::Identifier) || self.peek().lexeme != "self" {
+            return Err("Methods take 'self' as their first parameter.".to_string());
+        }
+        self.advance();
+        let params = if self.match_token(TokenType::Comma) {
+            self.params()?
+        } else {
+            self.consume(TokenType::RightParen, "Expect ')' after params.")?;
+            Vec::new()
+        };
+        if !self.match_token(TokenType::Arrow) {
+            return Err("Missing '->' in method declaration.".to_string());
+        }
+        Ok((params, self.parse_type()?))
+    }
+
+    // `import "path/to/module"` or `import math`; the path is relative to the
+    // importing file, without the `.vira` extension.
+    fn import(&mut self) -> Result<NodeId, String> {
+        if self.match_token(TokenType::String) || self.match_token(TokenType::Identifier) {
+            Ok(self.alloc(Item::Import(self.previous().lexeme)))
+        } else {
+            Err("Expect module name or path after 'import'.".to_string())
+        }
+    }
+
+    fn var_decl(&mut self) -> Result<NodeId, String> {
+        if self.match_token(TokenType::LeftParen) {
+            return self.tuple_decl();
+        }
+        let name = self.consume(TokenType::Identifier, "Expect variable name.")?.lexeme;
+        let typ = if self.match_token(TokenType::Colon) { Some(self.parse_type()?) } else { None };
+        self.consume(TokenType::Equals, "Expect '=' after variable.")?;
+        let init = self.expression()?;
+        Ok(self.alloc(Stmt::VarDecl(name, typ, init)))
+    }
+
+    fn tuple_decl(&mut self) -> Result<NodeId, String> {
+        let mut names = Vec::new();
+        loop {
+            names.push(self.consume(TokenType::Identifier, "Expect variable name in tuple pattern.")?.lexeme);
+            if !self.match_token(TokenType::Comma) || self.check(TokenType::RightParen) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after tuple pattern.")?;
+        let typ = if self.match_token(TokenType::Colon) { Some(self.parse_type()?) } else { None };
+        self.consume(TokenType::Equals, "Expect '=' after tuple pattern.")?;
+        let init = self.expression()?;
+        Ok(self.alloc(Stmt::TupleDecl(names, typ, init)))
+    }
+
+    // Constants are evaluated while parsing, so their declaration carries the
+    // folded literal and later constants can build on them.
+    fn const_decl(&mut self) -> Result<NodeId, String> {
+        let name = self.consume(TokenType::Identifier, "Expect constant name.")?.lexeme;
+        let declared = if self.match_token(TokenType::Colon) { Some(self.parse_type()?) } else { None };
+        self.consume(TokenType::Equals, "Expect '=' after constant.")?;
+        let init = self.expression()?;
+        if self.consts.contains_key(&name) {
+            return Err(format!("Constant '{}' is already defined.", name));
+        }
+        let value = (self.fold)(self.arena, init, &self.consts, &self.declarations)?;
+        let typ = literal_type(self.arena, value).ok_or("Const initializer is not a constant expression.")?;
+        if let Some(declared) = declared.as_ref().filter(|declared| self.expand(declared) != typ) {
+            return Err(format!("Constant '{}' is declared as {} but its value is {}.", name, declared, typ));
+        }
+        self.consts.insert(name.clone(), value);
+        let decl = self.alloc(Item::ConstDecl(name, declared.unwrap_or(typ), value));
+        self.arena.set_folded_from(decl, init.node());
+        Ok(decl)
+    }
+
+    // `x = e`, or a compound assignment such as `x += e`.
+    fn at_assignment(&self) -> bool {
+        self.check(TokenType::Identifier)
+            && self.tokens.get(self.current + 1).is_some_and(|t| t.typ == TokenType::Equals || compound(&t.typ).is_some())
+    }
+
+    fn assignment(&mut self) -> Result<NodeId, String> {
+        let name = self.advance().lexeme;
+        let op = compound(&self.advance().typ);
+        let value = self.expression()?;
+        Ok(match op {
+            Some(op) => self.alloc(Stmt::CompoundAssign(name, op, value)),
+            None => self.alloc(Stmt::Assign(name, value)),
+        })
+    }
+
+    fn if_stmt(&mut self) -> Result<NodeId, String> {
+        let cond = self.expression()?;
+        let then = self.statement()?;
+        let else_branch = if self.match_token(TokenType::Else) {
+            Some(self.statement()?)
+        } else {
+            None
+        };
+        Ok(self.alloc(Expr::If(cond, then, else_branch)))
+    }
+
+    // In expression position both branches are blocks and `else` is required,
+    // so the expression always has a value.
+    fn if_expr(&mut self) -> Result<ExprId, String> {
+        let start = self.current.saturating_sub(1);
+        let cond = self.expression()?;
+        self.consume(TokenType::LeftBrace, "Expect '{' after if condition.")?;
+        let then = self.block()?;
+        self.consume(TokenType::Else, "An if expression needs an else branch.")?;
+        let else_branch = if self.match_token(TokenType::If) {
+            self.if_expr()?
+        } else {
+            self.consume(TokenType::LeftBrace, "Expect '{' after else.")?;
+            self.block()?
+        };
+        Ok(self.expr_from(start, Expr::If(cond, then.node(), Some(else_branch.node()))))
+    }
+
+    fn while_stmt(&mut self) -> Result<NodeId, String> {
+        let cond = self.expression()?;
+        let body = self.statement()?;
+        Ok(self.alloc(Stmt::While(cond, body)))
+    }
+
+    fn for_stmt(&mut self) -> Result<NodeId, String> {
+        if self.check(TokenType::Identifier)
+            && matches!(self.tokens.get(self.current + 1).map(|t| &t.typ), Some(TokenType::In))
+        {
+            let name = self.advance().lexeme;
+            self.advance();
+            let iterable = self.expression()?;
+            let body = self.statement()?;
+            return Ok(self.alloc(Stmt::ForIn(name, iterable, body)));
+        }
+        let init = self.for_clause()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after the for loop's initializer.")?;
+        let cond = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after the for loop's condition.")?;
+        let incr = self.for_clause()?;
+        let body = self.statement()?;
+        Ok(self.alloc(Stmt::For(init, cond, incr, body)))
+    }
+
+    // The initializer and increment of a C-style for loop: a let, an
+    // assignment or an expression.
+    fn for_clause(&mut self) -> Result<NodeId, String> {
+        if self.check(TokenType::Let) || self.at_assignment() {
+            self.statement()
+        } else {
+            Ok(self.expression()?.node())
+        }
+    }
+
+    fn return_stmt(&mut self) -> Result<NodeId, String> {
+        let expr = if !self.check(TokenType::RightBrace) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        Ok(self.alloc(Stmt::Return(expr)))
+    }
+
+    fn defer_stmt(&mut self) -> Result<NodeId, String> {
+        let start = self.current.saturating_sub(1);
+        let stmt = self.statement()?;
+        // The deferred statement runs while the block is already exiting,
+        // possibly because of a `return`.
+        if returns(self.arena, stmt) {
+            return Err("'return' is not allowed inside defer.".to_string());
+        }
+        Ok(self.alloc_from(start, Stmt::Defer(stmt)))
+    }
+
+    fn try_stmt(&mut self) -> Result<NodeId, String> {
+        self.consume(TokenType::LeftBrace, "Expect '{' after try.")?;
+        let body = self.block()?;
+        self.consume(TokenType::Catch, "Expect 'catch' after try block.")?;
+        self.consume(TokenType::LeftParen, "Expect '(' after catch.")?;
+        let name = self.consume(TokenType::Identifier, "Expect a name for the thrown value.")?.lexeme;
+        self.consume(TokenType::RightParen, "Expect ')' after the catch name.")?;
+        self.consume(TokenType::LeftBrace, "Expect '{' after catch (...).")?;
+        let handler = self.block()?;
+        Ok(self.alloc(Stmt::Try(body, name, handler)))
+    }
+
+    fn write_stmt(&mut self) -> Result<NodeId, String> {
+        let expr = self.expression()?;
+        Ok(self.alloc(Stmt::Write(expr)))
+    }
+
+    // Called with the `{` consumed.
+    fn block(&mut self) -> Result<ExprId, String> {
+        let start = self.current.saturating_sub(1);
+        let mut statements = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let stmt = if self.match_token(TokenType::Defer) { self.defer_stmt()? } else { self.statement()? };
+            statements.push(stmt);
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+        Ok(self.expr_from(start, Expr::Block(statements)))
+    }
+
+    fn expression_stmt(&mut self) -> Result<NodeId, String> {
+        let expr = self.expression()?;
+        Ok(expr.node())
+    }
+
+    fn expression(&mut self) -> Result<ExprId, String> {
+        self.binary(0)
+    }
+
+    // Precedence climbing over `OPERATORS`: parses a unary operand, then every
+    // operator that binds tighter than `min` together with its right operand.
+    fn binary(&mut self, min: u8) -> Result<ExprId, String> {
+        let start = self.current;
+        let mut expr = self.unary()?;
+        while let Some((power, op)) = infix(&self.peek().typ).filter(|(power, _)| *power > min) {
+            self.advance();
+            let right = self.binary(if matches!(op, Infix::Coalesce) { power - 1 } else { power })?;
+            expr = match op {
+                Infix::Binary(op) => self.expr_from(start, Expr::Binary(expr, op, right)),
+                Infix::Range => return Ok(self.expr_from(start, Expr::Range(expr, right))),
+                Infix::Coalesce => self.expr_from(start, Expr::Coalesce(expr, right)),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<ExprId, String> {
+        let start = self.current;
+        if let Some(value) = self.min_int() {
+            return Ok(self.expr_from(start, Expr::Literal(value)));
+        }
+        if self.match_token(TokenType::Minus) || self.match_token(TokenType::Bang) {
+            let op = if matches!(self.previous().typ, TokenType::Minus) {
+                UnaryOp::Neg
+            } else {
+                UnaryOp::Not
+            };
+            let right = self.unary()?;
+            Ok(self.expr_from(start, Expr::Unary(op, right)))
+        } else {
+            self.postfix()
+        }
+    }
+
+    fn postfix(&mut self) -> Result<ExprId, String> {
+        let start = self.current;
+        let mut expr = self.primary()?;
+        loop {
+            if self.match_token(TokenType::Dot) {
+                if self.match_token(TokenType::Number) {
+                    let index = self.previous().lexeme.parse().map_err(|_| "Invalid tuple index.".to_string())?;
+                    expr = self.expr_from(start, Expr::TupleIndex(expr, index));
+                    continue;
+                }
+                let field = self.consume(TokenType::Identifier, "Expect field name after '.'.")?.lexeme;
+                if self.at_call() {
+                    self.advance();
+                    let args = self.arguments()?;
+                    expr = self.expr_from(start, Expr::MethodCall(expr, field, args));
+                } else {
+                    expr = self.expr_from(start, Expr::FieldAccess(expr, field));
+                }
+            } else if self.match_token(TokenType::QuestionDot) {
+                let field = self.consume(TokenType::Identifier, "Expect field name after '?.'.")?.lexeme;
+                expr = self.expr_from(start, Expr::SafeAccess(expr, field));
+            } else if self.match_token(TokenType::LeftBracket) {
+                let index = self.expression()?;
+                self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+                expr = self.expr_from(start, Expr::Index(expr, index));
+            } else if self.at_call() {
+                self.advance();
+                let args = self.arguments()?;
+                expr = self.expr_from(start, Expr::Apply(expr, args));
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+
+    // The text of a string with `${expr}` in it, split into its text and
+    // expressions. Braces nest inside an expression, so `${f({"a": 1})}`
+    // would end at the last `}`, but a string cannot, as strings have no
+    // escapes for the quotes around them.
+    fn interpolation(&mut self, text: &str) -> Result<ExprId, String> {
+        let (line, mut col) = (self.previous().line, self.previous().col + 1);
+        let mut parts = Vec::new();
+        let mut rest = text;
+        while let Some(at) = rest.find("${") {
+            if at > 0 {
+                parts.push(self.expr(Expr::StringLiteral(rest[..at].to_string())));
+            }
+            col += rest[..at + 2].chars().count();
+            rest = &rest[at + 2..];
+            let mut depth = 0;
+            let end = rest
+                .char_indices()
+                .find(|&(_, c)| {
+                    depth += match c {
+                        '{' => 1,
+                        '}' => -1,
+                        _ => 0,
+                    };
+                    depth < 0
+                })
+                .map(|(end, _)| end)
+                .ok_or("Unterminated '${' in string.")?;
+            parts.push(self.embedded(&rest[..end], line, col)?);
+            col += rest[..=end].chars().count();
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(self.expr(Expr::StringLiteral(rest.to_string())));
+        }
+        Ok(self.expr(Expr::Interpolation(parts)))
+    }
+
+    // Parses the expression of a `${...}` that starts at `line` and `col`,
+    // with the declarations in scope where the string is.
+    fn embedded(&mut self, source: &str, line: usize, col: usize) -> Result<ExprId, String> {
+        let mut tokens = tokenize(source);
+        tokens.retain(|t| t.typ != TokenType::Comment);
+        for token in &mut tokens {
+            token.line = line;
+            token.col += col - 1;
+            token.end = (line, token.end.1 + col - 1);
+        }
+        if let Some(error) = tokens.iter().find(|t| t.typ == TokenType::Error) {
+            return Err(error.lexeme.clone());
+        }
+        let outer = (std::mem::replace(&mut self.tokens, tokens), std::mem::replace(&mut self.current, 0));
+        self.starts.push(0);
+        let parsed = self.expression().and_then(|expr| {
+            if self.is_at_end() {
+                Ok(expr)
+            } else {
+                Err(format!("Unexpected '{}' in '${{...}}'.", self.peek().lexeme))
+            }
+        });
+        self.starts.pop();
+        // After an error the tokens of the `${...}` stay, so that it is
+        // reported where it is in the string.
+        if parsed.is_ok() {
+            (self.tokens, self.current) = outer;
+        }
+        parsed
+    }
+
+    // A '(' on the next line starts a new statement, not a call.
+    fn at_call(&self) -> bool {
+        self.check(TokenType::LeftParen) && self.peek().line == self.previous().line
+    }
+
+    // `Name { field: ...` is a struct literal; checking for the `field:` pair
+    // keeps `if flag { ... }` parsing as a condition followed by a block.
+    fn at_struct_literal(&self) -> bool {
+        self.check(TokenType::LeftBrace)
+            && matches!(self.tokens.get(self.current + 1).map(|t| &t.typ), Some(TokenType::Identifier))
+            && matches!(self.tokens.get(self.current + 2).map(|t| &t.typ), Some(TokenType::Colon))
+    }
+
+    // A constant expression that has to be an int, for sizes and discriminants.
+    fn const_int(&mut self, id: ExprId) -> Result<i64, String> {
+        let value = (self.fold)(self.arena, id, &self.consts, &self.declarations)?;
+        match (&self.arena[value], literal_type(self.arena, value)) {
+            (Expr::Literal(v), _) => Ok(*v),
+            (_, Some(typ)) => Err(format!("Expected a constant int, not {}.", typ)),
+            (_, None) => Err("Not a constant expression.".to_string()),
+        }
+    }
+
+    // `[value; count]`: `count` copies of `value`, evaluated once. The count
+    // is a constant, so the call takes the folded length.
+    fn array_repeat(&mut self, value: ExprId) -> Result<ExprId, String> {
+        let count = self.expression()?;
+        let len = self.const_int(count).map_err(|e| format!("Array size: {}", e))?;
+        if len < 0 {
+            return Err(format!("Array size {} is negative.", len));
+        }
+        self.consume(TokenType::RightBracket, "Expect ']' after array size.")?;
+        let len = self.expr(Expr::Literal(len));
+        Ok(self.expr(Expr::Call("array_filled".to_string(), vec![len, value])))
+    }
+
+    fn struct_literal(&mut self, name: String) -> Result<ExprId, String> {
+        self.consume(TokenType::LeftBrace, "Expect '{' in struct literal.")?;
+        let mut fields = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let field_name = self.consume(TokenType::Identifier, "Expect field name.")?.lexeme;
+            self.consume(TokenType::Colon, "Expect ':' after field name.")?;
+            let value = self.expression()?;
+            fields.push((field_name, value));
+            if !self.match_token(TokenType::Comma) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after struct literal.")?;
+        Ok(self.expr(Expr::StructLiteral(name, fields)))
+    }
+
+    fn match_expr(&mut self) -> Result<ExprId, String> {
+        let scrutinee = self.expression()?;
+        self.consume(TokenType::LeftBrace, "Expect '{' after match value.")?;
+        let mut arms = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let pattern = self.pattern()?;
+            self.consume(TokenType::FatArrow, "Expect '=>' after pattern.")?;
+            let body = if self.match_token(TokenType::LeftBrace) {
+                self.block()?
+            } else {
+                self.expression()?
+            };
+            arms.push((pattern, body));
+            self.match_token(TokenType::Comma);
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after match arms.")?;
+        if arms.is_empty() {
+            return Err("Match needs at least one arm.".to_string());
+        }
+        Ok(self.expr(Expr::Match(scrutinee, arms)))
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, String> {
+        if let Some(value) = self.min_int() {
+            return Ok(Pattern::Int(value));
+        }
+        if self.match_token(TokenType::Minus) {
+            return match self.pattern()? {
+                Pattern::Int(v) => Ok(Pattern::Int(-v)),
+                Pattern::Float(v) => Ok(Pattern::Float(-v)),
+                _ => Err("Expect number after '-' in pattern.".to_string()),
+            };
+        }
+        if self.match_token(TokenType::Number) {
+            let value = self.previous().lexeme.parse().map_err(|_| "Invalid number.".to_string())?;
+            Ok(Pattern::Int(value))
+        } else if self.match_token(TokenType::Float) {
+            Ok(Pattern::Float(float_literal(&self.previous().lexeme)?))
+        } else if self.match_token(TokenType::True) {
+            Ok(Pattern::Bool(true))
+        } else if self.match_token(TokenType::False) {
+            Ok(Pattern::Bool(false))
+        } else if self.match_token(TokenType::String) {
+            Ok(Pattern::String(self.previous().lexeme))
+        } else if self.match_token(TokenType::Identifier) {
+            let name = self.previous().lexeme;
+            if name == "_" {
+                Ok(Pattern::Wildcard)
+            } else if self.match_token(TokenType::ColonColon) {
+                let variant = self.consume(TokenType::Identifier, "Expect variant name after '::'.")?.lexeme;
+                let mut fields = Vec::new();
+                if self.match_token(TokenType::LeftParen) {
+                    if !self.check(TokenType::RightParen) {
+                        loop {
+                            fields.push(self.pattern()?);
+                            if !self.match_token(TokenType::Comma) {
+                                break;
+                            }
+                        }
+                    }
+                    self.consume(TokenType::RightParen, "Expect ')' after variant patterns.")?;
+                }
+                Ok(Pattern::EnumVariant(name, variant, fields))
+            } else {
+                Ok(Pattern::Binding(name))
+            }
+        } else {
+            Err(format!("Expect pattern, found {:?}.", self.peek().typ))
+        }
+    }
+
+    fn primary(&mut self) -> Result<ExprId, String> {
+        self.spanned(Self::primary_node)
+    }
+
+    fn primary_node(&mut self) -> Result<ExprId, String> {
+        if self.match_token(TokenType::Match) {
+            self.match_expr()
+        } else if self.match_token(TokenType::If) {
+            self.if_expr()
+        } else if self.match_token(TokenType::Func) || self.match_token(TokenType::Pipe) || self.match_token(TokenType::Or) {
+            self.lambda()
+        } else if self.match_token(TokenType::Number) {
+            let value: i64 = self.previous().lexeme.parse().map_err(|_| "Invalid number.".to_string())?;
+            Ok(self.expr(Expr::Literal(value)))
+        } else if self.match_token(TokenType::Float) {
+            let value = float_literal(&self.previous().lexeme)?;
+            Ok(self.expr(Expr::FloatLiteral(value)))
+        } else if self.match_token(TokenType::True) {
+            Ok(self.expr(Expr::BoolLiteral(true)))
+        } else if self.match_token(TokenType::False) {
+            Ok(self.expr(Expr::BoolLiteral(false)))
+        } else if self.match_token(TokenType::Nil) {
+            Ok(self.expr(Expr::Nil))
+        } else if self.match_token(TokenType::String) {
+            let text = self.previous().lexeme.clone();
+            if text.contains("${") {
+                self.interpolation(&text)
+            } else {
+                Ok(self.expr(Expr::StringLiteral(text)))
+            }
+        } else if self.match_token(TokenType::Identifier) {
+            let name = self.previous().lexeme.clone();
+            if self.at_call() {
+                self.advance();
+                let args = self.arguments()?;
+                Ok(self.expr(Expr::Call(name, args)))
+            } else if self.match_token(TokenType::ColonColon) {
+                let variant = self.consume(TokenType::Identifier, "Expect variant name after '::'.")?.lexeme;
+                let mut args = Vec::new();
+                if self.at_call() {
+                    self.advance();
+                    args = self.arguments()?;
+                }
+                Ok(self.expr(Expr::EnumConstructor(name, variant, args)))
+            } else if self.at_struct_literal() {
+                self.struct_literal(name)
+            } else {
+                Ok(self.expr(Expr::VarRef(name)))
+            }
+        } else if self.match_token(TokenType::LeftBracket) {
+            let mut elements = Vec::new();
+            if !self.check(TokenType::RightBracket) {
+                let first = self.expression()?;
+                if self.match_token(TokenType::Semicolon) {
+                    return self.array_repeat(first);
+                }
+                elements.push(first);
+                while self.match_token(TokenType::Comma) {
+                    elements.push(self.expression()?);
+                }
+            }
+            self.consume(TokenType::RightBracket, "Expect ']' after array.")?;
+            Ok(self.expr(Expr::ArrayLiteral(elements)))
+        } else if self.match_token(TokenType::LeftBrace) {
+            // A `{` that starts a statement is a block; anywhere else it
+            // starts a map literal.
+            let mut entries = Vec::new();
+            while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+                let key = self.expression()?;
+                self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+                let value = self.expression()?;
+                entries.push((key, value));
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+            self.consume(TokenType::RightBrace, "Expect '}' after map literal.")?;
+            Ok(self.expr(Expr::MapLiteral(entries)))
+        } else if self.match_token(TokenType::LeftParen) {
+            let expr = self.expression()?;
+            if !self.match_token(TokenType::Comma) {
+                self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+                return Ok(expr);
+            }
+            // `(a, b)` is a tuple; `(a,)` is a tuple with one element.
+            let mut elements = vec![expr];
+            while !self.check(TokenType::RightParen) && !self.is_at_end() {
+                elements.push(self.expression()?);
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+            self.consume(TokenType::RightParen, "Expect ')' after tuple.")?;
+            Ok(self.expr(Expr::Tuple(elements)))
+        } else if self.is_at_end() {
+            Err("Unexpected end of input.".to_string())
+        } else {
+            // `parse` puts the position in front.
+            Err(format!("Unexpected '{}'.", self.peek().lexeme))
+        }
+    }
+
+    fn parse_type(&mut self) -> Result<ViraType, String> {
+        let typ = self.plain_type()?;
+        if !self.match_token(TokenType::Question) {
+            return Ok(typ);
+        }
+        if let ViraType::Optional(_) = typ {
+            return Err("A type can only be made optional once.".to_string());
+        }
+        Ok(ViraType::Optional(Box::new(typ)))
+    }
+
+    fn plain_type(&mut self) -> Result<ViraType, String> {
+        if self.match_token(TokenType::Func) {
+            self.consume(TokenType::LeftParen, "Expect '(' in function type.")?;
+            let mut params = Vec::new();
+            if !self.check(TokenType::RightParen) {
+                loop {
+                    params.push(self.parse_type()?);
+                    if !self.match_token(TokenType::Comma) {
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenType::RightParen, "Expect ')' in function type.")?;
+            self.consume(TokenType::Arrow, "Expect '->' in function type.")?;
+            let ret = self.parse_type()?;
+            return Ok(ViraType::Function(params, Box::new(ret)));
+        }
+        if self.match_token(TokenType::LeftParen) {
+            let first = self.parse_type()?;
+            if !self.match_token(TokenType::Comma) {
+                self.consume(TokenType::RightParen, "Expect ')' after type.")?;
+                return Ok(first);
+            }
+            let mut elements = vec![first];
+            while !self.check(TokenType::RightParen) && !self.is_at_end() {
+                elements.push(self.parse_type()?);
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+            self.consume(TokenType::RightParen, "Expect ')' after tuple type.")?;
+            return Ok(ViraType::Tuple(elements));
+        }
+        let typ_str = match self.peek().typ {
+            TokenType::Identifier | TokenType::IntType | TokenType::FloatType | TokenType::BoolType | TokenType::StringType => {
+                self.advance().lexeme
+            }
+            _ => return Err("Expect type.".to_string()),
+        };
+        match typ_str.as_str() {
+            "int" => Ok(ViraType::Int),
+            "float" => Ok(ViraType::Float),
+            "decimal" => Ok(ViraType::Decimal),
+            "complex" => Ok(ViraType::Complex),
+            "bool" => Ok(ViraType::Bool),
+            "string" => Ok(ViraType::String),
+            "range" => Ok(ViraType::Range),
+            "array" => {
+                self.consume(TokenType::Less, "Expect '<' for array type.")?;
+                let inner = self.parse_type()?;
+                self.consume(TokenType::Greater, "Expect '>' for array type.")?;
+                Ok(ViraType::Array(Box::new(inner)))
+            }
+            "map" => {
+                self.consume(TokenType::Less, "Expect '<' for map type.")?;
+                let key = self.parse_type()?;
+                self.consume(TokenType::Comma, "Expect ',' between map key and value types.")?;
+                let value = self.parse_type()?;
+                self.consume(TokenType::Greater, "Expect '>' for map type.")?;
+                Ok(ViraType::Map(Box::new(key), Box::new(value)))
+            }
+            _ if self.type_params.contains(&typ_str) => Ok(ViraType::TypeVar(typ_str)),
+            _ => match self.aliases.get(&typ_str) {
+                Some(_) if self.keep_aliases => Ok(ViraType::Struct(typ_str)),
+                Some(typ) => Ok(typ.clone()),
+                None => {
+                    self.named_types.insert(typ_str.clone());
+                    Ok(ViraType::Struct(typ_str))
+                }
+            },
+        }
+    }
+
+    // `typ` with the aliases kept in it replaced by the types they name.
+    fn expand(&self, typ: &ViraType) -> ViraType {
+        match typ {
+            ViraType::Struct(name) => self.aliases.get(name).map(|alias| self.expand(alias)).unwrap_or_else(|| typ.clone()),
+            ViraType::Array(inner) => ViraType::Array(Box::new(self.expand(inner))),
+            ViraType::Optional(inner) => ViraType::Optional(Box::new(self.expand(inner))),
+            ViraType::Function(params, ret) => ViraType::Function(params.iter().map(|p| self.expand(p)).collect(), Box::new(self.expand(ret))),
+            ViraType::Tuple(elements) => ViraType::Tuple(elements.iter().map(|e| self.expand(e)).collect()),
+            ViraType::Map(key, value) => ViraType::Map(Box::new(self.expand(key)), Box::new(self.expand(value))),
+            _ => typ.clone(),
+        }
+    }
+
+    fn consume(&mut self, typ: TokenType, msg: &str) -> Result<Token, String> {
+        if self.check(typ) {
+            Ok(self.advance())
+        } else {
+            Err(msg.to_string())
+        }
+    }
+
+    // `-9223372036854775808`, the one int whose digits alone are out of
+    // range, read as the literal it is printed as.
+    fn min_int(&mut self) -> Option<i64> {
+        let number = self.token_at(Some(self.current + 1));
+        if !self.check(TokenType::Minus) || number.typ != TokenType::Number {
+            return None;
+        }
+        let value = format!("-{}", number.lexeme).parse::<i64>().ok().filter(|&value| value == i64::MIN)?;
+        // `-9223372036854775808.abs()` negates what the method returns.
+        if matches!(self.token_at(Some(self.current + 2)).typ, TokenType::Dot | TokenType::LeftBracket) {
+            return None;
+        }
+        self.advance();
+        self.advance();
+        Some(value)
+    }
+
+    fn match_token(&mut self, typ: TokenType) -> bool {
+        if self.check(typ) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn check(&self, typ: TokenType) -> bool {
+        if self.is_at_end() {
+            false
+        } else {
+            self.peek().typ == typ
+        }
+    }
+
+    fn advance(&mut self) -> Token {
+        if !self.is_at_end() {
+            let token = self.peek();
+            AT.with(|at| at.set((token.line, token.col)));
+            self.current += 1;
+        }
+        self.previous()
+    }
+
+    // Out-of-range positions read as end of input, so a token stream without
+    // a trailing Eof cannot make the parser panic.
+    fn token_at(&self, index: Option<usize>) -> Token {
+        index
+            .and_then(|i| self.tokens.get(i))
+            .cloned()
+            .unwrap_or(Token { typ: TokenType::Eof, lexeme: "".to_string(), line: 0, col: 0, end: (0, 0) })
+    }
+
+    fn previous(&self) -> Token {
+        self.token_at(self.current.checked_sub(1))
+    }
+
+    fn peek(&self) -> Token {
+        self.token_at(Some(self.current))
+    }
+
+    fn is_at_end(&self) -> bool {
+        matches!(self.peek().typ, TokenType::Eof)
+    }
+}
+
+// A float literal that is too large for a float is an error rather than
+// infinity, which no literal prints as.
+fn float_literal(lexeme: &str) -> Result<f64, String> {
+    match lexeme.parse::<f64>() {
+        Ok(value) if value.is_finite() => Ok(value),
+        Ok(_) => Err(format!("Float literal '{}' is out of range.", lexeme)),
+        Err(_) => Err("Invalid float.".to_string()),
+    }
+}
+
+// The folding a parser does without `fold_with`.
+fn literals(arena: &mut Arena, id: ExprId, consts: &HashMap<String, ExprId>, _declarations: &[NodeId]) -> Result<ExprId, String> {
+    match arena[id].clone() {
+        Expr::Literal(_) | Expr::FloatLiteral(_) | Expr::BoolLiteral(_) | Expr::StringLiteral(_) => Ok(id),
+        // A copy, so that every use of the constant is a tree of its own.
+        Expr::VarRef(name) => consts.get(&name).map(|&value| arena.deep_copy_expr(value)).ok_or(format!("'{}' is not a constant.", name)),
+        Expr::ArrayLiteral(items) => {
+            let items = items.into_iter().map(|item| literals(arena, item, consts, _declarations)).collect::<Result<_, _>>()?;
+            Ok(arena.alloc_expr(Expr::ArrayLiteral(items)))
+        }
+        Expr::Unary(UnaryOp::Neg, operand) => {
+            let operand = literals(arena, operand, consts, _declarations)?;
+            let negated = match arena[operand] {
+                Expr::Literal(v) => Expr::Literal(v.checked_neg().ok_or("Integer overflow in constant.")?),
+                Expr::FloatLiteral(v) => Expr::FloatLiteral(-v),
+                _ => return Err("Invalid unary op in constant.".to_string()),
+            };
+            Ok(arena.alloc_expr(negated))
+        }
+        _ => Err("Only literals, constants and arrays of them fold without an evaluator.".to_string()),
+    }
+}
+
+// Whether `node` returns from the function it appears in. Nested functions
+// return from themselves.
+fn returns(arena: &Arena, id: NodeId) -> bool {
+    let node = &arena[id];
+    if matches!(node, AstNode::Item(Item::FuncDecl(..)) | AstNode::Expr(Expr::Lambda(..)) | AstNode::Item(Item::ImplDecl(..))) {
+        return false;
+    }
+    let mut found = matches!(node, AstNode::Stmt(Stmt::Return(_)));
+    node.for_each_child(&mut |child| found = found || returns(arena, child));
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic;
+    use crate::printer;
+
+    fn parse(source: &str) -> Result<(Arena, Vec<NodeId>), String> {
+        let mut arena = Arena::new();
+        let items = Parser::new(tokenize(source), &mut arena).parse().map_err(|errors| diagnostic::render(&errors))?;
+        Ok((arena, items))
+    }
+
+    #[test]
+    fn float_literals_past_the_largest_float_are_rejected() {
+        assert_eq!(parse("const X: float = 1e999").err(), Some("1:23: Float literal '1e999' is out of range.".to_string()));
+        assert_eq!(parse("const X: float = -1e999").err(), Some("1:24: Float literal '1e999' is out of range.".to_string()));
+        assert!(parse("const X: float = 1e308").is_ok());
+    }
+
+    #[test]
+    fn compound_assignments_keep_their_operator() -> Result<(), String> {
+        for (source, op) in [("x += 1", BinOp::Add), ("x -= 1", BinOp::Sub), ("x *= 1", BinOp::Mul), ("x /= 1", BinOp::Div), ("x %= 1", BinOp::Mod)] {
+            let (arena, items) = parse(source)?;
+            let parsed = items.first().map(|&id| &arena[id]);
+            assert!(matches!(parsed, Some(AstNode::Stmt(Stmt::CompoundAssign(name, parsed, _))) if name == "x" && *parsed == op), "{}", source);
+            assert_eq!(printer::print_program(&arena, &items), format!("{}\n", source));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn interpolated_strings_split_into_text_and_expressions() -> Result<(), String> {
+        let (arena, items) = parse(r#"write "a ${x + 1} {b} ${f(y)}""#)?;
+        let Some(&AstNode::Stmt(Stmt::Write(string))) = items.first().map(|&id| &arena[id]) else { return Err("Expected a write.".to_string()) };
+        let Expr::Interpolation(parts) = &arena[string] else { return Err(format!("Expected an interpolation, got {:?}.", arena[string])) };
+        let printed: Vec<String> = parts.iter().map(|&part| printer::print(&arena, part.node())).collect();
+        assert_eq!(printed, ["\"a \"", "x + 1", "\" {b} \"", "f(y)"]);
+        assert_eq!(printer::print_program(&arena, &items), "write \"a ${x + 1} {b} ${f(y)}\"\n");
+        Ok(())
+    }
+
+    #[test]
+    fn interpolation_errors() {
+        assert_eq!(parse(r#"write "a ${x y}""#).err(), Some("1:14: Unexpected 'y' in '${...}'.".to_string()));
+        assert_eq!(parse(r#"write "a ${x""#).err(), Some("1:14: Unterminated '${' in string.".to_string()));
+    }
+
+    #[test]
+    fn for_loops_take_a_let_an_assignment_or_an_expression_in_each_clause() -> Result<(), String> {
+        for source in ["for let i = 0; i < 3; i = i + 1 {\n}", "for i = 0; i < n; i += 2 {\n}", "for start(); more(); next() {\n}"] {
+            let (arena, items) = parse(source)?;
+            assert!(matches!(items.first().map(|&id| &arena[id]), Some(AstNode::Stmt(Stmt::For(..)))), "{}", source);
+            assert_eq!(printer::print_program(&arena, &items), format!("{}\n", source));
+        }
+        let (arena, items) = parse("for i in 0..3 {\n}")?;
+        assert!(matches!(items.first().map(|&id| &arena[id]), Some(AstNode::Stmt(Stmt::ForIn(name, ..))) if name == "i"));
+        Ok(())
+    }
+
+    #[test]
+    fn lambdas_need_their_types() -> Result<(), String> {
+        let (arena, items) = parse("let f = |x: int, y: float| -> float x + y")?;
+        let init = match items.first().map(|&id| &arena[id]) {
+            Some(AstNode::Stmt(Stmt::VarDecl(_, _, init))) => *init,
+            other => return Err(format!("Expected a let, got {:?}.", other)),
+        };
+        assert!(matches!(&arena[init], Expr::Lambda(params, ViraType::Float, _) if params.get(1) == Some(&("y".to_string(), ViraType::Float))));
+        assert_eq!(parse("let f = |x| -> int x").err(), Some("1:11: Expect ':' after param name.".to_string()));
+        assert_eq!(parse("let f = |x: int| x").err(), Some("1:18: Missing '->' in anonymous function.".to_string()));
+        assert_eq!(parse("let f = func(x: int) { x }").err(), Some("1:22: Missing '->' in anonymous function.".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn for_loop_errors() {
+        assert_eq!(parse("for let i = 0 i < 3; i += 1 {}").err(), Some("1:15: Expect ';' after the for loop's initializer.".to_string()));
+        assert_eq!(parse("for let i = 0; i < 3 i += 1 {}").err(), Some("1:22: Expect ';' after the for loop's condition.".to_string()));
+        // A missing condition or body is reported where it should start.
+        assert_eq!(parse("for let i = 0; ; i += 1 {}").err(), Some("1:16: Unexpected ';'.".to_string()));
+        assert_eq!(parse("for let i = 0; i < 3; i += 1").err(), Some("1:29: Unexpected end of input.".to_string()));
+    }
+}
@@ -0,0 +1,705 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::arena::{Arena, ExprId, NodeId};
+use crate::ast::{AstNode, BinOp, Expr, Item, Pattern, Stmt, UnaryOp, ViraType};
+use crate::tokenizer::{tokenize, TokenType};
+
+// Canonical source printing. Output re-parses to the same tree: operands are
+// parenthesized only where precedence requires it, a `let` carries a type
+// only when it was annotated, and anonymous functions use the
+// `|x: int| -> int` form.
+
+const LAMBDA: u8 = 0;
+const RANGE: u8 = 1;
+const COALESCE: u8 = 6;
+const UNARY: u8 = 9;
+const POSTFIX: u8 = 10;
+
+fn precedence(op: BinOp) -> u8 {
+    match op {
+        BinOp::Or => 2,
+        BinOp::And => 3,
+        BinOp::Eq | BinOp::Neq => 4,
+        BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge => 5,
+        BinOp::Add | BinOp::Sub => 7,
+        BinOp::Mul | BinOp::Div | BinOp::Mod => 8,
+    }
+}
+
+fn node_precedence(node: &AstNode) -> u8 {
+    match node {
+        AstNode::Expr(Expr::Lambda(..)) => LAMBDA,
+        AstNode::Expr(Expr::Range(..)) => RANGE,
+        AstNode::Expr(Expr::Coalesce(..)) => COALESCE,
+        AstNode::Expr(Expr::Binary(_, op, _)) => precedence(*op),
+        AstNode::Expr(Expr::Unary(..)) => UNARY,
+        _ => POSTFIX,
+    }
+}
+
+pub fn bin_op(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "+",
+        BinOp::Sub => "-",
+        BinOp::Mul => "*",
+        BinOp::Div => "/",
+        BinOp::Mod => "%",
+        BinOp::Eq => "==",
+        BinOp::Neq => "!=",
+        BinOp::Lt => "<",
+        BinOp::Gt => ">",
+        BinOp::Le => "<=",
+        BinOp::Ge => ">=",
+        BinOp::And => "&&",
+        BinOp::Or => "||",
+    }
+}
+
+// Names that would lex as something else (keywords, type names) are printed
+// as raw identifiers.
+fn ident(name: &str) -> String {
+    let tokens = tokenize(name);
+    match tokens.as_slice() {
+        [token, eof] if token.typ == TokenType::Identifier && token.lexeme == name && eof.typ == TokenType::Eof => name.to_string(),
+        _ => format!("`{}`", name),
+    }
+}
+
+// The `: T` of a let, empty when its type is inferred.
+fn annotation(typ: &Option<ViraType>) -> String {
+    typ.as_ref().map_or(String::new(), |typ| format!(": {}", typ))
+}
+
+// Constant folding can produce floats with no literal spelling; these print
+// as constant expressions that fold back to the same value.
+fn float(value: f64) -> String {
+    if value.is_nan() {
+        "(0.0 / 0.0)".to_string()
+    } else if value.is_infinite() {
+        // Past `f64::MAX` a literal is out of range, so overflow to reach it.
+        if value > 0.0 { "(1e308 * 10.0)" } else { "(-1e308 * 10.0)" }.to_string()
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+// The node a printed expression begins with, following operands that are
+// printed without parentheses.
+fn leftmost(arena: &Arena, id: NodeId) -> NodeId {
+    let precedence_of = |id: NodeId| node_precedence(&arena[id]);
+    match &arena[id] {
+        AstNode::Expr(Expr::Binary(left, op, _)) if precedence_of(left.node()) >= precedence(*op) => leftmost(arena, left.node()),
+        AstNode::Expr(Expr::Range(start, _)) if precedence_of(start.node()) > RANGE => leftmost(arena, start.node()),
+        AstNode::Expr(Expr::Coalesce(left, _)) if precedence_of(left.node()) > COALESCE => leftmost(arena, left.node()),
+        AstNode::Expr(Expr::Index(base, _) | Expr::FieldAccess(base, _) | Expr::SafeAccess(base, _) | Expr::MethodCall(base, _, _)) if precedence_of(base.node()) >= POSTFIX => {
+            leftmost(arena, base.node())
+        }
+        AstNode::Expr(Expr::IntToFloat(operand)) if precedence_of(operand.node()) >= POSTFIX => leftmost(arena, operand.node()),
+        AstNode::Expr(Expr::TupleIndex(base, _)) if !matches!(arena[*base], Expr::Literal(_) | Expr::FloatLiteral(_)) => leftmost(arena, base.node()),
+        AstNode::Expr(Expr::Apply(callee, _)) if matches!(arena[*callee], Expr::Call(..) | Expr::Apply(..) | Expr::Index(..) | Expr::MethodCall(..)) => {
+            leftmost(arena, callee.node())
+        }
+        _ => id,
+    }
+}
+
+pub fn print_program(arena: &Arena, nodes: &[NodeId]) -> String {
+    let mut printer = Printer { arena, out: String::new(), indent: 0, source: None };
+    for &node in nodes {
+        printer.statement(node);
+        printer.out.push('\n');
+    }
+    printer.out
+}
+
+// The source of one node, for messages.
+pub fn print(arena: &Arena, id: NodeId) -> String {
+    let mut printer = Printer { arena, out: String::new(), indent: 0, source: None };
+    printer.node(id);
+    printer.out
+}
+
+// The comments of a source file, by the node each goes with: those on the
+// lines before it, the one after it on its last line, and, for a block,
+// impl or match, those before its closing `}`.
+#[derive(Default)]
+pub struct Comments {
+    pub before: HashMap<NodeId, Vec<String>>,
+    pub after: HashMap<NodeId, String>,
+    pub closing: HashMap<NodeId, Vec<String>>,
+}
+
+// An item as `fmt` prints it: constants with the initializer written rather
+// than the value it folds to, and the comments with their nodes.
+pub fn print_source(arena: &Arena, id: NodeId, comments: &Comments) -> String {
+    let mut printer = Printer { arena, out: String::new(), indent: 0, source: Some(comments) };
+    printer.comments_before(id);
+    printer.statement(id);
+    printer.out
+}
+
+impl fmt::Display for ViraType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ViraType::Int => write!(f, "int"),
+            ViraType::Float => write!(f, "float"),
+            ViraType::Decimal => write!(f, "decimal"),
+            ViraType::Complex => write!(f, "complex"),
+            ViraType::Bool => write!(f, "bool"),
+            ViraType::String => write!(f, "string"),
+            ViraType::Range => write!(f, "range"),
+            ViraType::Array(inner) => write!(f, "array<{}>", inner),
+            ViraType::Map(key, value) => write!(f, "map<{}, {}>", key, value),
+            ViraType::Struct(name) | ViraType::Enum(name) | ViraType::TypeVar(name) => write!(f, "{}", ident(name)),
+            ViraType::Function(params, ret) => {
+                let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+                write!(f, "func({}) -> {}", params.join(", "), ret)
+            }
+            ViraType::Tuple(elements) if elements.len() == 1 => write!(f, "({},)", elements[0]),
+            ViraType::Tuple(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "({})", elements.join(", "))
+            }
+            // `func() -> int?` returns an optional.
+            ViraType::Optional(inner) if matches!(**inner, ViraType::Function(..)) => write!(f, "({})?", inner),
+            ViraType::Optional(inner) => write!(f, "{}?", inner),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Int(v) => write!(f, "{}", v),
+            Pattern::Float(v) if v.is_sign_negative() => write!(f, "-{}", float(-v)),
+            Pattern::Float(v) => write!(f, "{}", float(*v)),
+            Pattern::Bool(v) => write!(f, "{}", v),
+            Pattern::String(s) => write!(f, "\"{}\"", s),
+            Pattern::Binding(name) => write!(f, "{}", ident(name)),
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::EnumVariant(name, variant, fields) => {
+                write!(f, "{}::{}", ident(name), ident(variant))?;
+                if !fields.is_empty() {
+                    let fields: Vec<String> = fields.iter().map(|p| p.to_string()).collect();
+                    write!(f, "({})", fields.join(", "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+struct Printer<'a> {
+    arena: &'a Arena,
+    out: String,
+    indent: usize,
+    // Printing for `fmt`, with these comments.
+    source: Option<&'a Comments>,
+}
+
+impl<'a> Printer<'a> {
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.out.push_str(&"    ".repeat(self.indent));
+    }
+
+    fn comments_before(&mut self, id: NodeId) {
+        for comment in self.source.and_then(|comments| comments.before.get(&id)).into_iter().flatten() {
+            self.out.push_str(comment);
+            self.newline();
+        }
+    }
+
+    fn comment_after(&mut self, id: NodeId) {
+        if let Some(comment) = self.source.and_then(|comments| comments.after.get(&id)) {
+            self.out.push(' ');
+            self.out.push_str(comment);
+        }
+    }
+
+    fn closing_comments(&mut self, id: NodeId) {
+        for comment in self.source.and_then(|comments| comments.closing.get(&id)).into_iter().flatten() {
+            self.newline();
+            self.out.push_str(comment);
+        }
+    }
+
+    fn list(&mut self, items: &[ExprId]) {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.node(item.node());
+        }
+    }
+
+    fn params(&mut self, params: &[(String, ViraType)]) {
+        let params: Vec<String> = params.iter().map(|(name, typ)| format!("{}: {}", ident(name), typ)).collect();
+        self.out.push_str(&params.join(", "));
+    }
+
+    // A method's parameters, with the implicit type of `self` left out.
+    fn method(&mut self, name: &str, params: &[(String, ViraType)], ret: &ViraType) {
+        self.out.push_str(&format!("func {}(self", ident(name)));
+        if !params.is_empty() {
+            self.out.push_str(", ");
+            self.params(params);
+        }
+        self.out.push_str(&format!(") -> {}", ret));
+    }
+
+    // Where a statement starts, a leading `{` would parse as a block, and a
+    // leading `if` as an if statement that ends at its last branch.
+    fn statement(&mut self, node: NodeId) {
+        let first = leftmost(self.arena, node);
+        // A leading `-` would continue the previous line as a subtraction.
+        let negative = match &self.arena[first] {
+            AstNode::Expr(Expr::Unary(UnaryOp::Neg, _)) => true,
+            AstNode::Expr(Expr::Literal(v)) => *v < 0,
+            AstNode::Expr(Expr::FloatLiteral(v)) => v.is_sign_negative(),
+            _ => false,
+        };
+        let ambiguous = negative || matches!(self.arena[first], AstNode::Expr(Expr::MapLiteral(_))) || (matches!(self.arena[first], AstNode::Expr(Expr::If(..))) && first != node);
+        self.parenthesized(node, ambiguous);
+    }
+
+    // Arm and anonymous function bodies that start with `{` are blocks.
+    fn body(&mut self, node: NodeId) {
+        self.parenthesized(node, matches!(self.arena[leftmost(self.arena, node)], AstNode::Expr(Expr::MapLiteral(_))));
+    }
+
+    fn parenthesized(&mut self, node: NodeId, parens: bool) {
+        if parens {
+            self.out.push('(');
+            self.node(node);
+            self.out.push(')');
+        } else {
+            self.node(node);
+        }
+    }
+
+    // Prints `node` as an operand, parenthesized when it binds looser than
+    // `min` requires.
+    fn operand(&mut self, node: NodeId, min: u8) {
+        self.parenthesized(node, node_precedence(&self.arena[node]) < min);
+    }
+
+    fn node(&mut self, id: NodeId) {
+        let arena = self.arena;
+        match &arena[id] {
+            AstNode::Expr(Expr::Literal(v)) => self.out.push_str(&v.to_string()),
+            AstNode::Expr(Expr::FloatLiteral(v)) => self.out.push_str(&float(*v)),
+            AstNode::Expr(Expr::BoolLiteral(v)) => self.out.push_str(&v.to_string()),
+            AstNode::Expr(Expr::StringLiteral(s)) => self.out.push_str(&format!("\"{}\"", s)),
+            AstNode::Expr(Expr::Interpolation(parts)) => {
+                self.out.push('"');
+                for part in parts {
+                    match &arena[*part] {
+                        Expr::StringLiteral(text) => self.out.push_str(text),
+                        _ => {
+                            self.out.push_str("${");
+                            self.node(part.node());
+                            self.out.push('}');
+                        }
+                    }
+                }
+                self.out.push('"');
+            }
+            AstNode::Expr(Expr::Nil) => self.out.push_str("nil"),
+            AstNode::Expr(Expr::Binary(left, op, right)) => {
+                let prec = precedence(*op);
+                self.operand(left.node(), prec);
+                self.out.push_str(&format!(" {} ", bin_op(*op)));
+                self.operand(right.node(), prec + 1);
+            }
+            AstNode::Expr(Expr::Unary(op, operand)) => {
+                self.out.push_str(match op {
+                    UnaryOp::Neg => "-",
+                    UnaryOp::Not => "!",
+                });
+                self.operand(operand.node(), UNARY);
+            }
+            // Implicit in the source, where checking inserts it again.
+            AstNode::Expr(Expr::IntToFloat(operand)) => self.operand(operand.node(), POSTFIX),
+            AstNode::Stmt(Stmt::VarDecl(name, typ, init)) => {
+                self.out.push_str(&format!("let {}{} = ", ident(name), annotation(typ)));
+                self.node(init.node());
+            }
+            AstNode::Stmt(Stmt::TupleDecl(names, typ, init)) => {
+                let names: Vec<String> = names.iter().map(|name| ident(name)).collect();
+                self.out.push_str(&format!("let ({}){} = ", names.join(", "), annotation(typ)));
+                self.node(init.node());
+            }
+            AstNode::Item(Item::ConstDecl(name, typ, value)) => {
+                self.out.push_str(&format!("const {}: {} = ", ident(name), typ));
+                let value = if self.source.is_some() { arena.folded_from(id).unwrap_or(value.node()) } else { value.node() };
+                self.node(value);
+            }
+            AstNode::Item(Item::ConfigDecl(entries)) => {
+                self.out.push_str("config {");
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    self.out.push_str(if i > 0 { ", " } else { " " });
+                    self.out.push_str(&format!("{} = ", ident(key)));
+                    self.node(value.node());
+                }
+                self.out.push_str(if entries.is_empty() { "}" } else { " }" });
+            }
+            AstNode::Item(Item::DataDecl(name, typ, delimiter, contents)) => {
+                self.out.push_str(&format!("data {}", ident(name)));
+                if *typ != ViraType::String {
+                    self.out.push_str(&format!(": {}", typ));
+                }
+                self.out.push_str(&format!(" = <<<{}\n", delimiter));
+                if !contents.is_empty() {
+                    self.out.push_str(&format!("{}\n", contents));
+                }
+                self.out.push_str(delimiter);
+            }
+            AstNode::Stmt(Stmt::Assign(name, value)) => {
+                self.out.push_str(&format!("{} = ", ident(name)));
+                self.node(value.node());
+            }
+            AstNode::Stmt(Stmt::CompoundAssign(name, op, value)) => {
+                self.out.push_str(&format!("{} {}= ", ident(name), bin_op(*op)));
+                self.node(value.node());
+            }
+            AstNode::Expr(Expr::VarRef(name)) => self.out.push_str(&ident(name)),
+            AstNode::Item(Item::FuncDecl(name, type_params, params, ret, body)) => {
+                self.out.push_str(&format!("func {}", ident(name)));
+                if !type_params.is_empty() {
+                    let type_params: Vec<String> = type_params.iter().map(|t| ident(t)).collect();
+                    self.out.push_str(&format!("<{}>", type_params.join(", ")));
+                }
+                self.out.push('(');
+                self.params(params);
+                self.out.push_str(&format!(") -> {} ", ret));
+                self.node(*body);
+            }
+            AstNode::Expr(Expr::Call(name, args)) => {
+                self.out.push_str(&format!("{}(", ident(name)));
+                self.list(args);
+                self.out.push(')');
+            }
+            AstNode::Expr(Expr::Apply(callee, args)) => {
+                // `f(x)` would parse as a named call and `a.f(x)` as a method
+                // call, so only the other postfix callees are printed bare.
+                if matches!(arena[*callee], Expr::Call(..) | Expr::Apply(..) | Expr::Index(..) | Expr::MethodCall(..)) {
+                    self.node(callee.node());
+                } else {
+                    self.out.push('(');
+                    self.node(callee.node());
+                    self.out.push(')');
+                }
+                self.out.push('(');
+                self.list(args);
+                self.out.push(')');
+            }
+            AstNode::Expr(Expr::If(cond, then, else_)) => {
+                self.out.push_str("if ");
+                self.node(cond.node());
+                self.out.push(' ');
+                self.node(*then);
+                if let Some(else_) = else_ {
+                    self.out.push_str(" else ");
+                    self.node(*else_);
+                }
+            }
+            AstNode::Stmt(Stmt::While(cond, body)) => {
+                self.out.push_str("while ");
+                self.node(cond.node());
+                self.out.push(' ');
+                self.node(*body);
+            }
+            AstNode::Stmt(Stmt::For(init, cond, incr, body)) => {
+                self.out.push_str("for ");
+                self.node(*init);
+                self.out.push_str("; ");
+                self.node(cond.node());
+                self.out.push_str("; ");
+                self.node(*incr);
+                self.out.push(' ');
+                self.node(*body);
+            }
+            AstNode::Stmt(Stmt::ForIn(name, iterable, body)) => {
+                self.out.push_str(&format!("for {} in ", ident(name)));
+                self.node(iterable.node());
+                self.out.push(' ');
+                self.node(*body);
+            }
+            AstNode::Stmt(Stmt::Return(expr)) => {
+                self.out.push_str("return");
+                if let Some(expr) = expr {
+                    self.out.push(' ');
+                    self.node(expr.node());
+                }
+            }
+            AstNode::Expr(Expr::Block(stmts)) => {
+                self.out.push('{');
+                self.indent += 1;
+                for stmt in stmts {
+                    self.newline();
+                    self.comments_before(*stmt);
+                    self.statement(*stmt);
+                    self.comment_after(*stmt);
+                }
+                self.closing_comments(id);
+                self.indent -= 1;
+                self.newline();
+                self.out.push('}');
+            }
+            AstNode::Stmt(Stmt::Write(expr)) => {
+                self.out.push_str("write ");
+                self.node(expr.node());
+            }
+            AstNode::Expr(Expr::ArrayLiteral(items)) => {
+                self.out.push('[');
+                self.list(items);
+                self.out.push(']');
+            }
+            AstNode::Expr(Expr::Index(base, index)) => {
+                self.operand(base.node(), POSTFIX);
+                self.out.push('[');
+                self.node(index.node());
+                self.out.push(']');
+            }
+            AstNode::Item(Item::StructDecl(name, fields)) => {
+                self.out.push_str(&format!("struct {} {{ ", ident(name)));
+                self.params(fields);
+                self.out.push_str(" }");
+            }
+            AstNode::Expr(Expr::StructLiteral(name, fields)) => {
+                self.out.push_str(&format!("{} {{ ", ident(name)));
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.out.push_str(&format!("{}: ", ident(field)));
+                    self.node(value.node());
+                }
+                self.out.push_str(" }");
+            }
+            AstNode::Expr(Expr::FieldAccess(base, field)) => {
+                self.operand(base.node(), POSTFIX);
+                self.out.push_str(&format!(".{}", ident(field)));
+            }
+            AstNode::Expr(Expr::SafeAccess(base, field)) => {
+                self.operand(base.node(), POSTFIX);
+                self.out.push_str(&format!("?.{}", ident(field)));
+            }
+            AstNode::Expr(Expr::Tuple(items)) => {
+                self.out.push('(');
+                self.list(items);
+                if items.len() == 1 {
+                    self.out.push(',');
+                }
+                self.out.push(')');
+            }
+            AstNode::Expr(Expr::MapLiteral(entries)) => {
+                self.out.push('{');
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    self.out.push_str(if i > 0 { ", " } else { " " });
+                    self.node(key.node());
+                    self.out.push_str(": ");
+                    self.node(value.node());
+                }
+                self.out.push_str(if entries.is_empty() { "}" } else { " }" });
+            }
+            AstNode::Expr(Expr::TupleIndex(base, index)) => {
+                // `1.0` would lex as a float.
+                if matches!(arena[*base], Expr::Literal(_) | Expr::FloatLiteral(_)) {
+                    self.out.push('(');
+                    self.node(base.node());
+                    self.out.push(')');
+                } else {
+                    self.operand(base.node(), POSTFIX);
+                }
+                self.out.push_str(&format!(".{}", index));
+            }
+            AstNode::Item(Item::EnumDecl(name, variants)) => {
+                // A discriminant is only written where it is not the one
+                // the previous variant implies.
+                let mut implied = Some(0);
+                let variants: Vec<String> = variants
+                    .iter()
+                    .map(|(variant, payload, discriminant)| {
+                        let mut text = if payload.is_empty() {
+                            ident(variant)
+                        } else {
+                            let payload: Vec<String> = payload.iter().map(|t| t.to_string()).collect();
+                            format!("{}({})", ident(variant), payload.join(", "))
+                        };
+                        if implied != Some(*discriminant) {
+                            text.push_str(&format!(" = {}", discriminant));
+                        }
+                        implied = discriminant.checked_add(1);
+                        text
+                    })
+                    .collect();
+                self.out.push_str(&format!("enum {} {{ {} }}", ident(name), variants.join(", ")));
+            }
+            AstNode::Item(Item::TraitDecl(name, methods)) => {
+                self.out.push_str(&format!("trait {} {{", ident(name)));
+                self.indent += 1;
+                for (method, params, ret) in methods {
+                    self.newline();
+                    self.method(method, params, ret);
+                }
+                self.indent -= 1;
+                self.newline();
+                self.out.push('}');
+            }
+            AstNode::Item(Item::ImplDecl(trait_name, type_name, methods)) => {
+                match trait_name {
+                    Some(trait_name) => self.out.push_str(&format!("impl {} for {} {{", ident(trait_name), ident(type_name))),
+                    None => self.out.push_str(&format!("impl {} {{", ident(type_name))),
+                }
+                self.indent += 1;
+                for method in methods {
+                    self.newline();
+                    self.comments_before(method.node());
+                    match &arena[*method] {
+                        Item::FuncDecl(name, _, params, ret, body) => {
+                            self.method(name, params.get(1..).unwrap_or_default(), ret);
+                            self.out.push(' ');
+                            self.node(*body);
+                        }
+                        _ => self.node(method.node()),
+                    }
+                    self.comment_after(method.node());
+                }
+                self.closing_comments(id);
+                self.indent -= 1;
+                self.newline();
+                self.out.push('}');
+            }
+            AstNode::Expr(Expr::MethodCall(receiver, method, args)) => {
+                self.operand(receiver.node(), POSTFIX);
+                self.out.push_str(&format!(".{}(", ident(method)));
+                self.list(args);
+                self.out.push(')');
+            }
+            AstNode::Expr(Expr::EnumConstructor(name, variant, args)) => {
+                self.out.push_str(&format!("{}::{}", ident(name), ident(variant)));
+                if !args.is_empty() {
+                    self.out.push('(');
+                    self.list(args);
+                    self.out.push(')');
+                }
+            }
+            AstNode::Expr(Expr::Match(scrutinee, arms)) => {
+                self.out.push_str("match ");
+                self.node(scrutinee.node());
+                self.out.push_str(" {");
+                self.indent += 1;
+                for (pattern, body) in arms {
+                    self.newline();
+                    self.comments_before(body.node());
+                    self.out.push_str(&format!("{} => ", pattern));
+                    self.body(body.node());
+                    self.out.push(',');
+                    self.comment_after(body.node());
+                }
+                self.closing_comments(id);
+                self.indent -= 1;
+                self.newline();
+                self.out.push('}');
+            }
+            AstNode::Expr(Expr::Coalesce(left, right)) => {
+                self.operand(left.node(), COALESCE + 1);
+                self.out.push_str(" ?? ");
+                self.operand(right.node(), COALESCE);
+            }
+            AstNode::Expr(Expr::Range(start, end)) => {
+                self.operand(start.node(), RANGE + 1);
+                self.out.push_str("..");
+                self.operand(end.node(), RANGE + 1);
+            }
+            AstNode::Expr(Expr::Lambda(params, ret, body)) => {
+                self.out.push('|');
+                self.params(params);
+                self.out.push_str(&format!("| -> {} ", ret));
+                self.body(body.node());
+            }
+            AstNode::Item(Item::Import(path)) => self.out.push_str(&format!("import \"{}\"", path)),
+            AstNode::Item(Item::Pub(item)) => {
+                self.out.push_str("pub ");
+                self.node(*item);
+            }
+            AstNode::Item(Item::Attribute(name, args, item)) => {
+                self.out.push_str(&format!("@{}", ident(name)));
+                if !args.is_empty() {
+                    self.out.push('(');
+                    self.list(args);
+                    self.out.push(')');
+                }
+                self.newline();
+                self.node(*item);
+            }
+            AstNode::Stmt(Stmt::Defer(stmt)) => {
+                self.out.push_str("defer ");
+                self.statement(*stmt);
+            }
+            AstNode::Item(Item::TypeAlias(name, typ)) => self.out.push_str(&format!("type {} = {}", ident(name), typ)),
+            AstNode::Stmt(Stmt::Throw(value)) => {
+                self.out.push_str("throw ");
+                self.node(value.node());
+            }
+            AstNode::Stmt(Stmt::Try(body, name, handler)) => {
+                self.out.push_str("try ");
+                self.node(body.node());
+                self.out.push_str(&format!(" catch ({}) ", ident(name)));
+                self.node(handler.node());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic;
+    use crate::diff::same_tree;
+    use crate::parser::Parser;
+    use crate::tokenizer::tokenize;
+
+    // Programs that between them use every kind of node the parser makes.
+    const PROGRAMS: [&str; 16] = [
+        "let x: int = -1 + 2 * (3 - 4) / 5 % 6\nwrite !(x < 1 && x >= 0 || x != 2)\n",
+        "func add<T>(a: T, b: T) -> T { return a + b }\nwrite add(1, 2)\nwrite add(1.5, 2.0)\n",
+        "struct Point { x: int, y: float }\nlet p = Point { x: 1, y: 2.5 }\nwrite p.x\n",
+        "enum Color { Red, Green = 4, Rgb(int, int, int) }\nwrite match Color::Rgb(1, 2, 3) { Color::Rgb(r, _, _) => r, Color::Red => -1, _ => 0 }\n",
+        "let xs = [1, 2, 3]\nwrite xs[1]\nlet ys = [0; 4]\nif xs[0] < 2 { write \"small\" } else if false { write 1 } else { write \"big\" }\n",
+        "while false { write 1 }\nfor let i: int = 0; i < 3; i = i + 1 { write i }\nfor i in 0..3 { write -i }\n",
+        "let f = |x: int| -> int x + 1\nlet g = func(a: int) -> int { a * 2 }\nwrite g(f(1))\nwrite (f)(2)\n",
+        "let t: (int, (string,)) = (1, (\"a\",))\nlet (a, b): (int, (string,)) = t\nwrite t.1.0\n",
+        "let m: map<string, int> = { \"a\": 1, \"b\": 2 }\nwrite m[\"a\"]\n",
+        "trait Shape { func area(self) -> float }\nimpl Shape for Circle { func area(self) -> float { self.r } }\nimpl Circle { func add(self, o: Circle) -> Circle { o } }\n",
+        "import \"geometry\"\npub func area(r: float) -> float { return r * r }\npub const PI: float = 3.14\nwrite geometry::area(2.0)\n",
+        "func f() -> int { defer write 1\n defer { write 2 }\n return 3 }\ntry { throw f() } catch (e) { write e }\n",
+        "@derive(hash, eq)\nstruct P { x: int }\n@test\nfunc t() -> int { return 0 }\nconfig { threads = 2, verbose = true }\n",
+        "type Ids = array<int>\nstruct Node { v: int, next: Node? }\nlet n: Node? = nil\nwrite n?.next?.v ?? 0\n",
+        "data text = <<<END\n  a <<<b\nEND\nwrite text\n",
+        "let n = 1\nn += 2\nn %= 3\nwrite \"i is ${n + 1} of ${[n][0]} {}\"\nwrite `for`\n",
+    ];
+
+    fn parse(arena: &mut Arena, source: &str) -> Result<Vec<NodeId>, String> {
+        Parser::new(tokenize(source), arena).parse().map_err(|errors| diagnostic::render(&errors))
+    }
+
+    #[test]
+    fn printed_programs_parse_back_to_the_same_tree() -> Result<(), String> {
+        for source in PROGRAMS {
+            let mut arena = Arena::new();
+            let items = parse(&mut arena, source)?;
+            let printed = print_program(&arena, &items);
+            let mut reparsed = Arena::new();
+            let again = parse(&mut reparsed, &printed).map_err(|e| format!("{}\nprinted as\n{}\ndoes not parse: {}", source, printed, e))?;
+            let same = items.len() == again.len() && items.iter().zip(&again).all(|(&a, &b)| same_tree(&arena, a, &reparsed, b));
+            assert!(same, "{}\nprinted as\n{}\nparses to another tree", source, printed);
+            // Printing is a fixed point after the first time.
+            assert_eq!(print_program(&reparsed, &again), printed);
+        }
+        Ok(())
+    }
+}
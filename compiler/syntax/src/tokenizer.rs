@@ -0,0 +1,621 @@
+#![deny(clippy::indexing_slicing)]
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::arena::Position;
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub typ: TokenType,
+    pub lexeme: String,
+    pub line: usize,
+    pub col: usize,
+    // Just past the last character; `tokenize` sets it once the token is read.
+    pub end: Position,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenType {
+    Func,
+    Let,
+    Const,
+    If,
+    Else,
+    While,
+    For,
+    Return,
+    Defer,
+    Throw,
+    Try,
+    Catch,
+    Write,
+    Struct,
+    Enum,
+    Trait,
+    Impl,
+    Match,
+    In,
+    Import,
+    Pub,
+    True,
+    False,
+    Nil,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Mod,
+    EqualEqual,
+    BangEqual,
+    Bang,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+    Pipe,
+    LeftBracket,
+    RightBracket,
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    Colon,
+    ColonColon,
+    Dot,
+    DotDot,
+    Equals,
+    // `+=`, `-=`, `*=`, `/=` and `%=`.
+    PlusEquals,
+    MinusEquals,
+    StarEquals,
+    SlashEquals,
+    ModEquals,
+    Comma,
+    Semicolon,
+    Arrow,
+    FatArrow,
+    At,
+    // `?` after a type, `??` between an optional and its fallback, `?.`
+    // for field access that passes nil through.
+    Question,
+    QuestionQuestion,
+    QuestionDot,
+    Number,
+    Float,
+    String,
+    Identifier,
+    IntType,
+    FloatType,
+    BoolType,
+    StringType,
+    // `<<<END`, the lines up to one that is just `END`, and that line. The
+    // lexeme is the delimiter, a newline, then the lines between, verbatim.
+    Heredoc,
+    // A `//` or `/* */` comment, kept so tools that print source can
+    // reproduce it; the parser skips these.
+    Comment,
+    // A malformed lexeme; the lexeme holds the diagnostic message.
+    Error,
+    Eof,
+}
+
+// Tracks the 1-based line and column of the next character.
+struct Cursor<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    col: usize,
+}
+
+impl Cursor<'_> {
+    fn peek(&mut self) -> Option<&char> {
+        self.chars.peek()
+    }
+
+    fn peek_second(&self) -> Option<char> {
+        self.chars.clone().nth(1)
+    }
+
+    fn take_digits(&mut self, into: &mut String) {
+        while let Some(&next) = self.chars.peek() {
+            if !next.is_ascii_digit() {
+                break;
+            }
+            into.push(next);
+            self.next();
+        }
+    }
+}
+
+// Number literals: `12`, `1.5`, `1.`, `.5`, and an optional exponent
+// (`1e9`, `1.5E-3`). A trailing dot is only part of the literal when it is not
+// followed by another dot or an identifier, so `0..n` and `1.abs()` still lex
+// as separate tokens. `1.2.3` is rejected as a whole.
+fn number(chars: &mut Cursor, mut num: String, line: usize, col: usize) -> Token {
+    let mut is_float = num.starts_with('.');
+    chars.take_digits(&mut num);
+    if !is_float && chars.peek() == Some(&'.') {
+        let after_dot = chars.peek_second();
+        if !after_dot.is_some_and(|c| c == '.' || c.is_alphabetic() || c == '_') {
+            is_float = true;
+            num.push('.');
+            chars.next();
+            chars.take_digits(&mut num);
+        }
+    }
+    if matches!(chars.peek(), Some('e') | Some('E')) {
+        is_float = true;
+        num.push('e');
+        chars.next();
+        if let Some(&sign) = chars.peek().filter(|c| **c == '+' || **c == '-') {
+            num.push(sign);
+            chars.next();
+        }
+        let before = num.len();
+        chars.take_digits(&mut num);
+        if num.len() == before {
+            return Token {
+                typ: TokenType::Error,
+                lexeme: format!("Malformed exponent in number literal '{}'.", num),
+                line,
+                col,
+                end: (line, col),
+            };
+        }
+    }
+    if is_float && chars.peek() == Some(&'.') && chars.peek_second().is_some_and(|c| c.is_ascii_digit()) {
+        while let Some(&next) = chars.peek() {
+            if !(next.is_ascii_digit() || next == '.') {
+                break;
+            }
+            num.push(next);
+            chars.next();
+        }
+        return Token { typ: TokenType::Error, lexeme: format!("Malformed number literal '{}'.", num), line, col, end: (line, col) };
+    }
+    let typ = if is_float { TokenType::Float } else { TokenType::Number };
+    Token { typ, lexeme: num, line, col, end: (line, col) }
+}
+
+// Block comments nest, so `/* a /* b */ c */` is a single comment. The
+// cursor is just past the opening `/`.
+fn block_comment(chars: &mut Cursor, line: usize, col: usize) -> Token {
+    let mut text = "/".to_string();
+    let mut depth = 0;
+    let mut opening = true;
+    while let Some(c) = chars.next() {
+        text.push(c);
+        if opening || (c == '/' && chars.peek() == Some(&'*')) {
+            depth += 1;
+        } else if c == '*' && chars.peek() == Some(&'/') {
+            depth -= 1;
+        } else {
+            continue;
+        }
+        if !opening {
+            text.extend(chars.next());
+        }
+        opening = false;
+        if depth == 0 {
+            return Token { typ: TokenType::Comment, lexeme: text, line, col, end: (line, col) };
+        }
+    }
+    Token { typ: TokenType::Error, lexeme: "Unterminated block comment.".to_string(), line, col, end: (line, col) }
+}
+
+// The cursor is just past `<<<`. The delimiter ends its line; the line that
+// closes the heredoc may be indented.
+fn heredoc(chars: &mut Cursor, line: usize, col: usize) -> Token {
+    let error = |message: String| Token { typ: TokenType::Error, lexeme: message, line, col, end: (line, col) };
+    let mut delimiter = String::new();
+    while let Some(&next) = chars.peek() {
+        if !is_ident_continue(next) {
+            break;
+        }
+        delimiter.push(next);
+        chars.next();
+    }
+    if delimiter.is_empty() {
+        return error("Expect a delimiter after '<<<'.".to_string());
+    }
+    let rest: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+    if !rest.trim().is_empty() {
+        return error(format!("Expect the end of the line after '<<<{}'.", delimiter));
+    }
+    let mut lines = Vec::new();
+    while chars.peek().is_some() {
+        let text: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+        if text.trim() == delimiter {
+            return Token { typ: TokenType::Heredoc, lexeme: format!("{}\n{}", delimiter, lines.join("\n")), line, col, end: (line, col) };
+        }
+        lines.push(text);
+    }
+    error(format!("Unterminated heredoc; expected a line with just '{}'.", delimiter))
+}
+
+// Identifiers follow Unicode UAX #31 (XID_Start / XID_Continue), plus `_`.
+fn is_ident_start(c: char) -> bool {
+    c == '_' || unicode_ident::is_xid_start(c)
+}
+
+fn is_ident_continue(c: char) -> bool {
+    unicode_ident::is_xid_continue(c)
+}
+
+// Whether the previous token can end an operand, in which case a following
+// `.5` is member access rather than a leading-dot float.
+fn ends_operand(tokens: &[Token]) -> bool {
+    matches!(
+        tokens.last().map(|t| &t.typ),
+        Some(
+            TokenType::Identifier
+                | TokenType::Number
+                | TokenType::Float
+                | TokenType::String
+                | TokenType::True
+                | TokenType::False
+                | TokenType::Nil
+                | TokenType::RightParen
+                | TokenType::RightBracket
+                | TokenType::RightBrace
+        )
+    )
+}
+
+impl Iterator for Cursor<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+        Some(c)
+    }
+}
+
+pub fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = Cursor { chars: source.chars().peekable(), line: 1, col: 1 };
+
+    loop {
+        let (line, col) = (chars.line, chars.col);
+        let read = tokens.len();
+        let Some(c) = chars.next() else { break };
+        match c {
+            ' ' | '\r' | '\t' => continue,
+            '\n' => {},
+            '+' | '-' | '*' | '/' | '%' if chars.peek() == Some(&'=') => {
+                chars.next();
+                let typ = match c {
+                    '+' => TokenType::PlusEquals,
+                    '-' => TokenType::MinusEquals,
+                    '*' => TokenType::StarEquals,
+                    '/' => TokenType::SlashEquals,
+                    _ => TokenType::ModEquals,
+                };
+                tokens.push(Token { typ, lexeme: format!("{}=", c), line, col, end: (line, col) });
+            }
+            '+' => tokens.push(Token { typ: TokenType::Plus, lexeme: "+".to_string(), line, col, end: (line, col) }),
+            '-' => {
+                if chars.peek() == Some(&'>') {
+                    chars.next();
+                    tokens.push(Token { typ: TokenType::Arrow, lexeme: "->".to_string(), line, col, end: (line, col) });
+                } else {
+                    tokens.push(Token { typ: TokenType::Minus, lexeme: "-".to_string(), line, col, end: (line, col) });
+                }
+            }
+            '*' => tokens.push(Token { typ: TokenType::Star, lexeme: "*".to_string(), line, col, end: (line, col) }),
+            '/' if chars.peek() == Some(&'/') => {
+                let mut text = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next == '\n' {
+                        break;
+                    }
+                    text.push(next);
+                    chars.next();
+                }
+                tokens.push(Token { typ: TokenType::Comment, lexeme: text, line, col, end: (line, col) });
+            }
+            '/' if chars.peek() == Some(&'*') => tokens.push(block_comment(&mut chars, line, col)),
+            '/' => tokens.push(Token { typ: TokenType::Slash, lexeme: "/".to_string(), line, col, end: (line, col) }),
+            '%' => tokens.push(Token { typ: TokenType::Mod, lexeme: "%".to_string(), line, col, end: (line, col) }),
+            '=' => {
+                if chars.peek() == Some(&'=') {
+                    chars.next();
+                    tokens.push(Token { typ: TokenType::EqualEqual, lexeme: "==".to_string(), line, col, end: (line, col) });
+                } else if chars.peek() == Some(&'>') {
+                    chars.next();
+                    tokens.push(Token { typ: TokenType::FatArrow, lexeme: "=>".to_string(), line, col, end: (line, col) });
+                } else {
+                    tokens.push(Token { typ: TokenType::Equals, lexeme: "=".to_string(), line, col, end: (line, col) });
+                }
+            }
+            '!' => {
+                if chars.peek() == Some(&'=') {
+                    chars.next();
+                    tokens.push(Token { typ: TokenType::BangEqual, lexeme: "!=".to_string(), line, col, end: (line, col) });
+                } else {
+                    tokens.push(Token { typ: TokenType::Bang, lexeme: "!".to_string(), line, col, end: (line, col) });
+                }
+            }
+            '<' => {
+                if chars.peek() == Some(&'<') && chars.peek_second() == Some('<') {
+                    chars.next();
+                    chars.next();
+                    tokens.push(heredoc(&mut chars, line, col));
+                } else if chars.peek() == Some(&'=') {
+                    chars.next();
+                    tokens.push(Token { typ: TokenType::LessEqual, lexeme: "<=".to_string(), line, col, end: (line, col) });
+                } else {
+                    tokens.push(Token { typ: TokenType::Less, lexeme: "<".to_string(), line, col, end: (line, col) });
+                }
+            }
+            '>' => {
+                if chars.peek() == Some(&'=') {
+                    chars.next();
+                    tokens.push(Token { typ: TokenType::GreaterEqual, lexeme: ">=".to_string(), line, col, end: (line, col) });
+                } else {
+                    tokens.push(Token { typ: TokenType::Greater, lexeme: ">".to_string(), line, col, end: (line, col) });
+                }
+            }
+            '&' if chars.peek() == Some(&'&') => {
+                chars.next();
+                tokens.push(Token { typ: TokenType::And, lexeme: "&&".to_string(), line, col, end: (line, col) });
+            }
+            '|' => {
+                if chars.peek() == Some(&'|') {
+                    chars.next();
+                    tokens.push(Token { typ: TokenType::Or, lexeme: "||".to_string(), line, col, end: (line, col) });
+                } else {
+                    tokens.push(Token { typ: TokenType::Pipe, lexeme: "|".to_string(), line, col, end: (line, col) });
+                }
+            }
+            '[' => tokens.push(Token { typ: TokenType::LeftBracket, lexeme: "[".to_string(), line, col, end: (line, col) }),
+            '@' => tokens.push(Token { typ: TokenType::At, lexeme: "@".to_string(), line, col, end: (line, col) }),
+            '?' => match chars.peek() {
+                Some('?') => {
+                    chars.next();
+                    tokens.push(Token { typ: TokenType::QuestionQuestion, lexeme: "??".to_string(), line, col, end: (line, col) });
+                }
+                Some('.') => {
+                    chars.next();
+                    tokens.push(Token { typ: TokenType::QuestionDot, lexeme: "?.".to_string(), line, col, end: (line, col) });
+                }
+                _ => tokens.push(Token { typ: TokenType::Question, lexeme: "?".to_string(), line, col, end: (line, col) }),
+            },
+            ']' => tokens.push(Token { typ: TokenType::RightBracket, lexeme: "]".to_string(), line, col, end: (line, col) }),
+            '(' => tokens.push(Token { typ: TokenType::LeftParen, lexeme: "(".to_string(), line, col, end: (line, col) }),
+            ')' => tokens.push(Token { typ: TokenType::RightParen, lexeme: ")".to_string(), line, col, end: (line, col) }),
+            '{' => tokens.push(Token { typ: TokenType::LeftBrace, lexeme: "{".to_string(), line, col, end: (line, col) }),
+            '}' => tokens.push(Token { typ: TokenType::RightBrace, lexeme: "}".to_string(), line, col, end: (line, col) }),
+            ':' => {
+                if chars.peek() == Some(&':') {
+                    chars.next();
+                    tokens.push(Token { typ: TokenType::ColonColon, lexeme: "::".to_string(), line, col, end: (line, col) });
+                } else {
+                    tokens.push(Token { typ: TokenType::Colon, lexeme: ":".to_string(), line, col, end: (line, col) });
+                }
+            }
+            '.' => {
+                if chars.peek() == Some(&'.') {
+                    chars.next();
+                    tokens.push(Token { typ: TokenType::DotDot, lexeme: "..".to_string(), line, col, end: (line, col) });
+                } else if chars.peek().is_some_and(|c| c.is_ascii_digit()) && !ends_operand(&tokens) {
+                    let token = number(&mut chars, ".".to_string(), line, col);
+                    tokens.push(token);
+                } else {
+                    tokens.push(Token { typ: TokenType::Dot, lexeme: ".".to_string(), line, col, end: (line, col) });
+                    // A tuple index: `t.0.1` is two indexes, not `t` and `0.1`.
+                    if chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+                        let mut index = String::new();
+                        chars.take_digits(&mut index);
+                        tokens.push(Token { typ: TokenType::Number, lexeme: index, line, col: col + 1, end: (line, col + 1) });
+                    }
+                }
+            }
+            ',' => tokens.push(Token { typ: TokenType::Comma, lexeme: ",".to_string(), line, col, end: (line, col) }),
+            ';' => tokens.push(Token { typ: TokenType::Semicolon, lexeme: ";".to_string(), line, col, end: (line, col) }),
+            '"' => {
+                // Strings end at the line; an unterminated one is reported at
+                // its opening quote and lexing resumes on the next line.
+                let mut string = String::new();
+                let mut terminated = false;
+                while let Some(&next) = chars.peek() {
+                    if next == '\n' {
+                        break;
+                    }
+                    chars.next();
+                    if next == '"' {
+                        terminated = true;
+                        break;
+                    }
+                    string.push(next);
+                }
+                let token = if terminated {
+                    Token { typ: TokenType::String, lexeme: string, line, col, end: (line, col) }
+                } else {
+                    Token { typ: TokenType::Error, lexeme: "Unterminated string literal.".to_string(), line, col, end: (line, col) }
+                };
+                tokens.push(token);
+            }
+            '0'..='9' => {
+                let token = number(&mut chars, c.to_string(), line, col);
+                tokens.push(token);
+            }
+            '`' => {
+                // Raw identifier: `for` names an identifier called "for".
+                let mut id = String::new();
+                let mut terminated = false;
+                while let Some(&next) = chars.peek() {
+                    if next == '\n' {
+                        break;
+                    }
+                    chars.next();
+                    if next == '`' {
+                        terminated = true;
+                        break;
+                    }
+                    id.push(next);
+                }
+                let valid = id.chars().next().is_some_and(is_ident_start) && id.chars().all(is_ident_continue);
+                let token = if !terminated {
+                    Token { typ: TokenType::Error, lexeme: "Unterminated raw identifier.".to_string(), line, col, end: (line, col) }
+                } else if !valid {
+                    Token { typ: TokenType::Error, lexeme: format!("Invalid raw identifier '`{}`'.", id), line, col, end: (line, col) }
+                } else {
+                    Token { typ: TokenType::Identifier, lexeme: id.nfc().collect(), line, col, end: (line, col) }
+                };
+                tokens.push(token);
+            }
+            _ if is_ident_start(c) => {
+                let mut id = String::new();
+                id.push(c);
+                while let Some(&next) = chars.peek() {
+                    if is_ident_continue(next) {
+                        id.push(next);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                // Identifiers are compared after NFC normalization, so composed
+                // and decomposed spellings of the same name are one identifier.
+                let id: String = id.nfc().collect();
+                let typ = match id.as_str() {
+                    "func" => TokenType::Func,
+                    "let" => TokenType::Let,
+                    "const" => TokenType::Const,
+                    "if" => TokenType::If,
+                    "else" => TokenType::Else,
+                    "while" => TokenType::While,
+                    "for" => TokenType::For,
+                    "return" => TokenType::Return,
+                    "defer" => TokenType::Defer,
+                    "throw" => TokenType::Throw,
+                    "try" => TokenType::Try,
+                    "catch" => TokenType::Catch,
+                    "write" => TokenType::Write,
+                    "struct" => TokenType::Struct,
+                    "enum" => TokenType::Enum,
+                    "trait" => TokenType::Trait,
+                    "impl" => TokenType::Impl,
+                    "match" => TokenType::Match,
+                    "in" => TokenType::In,
+                    "import" => TokenType::Import,
+                    "pub" => TokenType::Pub,
+                    "true" => TokenType::True,
+                    "false" => TokenType::False,
+                    "nil" => TokenType::Nil,
+                    "int" => TokenType::IntType,
+                    "float" => TokenType::FloatType,
+                    "bool" => TokenType::BoolType,
+                    "string" => TokenType::StringType,
+                    _ => TokenType::Identifier,
+                };
+                tokens.push(Token { typ, lexeme: id, line, col, end: (line, col) });
+            }
+            _ => tokens.push(Token { typ: TokenType::Error, lexeme: format!("Unexpected character '{}'.", c), line, col, end: (line, col) }),
+        }
+        // A token ends where the next one read with it starts, the last one where the cursor stopped.
+        let ends: Vec<Position> = tokens.iter().skip(read + 1).map(|t| (t.line, t.col)).chain([(chars.line, chars.col)]).collect();
+        for (token, end) in tokens.iter_mut().skip(read).zip(ends) {
+            token.end = end;
+        }
+    }
+    tokens.push(Token { typ: TokenType::Eof, lexeme: "".to_string(), line: chars.line, col: chars.col, end: (chars.line, chars.col) });
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The tokens of `source` before the end, with where each starts.
+    fn lex(source: &str) -> Vec<(TokenType, String, usize, usize)> {
+        tokenize(source).into_iter().filter(|t| t.typ != TokenType::Eof).map(|t| (t.typ, t.lexeme, t.line, t.col)).collect()
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        assert_eq!(
+            lex("a /* x /* y */ z */ b"),
+            vec![
+                (TokenType::Identifier, "a".to_string(), 1, 1),
+                (TokenType::Comment, "/* x /* y */ z */".to_string(), 1, 3),
+                (TokenType::Identifier, "b".to_string(), 1, 21),
+            ]
+        );
+        assert_eq!(lex("/* a\n/* b */\n*/ c").last(), Some(&(TokenType::Identifier, "c".to_string(), 3, 4)));
+        assert_eq!(lex("/* a /* b */"), vec![(TokenType::Error, "Unterminated block comment.".to_string(), 1, 1)]);
+    }
+
+    #[test]
+    fn tokens_end_past_their_last_character() {
+        let spans = |source: &str| tokenize(source).into_iter().map(|t| ((t.line, t.col), t.end)).collect::<Vec<_>>();
+        assert_eq!(spans("ab /* x\ny */ t.0"), vec![((1, 1), (1, 3)), ((1, 4), (2, 5)), ((2, 6), (2, 7)), ((2, 7), (2, 8)), ((2, 8), (2, 9)), ((2, 9), (2, 9))]);
+    }
+
+    #[test]
+    fn heredocs_keep_their_lines_verbatim() {
+        assert_eq!(
+            lex("let s = <<<END\n  one\n\"two\"\n  END\nx"),
+            vec![
+                (TokenType::Let, "let".to_string(), 1, 1),
+                (TokenType::Identifier, "s".to_string(), 1, 5),
+                (TokenType::Equals, "=".to_string(), 1, 7),
+                (TokenType::Heredoc, "END\n  one\n\"two\"".to_string(), 1, 9),
+                (TokenType::Identifier, "x".to_string(), 5, 1),
+            ]
+        );
+        let error = |source: &str| lex(source).into_iter().find(|t| t.0 == TokenType::Error).map(|t| t.1);
+        assert_eq!(error("<<<\nx\n"), Some("Expect a delimiter after '<<<'.".to_string()));
+        assert_eq!(error("<<<END x\nEND\n"), Some("Expect the end of the line after '<<<END'.".to_string()));
+        assert_eq!(error("<<<END\nx\n"), Some("Unterminated heredoc; expected a line with just 'END'.".to_string()));
+    }
+
+    #[test]
+    fn raw_identifiers_name_keywords() {
+        assert_eq!(lex("`for` for"), vec![(TokenType::Identifier, "for".to_string(), 1, 1), (TokenType::For, "for".to_string(), 1, 7)]);
+        assert_eq!(lex("`a b`"), vec![(TokenType::Error, "Invalid raw identifier '`a b`'.".to_string(), 1, 1)]);
+        assert_eq!(lex("`1x`").first().map(|t| t.0.clone()), Some(TokenType::Error));
+        assert_eq!(
+            lex("`for\nx"),
+            vec![(TokenType::Error, "Unterminated raw identifier.".to_string(), 1, 1), (TokenType::Identifier, "x".to_string(), 2, 1)]
+        );
+    }
+
+    #[test]
+    fn unterminated_strings_end_at_the_line() {
+        assert_eq!(
+            lex("x = \"abc\ny"),
+            vec![
+                (TokenType::Identifier, "x".to_string(), 1, 1),
+                (TokenType::Equals, "=".to_string(), 1, 3),
+                (TokenType::Error, "Unterminated string literal.".to_string(), 1, 5),
+                (TokenType::Identifier, "y".to_string(), 2, 1),
+            ]
+        );
+        assert_eq!(lex("\"a\" \"").last(), Some(&(TokenType::Error, "Unterminated string literal.".to_string(), 1, 5)));
+    }
+
+    #[test]
+    fn unknown_characters_are_errors() {
+        assert_eq!(
+            lex("a & b"),
+            vec![
+                (TokenType::Identifier, "a".to_string(), 1, 1),
+                (TokenType::Error, "Unexpected character '&'.".to_string(), 1, 3),
+                (TokenType::Identifier, "b".to_string(), 1, 5),
+            ]
+        );
+        assert_eq!(lex("x\n  $").last(), Some(&(TokenType::Error, "Unexpected character '$'.".to_string(), 2, 3)));
+    }
+}
@@ -0,0 +1,38 @@
+use crate::arena::{Arena, NodeId};
+
+// Traversals over the tree. A pass implements `visit` for the nodes it cares
+// about and calls `walk` for the rest, which visits the children in source
+// order; not calling it skips the subtree. `VisitorMut` is the same for
+// passes that rewrite nodes in place. A visit returns nothing, so a pass
+// that makes a value of each node, as the interpreter does, keeps the values
+// in its own fields.
+
+pub trait Visitor<'a> {
+    fn visit(&mut self, arena: &'a Arena, id: NodeId) {
+        walk(self, arena, id);
+    }
+}
+
+pub fn walk<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, arena: &'a Arena, id: NodeId) {
+    arena[id].for_each_child(&mut |child| visitor.visit(arena, child));
+}
+
+pub trait VisitorMut {
+    fn visit_mut(&mut self, arena: &mut Arena, id: NodeId) {
+        walk_mut(self, arena, id);
+    }
+}
+
+// The children are read before any is visited, so a visit may replace the
+// node it is given without disturbing the walk.
+pub fn walk_mut<V: VisitorMut + ?Sized>(visitor: &mut V, arena: &mut Arena, id: NodeId) {
+    for child in children(arena, id) {
+        visitor.visit_mut(arena, child);
+    }
+}
+
+pub fn children(arena: &Arena, id: NodeId) -> Vec<NodeId> {
+    let mut children = Vec::new();
+    arena[id].for_each_child(&mut |child| children.push(child));
+    children
+}